@@ -0,0 +1,49 @@
+//! Serialization throughput, e.g. `cargo bench --bench serialize`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use php_serde::to_vec;
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct Row {
+    id: i64,
+    name: String,
+    score: f64,
+    tags: Vec<String>,
+}
+
+fn integers(c: &mut Criterion) {
+    let v: Vec<i64> = (0..100_000).map(|i| i * 7919 - 400_000).collect();
+    let mut group = c.benchmark_group("integers");
+    group.throughput(Throughput::Elements(v.len() as u64));
+    group.bench_function("to_vec", |b| b.iter(|| to_vec(black_box(&v)).unwrap()));
+    group.finish();
+}
+
+fn floats(c: &mut Criterion) {
+    let v: Vec<f64> = (0..100_000)
+        .map(|i| f64::from(i) * 1.618_033_988_7 / 3.0)
+        .collect();
+    let mut group = c.benchmark_group("floats");
+    group.throughput(Throughput::Elements(v.len() as u64));
+    group.bench_function("to_vec", |b| b.iter(|| to_vec(black_box(&v)).unwrap()));
+    group.finish();
+}
+
+fn records(c: &mut Criterion) {
+    let v: Vec<Row> = (0..10_000)
+        .map(|i| Row {
+            id: i,
+            name: format!("user{i}"),
+            score: i as f64 / 7.0,
+            tags: vec!["a".to_owned(), "bc".to_owned()],
+        })
+        .collect();
+    let mut group = c.benchmark_group("records");
+    group.throughput(Throughput::Elements(v.len() as u64));
+    group.bench_function("to_vec", |b| b.iter(|| to_vec(black_box(&v)).unwrap()));
+    group.finish();
+}
+
+criterion_group!(benches, integers, floats, records);
+criterion_main!(benches);
@@ -0,0 +1,192 @@
+//! Restricting the classes of deserialized objects.
+
+use crate::key::PhpKey;
+use crate::value::Value;
+
+/// Class PHP substitutes for objects of classes that may not be created.
+pub const INCOMPLETE_CLASS: &str = "__PHP_Incomplete_Class";
+
+/// Property of an incomplete object holding the original class name.
+pub const INCOMPLETE_CLASS_NAME: &str = "__PHP_Incomplete_Class_Name";
+
+/// Classes that may be deserialized, like the `allowed_classes` option of
+/// PHP's `unserialize`.
+///
+/// Objects of other classes become incomplete objects: a `Value::Object` of
+/// class `__PHP_Incomplete_Class`, whose first property
+/// `__PHP_Incomplete_Class_Name` holds the original class name, followed by
+/// the raw properties. Like in PHP, the payload of custom serialized (`C:`)
+/// objects is dropped. Serializing an incomplete object writes it back under
+/// its original class.
+///
+/// The check applies wherever the class of an object is retained, i.e. when
+/// reading a [`Value`], a [`CustomSerialized`](crate::CustomSerialized) or
+/// through a [`ClassRegistry`](crate::ClassRegistry). Objects deserialized
+/// into plain Rust types lose their class name anyway.
+///
+/// ```rust
+/// use php_serde::{from_bytes_with_options, to_vec, AllowedClasses, DeserializerOptions, Value};
+///
+/// let input = br#"O:6:"Gadget":1:{s:3:"cmd";s:2:"ls";}"#;
+/// let options = DeserializerOptions::new().allowed_classes(AllowedClasses::only(["App\\User"]));
+/// let value: Value = from_bytes_with_options(input, &options).unwrap();
+///
+/// assert_eq!(value.class(), Some("__PHP_Incomplete_Class"));
+/// assert_eq!(
+///     value.get("__PHP_Incomplete_Class_Name"),
+///     Some(&Value::from("Gadget"))
+/// );
+/// assert_eq!(to_vec(&value).unwrap(), &input[..]);
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum AllowedClasses {
+    /// All classes are allowed, the default.
+    #[default]
+    All,
+    /// No classes are allowed.
+    None,
+    /// Only the listed classes are allowed. Class names are compared case
+    /// insensitively, and may be given with a leading backslash.
+    Only(Vec<String>),
+}
+
+impl AllowedClasses {
+    /// Allow only the given classes.
+    pub fn only<I, S>(classes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        AllowedClasses::Only(
+            classes
+                .into_iter()
+                .map(|class| class.as_ref().to_owned())
+                .collect(),
+        )
+    }
+
+    /// Returns `true` if objects of the class may be deserialized.
+    pub fn allows(&self, class: &str) -> bool {
+        match self {
+            AllowedClasses::All => true,
+            AllowedClasses::None => false,
+            AllowedClasses::Only(classes) => {
+                let class = strip_backslash(class);
+                classes
+                    .iter()
+                    .any(|allowed| strip_backslash(allowed).eq_ignore_ascii_case(class))
+            }
+        }
+    }
+}
+
+fn strip_backslash(class: &str) -> &str {
+    class.strip_prefix('\\').unwrap_or(class)
+}
+
+/// Build the incomplete object standing in for an object of a disallowed
+/// class.
+pub fn incomplete(class: String, properties: Vec<(PhpKey, Value)>) -> Value {
+    let name = (
+        PhpKey::from(INCOMPLETE_CLASS_NAME),
+        Value::String(class.into_bytes()),
+    );
+    Value::Object {
+        class: INCOMPLETE_CLASS.to_owned(),
+        properties: std::iter::once(name).chain(properties).collect(),
+    }
+}
+
+/// Split an incomplete object into its original class name and properties.
+///
+/// Returns `None` for other objects, or if the class name is missing.
+pub fn original_class<'a>(
+    class: &str,
+    properties: &'a [(PhpKey, Value)],
+) -> Option<(&'a str, &'a [(PhpKey, Value)])> {
+    if class != INCOMPLETE_CLASS {
+        return None;
+    }
+
+    match properties.split_first()? {
+        ((key, name), rest) if *key == PhpKey::from(INCOMPLETE_CLASS_NAME) => {
+            Some((name.as_str()?, rest))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AllowedClasses;
+    use crate::{
+        from_bytes, from_bytes_with_options, to_vec, ClassRegistry, CustomSerialized,
+        DeserializerOptions, PhpDeserializer, Value,
+    };
+    use serde::Deserialize;
+
+    fn options(allowed: AllowedClasses) -> DeserializerOptions {
+        DeserializerOptions::new().allowed_classes(allowed)
+    }
+
+    #[test]
+    fn allows() {
+        let allowed = AllowedClasses::only(["\\App\\User", "DateTime"]);
+        assert!(allowed.allows("App\\User"));
+        assert!(allowed.allows("\\datetime"));
+        assert!(!allowed.allows("App\\Admin"));
+        assert!(AllowedClasses::default().allows("Foo"));
+        assert!(!AllowedClasses::None.allows("Foo"));
+    }
+
+    #[test]
+    fn incomplete_objects() {
+        let input = br#"a:2:{i:0;O:1:"A":1:{s:1:"x";i:1;}i:1;O:1:"B":1:{s:1:"y";i:2;}}"#;
+
+        let value: Value =
+            from_bytes_with_options(input, &options(AllowedClasses::only(["a"]))).unwrap();
+        assert_eq!(value.get(0).and_then(Value::class), Some("A"));
+
+        let b = value.get(1).unwrap();
+        assert_eq!(b.class(), Some("__PHP_Incomplete_Class"));
+        assert_eq!(
+            b.get("__PHP_Incomplete_Class_Name"),
+            Some(&Value::from("B"))
+        );
+        assert_eq!(b.get("y"), Some(&Value::Int(2)));
+
+        // Written back under the original class.
+        assert_eq!(to_vec(&value).unwrap(), &input[..]);
+    }
+
+    #[test]
+    fn custom_records() {
+        let input = br#"C:3:"Foo":3:{i:1}"#;
+
+        let value: Value = from_bytes_with_options(input, &options(AllowedClasses::None)).unwrap();
+        assert_eq!(value, super::incomplete("Foo".to_owned(), Vec::new()));
+
+        assert!(
+            from_bytes_with_options::<CustomSerialized>(input, &options(AllowedClasses::None))
+                .is_err()
+        );
+
+        let record: CustomSerialized = from_bytes(input).unwrap();
+        assert_eq!(record.class, "Foo");
+    }
+
+    #[test]
+    fn registry() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct A {
+            x: i64,
+        }
+
+        let mut registry = ClassRegistry::<A>::new();
+        registry.register::<A>("A");
+
+        let input = br#"O:1:"A":1:{s:1:"x";i:1;}"#;
+        let mut de = PhpDeserializer::with_options(&input[..], options(AllowedClasses::None));
+        assert!(serde::de::DeserializeSeed::deserialize(&registry, &mut de).is_err());
+    }
+}
@@ -0,0 +1,94 @@
+//! `proptest` strategies for generating random PHP values.
+//!
+//! Enabled by the `arbitrary` feature.
+
+use crate::custom::CustomSerialized;
+use crate::key::PhpKey;
+use crate::value::Value;
+use proptest::collection::{btree_map, vec};
+use proptest::prelude::*;
+
+/// Maximum nesting depth of generated arrays.
+const MAX_DEPTH: u32 = 4;
+/// Rough upper bound on the total number of nodes in a generated value.
+const MAX_NODES: u32 = 64;
+/// Maximum number of entries in a single generated array.
+const MAX_ENTRIES: u32 = 8;
+/// Pattern of generated class names, possibly namespaced.
+const CLASS_NAME: &str = "[A-Z][A-Za-z0-9_]{0,11}(\\\\[A-Z][A-Za-z0-9_]{0,11}){0,2}";
+
+impl Arbitrary for PhpKey {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<PhpKey>;
+
+    /// Generates integer keys and short identifier-like or binary string
+    /// keys, normalized the way PHP would.
+    fn arbitrary_with(_args: ()) -> Self::Strategy {
+        prop_oneof![
+            (0..100_i64).prop_map(PhpKey::Int),
+            any::<i64>().prop_map(PhpKey::Int),
+            "[a-z_][a-z0-9_]{0,15}".prop_map(PhpKey::from),
+            vec(any::<u8>(), 0..16).prop_map(PhpKey::from),
+        ]
+        .boxed()
+    }
+}
+
+impl Arbitrary for Value {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Value>;
+
+    /// Generates valid value trees of bounded depth and size.
+    ///
+    /// Arrays are either lists with keys `0..n` or associative arrays with
+    /// unique keys, mirroring what PHP code typically produces. Objects use
+    /// plausible, possibly namespaced class names. Floats are always finite.
+    fn arbitrary_with(_args: ()) -> Self::Strategy {
+        let leaf = prop_oneof![
+            Just(Value::Null),
+            any::<bool>().prop_map(Value::Bool),
+            any::<i64>().prop_map(Value::Int),
+            (proptest::num::f64::NORMAL | proptest::num::f64::SUBNORMAL | proptest::num::f64::ZERO)
+                .prop_map(Value::Float),
+            "[ -~]{0,24}".prop_map(Value::from),
+            vec(any::<u8>(), 0..24).prop_map(Value::String),
+            (CLASS_NAME, vec(any::<u8>(), 0..24))
+                .prop_map(|(class, payload)| Value::Custom(CustomSerialized { class, payload })),
+            (CLASS_NAME, "[A-Z][A-Za-z0-9_]{0,11}")
+                .prop_map(|(class, case)| Value::Enum { class, case }),
+        ];
+
+        leaf.prop_recursive(MAX_DEPTH, MAX_NODES, MAX_ENTRIES, |inner| {
+            prop_oneof![
+                vec(inner.clone(), 0..MAX_ENTRIES as usize).prop_map(Value::from),
+                btree_map(any::<PhpKey>(), inner.clone(), 0..MAX_ENTRIES as usize)
+                    .prop_map(|entries| entries.into_iter().collect()),
+                (
+                    CLASS_NAME,
+                    btree_map(any::<PhpKey>(), inner, 0..MAX_ENTRIES as usize)
+                )
+                    .prop_map(|(class, properties)| Value::Object {
+                        class,
+                        properties: properties.into_iter().collect(),
+                    }),
+            ]
+        })
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{from_bytes, to_vec, Value};
+    use proptest::prelude::any;
+    use proptest::proptest;
+
+    proptest! {
+        #[test]
+        fn roundtrip_value(v in any::<Value>()) {
+            let serialized = to_vec(&v).expect("Serialization failed");
+            let deserialized: Value = from_bytes(&serialized).expect("Deserialization failed");
+            assert_eq!(deserialized, v);
+        }
+    }
+}
@@ -0,0 +1,377 @@
+//! Deserializing from asynchronous readers, with the `tokio` feature.
+
+use crate::de::from_bytes_with_options;
+use crate::error::{Error, Result};
+use crate::options::DeserializerOptions;
+use serde::de::DeserializeOwned;
+use std::convert::TryFrom;
+use std::io;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
+
+/// Longest number or boolean accepted, including its terminating `;`.
+const MAX_SCALAR: usize = 1024;
+
+/// Deserialize a single value from an asynchronous reader, e.g. a socket.
+///
+/// `Deserialize` implementations are synchronous, so the value is read in
+/// two steps: its bytes are collected without blocking, following the type
+/// tags and length headers, and then deserialized. Unlike `from_reader`,
+/// this holds all bytes of the value in memory, though nothing after it.
+/// Declared lengths are not trusted for allocation, and
+/// `DeserializerOptions::max_buffered` limits the bytes held, which
+/// untrusted input should be read with.
+///
+/// Input is buffered internally, which may read past the end of the value.
+///
+/// ```rust
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// use php_serde::from_async_reader;
+///
+/// let input: &[u8] = br#"a:2:{i:0;s:1:"a";i:1;s:1:"b";}"#;
+/// let v: Vec<String> = from_async_reader(input).await.unwrap();
+/// assert_eq!(v, vec!["a", "b"]);
+/// # });
+/// ```
+pub async fn from_async_reader<R, T>(reader: R) -> Result<T>
+where
+    R: AsyncRead + Unpin,
+    T: DeserializeOwned,
+{
+    from_async_reader_with_options(reader, &DeserializerOptions::default()).await
+}
+
+/// Deserialize a single value from an asynchronous reader, with non-default
+/// options.
+///
+/// The size, length and depth limits of `options` already apply while the
+/// bytes of the value are collected.
+pub async fn from_async_reader_with_options<R, T>(
+    reader: R,
+    options: &DeserializerOptions,
+) -> Result<T>
+where
+    R: AsyncRead + Unpin,
+    T: DeserializeOwned,
+{
+    let mut scanner = Scanner {
+        reader: BufReader::new(reader),
+        buf: Vec::new(),
+        options,
+    };
+    scanner.value().await.map_err(|e| {
+        e.at(scanner.buf.len())
+            .with_excerpt(&scanner.buf, options.error_context)
+    })?;
+    from_bytes_with_options(&scanner.buf, options)
+}
+
+/// Collects the bytes of one serialized value from an asynchronous reader.
+struct Scanner<'a, R> {
+    reader: R,
+    buf: Vec<u8>,
+    options: &'a DeserializerOptions,
+}
+
+impl<R> Scanner<'_, R>
+where
+    R: AsyncBufRead + Unpin,
+{
+    /// Copy a complete value.
+    async fn value(&mut self) -> Result<()> {
+        // Values left to copy on each level, array keys count as values.
+        let mut remaining = vec![1];
+
+        while let Some(left) = remaining.last_mut() {
+            if *left == 0 {
+                remaining.pop();
+                if !remaining.is_empty() {
+                    self.expect(b'}').await?;
+                }
+                continue;
+            }
+            *left -= 1;
+
+            let sym = self.byte().await?;
+            if sym == b'N' {
+                self.expect(b';').await?;
+                continue;
+            }
+            self.expect(b':').await?;
+
+            let num_entries = match sym {
+                b'b' | b'i' | b'd' => {
+                    self.scalar().await?;
+                    continue;
+                }
+                b's' | b'E' => {
+                    self.string(b';').await?;
+                    continue;
+                }
+                b'r' | b'R' if self.options.resolve_references => {
+                    self.scalar().await?;
+                    continue;
+                }
+                b'C' => {
+                    self.string(b':').await?;
+                    let length = self.length().await?;
+                    self.expect(b'{').await?;
+                    self.bytes(length).await?;
+                    self.expect(b'}').await?;
+                    continue;
+                }
+                b'a' => self.length().await?,
+                b'O' => {
+                    self.string(b':').await?;
+                    self.length().await?
+                }
+                c => return Err(Error::InvalidTypeIndicator(char::from(c))),
+            };
+            self.expect(b'{').await?;
+
+            // Same limit as when deserializing, which only counts levels
+            // with elements.
+            if num_entries > 0 && remaining.len() > self.options.max_depth {
+                return Err(Error::DepthLimitExceeded);
+            }
+            let num_values = num_entries
+                .checked_mul(2)
+                .ok_or(Error::LengthLimitExceeded(num_entries))?;
+            remaining.push(num_values);
+        }
+
+        Ok(())
+    }
+
+    /// Copy a single byte.
+    async fn byte(&mut self) -> Result<u8> {
+        let c = self.reader.read_u8().await.map_err(read_error)?;
+        self.buf.push(c);
+        self.check_size()?;
+        Ok(c)
+    }
+
+    /// Number of bytes that may be read at most before checking the size,
+    /// one more than fit into `DeserializerOptions::max_buffered`.
+    fn room(&self) -> u64 {
+        let room = self.options.max_buffered.saturating_sub(self.buf.len());
+        u64::try_from(room).unwrap_or(u64::MAX).saturating_add(1)
+    }
+
+    /// Fail if more than `DeserializerOptions::max_buffered` bytes were
+    /// copied.
+    fn check_size(&self) -> Result<()> {
+        if self.buf.len() > self.options.max_buffered {
+            return Err(Error::LengthLimitExceeded(self.buf.len()));
+        }
+        Ok(())
+    }
+
+    /// Copy a specific byte.
+    async fn expect(&mut self, expected: u8) -> Result<()> {
+        let actual = self.byte().await?;
+        if actual == expected {
+            Ok(())
+        } else {
+            Err(Error::Unexpected {
+                expected: char::from(expected),
+                actual: char::from(actual),
+            })
+        }
+    }
+
+    /// Copy bytes up to and including `delim`, returning the bytes before it.
+    async fn until(&mut self, delim: u8) -> Result<&[u8]> {
+        let start = self.buf.len();
+        let room = self.room();
+        (&mut self.reader)
+            .take(room.min(MAX_SCALAR as u64))
+            .read_until(delim, &mut self.buf)
+            .await
+            .map_err(read_error)?;
+        self.check_size()?;
+
+        match self.buf[start..].split_last() {
+            Some((&last, data)) if last == delim => Ok(data),
+            _ if self.buf.len() - start == MAX_SCALAR => {
+                Err(Error::LengthLimitExceeded(self.buf.len() - start))
+            }
+            _ => Err(Error::UnexpectedEof),
+        }
+    }
+
+    /// Copy the rest of a number or boolean, up to the terminating `;`.
+    ///
+    /// The value itself is checked when it is deserialized.
+    async fn scalar(&mut self) -> Result<()> {
+        self.until(b';').await.map(|_| ())
+    }
+
+    /// Copy a length header, including the colon after it.
+    async fn length(&mut self) -> Result<usize> {
+        let max_length = self.options.max_length;
+        let digits = self.until(b':').await?;
+        if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+            return Err(Error::ExpectedDigit {
+                actual: char::from(*digits.first().unwrap_or(&b':')),
+            });
+        }
+
+        let length = std::str::from_utf8(digits)
+            .ok()
+            .and_then(|digits| digits.parse().ok())
+            .unwrap_or(usize::MAX);
+        if length > max_length {
+            return Err(Error::LengthLimitExceeded(length));
+        }
+        Ok(length)
+    }
+
+    /// Copy a length-prefixed, quoted string, followed by `delim`.
+    async fn string(&mut self, delim: u8) -> Result<()> {
+        let length = self.length().await?;
+        self.expect(b'"').await?;
+        self.bytes(length).await?;
+        self.expect(b'"').await?;
+        self.expect(delim).await
+    }
+
+    /// Copy exactly `length` bytes.
+    ///
+    /// The buffer grows as data arrives, instead of being allocated up front.
+    async fn bytes(&mut self, length: usize) -> Result<()> {
+        let room = self.room();
+        let copied = (&mut self.reader)
+            .take(room.min(length as u64))
+            .read_to_end(&mut self.buf)
+            .await
+            .map_err(read_error)?;
+        self.check_size()?;
+
+        if copied < length {
+            return Err(Error::UnexpectedEof);
+        }
+        Ok(())
+    }
+}
+
+fn read_error(e: io::Error) -> Error {
+    if e.kind() == io::ErrorKind::UnexpectedEof {
+        Error::UnexpectedEof
+    } else {
+        Error::ReadSerialized(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_async_reader, from_async_reader_with_options};
+    use crate::{DeserializerOptions, Error, Value};
+    use serde::Deserialize;
+    use std::future::Future;
+    use tokio::io::AsyncReadExt;
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn values() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Session {
+            user: String,
+            roles: Vec<String>,
+            expires: f64,
+        }
+
+        let input = br#"O:7:"Session":3:{s:4:"user";s:3:"bob";s:5:"roles";a:1:{i:0;s:5:"admin";}s:7:"expires";d:1.5;}"#;
+        // Split into pieces, arriving one after another.
+        let reader = input[..20].chain(&input[20..50]).chain(&input[50..]);
+        let session: Session = block_on(from_async_reader(reader)).unwrap();
+        assert_eq!(
+            session,
+            Session {
+                user: "bob".to_owned(),
+                roles: vec!["admin".to_owned()],
+                expires: 1.5,
+            }
+        );
+
+        for input in [
+            &b"N;"[..],
+            b"b:1;",
+            b"i:-7;",
+            br#"C:3:"Foo":3:{i:1}"#,
+            b"a:1:{i:0;a:0:{}}",
+        ] {
+            let value: Value = block_on(from_async_reader(input)).unwrap();
+            assert_eq!(crate::to_vec(&value).unwrap(), input);
+        }
+
+        // References are collected as they are, to be resolved afterwards.
+        let options = DeserializerOptions::new().resolve_references(true);
+        let input: &[u8] = b"a:2:{i:0;i:1;i:1;r:2;}";
+        let v: Vec<i64> = block_on(from_async_reader_with_options(input, &options)).unwrap();
+        assert_eq!(v, vec![1, 1]);
+    }
+
+    #[test]
+    fn trailing_input() {
+        let value: i64 = block_on(from_async_reader(&b"i:1;i:2;"[..])).unwrap();
+        assert_eq!(value, 1);
+    }
+
+    #[test]
+    fn malformed() {
+        let read = |input: &'static [u8]| {
+            block_on(from_async_reader::<_, Value>(input)).map_err(Error::into_inner)
+        };
+
+        assert!(matches!(read(b"a:2:{i:0;"), Err(Error::UnexpectedEof)));
+        assert!(matches!(read(br#"s:99:"abc";"#), Err(Error::UnexpectedEof)));
+        assert!(matches!(
+            read(b"x:1;"),
+            Err(Error::InvalidTypeIndicator('x'))
+        ));
+        assert!(read(b"a:1x:{}").is_err());
+
+        let options = DeserializerOptions::new().max_length(2).max_depth(1);
+        let read = |input: &'static [u8]| {
+            block_on(from_async_reader_with_options::<_, Value>(input, &options))
+                .map_err(Error::into_inner)
+        };
+        assert!(matches!(
+            read(br#"s:3:"abc";"#),
+            Err(Error::LengthLimitExceeded(3))
+        ));
+        assert!(matches!(
+            read(b"a:1:{i:0;a:1:{i:0;N;}}"),
+            Err(Error::DepthLimitExceeded)
+        ));
+    }
+
+    #[test]
+    fn size_limit() {
+        let input = format!("a:1:{{i:0;s:1000:\"{}\";}}", "x".repeat(1000));
+        let read = |max_buffered| {
+            let options = DeserializerOptions::new().max_buffered(max_buffered);
+            block_on(from_async_reader_with_options::<_, Value>(
+                input.as_bytes(),
+                &options,
+            ))
+            .map_err(Error::into_inner)
+        };
+
+        assert!(read(input.len()).is_ok());
+        // Fails once the limit is passed, within strings, numbers and
+        // headers alike.
+        for max_buffered in [0, 7, 12, 500, input.len() - 1] {
+            assert!(matches!(
+                read(max_buffered),
+                Err(Error::LengthLimitExceeded(len)) if len == max_buffered + 1
+            ));
+        }
+    }
+}
@@ -0,0 +1,240 @@
+//! Serializing to asynchronous writers, with the `tokio` feature.
+
+use crate::error::{Error, Result};
+use crate::options::SerializerOptions;
+use crate::ser::serialize_into;
+use serde::Serialize;
+use std::io::{self, Write};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+
+/// Write out serialization of value to an asynchronous writer, e.g. a
+/// socket.
+///
+/// `Serialize` implementations are synchronous and cannot wait for the
+/// writer, so the value is serialized on a blocking thread, see
+/// `tokio::task::spawn_blocking`, and passed over in chunks of
+/// `SerializerOptions::write_buffer_size` bytes as the writer takes them.
+/// At most a few chunks are held in memory at a time, however large the
+/// value is. For this, the value is moved to the thread, and must be owned.
+///
+/// Must be called within a `tokio` runtime. The writer is not flushed.
+///
+/// ```rust
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// use php_serde::to_async_writer;
+///
+/// let mut output = Vec::new();
+/// to_async_writer(&mut output, vec!["a", "b"]).await.unwrap();
+/// assert_eq!(output, br#"a:2:{i:0;s:1:"a";i:1;s:1:"b";}"#);
+/// # });
+/// ```
+pub async fn to_async_writer<W, T>(writer: W, value: T) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+    T: Serialize + Send + 'static,
+{
+    to_async_writer_with_options(writer, value, &SerializerOptions::default()).await
+}
+
+/// Write out serialization of value to an asynchronous writer, with
+/// non-default options.
+pub async fn to_async_writer_with_options<W, T>(
+    mut writer: W,
+    value: T,
+    options: &SerializerOptions,
+) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+    T: Serialize + Send + 'static,
+{
+    let (sender, mut chunks) = mpsc::channel(1);
+    let mut sink = ChunkSender {
+        chunk: Vec::new(),
+        capacity: options.write_buffer_size,
+        sender,
+    };
+    let options = options.clone();
+    let serializing = tokio::task::spawn_blocking(move || {
+        serialize_into(&mut sink, &value, &options)?;
+        sink.send().map_err(Error::WriteSerialized)
+    });
+
+    let mut written = Ok(());
+    while let Some(chunk) = chunks.recv().await {
+        written = writer.write_all(&chunk).await;
+        if written.is_err() {
+            // Stops serializing, the next chunk cannot be sent.
+            chunks.close();
+            break;
+        }
+    }
+
+    let serialized = match serializing.await {
+        Ok(result) => result,
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    };
+    // Failing to write is what stops serializing early.
+    written.map_err(Error::WriteSerialized)?;
+    serialized
+}
+
+/// Output collecting serialized data into chunks, sent to be written
+/// asynchronously.
+struct ChunkSender {
+    chunk: Vec<u8>,
+    /// Size at which a chunk is sent.
+    capacity: usize,
+    sender: mpsc::Sender<Vec<u8>>,
+}
+
+impl ChunkSender {
+    /// Send the chunk, if it is not empty.
+    fn send(&mut self) -> io::Result<()> {
+        if self.chunk.is_empty() {
+            return Ok(());
+        }
+        let chunk = std::mem::replace(&mut self.chunk, Vec::with_capacity(self.capacity));
+        self.sender
+            .blocking_send(chunk)
+            .map_err(|_| io::ErrorKind::BrokenPipe.into())
+    }
+}
+
+impl Write for ChunkSender {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.chunk.extend_from_slice(buf);
+        if self.chunk.len() >= self.capacity {
+            self.send()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_async_writer, to_async_writer_with_options};
+    use crate::{Error, SerializerOptions};
+    use serde::Serialize;
+    use std::future::Future;
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use tokio::io::AsyncWrite;
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn values() {
+        #[derive(Clone, Serialize)]
+        struct User {
+            id: i64,
+            name: &'static str,
+        }
+
+        let user = User { id: 7, name: "bob" };
+
+        let mut output = Vec::new();
+        block_on(to_async_writer(&mut output, user.clone())).unwrap();
+        assert_eq!(output, crate::to_vec(&user).unwrap());
+
+        let options = SerializerOptions::new().structs_as_lists(true);
+        let mut output = Vec::new();
+        block_on(to_async_writer_with_options(&mut output, user, &options)).unwrap();
+        assert_eq!(output, br#"a:2:{i:0;i:7;i:1;s:3:"bob";}"#);
+
+        assert!(matches!(
+            block_on(to_async_writer(Vec::new(), u64::MAX)),
+            Err(Error::IntegerOutOfRange(_))
+        ));
+    }
+
+    #[test]
+    fn write_error() {
+        /// Writer accepting a few bytes at a time, up to a limit.
+        struct Limited(usize);
+
+        impl AsyncWrite for Limited {
+            fn poll_write(
+                mut self: Pin<&mut Self>,
+                _cx: &mut Context<'_>,
+                buf: &[u8],
+            ) -> Poll<io::Result<usize>> {
+                if self.0 == 0 {
+                    return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+                }
+                let n = buf.len().min(self.0).min(3);
+                self.0 -= n;
+                Poll::Ready(Ok(n))
+            }
+
+            fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+                Poll::Ready(Ok(()))
+            }
+
+            fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+                Poll::Ready(Ok(()))
+            }
+        }
+
+        block_on(to_async_writer(Limited(14), vec![1])).unwrap();
+        assert!(matches!(
+            block_on(to_async_writer(Limited(13), vec![1])),
+            Err(Error::WriteSerialized(e)) if e.kind() == io::ErrorKind::BrokenPipe
+        ));
+    }
+
+    #[test]
+    fn chunks() {
+        /// Writer recording the size of each write.
+        #[derive(Default)]
+        struct Recorder {
+            output: Vec<u8>,
+            writes: Vec<usize>,
+        }
+
+        impl AsyncWrite for Recorder {
+            fn poll_write(
+                mut self: Pin<&mut Self>,
+                _cx: &mut Context<'_>,
+                buf: &[u8],
+            ) -> Poll<io::Result<usize>> {
+                self.output.extend_from_slice(buf);
+                self.writes.push(buf.len());
+                Poll::Ready(Ok(buf.len()))
+            }
+
+            fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+                Poll::Ready(Ok(()))
+            }
+
+            fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+                Poll::Ready(Ok(()))
+            }
+        }
+
+        // The output is passed on in chunks of about the buffer size, not
+        // in one piece.
+        let value: Vec<String> = (0..1000).map(|i| format!("item {i}")).collect();
+        let options = SerializerOptions::new().write_buffer_size(64);
+        let mut recorder = Recorder::default();
+        block_on(to_async_writer_with_options(
+            &mut recorder,
+            value.clone(),
+            &options,
+        ))
+        .unwrap();
+        assert_eq!(recorder.output, crate::to_vec(&value).unwrap());
+        assert!(recorder.writes.len() > 100);
+        assert!(recorder.writes.iter().all(|&len| len < 64 + 16));
+    }
+}
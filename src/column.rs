@@ -0,0 +1,19 @@
+//! Serialized database columns.
+
+/// A database column holding a serialized value.
+///
+/// With the `sqlx` feature, `Php<T>` is decoded from and encoded to the
+/// bytes of a column, so columns of any type that `Vec<u8>` is compatible
+/// with can be used, e.g. `TEXT` and `BLOB` columns in MySQL and SQLite,
+/// or `BYTEA` columns in PostgreSQL.
+///
+/// With the `mysql` feature, `Php<T>` implements `FromValue` and converts
+/// into a `Value` of `mysql_common`, the crate that `mysql` and
+/// `mysql_async` build on, so rows can be read with `from_row`. Values are
+/// converted with `try_from` before they are passed as statement
+/// parameters, since serializing them can fail.
+///
+/// [`Value`](crate::Value) is read and written the same way, without the
+/// wrapper.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Php<T>(pub T);
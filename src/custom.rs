@@ -0,0 +1,185 @@
+//! Objects using custom serialization (`C:` records).
+
+use crate::value::{CLASS_KEY, PAYLOAD_KEY, TOKEN};
+use serde::de::{self, MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+/// An object serialized by its own `Serializable::serialize()` method.
+///
+/// Classes implementing PHP's `Serializable` interface are written as
+/// `C:<len>:"<class>":<len>:{<payload>}`, where the payload is whatever the
+/// class chose to produce. It is often, but not necessarily, in PHP's
+/// serialization format itself.
+///
+/// `CustomSerialized` carries such records through unchanged, leaving it to
+/// the user to decode the payload if needed.
+///
+/// ```rust
+/// use php_serde::{from_bytes, to_vec, CustomSerialized};
+///
+/// let input = br#"C:11:"ArrayObject":21:{x:i:0;a:0:{};m:a:0:{}}"#;
+/// let record: CustomSerialized = from_bytes(input).unwrap();
+/// assert_eq!(record.class, "ArrayObject");
+/// assert_eq!(record.payload, b"x:i:0;a:0:{};m:a:0:{}");
+///
+/// assert_eq!(to_vec(&record).unwrap(), &input[..]);
+/// ```
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct CustomSerialized {
+    /// Fully qualified class name, without a leading backslash.
+    pub class: String,
+    /// Raw payload between the braces.
+    pub payload: Vec<u8>,
+}
+
+impl CustomSerialized {
+    /// Create a new record.
+    pub fn new(class: impl Into<String>, payload: impl Into<Vec<u8>>) -> Self {
+        CustomSerialized {
+            class: class.into(),
+            payload: payload.into(),
+        }
+    }
+}
+
+/// Serializes a byte slice with `serialize_bytes`.
+pub struct Bytes<'a>(pub &'a [u8]);
+
+impl Serialize for Bytes<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+/// Serialize a custom record as a map of its class and payload.
+///
+/// The PHP serializer recognizes the keys and writes a `C:` record, other
+/// formats see a regular map.
+pub fn serialize<S>(class: &str, payload: &[u8], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let mut map = serializer.serialize_map(Some(2))?;
+    map.serialize_entry(CLASS_KEY, class)?;
+    map.serialize_entry(PAYLOAD_KEY, &Bytes(payload))?;
+    map.end()
+}
+
+impl Serialize for CustomSerialized {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serialize(&self.class, &self.payload, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for CustomSerialized {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // Asking for the untyped form keeps SPL containers from being
+        // unwrapped into their contents.
+        deserializer.deserialize_newtype_struct(TOKEN, CustomVisitor)
+    }
+}
+
+/// Visitor for the map produced by `serialize`.
+struct CustomVisitor;
+
+impl<'de> Visitor<'de> for CustomVisitor {
+    type Value = CustomSerialized;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a custom serialized object")
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<CustomSerialized, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(self)
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<CustomSerialized, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut class = None;
+        let mut payload = None;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                CLASS_KEY => class = Some(map.next_value()?),
+                PAYLOAD_KEY => payload = Some(map.next_value()?),
+                _ => return Err(de::Error::custom("expected a custom serialized object")),
+            }
+        }
+
+        Ok(CustomSerialized {
+            class: class.ok_or_else(|| de::Error::missing_field("class"))?,
+            payload: payload.ok_or_else(|| de::Error::missing_field("payload"))?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CustomSerialized;
+    use crate::{from_bytes, to_vec, Value};
+    use serde::Deserialize;
+
+    #[test]
+    fn roundtrip() {
+        let input = br#"a:2:{i:0;C:3:"Foo":5:{a}b;c}i:1;C:3:"Bar":0:{}}"#;
+
+        let records: Vec<CustomSerialized> = from_bytes(input).unwrap();
+        assert_eq!(
+            records,
+            vec![
+                CustomSerialized::new("Foo", "a}b;c"),
+                CustomSerialized::new("Bar", ""),
+            ]
+        );
+        assert_eq!(to_vec(&records).unwrap(), &input[..]);
+
+        // SPL containers are kept as well.
+        let input = br#"C:11:"ArrayObject":21:{x:i:0;a:0:{};m:a:0:{}}"#;
+        let record: CustomSerialized = from_bytes(input).unwrap();
+        assert_eq!(record.class, "ArrayObject");
+
+        // Numeric payloads must not be coerced.
+        let record = CustomSerialized::new("Foo", "12");
+        assert_eq!(to_vec(&record).unwrap(), br#"C:3:"Foo":2:{12}"#);
+    }
+
+    #[test]
+    fn in_value() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Data {
+            x: CustomSerialized,
+        }
+
+        let input = br#"a:1:{s:1:"x";C:3:"Foo":3:{i:1}}"#;
+
+        let value: Value = from_bytes(input).unwrap();
+        assert_eq!(
+            value.get("x"),
+            Some(&Value::Custom(CustomSerialized::new("Foo", "i:1")))
+        );
+        assert_eq!(to_vec(&value).unwrap(), &input[..]);
+
+        assert_eq!(
+            crate::from_value::<Data>(value).unwrap(),
+            Data {
+                x: CustomSerialized::new("Foo", "i:1")
+            }
+        );
+    }
+}
@@ -0,0 +1,515 @@
+//! PHP `DateTime` and `DateTimeImmutable` objects.
+//!
+//! PHP serializes dates as objects with three properties:
+//!
+//! ```text
+//! O:8:"DateTime":3:{s:4:"date";s:26:"2021-03-04 05:06:07.000000";
+//!                   s:13:"timezone_type";i:3;s:8:"timezone";s:3:"UTC";}
+//! ```
+//!
+//! [`PhpDateTime`] represents these objects as-is. With the `chrono` and
+//! `time` features, the submodules of the same names can be used with
+//! `#[serde(with = "...")]` to map them to the date types of those crates.
+//!
+//! Only fixed UTC offsets are supported, since resolving time zone
+//! identifiers such as `Europe/Berlin` requires a time zone database.
+
+use crate::error::{Error, Result};
+use crate::value::{Value, CLASS_KEY};
+use serde::de;
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::convert::TryFrom;
+
+/// Class name of mutable dates.
+const DATE_TIME: &str = "DateTime";
+/// Class name of immutable dates.
+const DATE_TIME_IMMUTABLE: &str = "DateTimeImmutable";
+
+/// Time zone given as UTC offset, e.g. `+02:00`.
+pub const TIMEZONE_OFFSET: i64 = 1;
+/// Time zone given as abbreviation, e.g. `CEST`.
+pub const TIMEZONE_ABBREVIATION: i64 = 2;
+/// Time zone given as identifier, e.g. `Europe/Berlin`.
+pub const TIMEZONE_IDENTIFIER: i64 = 3;
+
+/// A serialized PHP `DateTime` or `DateTimeImmutable` object.
+///
+/// ```rust
+/// use php_serde::{from_bytes, to_vec, PhpDateTime};
+///
+/// let input = br#"O:8:"DateTime":3:{s:4:"date";s:26:"2021-03-04 05:06:07.000000";s:13:"timezone_type";i:1;s:8:"timezone";s:6:"+02:00";}"#;
+///
+/// let date: PhpDateTime = from_bytes(input).unwrap();
+/// assert_eq!(date.date, "2021-03-04 05:06:07.000000");
+/// assert_eq!(date.utc_offset().unwrap(), 7200);
+///
+/// assert_eq!(to_vec(&date).unwrap(), &input[..]);
+/// ```
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct PhpDateTime {
+    /// Whether this is a `DateTimeImmutable` instead of a `DateTime`.
+    pub immutable: bool,
+    /// Local date and time, formatted as `Y-m-d H:i:s.u`.
+    pub date: String,
+    /// Kind of time zone, one of `TIMEZONE_OFFSET`, `TIMEZONE_ABBREVIATION`
+    /// or `TIMEZONE_IDENTIFIER`.
+    pub timezone_type: i64,
+    /// Time zone, in the format given by `timezone_type`.
+    pub timezone: String,
+}
+
+/// Broken down date and time, with its UTC offset.
+///
+/// Used to convert from and to the date types of other crates.
+#[cfg_attr(not(any(feature = "chrono", feature = "time")), allow(dead_code))]
+#[derive(Debug, Eq, PartialEq)]
+struct Parts {
+    year: i32,
+    month: u8,
+    day: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+    microsecond: u32,
+    /// Offset from UTC in seconds.
+    offset: i32,
+}
+
+/// Offsets of the time zone abbreviations understood, in hours.
+const ABBREVIATIONS: &[(&str, i32)] = &[
+    ("UTC", 0),
+    ("GMT", 0),
+    ("Z", 0),
+    ("EST", -5),
+    ("EDT", -4),
+    ("CST", -6),
+    ("CDT", -5),
+    ("MST", -7),
+    ("MDT", -6),
+    ("PST", -8),
+    ("PDT", -7),
+    ("CET", 1),
+    ("CEST", 2),
+];
+
+/// Time zone identifiers that are always at UTC.
+const UTC_IDENTIFIERS: &[&str] = &["UTC", "Z", "GMT", "Etc/UTC", "Etc/GMT", "Universal", "Zulu"];
+
+/// Parse an offset like `+02:00`, returning seconds.
+fn parse_offset(s: &str) -> Option<i32> {
+    let (sign, rest) = match s.as_bytes().first()? {
+        b'+' => (1, &s[1..]),
+        b'-' => (-1, &s[1..]),
+        _ => return None,
+    };
+    let (hours, minutes) = rest.split_once(':')?;
+    let hours: i32 = hours.parse().ok()?;
+    let minutes: i32 = minutes.parse().ok()?;
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Format an offset in seconds like `+02:00`.
+#[cfg_attr(not(any(feature = "chrono", feature = "time")), allow(dead_code))]
+fn format_offset(offset: i32) -> String {
+    let sign = if offset < 0 { '-' } else { '+' };
+    let minutes = offset.abs() / 60;
+    format!("{sign}{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+impl PhpDateTime {
+    /// Create a `DateTime` from its properties.
+    pub fn new(date: impl Into<String>, timezone_type: i64, timezone: impl Into<String>) -> Self {
+        PhpDateTime {
+            immutable: false,
+            date: date.into(),
+            timezone_type,
+            timezone: timezone.into(),
+        }
+    }
+
+    /// Returns the class name, `DateTime` or `DateTimeImmutable`.
+    pub fn class(&self) -> &'static str {
+        if self.immutable {
+            DATE_TIME_IMMUTABLE
+        } else {
+            DATE_TIME
+        }
+    }
+
+    /// Returns the offset from UTC in seconds.
+    ///
+    /// Fails for time zone identifiers other than UTC and for unknown
+    /// abbreviations.
+    pub fn utc_offset(&self) -> Result<i32> {
+        let offset = match self.timezone_type {
+            TIMEZONE_OFFSET => parse_offset(&self.timezone),
+            TIMEZONE_ABBREVIATION => ABBREVIATIONS
+                .iter()
+                .find(|(abbr, _)| abbr.eq_ignore_ascii_case(&self.timezone))
+                .map(|(_, hours)| hours * 3600),
+            TIMEZONE_IDENTIFIER => UTC_IDENTIFIERS
+                .iter()
+                .any(|id| id.eq_ignore_ascii_case(&self.timezone))
+                .then_some(0),
+            _ => None,
+        };
+
+        offset.ok_or_else(|| {
+            Error::InvalidDateTime(format!("unsupported time zone: {}", self.timezone))
+        })
+    }
+}
+
+#[cfg_attr(not(any(feature = "chrono", feature = "time")), allow(dead_code))]
+impl PhpDateTime {
+    /// Break down into date, time and offset.
+    fn parts(&self) -> Result<Parts> {
+        let invalid = || Error::InvalidDateTime(format!("invalid date: {}", self.date));
+
+        let (date, time) = self.date.split_once(' ').ok_or_else(invalid)?;
+        let (negative, date) = match date.strip_prefix('-') {
+            Some(date) => (true, date),
+            None => (false, date),
+        };
+
+        let mut date = date.splitn(3, '-').map(str::parse::<u32>);
+        let mut next = || date.next().and_then(std::result::Result::ok);
+        let (year, month, day) = (next(), next(), next());
+
+        let (time, fraction) = time.split_once('.').unwrap_or((time, "0"));
+        let mut time = time.splitn(3, ':').map(str::parse::<u8>);
+        let mut next = || time.next().and_then(std::result::Result::ok);
+        let (hour, minute, second) = (next(), next(), next());
+
+        // Fractions are given in microseconds, but be lenient about digits.
+        let microsecond = format!("{fraction:0<6}")
+            .get(..6)
+            .and_then(|us| us.parse::<u32>().ok());
+
+        let year = year
+            .and_then(|y| i32::try_from(y).ok())
+            .ok_or_else(invalid)?;
+        Ok(Parts {
+            year: if negative { -year } else { year },
+            month: month
+                .and_then(|m| u8::try_from(m).ok())
+                .ok_or_else(invalid)?,
+            day: day.and_then(|d| u8::try_from(d).ok()).ok_or_else(invalid)?,
+            hour: hour.ok_or_else(invalid)?,
+            minute: minute.ok_or_else(invalid)?,
+            second: second.ok_or_else(invalid)?,
+            microsecond: microsecond.ok_or_else(invalid)?,
+            offset: self.utc_offset()?,
+        })
+    }
+
+    /// Assemble from date, time and offset.
+    ///
+    /// UTC is given as identifier, other offsets as such.
+    fn from_parts(parts: &Parts) -> Self {
+        let year = if parts.year < 0 {
+            format!("-{:04}", parts.year.unsigned_abs())
+        } else {
+            format!("{:04}", parts.year)
+        };
+        let date = format!(
+            "{year}-{:02}-{:02} {:02}:{:02}:{:02}.{:06}",
+            parts.month, parts.day, parts.hour, parts.minute, parts.second, parts.microsecond
+        );
+
+        if parts.offset == 0 {
+            PhpDateTime::new(date, TIMEZONE_IDENTIFIER, "UTC")
+        } else {
+            PhpDateTime::new(date, TIMEZONE_OFFSET, format_offset(parts.offset))
+        }
+    }
+}
+
+impl Serialize for PhpDateTime {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(4))?;
+        map.serialize_entry(CLASS_KEY, self.class())?;
+        map.serialize_entry("date", &self.date)?;
+        map.serialize_entry("timezone_type", &self.timezone_type)?;
+        map.serialize_entry("timezone", &self.timezone)?;
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for PhpDateTime {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // Going through `Value` keeps the class name.
+        let value = Value::deserialize(deserializer)?;
+
+        let field = |name: &'static str| {
+            value
+                .get(name)
+                .ok_or_else(|| de::Error::missing_field(name))
+        };
+        let invalid = |name: &'static str| de::Error::custom(format!("invalid `{name}`"));
+
+        Ok(PhpDateTime {
+            immutable: value
+                .class()
+                .is_some_and(|class| class.eq_ignore_ascii_case(DATE_TIME_IMMUTABLE)),
+            date: field("date")?
+                .as_str()
+                .ok_or_else(|| invalid("date"))?
+                .to_owned(),
+            timezone_type: field("timezone_type")?
+                .as_i64()
+                .ok_or_else(|| invalid("timezone_type"))?,
+            timezone: field("timezone")?
+                .as_str()
+                .ok_or_else(|| invalid("timezone"))?
+                .to_owned(),
+        })
+    }
+}
+
+/// Mapping to `chrono::DateTime`, for use with `#[serde(with = "...")]`.
+///
+/// ```rust
+/// use chrono::{DateTime, TimeZone, Utc};
+/// use php_serde::{from_bytes, to_vec};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Debug, Deserialize, PartialEq, Serialize)]
+/// struct Post {
+///     #[serde(with = "php_serde::datetime::chrono")]
+///     created: DateTime<Utc>,
+/// }
+///
+/// let input = br#"a:1:{s:7:"created";O:8:"DateTime":3:{s:4:"date";s:26:"2021-03-04 05:06:07.500000";s:13:"timezone_type";i:3;s:8:"timezone";s:3:"UTC";}}"#;
+///
+/// let post: Post = from_bytes(input).unwrap();
+/// assert_eq!(
+///     post.created,
+///     Utc.with_ymd_and_hms(2021, 3, 4, 5, 6, 7).unwrap()
+///         + chrono::Duration::milliseconds(500)
+/// );
+/// assert_eq!(to_vec(&post).unwrap(), &input[..]);
+/// ```
+#[cfg(feature = "chrono")]
+pub mod chrono {
+    use super::{Parts, PhpDateTime};
+    use crate::error::{Error, Result};
+    use ::chrono::{DateTime, Datelike, FixedOffset, NaiveDate, Offset, TimeZone, Timelike};
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+    use std::convert::TryFrom;
+
+    impl<Tz: TimeZone> From<&DateTime<Tz>> for PhpDateTime {
+        #[allow(clippy::cast_possible_truncation)]
+        fn from(dt: &DateTime<Tz>) -> Self {
+            let local = dt.naive_local();
+            PhpDateTime::from_parts(&Parts {
+                year: local.year(),
+                month: local.month() as u8,
+                day: local.day() as u8,
+                hour: local.hour() as u8,
+                minute: local.minute() as u8,
+                second: local.second() as u8,
+                // Leap seconds are not representable in PHP.
+                microsecond: local.nanosecond().min(999_999_999) / 1000,
+                offset: dt.offset().fix().local_minus_utc(),
+            })
+        }
+    }
+
+    impl TryFrom<&PhpDateTime> for DateTime<FixedOffset> {
+        type Error = Error;
+
+        fn try_from(dt: &PhpDateTime) -> Result<Self> {
+            let parts = dt.parts()?;
+            let invalid = || Error::InvalidDateTime(format!("invalid date: {}", dt.date));
+
+            let naive =
+                NaiveDate::from_ymd_opt(parts.year, u32::from(parts.month), u32::from(parts.day))
+                    .and_then(|date| {
+                        date.and_hms_micro_opt(
+                            u32::from(parts.hour),
+                            u32::from(parts.minute),
+                            u32::from(parts.second),
+                            parts.microsecond,
+                        )
+                    })
+                    .ok_or_else(invalid)?;
+
+            FixedOffset::east_opt(parts.offset)
+                .and_then(|offset| offset.from_local_datetime(&naive).single())
+                .ok_or_else(invalid)
+        }
+    }
+
+    /// Serialize a `DateTime` as a PHP `DateTime` object.
+    pub fn serialize<S, Tz>(
+        dt: &DateTime<Tz>,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        Tz: TimeZone,
+    {
+        PhpDateTime::from(dt).serialize(serializer)
+    }
+
+    /// Deserialize a `DateTime` from a PHP `DateTime` object.
+    pub fn deserialize<'de, D, Tz>(deserializer: D) -> std::result::Result<DateTime<Tz>, D::Error>
+    where
+        D: Deserializer<'de>,
+        Tz: TimeZone,
+        DateTime<Tz>: From<DateTime<FixedOffset>>,
+    {
+        let dt = PhpDateTime::deserialize(deserializer)?;
+        DateTime::<FixedOffset>::try_from(&dt)
+            .map(Into::into)
+            .map_err(de::Error::custom)
+    }
+}
+
+/// Mapping to `time::OffsetDateTime`, for use with `#[serde(with = "...")]`.
+///
+/// ```rust
+/// use php_serde::{from_bytes, to_vec};
+/// use serde::{Deserialize, Serialize};
+/// use time::{Date, Month, OffsetDateTime, UtcOffset};
+///
+/// #[derive(Debug, Deserialize, PartialEq, Serialize)]
+/// struct Post {
+///     #[serde(with = "php_serde::datetime::time")]
+///     created: OffsetDateTime,
+/// }
+///
+/// let input = br#"a:1:{s:7:"created";O:17:"DateTimeImmutable":3:{s:4:"date";s:26:"2021-03-04 05:06:07.000000";s:13:"timezone_type";i:1;s:8:"timezone";s:6:"-05:30";}}"#;
+///
+/// let post: Post = from_bytes(input).unwrap();
+/// let expected = Date::from_calendar_date(2021, Month::March, 4)
+///     .unwrap()
+///     .with_hms(5, 6, 7)
+///     .unwrap()
+///     .assume_offset(UtcOffset::from_hms(-5, -30, 0).unwrap());
+/// assert_eq!(post.created, expected);
+/// ```
+#[cfg(feature = "time")]
+pub mod time {
+    use super::{Parts, PhpDateTime};
+    use crate::error::{Error, Result};
+    use ::time::{Date, Month, OffsetDateTime, Time, UtcOffset};
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+    use std::convert::TryFrom;
+
+    impl From<&OffsetDateTime> for PhpDateTime {
+        fn from(dt: &OffsetDateTime) -> Self {
+            PhpDateTime::from_parts(&Parts {
+                year: dt.year(),
+                month: u8::from(dt.month()),
+                day: dt.day(),
+                hour: dt.hour(),
+                minute: dt.minute(),
+                second: dt.second(),
+                microsecond: dt.microsecond(),
+                offset: dt.offset().whole_seconds(),
+            })
+        }
+    }
+
+    impl TryFrom<&PhpDateTime> for OffsetDateTime {
+        type Error = Error;
+
+        fn try_from(dt: &PhpDateTime) -> Result<Self> {
+            let parts = dt.parts()?;
+            let invalid = |e: ::time::error::ComponentRange| {
+                Error::InvalidDateTime(format!("invalid date: {}: {e}", dt.date))
+            };
+
+            let month = Month::try_from(parts.month).map_err(invalid)?;
+            let date = Date::from_calendar_date(parts.year, month, parts.day).map_err(invalid)?;
+            let time =
+                Time::from_hms_micro(parts.hour, parts.minute, parts.second, parts.microsecond)
+                    .map_err(invalid)?;
+            let offset = UtcOffset::from_whole_seconds(parts.offset).map_err(invalid)?;
+
+            Ok(date.with_time(time).assume_offset(offset))
+        }
+    }
+
+    /// Serialize an `OffsetDateTime` as a PHP `DateTime` object.
+    pub fn serialize<S>(dt: &OffsetDateTime, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        PhpDateTime::from(dt).serialize(serializer)
+    }
+
+    /// Deserialize an `OffsetDateTime` from a PHP `DateTime` object.
+    pub fn deserialize<'de, D>(deserializer: D) -> std::result::Result<OffsetDateTime, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let dt = PhpDateTime::deserialize(deserializer)?;
+        OffsetDateTime::try_from(&dt).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_offset, parse_offset, Parts, PhpDateTime};
+    use crate::{from_bytes, to_vec};
+
+    #[test]
+    fn offsets() {
+        assert_eq!(parse_offset("+02:00"), Some(7200));
+        assert_eq!(parse_offset("-05:30"), Some(-19800));
+        assert_eq!(parse_offset("02:00"), None);
+        assert_eq!(format_offset(-19800), "-05:30");
+        assert_eq!(format_offset(0), "+00:00");
+
+        assert_eq!(PhpDateTime::new("", 2, "cest").utc_offset().unwrap(), 7200);
+        assert_eq!(PhpDateTime::new("", 3, "UTC").utc_offset().unwrap(), 0);
+        assert!(PhpDateTime::new("", 3, "Europe/Berlin")
+            .utc_offset()
+            .is_err());
+    }
+
+    #[test]
+    fn parts() {
+        let parts = Parts {
+            year: -44,
+            month: 3,
+            day: 15,
+            hour: 12,
+            minute: 0,
+            second: 1,
+            microsecond: 42,
+            offset: 3600,
+        };
+
+        let dt = PhpDateTime::from_parts(&parts);
+        assert_eq!(
+            dt,
+            PhpDateTime::new("-0044-03-15 12:00:01.000042", 1, "+01:00")
+        );
+        assert_eq!(dt.parts().unwrap(), parts);
+
+        assert!(PhpDateTime::new("2021-03-04", 3, "UTC").parts().is_err());
+        assert!(PhpDateTime::new("2021-03-04 25:00", 3, "UTC")
+            .parts()
+            .is_err());
+    }
+
+    #[test]
+    fn immutable() {
+        let input = br#"O:17:"DateTimeImmutable":3:{s:4:"date";s:26:"2000-01-01 00:00:00.000000";s:13:"timezone_type";i:3;s:8:"timezone";s:3:"UTC";}"#;
+
+        let dt: PhpDateTime = from_bytes(input).unwrap();
+        assert!(dt.immutable);
+        assert_eq!(to_vec(&dt).unwrap(), &input[..]);
+    }
+}
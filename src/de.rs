@@ -1,23 +1,145 @@
 //! PHP deserialization.
 
+use crate::allowed_classes;
 use crate::error::{Error, Result};
+use crate::input::{BufferInput, Input, SliceInput};
+use crate::key::{self, PhpKey};
+use crate::lenient::Lenient;
+use crate::numeric::{self, Number};
+use crate::options::{CyclicReferences, DeserializerOptions, DuplicateKeys, SerializerOptions};
+use crate::token::{Key, Token};
+use crate::validate::Report;
+use crate::value::{self, Value};
+use crate::{lenient, php_enum, positional, property, spl};
+use serde::de::value::{BorrowedStrDeserializer, MapDeserializer, SeqDeserializer};
 use serde::de::MapAccess;
-use serde::de::{Deserialize, DeserializeSeed, IntoDeserializer, SeqAccess, Visitor};
+use serde::de::{
+    self, Deserialize, DeserializeOwned, DeserializeSeed, EnumAccess, IgnoredAny, IntoDeserializer,
+    SeqAccess, Unexpected, VariantAccess, Visitor,
+};
 use serde::{forward_to_deserialize_any, Deserializer};
 use smallvec::SmallVec;
-use std::collections::BTreeMap;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::convert::TryFrom;
 use std::io;
 use std::io::{BufRead, Read};
+use std::ops::Range;
 
 /// Deserialize from byte slice.
 pub fn from_bytes<'de, T>(s: &'de [u8]) -> Result<T>
 where
     T: Deserialize<'de>,
 {
-    let buffered = io::BufReader::new(s);
-    let mut des = PhpDeserializer::new(buffered);
-    let value = T::deserialize(&mut des)?;
+    let mut des = PhpDeserializer::from_slice(s);
+    let value = T::deserialize(&mut des).map_err(|e| e.at(des.byte_offset()))?;
+    Ok(value)
+}
+
+/// Deserialize from an `io::Read` source, such as a file or socket.
+///
+/// Input is buffered internally, so there is no need to wrap `reader` in a
+/// `BufReader`. Strings are copied out of the input, which rules out
+/// borrowed targets such as `&str`.
+///
+/// ```rust
+/// use php_serde::from_reader;
+///
+/// let input: &[u8] = br#"a:2:{i:0;s:1:"a";i:1;s:1:"b";}"#;
+/// let v: Vec<String> = from_reader(input).unwrap();
+/// assert_eq!(v, vec!["a", "b"]);
+/// ```
+pub fn from_reader<R, T>(reader: R) -> Result<T>
+where
+    R: Read,
+    T: DeserializeOwned,
+{
+    let mut des = PhpDeserializer::new(io::BufReader::new(reader));
+    let value = T::deserialize(&mut des).map_err(|e| e.at(des.byte_offset()))?;
+    Ok(value)
+}
+
+/// Deserialize from byte slice, skipping values wrapped in `Lenient` that
+/// fail, and returning their errors alongside the result.
+///
+/// Errors outside of `Lenient` values still fail the whole input.
+pub fn from_bytes_lenient<'de, T>(s: &'de [u8]) -> Result<(T, Vec<Error>)>
+where
+    T: Deserialize<'de>,
+{
+    let mut des = PhpDeserializer::from_slice(s);
+    let value = T::deserialize(&mut des).map_err(|e| e.at(des.byte_offset()))?;
+    Ok((value, des.take_errors()))
+}
+
+/// Deserialize a single value from the start of a byte slice.
+///
+/// Unlike `from_bytes`, trailing input is expected and returned alongside
+/// the value, e.g. to read values embedded in a larger record.
+///
+/// ```rust
+/// use php_serde::from_bytes_partial;
+///
+/// let (n, rest): (i64, _) = from_bytes_partial(b"i:42;\r\nEND").unwrap();
+/// assert_eq!(n, 42);
+/// assert_eq!(rest, b"\r\nEND");
+/// ```
+pub fn from_bytes_partial<'de, T>(s: &'de [u8]) -> Result<(T, &'de [u8])>
+where
+    T: Deserialize<'de>,
+{
+    let mut des = PhpDeserializer::from_slice(s);
+    let value = T::deserialize(&mut des).map_err(|e| e.at(des.byte_offset()))?;
+    let rest = &s[des.byte_offset()..];
+    Ok((value, rest))
+}
+
+/// Deserialize only the value at `path` in `s`, without reading the rest.
+///
+/// `path` lists the keys leading to the value, separated by `.`, such as
+/// `data.user.email`. Integer keys are written in decimal, e.g. `items.0`,
+/// and private and protected properties by their plain names. The empty
+/// path leads to the whole value.
+///
+/// Entries before the value are passed over using their length headers,
+/// like values ignored by `Deserialize` implementations, and the input
+/// after it is not read at all. This makes reading a single field out of
+/// a large value cheap, but also means that errors outside of the path are
+/// not noticed. Returns `None` if there is no value at `path`.
+///
+/// ```rust
+/// use php_serde::extract;
+///
+/// let input = br#"a:2:{s:4:"logs";a:1:{i:0;s:5:"a lot";}s:4:"data";a:1:{s:4:"user";a:1:{s:5:"email";s:5:"a@b.c";}}}"#;
+/// let email: Option<&str> = extract(input, "data.user.email").unwrap();
+/// assert_eq!(email, Some("a@b.c"));
+///
+/// let missing: Option<String> = extract(input, "data.user.name").unwrap();
+/// assert_eq!(missing, None);
+/// ```
+pub fn extract<'de, T>(s: &'de [u8], path: &str) -> Result<Option<T>>
+where
+    T: Deserialize<'de>,
+{
+    let mut des = PhpDeserializer::from_slice(s);
+    let found = des.seek(path).map_err(|e| e.at(des.byte_offset()))?;
+    if !found {
+        return Ok(None);
+    }
+    let value = T::deserialize(&mut des).map_err(|e| e.at(des.byte_offset()))?;
+    Ok(Some(value))
+}
+
+/// Deserialize from byte slice, with non-default options.
+pub fn from_bytes_with_options<'de, T>(s: &'de [u8], options: &DeserializerOptions) -> Result<T>
+where
+    T: Deserialize<'de>,
+{
+    let mut des = PhpDeserializer::from_slice_with_options(s, options.clone());
+    let value = T::deserialize(&mut des).map_err(|e| {
+        e.at(des.byte_offset())
+            .with_excerpt(s, options.error_context)
+    })?;
     Ok(value)
 }
 
@@ -28,16 +150,15 @@ where
 struct Lookahead1<R> {
     reader: R,
     buffer: Option<u8>,
+    /// Number of bytes consumed so far, not counting a peeked byte.
+    offset: usize,
+    /// Largest string length or element count accepted in a header.
+    max_length: usize,
+    /// Bytes consumed, if they are kept to read referenced values again.
+    record: Option<Vec<u8>>,
 }
 
 impl<R: Read> Lookahead1<R> {
-    fn new(reader: R) -> Self {
-        Lookahead1 {
-            reader,
-            buffer: None,
-        }
-    }
-
     /// Fill `buffer` with the next byte if there is one.
     ///
     /// Has no effect if `buffer` is already full.
@@ -45,7 +166,12 @@ impl<R: Read> Lookahead1<R> {
         if self.buffer.is_none() {
             self.buffer = {
                 let mut buf: [u8; 1] = [0];
-                let length = self.reader.read(&mut buf).map_err(Error::ReadSerialized)?;
+                let length = loop {
+                    match self.reader.read(&mut buf) {
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                        result => break result.map_err(Error::ReadSerialized)?,
+                    }
+                };
 
                 if length == 0 {
                     None
@@ -68,7 +194,10 @@ impl<R: Read> Lookahead1<R> {
     fn read1(&mut self) -> Result<u8> {
         self.fill()?;
 
-        self.buffer.take().ok_or(Error::UnexpectedEof)
+        let c = self.buffer.take().ok_or(Error::UnexpectedEof)?;
+        self.offset += 1;
+        self.record(&[c]);
+        Ok(c)
     }
 
     /// Expect a specific character.
@@ -84,6 +213,19 @@ impl<R: Read> Lookahead1<R> {
         }
     }
 
+    /// Expect the type indicator of a specific type of value.
+    fn expect_type(&mut self, expected: u8) -> Result<()> {
+        let actual = self.read1()?;
+        if actual == expected {
+            Ok(())
+        } else {
+            Err(Error::UnexpectedType {
+                expected: char::from(expected),
+                actual: char::from(actual),
+            })
+        }
+    }
+
     /// Reads an unsigned integer, fails on EOF and non-digit, but stops on
     /// the first invalid character after at least one digit has been read.
     fn collect_unsigned(&mut self, buf: &mut SmallVec<[u8; 32]>) -> Result<()> {
@@ -118,23 +260,96 @@ impl<R: Read> Lookahead1<R> {
         Ok(())
     }
 
+    /// Read the value of an integer, after the `i:` part.
+    fn read_integer(&mut self) -> Result<i64> {
+        let mut buf = SmallVec::new();
+        self.collect_sign(&mut buf)?;
+        self.collect_unsigned(&mut buf)?;
+        self.expect(b';')?;
+        parse_bytes(buf)
+    }
+
+    /// Read the value of a float, after the `d:` part.
+    fn read_float(&mut self) -> Result<f64> {
+        let mut buf = SmallVec::new();
+
+        // Signed like integers.
+        self.collect_sign(&mut buf)?;
+
+        // Non-finite values are spelled out, as `NAN`, `INF` or `-INF`.
+        if let Some(c @ (b'N' | b'I')) = self.peek()? {
+            let (word, v) = match (c, buf.as_slice()) {
+                (b'N', []) => ("NAN", f64::NAN),
+                (b'I', []) => ("INF", f64::INFINITY),
+                (b'I', [b'-']) => ("INF", f64::NEG_INFINITY),
+                _ => {
+                    return Err(Error::ExpectedDigit {
+                        actual: char::from(c),
+                    })
+                }
+            };
+            for &c in word.as_bytes() {
+                self.expect(c)?;
+            }
+            self.expect(b';')?;
+            return Ok(v);
+        }
+
+        self.collect_unsigned(&mut buf)?;
+
+        // PHP omits decimal dots when serializing `.0` values.
+        let dot = self.peek()?;
+
+        if dot == Some(b'.') {
+            buf.push(b'.');
+            self.expect(b'.')?;
+
+            // The remainder is another digit string without sign.
+            self.collect_unsigned(&mut buf)?;
+        }
+
+        let exp = self.peek()?;
+
+        if let Some(exp) = exp {
+            if matches!(exp, b'e' | b'E') {
+                buf.push(b'E');
+                self.expect(exp)?;
+
+                self.collect_sign(&mut buf)?;
+                self.collect_unsigned(&mut buf)?;
+            }
+        }
+
+        self.expect(b';')?;
+
+        parse_bytes(buf)
+    }
+
+    /// Read a length or element count from a header.
+    fn read_length(&mut self) -> Result<usize> {
+        let mut buf = SmallVec::new();
+        self.collect_unsigned(&mut buf)?;
+        let length = parse_bytes(buf)?;
+
+        if length > self.max_length {
+            return Err(Error::LengthLimitExceeded(length));
+        }
+
+        Ok(length)
+    }
+
     /// Read raw PHP bytestring from input.
     fn read_raw_string(&mut self) -> Result<Vec<u8>> {
         // Thankfully, PHP strings are length-delimited, even though
         // they strangely enough include quotes as well.
-        let mut buf = SmallVec::new();
-        self.collect_unsigned(&mut buf)?;
-        let length: usize = parse_bytes(buf)?;
+        let length = self.read_length()?;
 
         // Delim and opening quote:
         self.expect(b':')?;
         self.expect(b'"')?;
 
-        // Inner string data. Note that this code will happily allocate
-        // up to 4 GB of RAM on the heap.
-        let mut data = vec![0; length];
-        self.read_exact(&mut data)?;
-        debug_assert!(data.len() == length);
+        // Inner string data.
+        let data = self.read_bytes(length)?;
 
         // Closing quote.
         self.expect(b'"')?;
@@ -143,12 +358,40 @@ impl<R: Read> Lookahead1<R> {
         Ok(data)
     }
 
+    /// Read raw PHP bytestring from input, borrowing it if the input allows.
+    fn read_string_ref<'de>(&mut self) -> Result<Bytes<'de>>
+    where
+        R: Input<'de>,
+    {
+        let length = self.read_length()?;
+
+        self.expect(b':')?;
+        self.expect(b'"')?;
+
+        // Borrowing is only possible without a peeked byte.
+        let borrowed = match self.buffer {
+            None => self.reader.borrow_bytes(length),
+            Some(_) => None,
+        };
+        let data = match borrowed {
+            Some(data) => {
+                self.offset += length;
+                self.record(data);
+                Bytes::Borrowed(data)
+            }
+            None => Bytes::Copied(self.read_bytes(length)?),
+        };
+
+        self.expect(b'"')?;
+        self.expect(b';')?;
+
+        Ok(data)
+    }
+
     /// Read an array header that follows after the `b"a:"` part.
     fn read_array_header(&mut self) -> Result<usize> {
         // Read number of elements.
-        let mut buf = SmallVec::new();
-        self.collect_unsigned(&mut buf)?;
-        let num_elements = parse_bytes(buf)?;
+        let num_elements = self.read_length()?;
 
         // Read opening part of array.
         self.expect(b':')?;
@@ -157,640 +400,3851 @@ impl<R: Read> Lookahead1<R> {
         Ok(num_elements)
     }
 
-    /// Read exactly defined number of bytes.
-    fn read_exact(&mut self, mut buf: &mut [u8]) -> Result<()> {
-        // Bail early on zero-length strings.
-        if buf.is_empty() {
-            return Ok(());
-        }
+    /// Read a length-prefixed, quoted class name, including the trailing colon.
+    fn read_class_name(&mut self) -> Result<String> {
+        // The class name is length-prefixed like a string, but terminated
+        // by a colon instead of a semicolon.
+        let length = self.read_length()?;
+
+        self.expect(b':')?;
+        self.expect(b'"')?;
+        let class = self.read_bytes(length)?;
+        self.expect(b'"')?;
+        self.expect(b':')?;
+
+        String::from_utf8(class).map_err(|e| Error::NotUtf8String(e.utf8_error()))
+    }
+
+    /// Read an object header that follows after the `b"O:"` part.
+    ///
+    /// Returns the class name and the number of properties.
+    fn read_object_header(&mut self) -> Result<(String, usize)> {
+        let class = self.read_class_name()?;
+
+        // Properties follow in the same shape as array entries.
+        let num_properties = self.read_array_header()?;
+
+        Ok((class, num_properties))
+    }
+
+    /// Read a custom serialized object that follows after the `b"C:"` part.
+    ///
+    /// Returns the class name and the raw payload.
+    fn read_custom(&mut self) -> Result<(String, Vec<u8>)> {
+        let class = self.read_class_name()?;
+
+        let length = self.read_length()?;
+
+        // The payload is opaque and delimited by its length only.
+        self.expect(b':')?;
+        self.expect(b'{')?;
+        let payload = self.read_bytes(length)?;
+        self.expect(b'}')?;
+
+        Ok((class, payload))
+    }
+
+    /// Read an enum case that follows after the `b"E:"` part.
+    ///
+    /// Returns the class name and the case name.
+    fn read_enum_case(&mut self) -> Result<(String, String)> {
+        // The record is shaped like a string, with a colon between class and
+        // case name.
+        let record = self.read_raw_string()?;
+        let (class, case) = php_enum::split_case(&record).ok_or_else(|| {
+            Error::DeserializationFailed(format!(
+                "invalid enum case: {}",
+                String::from_utf8_lossy(&record)
+            ))
+        })?;
+
+        let to_string = |s: &[u8]| {
+            String::from_utf8(s.to_vec()).map_err(|e| Error::NotUtf8String(e.utf8_error()))
+        };
+        Ok((to_string(class)?, to_string(case)?))
+    }
+
+    /// Read exactly `length` bytes.
+    ///
+    /// Declared lengths are not trusted: the buffer grows as data arrives,
+    /// instead of being allocated up front.
+    fn read_bytes(&mut self, length: usize) -> Result<Vec<u8>> {
+        const INITIAL_CAPACITY: usize = 64 * 1024;
+
+        let mut data = Vec::with_capacity(length.min(INITIAL_CAPACITY));
 
-        // If we have buffered a character, move it to buf.
-        if let Some(c) = self.buffer.take() {
-            buf[0] = c;
-            buf = &mut buf[1..];
+        // If we have buffered a character, move it to data.
+        if length > 0 {
+            data.extend(self.buffer.take());
         }
 
         // We can now read the remainder.
-        self.reader.read_exact(buf).map_err(Error::ReadSerialized)
+        let remainder = (length - data.len()) as u64;
+        (&mut self.reader)
+            .take(remainder)
+            .read_to_end(&mut data)
+            .map_err(Error::ReadSerialized)?;
+
+        if data.len() < length {
+            return Err(Error::UnexpectedEof);
+        }
+
+        self.offset += length;
+        self.record(&data);
+        Ok(data)
+    }
+
+    /// Skip exactly `length` bytes, without keeping them.
+    fn skip_bytes<'de>(&mut self, length: usize) -> Result<()>
+    where
+        R: Input<'de>,
+    {
+        let buffered = if length > 0 { self.buffer.take() } else { None };
+        if let Some(c) = buffered {
+            self.record(&[c]);
+        }
+
+        let remainder = length - usize::from(buffered.is_some());
+        if buffered.is_none() {
+            if let Some(data) = self.reader.borrow_bytes(remainder) {
+                self.offset += length;
+                self.record(data);
+                return Ok(());
+            }
+        }
+
+        let mut reader = (&mut self.reader).take(remainder as u64);
+        let skipped = match &mut self.record {
+            Some(record) => io::copy(&mut reader, record),
+            None => io::copy(&mut reader, &mut io::sink()),
+        }
+        .map_err(Error::ReadSerialized)?;
+        if skipped < remainder as u64 {
+            return Err(Error::UnexpectedEof);
+        }
+
+        self.offset += length;
+        Ok(())
+    }
+
+    /// Skip a length-prefixed, quoted string, followed by `delim`, returning
+    /// its length.
+    fn skip_string<'de>(&mut self, delim: u8) -> Result<usize>
+    where
+        R: Input<'de>,
+    {
+        let length = self.read_length()?;
+        self.expect(b':')?;
+        self.expect(b'"')?;
+        self.skip_bytes(length)?;
+        self.expect(b'"')?;
+        self.expect(delim)?;
+        Ok(length)
+    }
+
+    /// Keep consumed bytes, if they are recorded.
+    fn record(&mut self, data: &[u8]) {
+        if let Some(record) = &mut self.record {
+            record.extend_from_slice(data);
+        }
+    }
+
+    /// Skip everything up to and including `delim`.
+    fn skip_until(&mut self, delim: u8) -> Result<()> {
+        while self.read1()? != delim {}
+        Ok(())
     }
 }
 
+/// String data, borrowed from the input or copied out of it.
+enum Bytes<'de> {
+    Borrowed(&'de [u8]),
+    Copied(Vec<u8>),
+}
+
 /// PHP deserializer.
 ///
-/// Deserializes the format used by PHP's `serialize` function.
+/// Deserializes the format used by PHP's `serialize` function, from any
+/// `BufRead`, or from a slice via `from_slice`. Only the latter supports
+/// borrowed targets such as `&'de str` or `Cow<'de, [u8]>`.
 #[derive(Debug)]
 pub struct PhpDeserializer<R> {
     input: Lookahead1<R>,
+    options: DeserializerOptions,
+    /// Nesting depth of the value being read.
+    depth: usize,
+    /// Whether the struct deserialized next is read from a numeric array,
+    /// see `Positional`.
+    positional: bool,
+    /// Errors of values skipped by `Lenient`.
+    errors: Vec<Error>,
+    /// Where each value of the input recorded by `input` starts, the value
+    /// numbered `N` at index `N - 1`, with
+    /// `DeserializerOptions::resolve_references`.
+    starts: Option<Vec<usize>>,
+    /// Offset in the input at which the recorded input starts.
+    record_offset: usize,
+    /// Whether recorded input is read again, whose values are not numbered
+    /// again.
+    replaying: bool,
 }
 
 impl<R> PhpDeserializer<R>
 where
     R: BufRead,
 {
-    fn new(input: R) -> PhpDeserializer<R> {
+    /// Create a deserializer reading from `input`, with default options.
+    pub fn new(input: R) -> PhpDeserializer<R> {
+        PhpDeserializer::with_options(input, DeserializerOptions::default())
+    }
+
+    /// Create a deserializer reading from `input`.
+    pub fn with_options(input: R, options: DeserializerOptions) -> PhpDeserializer<R> {
+        PhpDeserializer::from_input(input, options)
+    }
+}
+
+impl<'de> PhpDeserializer<SliceInput<'de>> {
+    /// Create a deserializer borrowing from `input`, with default options.
+    pub fn from_slice(input: &'de [u8]) -> Self {
+        PhpDeserializer::from_slice_with_options(input, DeserializerOptions::default())
+    }
+
+    /// Create a deserializer borrowing from `input`.
+    pub fn from_slice_with_options(input: &'de [u8], options: DeserializerOptions) -> Self {
+        PhpDeserializer::from_input(SliceInput::new(input), options)
+    }
+
+    /// Create a deserializer borrowing from `input`, a part of a larger
+    /// record starting at `offset`, so that positions count from the start
+    /// of the record.
+    pub(crate) fn from_slice_at(input: &'de [u8], offset: usize) -> Self {
+        let mut de = PhpDeserializer::from_slice(input);
+        de.input.offset = offset;
+        de
+    }
+}
+
+impl<R> PhpDeserializer<R> {
+    fn from_input(input: R, options: DeserializerOptions) -> Self {
         PhpDeserializer {
-            input: Lookahead1::new(input),
+            input: Lookahead1 {
+                reader: input,
+                buffer: None,
+                offset: 0,
+                max_length: options.max_length,
+                record: options.resolve_references.then(Vec::new),
+            },
+            starts: options.resolve_references.then(Vec::new),
+            record_offset: 0,
+            options,
+            depth: 0,
+            positional: false,
+            errors: Vec::new(),
+            replaying: false,
         }
     }
 
-    fn peek(&mut self) -> Result<Option<u8>> {
-        self.input.peek()
+    /// Number of bytes consumed from the input so far.
+    pub fn byte_offset(&self) -> usize {
+        self.input.offset
     }
-}
 
-/// Parse a byte string using any `FromStr` function.
-fn parse_bytes<E, T: std::str::FromStr<Err = E>, B: AsRef<[u8]>>(buf: B) -> Result<T>
-where
-    E: std::fmt::Display + std::error::Error + Send + Sync + 'static,
-{
-    let s = std::str::from_utf8(buf.as_ref()).map_err(Error::NotUtf8String)?;
-    s.parse()
-        .map_err(|e: E| Error::NotAValidNumber(Box::new(e)))
+    /// Take the errors of values skipped by `Lenient` so far, in the order
+    /// they occurred.
+    pub fn take_errors(&mut self) -> Vec<Error> {
+        std::mem::take(&mut self.errors)
+    }
 }
 
-impl<'a, 'de, R> Deserializer<'de> for &'a mut PhpDeserializer<R>
+impl<'de, R> PhpDeserializer<R>
 where
-    R: BufRead,
+    R: Input<'de>,
 {
-    type Error = Error;
+    /// Skip whitespace between values. Returns `false` at the end of the input.
+    pub(crate) fn skip_whitespace(&mut self) -> Result<bool> {
+        while let Some(c) = self.input.peek()? {
+            if !c.is_ascii_whitespace() {
+                return Ok(true);
+            }
+            self.input.read1()?;
+        }
 
-    #[inline]
-    fn deserialize_any<V>(mut self, visitor: V) -> Result<V::Value>
+        Ok(false)
+    }
+
+    /// Returns the SPL container kind of a class, if it is allowed.
+    fn spl_container(&self, class: &str) -> Option<spl::Container> {
+        spl::Container::from_class(class).filter(|_| self.options.allowed_classes.allows(class))
+    }
+
+    /// Read an array key, which is either an integer or a string.
+    fn read_key(&mut self) -> Result<PhpKey> {
+        match self.input.read1()? {
+            b'i' => {
+                self.input.expect(b':')?;
+                Ok(PhpKey::Int(self.input.read_integer()?))
+            }
+            b's' => {
+                self.input.expect(b':')?;
+                Ok(PhpKey::String(self.input.read_raw_string()?))
+            }
+            c => Err(Error::UnsupportedArrayKeyType(char::from(c))),
+        }
+    }
+
+    /// Read the digits of a 128-bit integer written as a string, since PHP
+    /// integers cannot hold it. Returns `None` for other types.
+    fn read_wide_integer<V>(&mut self, visitor: &V) -> Result<Option<String>>
     where
         V: Visitor<'de>,
     {
-        // All fields start with a type, followed by a colon.
-        let sym = self.input.read1()?;
-
-        if sym == b'N' {
-            // `null` is a special case, since it is not followed by a colon.
-            self.input.expect(b';')?;
-            return visitor.visit_unit();
+        if self.input.peek()? != Some(b's') {
+            return Ok(None);
         }
 
+        self.input.expect(b's')?;
         self.input.expect(b':')?;
+        let raw = self.input.read_raw_string()?;
+        match String::from_utf8(raw) {
+            Ok(digits) => Ok(Some(digits)),
+            Err(e) => Err(de::Error::invalid_value(
+                Unexpected::Bytes(e.as_bytes()),
+                visitor,
+            )),
+        }
+    }
 
-        // See https://stackoverflow.com/questions/14297926/structure-of-a-serialized-php-string
-        match sym {
-            b'b' => {
-                let value = self.input.read1()?;
-                self.input.expect(b';')?;
+    /// Deserialize an integer or float, `integer` tells which one is wanted.
+    ///
+    /// With `DeserializerOptions::numeric_strings`, strings that are numeric
+    /// as a whole are accepted as well. With `DeserializerOptions::float_to_int`,
+    /// floats without fractional part are accepted for integers.
+    fn deserialize_number<V>(&mut self, integer: bool, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.at_reference() {
+            return self.resolve_reference(|de| de.deserialize_number(integer, visitor));
+        }
 
-                // Boolean.
-                match value {
-                    b'0' => visitor.visit_bool(false),
-                    b'1' => visitor.visit_bool(true),
-                    c => Err(Error::InvalidBooleanValue(char::from(c))),
+        let float_to_int = integer && self.options.float_to_int;
+
+        let number = match self.input.peek()? {
+            Some(b's') if self.options.numeric_strings => {
+                self.input.expect(b's')?;
+                self.input.expect(b':')?;
+                let raw = self.input.read_raw_string()?;
+                match numeric::parse_numeric(&raw) {
+                    Some((number, true)) => number,
+                    _ => return Err(de::Error::invalid_value(Unexpected::Bytes(&raw), &visitor)),
                 }
             }
-            b'i' => {
-                // Integer.
-                let mut buf = SmallVec::new();
+            Some(b'd') if float_to_int => Number::Float(f64::deserialize(&mut *self)?),
+            _ => return self.deserialize_any(visitor),
+        };
 
-                // Collect a potential sign, followed by the unsigned digits.
-                self.input.collect_sign(&mut buf)?;
-                self.input.collect_unsigned(&mut buf)?;
+        match number {
+            Number::Int(n) => visitor.visit_i64(n),
+            Number::Float(n) if float_to_int => visit_whole_float(n, visitor),
+            Number::Float(n) => visitor.visit_f64(n),
+        }
+    }
 
-                // Terminating semicolon.
-                self.input.expect(b';')?;
+    /// Deserialize a boolean.
+    ///
+    /// With `DeserializerOptions::loose_bools`, `0` and `1` are accepted as
+    /// integers and strings as well, and so is the empty string for `false`.
+    fn deserialize_loose_bool<V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.at_reference() {
+            return self.resolve_reference(|de| de.deserialize_loose_bool(visitor));
+        }
 
-                // Finally, pass to visitor.
-                visitor.visit_i64(parse_bytes(buf)?)
+        if !self.options.loose_bools || !matches!(self.input.peek()?, Some(b'i' | b's')) {
+            return self.deserialize_any(visitor);
+        }
+
+        match self.read_key()? {
+            PhpKey::Int(0) => visitor.visit_bool(false),
+            PhpKey::Int(1) => visitor.visit_bool(true),
+            PhpKey::String(s) => match s.as_slice() {
+                b"" | b"0" => visitor.visit_bool(false),
+                b"1" => visitor.visit_bool(true),
+                _ => Err(de::Error::invalid_value(Unexpected::Bytes(&s), &visitor)),
+            },
+            PhpKey::Int(n) => Err(de::Error::invalid_value(Unexpected::Signed(n), &visitor)),
+        }
+    }
+
+    /// Run `f` on a value nested one level deeper, enforcing
+    /// `DeserializerOptions::max_depth`.
+    fn nested<T, F>(&mut self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Self) -> Result<T>,
+    {
+        if self.depth >= self.options.max_depth {
+            return Err(Error::DepthLimitExceeded);
+        }
+
+        self.depth += 1;
+        let rval = f(self);
+        self.depth -= 1;
+        rval
+    }
+
+    /// Options for deserializing a payload nested at the current depth.
+    fn nested_options(&self) -> DeserializerOptions {
+        let mut options = self.options.clone();
+        options.max_depth = options.max_depth.saturating_sub(self.depth);
+        options
+    }
+
+    /// Serialize a value read from the input, to deserialize it again with
+    /// `nested_options`, so that it is not limited in depth by the serializer
+    /// more than by the deserializer.
+    fn buffer(&self, value: &Value) -> Result<Vec<u8>> {
+        let options = SerializerOptions::new().max_depth(self.nested_options().max_depth);
+        crate::to_vec_with_options(value, &options)
+    }
+
+    /// Number the value about to be read, and check whether it is a
+    /// reference, with `DeserializerOptions::resolve_references`.
+    ///
+    /// Values are numbered by where they start, so that numbering a value
+    /// again as it is passed from one `deserialize_*` method to another has
+    /// no effect.
+    fn at_reference(&mut self) -> bool {
+        let (Some(starts), Some(record)) = (&mut self.starts, &mut self.input.record) else {
+            return false;
+        };
+
+        let start = record.len();
+        if !self.replaying && starts.last() != Some(&start) {
+            // Values at the top are numbered on their own, like separate
+            // calls to `serialize`.
+            if self.depth == 0 {
+                record.clear();
+                starts.clear();
+                self.record_offset = self.input.offset;
             }
-            b'd' => {
-                // Float.
-                let mut buf = SmallVec::new();
+            starts.push(record.len());
+        }
 
-                // Same as integer:
-                self.input.collect_sign(&mut buf)?;
-                self.input.collect_unsigned(&mut buf)?;
+        // Read errors are left to the read that follows.
+        matches!(self.input.peek(), Ok(Some(b'r' | b'R')))
+    }
+
+    /// Read a reference, returning where the value it refers to is
+    /// recorded, or `None` for a cycle to be broken with `null`.
+    fn read_reference(&mut self) -> Result<Option<Range<usize>>> {
+        let position = self.input.offset - self.record_offset;
+        let sym = self.input.read1()?;
+        self.input.expect(b':')?;
+        let mut buf = SmallVec::new();
+        self.input.collect_unsigned(&mut buf)?;
+        self.input.expect(b';')?;
+        let number: usize = parse_bytes(buf)?;
+
+        let (Some(starts), Some(record)) = (&mut self.starts, &self.input.record) else {
+            return Err(Error::InvalidTypeIndicator(char::from(sym)));
+        };
 
-                // PHP omits decimal dots when serializing `.0` values.
-                let dot = self.input.peek()?;
+        // Unlike `r:`, `R:` is not numbered itself.
+        if sym == b'R' && !self.replaying {
+            starts.pop();
+        }
+
+        let start = number
+            .checked_sub(1)
+            .and_then(|index| starts.get(index))
+            .copied()
+            .ok_or(Error::InvalidReference(number))?;
+
+        // Values containing the reference end after it, or after what was
+        // recorded so far if they are still being read. The same goes for
+        // copies, which are read at the position of the original.
+        let options = DeserializerOptions::new().max_depth(self.options.max_depth);
+        let mut scan = PhpDeserializer::from_slice_with_options(&record[start..], options);
+        scan.replaying = true;
+        let end = match scan.skip_value() {
+            Ok(()) => start + scan.byte_offset(),
+            Err(Error::UnexpectedEof) => usize::MAX,
+            Err(e) => return Err(e),
+        };
+
+        if end > position {
+            return match self.options.cyclic_references {
+                CyclicReferences::Error => Err(Error::CyclicReference(number)),
+                CyclicReferences::Null => Ok(None),
+            };
+        }
+        Ok(Some(start..end))
+    }
+
+    /// Read a reference, and deserialize a copy of the value it refers to
+    /// with `f`.
+    ///
+    /// Kept out of line, so that the copy's deserializer does not add to
+    /// the stack frames of the `deserialize_*` methods calling this.
+    #[inline(never)]
+    fn resolve_reference<T, F>(&mut self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut PhpDeserializer<BufferInput>) -> Result<T>,
+    {
+        let range = self.read_reference()?;
+        let record = self.input.record.take().unwrap_or_default();
+
+        // The copy is read with the values recorded so far, to resolve the
+        // references it contains in turn. What it adds is dropped again.
+        let length = record.len();
+        let (copy, start) = match range {
+            Some(range) => (record[range.clone()].to_vec(), range.start),
+            None => (b"N;".to_vec(), length),
+        };
+        let mut de = PhpDeserializer::from_input(BufferInput::new(copy), self.nested_options());
+        de.input.offset = self.record_offset + start;
+        de.input.record = Some(record);
+        de.starts = self.starts.take();
+        de.record_offset = self.record_offset;
+        de.replaying = true;
+        de.errors = self.take_errors();
+
+        // Errors are positioned in the original.
+        let rval = f(&mut de).map_err(|e| e.at(de.byte_offset()));
+        self.input.record = de.input.record.take().map(|mut record| {
+            record.truncate(length);
+            record
+        });
+        self.starts = de.starts.take();
+        self.errors = de.errors;
+        rval
+    }
+
+    /// Skip a complete value, for `deserialize_ignored_any`.
+    ///
+    /// Only headers are parsed, to find where the value ends: strings are
+    /// passed over instead of being copied, and numbers are not checked.
+    fn skip_value(&mut self) -> Result<()> {
+        if self.at_reference() {
+            return self.read_reference().map(|_| ());
+        }
+
+        let sym = self.input.read1()?;
+        if sym == b'N' {
+            return self.input.expect(b';');
+        }
+        self.input.expect(b':')?;
 
-                if let Some(b'.') = dot {
-                    buf.push(b'.');
-                    self.input.expect(b'.')?;
+        let num_entries = match sym {
+            b'b' | b'i' | b'd' => return self.input.skip_until(b';'),
+            b'r' | b'R' if self.replaying => return self.input.skip_until(b';'),
+            b's' | b'E' => return self.input.skip_string(b';').map(|_| ()),
+            b'C' => {
+                self.input.skip_string(b':')?;
+                let length = self.input.read_length()?;
+                self.input.expect(b':')?;
+                self.input.expect(b'{')?;
+                self.input.skip_bytes(length)?;
+                return self.input.expect(b'}');
+            }
+            b'a' => self.input.read_array_header()?,
+            b'O' => {
+                self.input.skip_string(b':')?;
+                self.input.read_array_header()?
+            }
+            c => return Err(Error::InvalidTypeIndicator(char::from(c))),
+        };
 
-                    // The remainder is another digit string without sign.
-                    self.input.collect_unsigned(&mut buf)?;
+        for _ in 0..num_entries {
+            // Keys are not numbered like values.
+            match self.input.read1()? {
+                b'i' => {
+                    self.input.expect(b':')?;
+                    self.input.skip_until(b';')?;
                 }
+                b's' => {
+                    self.input.expect(b':')?;
+                    self.input.skip_string(b';')?;
+                }
+                c => return Err(Error::UnsupportedArrayKeyType(char::from(c))),
+            }
+            self.nested(Self::skip_value)?;
+        }
+        self.input.expect(b'}')
+    }
 
-                let exp = self.input.peek()?;
+    /// Move to the value at `path`, keys separated by `.`, for `extract`.
+    /// Returns `false` if there is no such value.
+    ///
+    /// Entries with other keys are passed over like by `skip_value`.
+    fn seek(&mut self, path: &str) -> Result<bool> {
+        if path.is_empty() {
+            return Ok(true);
+        }
 
-                if let Some(exp) = exp {
-                    if matches!(exp, b'e' | b'E') {
-                        buf.push(b'E');
-                        self.input.expect(exp)?;
+        for segment in path.split('.') {
+            let object = match self.input.read1()? {
+                b'a' => false,
+                b'O' => {
+                    self.input.expect(b':')?;
+                    self.input.skip_string(b':')?;
+                    true
+                }
+                _ => return Ok(false),
+            };
+            if !object {
+                self.input.expect(b':')?;
+            }
+            let num_entries = self.input.read_array_header()?;
 
-                        self.input.collect_sign(&mut buf)?;
-                        self.input.collect_unsigned(&mut buf)?;
+            let mut found = false;
+            for _ in 0..num_entries {
+                found = match self.input.read1()? {
+                    b'i' => {
+                        self.input.expect(b':')?;
+                        self.input.read_integer()?.to_string() == segment
+                    }
+                    b's' => {
+                        self.input.expect(b':')?;
+                        let key = self.input.read_raw_string()?;
+                        let name = if object {
+                            property::Visibility::demangle(&key).1
+                        } else {
+                            &key
+                        };
+                        name == segment.as_bytes()
                     }
+                    c => return Err(Error::UnsupportedArrayKeyType(char::from(c))),
+                };
+                if found {
+                    break;
                 }
+                self.nested(Self::skip_value)?;
+            }
+            if !found {
+                return Ok(false);
+            }
 
-                self.input.expect(b';')?;
+            if self.depth >= self.options.max_depth {
+                return Err(Error::DepthLimitExceeded);
+            }
+            self.depth += 1;
+        }
+        Ok(true)
+    }
+
+    /// Check a complete value without deserializing it, for `validate`.
+    ///
+    /// Walks the value like `skip_value`, but also checks scalars and
+    /// counts what it finds into `report`.
+    pub(crate) fn validate_value(&mut self, report: &mut Report) -> Result<()> {
+        report.values += 1;
+
+        let sym = self.input.read1()?;
+        if sym == b'N' {
+            return self.input.expect(b';');
+        }
+        self.input.expect(b':')?;
 
-                visitor.visit_f64(parse_bytes(buf)?)
+        let num_entries = match sym {
+            b'b' => {
+                let value = self.input.read1()?;
+                self.input.expect(b';')?;
+                return match value {
+                    b'0' | b'1' => Ok(()),
+                    c => Err(Error::InvalidBooleanValue(char::from(c))),
+                };
+            }
+            b'i' => {
+                return self.input.read_integer().map(|_| ());
             }
+            b'd' => return self.input.read_float().map(|_| ()),
             b's' => {
-                // PHP String.
+                report.strings += 1;
+                report.string_bytes += self.input.skip_string(b';')?;
+                return Ok(());
+            }
+            b'E' => return self.input.skip_string(b';').map(|_| ()),
+            b'C' => {
+                report.objects += 1;
+                self.input.skip_string(b':')?;
+                let length = self.input.read_length()?;
+                self.input.expect(b':')?;
+                self.input.expect(b'{')?;
+                self.input.skip_bytes(length)?;
+                return self.input.expect(b'}');
+            }
+            b'a' => {
+                report.arrays += 1;
+                self.input.read_array_header()?
+            }
+            b'O' => {
+                report.objects += 1;
+                self.input.skip_string(b':')?;
+                self.input.read_array_header()?
+            }
+            c => return Err(Error::InvalidTypeIndicator(char::from(c))),
+        };
 
-                let data = self.input.read_raw_string()?;
+        // Like the depth limit, only levels with elements count.
+        if num_entries > 0 {
+            report.max_depth = report.max_depth.max(self.depth + 1);
+        }
+        report.elements += num_entries;
 
-                // We now have the complete bytestring, no further parsing required.
-                visitor.visit_seq(serde::de::value::SeqDeserializer::new(data.into_iter()))
+        for _ in 0..num_entries {
+            match self.input.read1()? {
+                b'i' => {
+                    self.input.expect(b':')?;
+                    self.input.read_integer()?;
+                }
+                b's' => {
+                    self.input.expect(b':')?;
+                    report.strings += 1;
+                    report.string_bytes += self.input.skip_string(b';')?;
+                }
+                c => return Err(Error::UnsupportedArrayKeyType(char::from(c))),
             }
-            b'a' => {
-                // Array.
-                let num_elements = self.input.read_array_header()?;
+            self.nested(|de| de.validate_value(report))?;
+        }
+        self.input.expect(b'}')
+    }
 
-                // We support two ways of array deserialization: tuple and struct.
-                //
-                // Numeric arrays are deserialized as tuples and assumed to
-                // contain no missing keys.
-                //
-                // Associative arrays must contain only string keys and are
-                // serialized as mappings.
-                //
-                // Other variants are currently not supported and would require
-                // hashmaps and variant types.
+    /// Read the next token of a value for `Lexer`, borrowing strings from
+    /// `input`, the slice being read.
+    ///
+    /// Arrays and objects are read up to their opening brace.
+    pub(crate) fn read_token(&mut self, input: &'de [u8]) -> Result<Token<'de>> {
+        let sym = self.input.read1()?;
+        if sym == b'N' {
+            self.input.expect(b';')?;
+            return Ok(Token::Null);
+        }
+        self.input.expect(b':')?;
 
-                let rval = match self.input.peek()? {
-                    Some(b'i' | b'}') => {
-                        // Numeric or empty array.
-                        visitor.visit_seq(ArraySequence::new(&mut self, num_elements))
-                    }
-                    Some(b's') => {
-                        // Associative array.
-                        visitor.visit_map(ArrayMapping::new(&mut self, num_elements))
-                    }
-                    Some(c) => Err(Error::UnsupportedArrayKeyType(char::from(c))),
-                    None => return Err(Error::UnexpectedEof),
-                };
+        match sym {
+            b'b' => {
+                let value = self.input.read1()?;
+                self.input.expect(b';')?;
+                match value {
+                    b'0' => Ok(Token::Bool(false)),
+                    b'1' => Ok(Token::Bool(true)),
+                    c => Err(Error::InvalidBooleanValue(char::from(c))),
+                }
+            }
+            b'i' => self.input.read_integer().map(Token::Int),
+            b'd' => self.input.read_float().map(Token::Float),
+            b's' => self.borrow_string(input, b';').map(Token::Str),
+            b'E' => {
+                let record = self.borrow_string(input, b';')?;
+                let (class, case) = php_enum::split_case(record).ok_or_else(|| {
+                    Error::DeserializationFailed(format!(
+                        "invalid enum case: {}",
+                        String::from_utf8_lossy(record)
+                    ))
+                })?;
+                Ok(Token::Enum { class, case })
+            }
+            b'C' => {
+                let class = self.borrow_string(input, b':')?;
+                let length = self.input.read_length()?;
+                self.input.expect(b':')?;
+                self.input.expect(b'{')?;
+                self.input.skip_bytes(length)?;
+                let end = self.byte_offset();
                 self.input.expect(b'}')?;
-                rval
+                Ok(Token::Custom {
+                    class,
+                    payload: &input[end - length..end],
+                })
             }
+            b'a' => self.input.read_array_header().map(Token::ArrayStart),
             b'O' => {
-                // Object.
-                Err(Error::MissingFeature(
-                    "Object deserialization is not implemented, sorry.",
-                ))
+                let class = self.borrow_string(input, b':')?;
+                let len = self.input.read_array_header()?;
+                Ok(Token::ObjectStart { class, len })
+            }
+            b'r' | b'R' => {
+                let mut buf = SmallVec::new();
+                self.input.collect_unsigned(&mut buf)?;
+                self.input.expect(b';')?;
+                let number = parse_bytes(buf)?;
+                Ok(match sym {
+                    b'r' => Token::Reference(number),
+                    _ => Token::MutableReference(number),
+                })
             }
-            // Unknown character, not valid.
             c => Err(Error::InvalidTypeIndicator(char::from(c))),
         }
     }
 
-    #[inline]
-    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
-    where
-        V: Visitor<'de>,
-    {
-        // Characters are serialized as 32 bit numbers values.
-        self.input.expect(b'i')?;
-        self.input.expect(b':')?;
+    /// Read an array key or property name for `Lexer`, like `read_token`.
+    pub(crate) fn read_key_token(&mut self, input: &'de [u8]) -> Result<Key<'de>> {
+        match self.input.read1()? {
+            b'i' => {
+                self.input.expect(b':')?;
+                self.input.read_integer().map(Key::Int)
+            }
+            b's' => {
+                self.input.expect(b':')?;
+                self.borrow_string(input, b';').map(Key::Bytes)
+            }
+            c => Err(Error::UnsupportedArrayKeyType(char::from(c))),
+        }
+    }
 
-        let mut buf = SmallVec::new();
-        self.input.collect_unsigned(&mut buf)?;
-        // No sign.
+    /// Read the `}` closing an array or object, for `Lexer`.
+    pub(crate) fn read_end_token(&mut self) -> Result<()> {
+        self.input.expect(b'}')
+    }
 
-        self.input.expect(b';')?;
+    /// Skip a string followed by `delim`, returning its bytes in `input`.
+    fn borrow_string(&mut self, input: &'de [u8], delim: u8) -> Result<&'de [u8]> {
+        let length = self.input.skip_string(delim)?;
+        // Followed by the closing quote and `delim`.
+        let end = self.byte_offset() - 2;
+        Ok(&input[end - length..end])
+    }
 
-        // We parse to a 32 bit unsigned value.
-        let raw: u32 = parse_bytes(&buf)?;
-        visitor.visit_char(char::try_from(raw).map_err(Error::CharConversionFailed)?)
+    /// Read the entries of an array or properties of an object as `Value`s,
+    /// after its header.
+    fn read_entries(&mut self, num_entries: usize) -> Result<Vec<(PhpKey, Value)>> {
+        let mut entries = Vec::new();
+        for _ in 0..num_entries {
+            let key = self.read_key()?;
+            entries.push((key, self.nested(|de| Value::deserialize(de))?));
+        }
+        self.input.expect(b'}')?;
+        Ok(entries)
     }
 
-    #[inline]
-    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    /// Read the entries of an array like `read_entries`, keeping only the
+    /// last value of duplicate keys, at the position of the first one.
+    fn read_deduplicated(&mut self, num_elements: usize) -> Result<Vec<(PhpKey, Value)>> {
+        let mut entries: Vec<(PhpKey, Value)> = Vec::new();
+        let mut positions: HashMap<PhpKey, usize> = HashMap::new();
+
+        for (key, value) in self.read_entries(num_elements)? {
+            match positions.entry(key) {
+                Entry::Occupied(position) => entries[*position.get()].1 = value,
+                Entry::Vacant(position) => {
+                    entries.push((position.key().clone(), value));
+                    position.insert(entries.len() - 1);
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Read the entries of an array with `DuplicateKeys::Last`.
+    ///
+    /// Returns a deserializer over the deduplicated array, to read it with
+    /// the same options as the original input.
+    fn deduplicated(&mut self, num_elements: usize) -> Result<PhpDeserializer<BufferInput>> {
+        let entries = self.read_deduplicated(num_elements)?;
+        let buf = self.buffer(&Value::Array(entries))?;
+
+        let mut options = self.nested_options();
+        options.duplicate_keys = None;
+        Ok(PhpDeserializer::from_input(BufferInput::new(buf), options))
+    }
+
+    /// Returns `true` if arrays must be deduplicated before reading them.
+    fn keeps_last_duplicate(&self) -> bool {
+        self.options.duplicate_keys == Some(DuplicateKeys::Last)
+    }
+
+    /// Deserialize an array or object as a map.
+    ///
+    /// If `fields` is given, other keys are rejected.
+    fn deserialize_mapping<V>(
+        &mut self,
+        fields: Option<&'static [&'static str]>,
+        visitor: V,
+    ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.input.expect(b's')?;
-        self.input.expect(b':')?;
-        // Actual UTF-8 strings are not a thing in PHP, but we offer this conversion
-        // as a convenience.
-        let raw = self.input.read_raw_string()?;
-        visitor
-            .visit_string(String::from_utf8(raw).map_err(|e| Error::NotUtf8String(e.utf8_error()))?)
+        if self.at_reference() {
+            return self.resolve_reference(|de| de.deserialize_mapping(fields, visitor));
+        }
+
+        // Similar to `deserialize_struct`, we need to cover the case of the empty map.
+        // Objects are accepted as well, their properties form the map.
+        let mut mapping = match self.input.read1()? {
+            b'a' => {
+                self.input.expect(b':')?;
+                let num_elements = self.input.read_array_header()?;
+                if self.keeps_last_duplicate() {
+                    return self
+                        .deduplicated(num_elements)?
+                        .deserialize_mapping(fields, visitor);
+                }
+                ArrayMapping::new(self, num_elements)
+            }
+            b'O' => {
+                self.input.expect(b':')?;
+                let (class, num_properties) = self.input.read_object_header()?;
+                if self.spl_container(&class).is_some() {
+                    let properties = self.read_entries(num_properties)?;
+                    let value = Value::Object { class, properties };
+                    return spl::unwrap(value)?.deserialize_map(visitor);
+                }
+                ArrayMapping::for_object(self, num_properties)
+            }
+            b'C' => {
+                self.input.expect(b':')?;
+                let (class, payload) = self.input.read_custom()?;
+                if !self.options.allowed_classes.allows(&class) {
+                    return allowed_classes::incomplete(class, Vec::new()).deserialize_map(visitor);
+                }
+                if self.spl_container(&class) == Some(spl::Container::ArrayObject) {
+                    return array_object_storage(&payload, self.nested_options())?
+                        .deserialize_map(visitor);
+                }
+                return visit_record(class, value::PAYLOAD_KEY, payload, visitor);
+            }
+            c => {
+                return Err(Error::UnexpectedType {
+                    expected: 'a',
+                    actual: char::from(c),
+                })
+            }
+        };
+        mapping.fields = fields;
+        let rval = visitor.visit_map(mapping)?;
+        self.input.expect(b'}')?;
+
+        Ok(rval)
     }
 
-    #[inline]
-    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    /// Deserialize a value wrapped in `Lenient`, keeping its error aside if
+    /// it fails.
+    ///
+    /// The value is read as `Value` first, so that the input can be read on
+    /// after a failure, and then deserialized from its serialization.
+    fn deserialize_lenient<V>(&mut self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        // A `null` value indicates our `None` here.
-        if let Some(b'N') = self.input.peek()? {
-            self.input.expect(b'N')?;
-            self.input.expect(b';')?;
-            visitor.visit_none()
-        } else {
-            // Otherwise, we can parse the actual value.
-            visitor.visit_some(self)
+        let offset = self.byte_offset();
+        let value = Value::deserialize(&mut *self)?;
+        let buf = self.buffer(&value)?;
+
+        // Positions within the value count from its start in the input, and
+        // nested `Lenient` values add to the same errors.
+        let mut de = PhpDeserializer::from_input(BufferInput::new(buf), self.nested_options());
+        de.input.offset = offset;
+        de.errors = self.take_errors();
+
+        let mut error = None;
+        let rval = visitor.visit_map(LenientAccess {
+            de: &mut de,
+            error: &mut error,
+            done: false,
+        });
+        self.errors = de.errors;
+        if let Some(e) = error {
+            self.errors.push(e.at(offset));
         }
+        rval
     }
 
-    #[inline]
-    fn deserialize_struct<V>(self, _name: &str, _fields: &[&str], visitor: V) -> Result<V::Value>
+    /// Deserialize an array with integer keys, after its header, as a
+    /// sequence if it is a list and as a map otherwise, e.g. a list with
+    /// holes left by `unset` or a map keyed by ID.
+    ///
+    /// Input in memory is scanned ahead to tell, so that the entries are
+    /// passed on as they are read, e.g. when transcoding large arrays.
+    /// Otherwise the entries are read as `Value` first, and then
+    /// deserialized from their serialization, once: the arrays within are
+    /// in memory by then, and are scanned like any other.
+    #[inline(never)]
+    fn deserialize_numeric_array<V>(&mut self, num_elements: usize, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        // We need to explicitly implement struct deserialization to be able
-        // to distinguish between empty numeric arrays and empty associative
-        // arrays.
-        self.deserialize_map(visitor)
+        if let Some(list) = self.scan_list(num_elements) {
+            return self.visit_entries(num_elements, list, visitor);
+        }
+
+        let offset = self.byte_offset();
+        let entries = self.read_entries(num_elements)?;
+        let list = value::is_list(&entries);
+        let buf = self.buffer(&Value::Array(entries))?;
+
+        let mut de = PhpDeserializer::from_input(BufferInput::new(buf), self.nested_options());
+        de.errors = self.take_errors();
+        let rval = de
+            .visit_array(offset, list, visitor)
+            .map_err(|e| e.at(de.byte_offset()));
+        self.errors = de.errors;
+        rval
     }
 
-    #[inline]
-    fn deserialize_map<V>(mut self, visitor: V) -> Result<V::Value>
+    /// Visit an array as a sequence or as a map, see
+    /// `deserialize_numeric_array`.
+    fn visit_array<V>(&mut self, offset: usize, list: bool, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        // Similar to `deserialize_struct`, we need to cover the case of the empty map.
         self.input.expect(b'a')?;
         self.input.expect(b':')?;
         let num_elements = self.input.read_array_header()?;
-        let rval = visitor.visit_map(ArrayMapping::new(&mut self, num_elements));
-        self.input.expect(b'}')?;
 
-        rval
+        // Positions within the entries count from their start in the input.
+        self.input.offset = offset;
+        self.visit_entries(num_elements, list, visitor)
     }
 
-    #[inline]
-    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    /// Visit the entries of an array as a sequence or as a map, after its
+    /// header.
+    fn visit_entries<V>(&mut self, num_elements: usize, list: bool, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_newtype_struct(self)
-    }
-
-    forward_to_deserialize_any! {
-        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 str
-        bytes byte_buf unit unit_struct seq tuple
-        enum identifier ignored_any tuple_struct
+        let rval = if list {
+            visitor.visit_seq(ArraySequence::new(self, num_elements))
+        } else {
+            visitor.visit_map(ArrayMapping::new(self, num_elements))
+        }?;
+        self.input.expect(b'}')?;
+        Ok(rval)
     }
-}
 
-/// Numeric array sequence helper.
-#[derive(Debug)]
-struct ArraySequence<'a, R> {
-    de: &'a mut PhpDeserializer<R>,
-    num_elements: usize,
-    index: usize,
-}
+    /// Tell whether the keys of an array are `0..n` in order, by scanning
+    /// ahead of its entries, with the `i` of the first key peeked.
+    ///
+    /// Returns `None` if the input is not in memory. Malformed entries are
+    /// left to fail when they are read.
+    fn scan_list(&self, num_elements: usize) -> Option<bool> {
+        let rest = self.input.reader.rest()?;
+        let max_depth = self.options.max_depth.saturating_sub(self.depth);
+        let options = DeserializerOptions::new().max_depth(max_depth);
+        let mut scan = PhpDeserializer::from_slice_with_options(rest, options);
+        scan.replaying = true;
 
-impl<'a, R> ArraySequence<'a, R> {
-    fn new(de: &'a mut PhpDeserializer<R>, num_elements: usize) -> Self {
-        ArraySequence {
-            de,
-            num_elements,
-            index: 0,
+        for index in 0..num_elements {
+            let key = match index {
+                0 => scan
+                    .input
+                    .expect(b':')
+                    .and_then(|()| scan.input.read_integer())
+                    .map(PhpKey::Int),
+                _ => scan.read_key(),
+            };
+            match key {
+                Ok(PhpKey::Int(n)) if usize::try_from(n) == Ok(index) => (),
+                _ => return Some(false),
+            }
+            if scan.nested(PhpDeserializer::skip_value).is_err() {
+                return Some(false);
+            }
         }
+        Some(true)
     }
-}
 
-impl<'a, 'de, R> SeqAccess<'de> for ArraySequence<'a, R>
-where
-    R: BufRead,
-{
-    type Error = Error;
-
-    fn size_hint(&self) -> Option<usize> {
-        Some(self.num_elements - self.index)
-    }
-
-    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    /// Deserialize any value without guessing at its Rust representation.
+    ///
+    /// Used for `Value`: strings are passed on as bytestrings and arrays are
+    /// always passed on as maps, preserving their keys.
+    fn deserialize_value<V>(&mut self, visitor: V) -> Result<V::Value>
     where
-        T: DeserializeSeed<'de>,
+        V: Visitor<'de>,
     {
-        if self.num_elements == self.index {
-            return Ok(None);
+        if self.at_reference() {
+            return self.resolve_reference(|de| de.deserialize_value(visitor));
         }
 
-        // Get the index; we are assuming to have a PHP array in regular
-        // "array style", that is with only numerical keys stored in order.
-        //
-        // TODO: Possibly change this behavior to handle arrays with out-of-order keys.
-        let idx = usize::deserialize(&mut *self.de)?;
-        if idx != self.index {
-            return Err(Error::IndexMismatch {
-                expected: self.index,
-                actual: idx,
-            });
+        match self.input.peek()? {
+            Some(b's') => {
+                self.input.expect(b's')?;
+                self.input.expect(b':')?;
+                visitor.visit_byte_buf(self.input.read_raw_string()?)
+            }
+            Some(b'a') if self.keeps_last_duplicate() => {
+                self.input.expect(b'a')?;
+                self.input.expect(b':')?;
+                let num_elements = self.input.read_array_header()?;
+                let entries = self.read_deduplicated(num_elements)?;
+                Value::Array(entries).deserialize_newtype_struct(value::TOKEN, visitor)
+            }
+            Some(b'a') => self.deserialize_map(visitor),
+            Some(b'O') => {
+                // The class name is passed on as a special first entry.
+                self.input.expect(b'O')?;
+                self.input.expect(b':')?;
+                let (class, num_properties) = self.input.read_object_header()?;
+                if !self.options.allowed_classes.allows(&class) {
+                    let properties = self.read_entries(num_properties)?;
+                    return allowed_classes::incomplete(class, properties)
+                        .deserialize_newtype_struct(value::TOKEN, visitor);
+                }
+                let mut mapping = ArrayMapping::new(self, num_properties);
+                mapping.class = Some(class);
+                let rval = visitor.visit_map(mapping)?;
+                self.input.expect(b'}')?;
+                Ok(rval)
+            }
+            Some(b'C') => {
+                // Passed on as is, even for SPL containers.
+                self.input.expect(b'C')?;
+                self.input.expect(b':')?;
+                let (class, payload) = self.input.read_custom()?;
+                if !self.options.allowed_classes.allows(&class) {
+                    return allowed_classes::incomplete(class, Vec::new())
+                        .deserialize_newtype_struct(value::TOKEN, visitor);
+                }
+                visit_record(class, value::PAYLOAD_KEY, payload, visitor)
+            }
+            _ => self.deserialize_any(visitor),
         }
-        debug_assert_eq!(idx, self.index);
-        self.index += 1;
-
-        // We can now deserialize the actual value.
-        seed.deserialize(&mut *self.de).map(Some)
     }
 }
 
-/// Associative array helper.
-#[derive(Debug)]
-struct ArrayMapping<'a, R> {
-    de: &'a mut PhpDeserializer<R>,
-    num_elements: usize,
-    index: usize,
-}
+/// Pass a float on as integer, if it has no fractional part.
+///
+/// Range checks beyond `i64` and `u64` are left to the visitor.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn visit_whole_float<'de, V>(n: f64, visitor: V) -> Result<V::Value>
+where
+    V: Visitor<'de>,
+{
+    const TWO_POW_63: f64 = 9_223_372_036_854_775_808.0;
+    const TWO_POW_64: f64 = 18_446_744_073_709_551_616.0;
 
-impl<'a, R> ArrayMapping<'a, R> {
-    fn new(de: &'a mut PhpDeserializer<R>, num_elements: usize) -> Self {
-        ArrayMapping {
-            de,
-            num_elements,
-            index: 0,
+    // The fractional part of infinities and NaN is NaN.
+    if n.fract() == 0.0 {
+        if (-TWO_POW_63..TWO_POW_63).contains(&n) {
+            return visitor.visit_i64(n as i64);
+        }
+        if (0.0..TWO_POW_64).contains(&n) {
+            return visitor.visit_u64(n as u64);
         }
     }
+
+    Err(de::Error::invalid_value(Unexpected::Float(n), &visitor))
 }
 
-impl<'a, 'de, R> MapAccess<'de> for ArrayMapping<'a, R>
+/// Parse a byte string using any `FromStr` function.
+fn parse_bytes<E, T: std::str::FromStr<Err = E>, B: AsRef<[u8]>>(buf: B) -> Result<T>
 where
-    R: BufRead,
+    E: std::fmt::Display + std::error::Error + Send + Sync + 'static,
+{
+    let s = std::str::from_utf8(buf.as_ref()).map_err(Error::NotUtf8String)?;
+    s.parse()
+        .map_err(|e: E| Error::NotAValidNumber(Box::new(e)))
+}
+
+/// Implement the numeric `deserialize_*` methods with `deserialize_number`.
+macro_rules! deserialize_number {
+    ($integer:expr => $($method:ident)*) => {
+        $(
+            #[inline]
+            fn $method<V>(self, visitor: V) -> Result<V::Value>
+            where
+                V: Visitor<'de>,
+            {
+                self.deserialize_number($integer, visitor)
+            }
+        )*
+    };
+}
+
+impl<'a, 'de, R> Deserializer<'de> for &'a mut PhpDeserializer<R>
+where
+    R: Input<'de>,
 {
     type Error = Error;
 
-    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    #[inline]
+    fn deserialize_any<V>(mut self, visitor: V) -> Result<V::Value>
     where
-        K: DeserializeSeed<'de>,
+        V: Visitor<'de>,
     {
-        // We are keeping count, so no need to check for end delimiting symbols.
-        if self.index == self.num_elements {
-            return Ok(None);
+        if self.at_reference() {
+            return self.resolve_reference(|de| de.deserialize_any(visitor));
+        }
+
+        // All fields start with a type, followed by a colon.
+        let sym = self.input.read1()?;
+
+        if sym == b'N' {
+            // `null` is a special case, since it is not followed by a colon.
+            self.input.expect(b';')?;
+            return visitor.visit_unit();
+        }
+
+        self.input.expect(b':')?;
+
+        // See https://stackoverflow.com/questions/14297926/structure-of-a-serialized-php-string
+        match sym {
+            b'b' => {
+                let value = self.input.read1()?;
+                self.input.expect(b';')?;
+
+                // Boolean.
+                match value {
+                    b'0' => visitor.visit_bool(false),
+                    b'1' => visitor.visit_bool(true),
+                    c => Err(Error::InvalidBooleanValue(char::from(c))),
+                }
+            }
+            b'i' => {
+                // Integer.
+                let mut buf = SmallVec::new();
+
+                // Collect a potential sign, followed by the unsigned digits.
+                self.input.collect_sign(&mut buf)?;
+                self.input.collect_unsigned(&mut buf)?;
+
+                // Terminating semicolon.
+                self.input.expect(b';')?;
+
+                // Finally, pass to visitor.
+                visitor.visit_i64(parse_bytes(buf)?)
+            }
+            b'd' => {
+                // Float.
+                visitor.visit_f64(self.input.read_float()?)
+            }
+            b's' => {
+                // PHP String, lent out of the input if possible. Strings
+                // that are valid UTF-8 are offered as text, for targets
+                // such as untagged enums that take what they are given.
+                match self.input.read_string_ref()? {
+                    Bytes::Borrowed(raw) => match std::str::from_utf8(raw) {
+                        Ok(s) => visitor.visit_borrowed_str(s),
+                        Err(_) => visitor.visit_borrowed_bytes(raw),
+                    },
+                    Bytes::Copied(raw) => match String::from_utf8(raw) {
+                        Ok(s) => visitor.visit_string(s),
+                        Err(e) => visitor.visit_byte_buf(e.into_bytes()),
+                    },
+                }
+            }
+            b'a' => {
+                // Array.
+                let num_elements = self.input.read_array_header()?;
+                if self.keeps_last_duplicate() {
+                    return self.deduplicated(num_elements)?.deserialize_any(visitor);
+                }
+
+                // We support two ways of array deserialization: tuple and struct.
+                //
+                // Numeric arrays are deserialized as tuples if their keys
+                // are `0..n` in order, and as mappings otherwise.
+                //
+                // Associative arrays are deserialized as mappings. Only the
+                // first key decides, later keys may be integers as well.
+
+                let rval = match self.input.peek()? {
+                    Some(b'}') => {
+                        // Empty array.
+                        visitor.visit_seq(ArraySequence::new(&mut self, num_elements))
+                    }
+                    Some(b'i') => {
+                        // Numeric array, which need not be a list.
+                        return self.deserialize_numeric_array(num_elements, visitor);
+                    }
+                    Some(b's') => {
+                        // Associative array.
+                        visitor.visit_map(ArrayMapping::new(&mut self, num_elements))
+                    }
+                    Some(c) => Err(Error::UnsupportedArrayKeyType(char::from(c))),
+                    None => return Err(Error::UnexpectedEof),
+                }?;
+                self.input.expect(b'}')?;
+                Ok(rval)
+            }
+            b'O' => {
+                // Object. Without further hints, the class name is dropped
+                // and the properties are treated like an associative array.
+                let (class, num_properties) = self.input.read_object_header()?;
+                if self.spl_container(&class).is_some() {
+                    let properties = self.read_entries(num_properties)?;
+                    let value = Value::Object { class, properties };
+                    return spl::unwrap(value)?.deserialize_any(visitor);
+                }
+                let rval = visitor.visit_map(ArrayMapping::for_object(self, num_properties))?;
+                self.input.expect(b'}')?;
+                Ok(rval)
+            }
+            b'C' => {
+                // Custom serialized object.
+                let (class, payload) = self.input.read_custom()?;
+                if !self.options.allowed_classes.allows(&class) {
+                    return allowed_classes::incomplete(class, Vec::new()).deserialize_any(visitor);
+                }
+                if self.spl_container(&class) == Some(spl::Container::ArrayObject) {
+                    return array_object_storage(&payload, self.nested_options())?
+                        .deserialize_any(visitor);
+                }
+                visit_record(class, value::PAYLOAD_KEY, payload, visitor)
+            }
+            b'E' => {
+                // Enum case.
+                let (class, case) = self.input.read_enum_case()?;
+                visit_record(class, value::CASE_KEY, case.into_bytes(), visitor)
+            }
+            // Unknown character, not valid.
+            c => Err(Error::InvalidTypeIndicator(char::from(c))),
         }
+    }
+
+    #[inline]
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_loose_bool(visitor)
+    }
 
-        // Keys can be integers or strings.
-        if let Some(b'i') = self.de.peek()? {
-            return seed.deserialize(&mut *self.de).map(Some);
+    #[inline]
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.at_reference() {
+            return self.resolve_reference(|de| de.deserialize_char(visitor));
         }
 
-        // We need to hint that we are deserializing a string, since PHP
-        // strings are not fit to be keys. For this reason, we perform the
-        // deserialization here:
-        let key = String::deserialize(&mut *self.de)?;
+        // Characters are serialized as 32 bit numbers values.
+        self.input.expect_type(b'i')?;
+        self.input.expect(b':')?;
+
+        let mut buf = SmallVec::new();
+        self.input.collect_unsigned(&mut buf)?;
+        // No sign.
+
+        self.input.expect(b';')?;
 
-        // Pass the already deserialized string on.
-        seed.deserialize(key.into_deserializer()).map(Some)
+        // We parse to a 32 bit unsigned value.
+        let raw: u32 = parse_bytes(&buf)?;
+        visitor.visit_char(char::try_from(raw).map_err(Error::CharConversionFailed)?)
     }
 
-    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    #[inline]
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
     where
-        V: DeserializeSeed<'de>,
+        V: Visitor<'de>,
     {
-        self.index += 1;
-        seed.deserialize(&mut *self.de)
+        if self.at_reference() {
+            return self.resolve_reference(|de| de.deserialize_str(visitor));
+        }
+
+        self.input.expect_type(b's')?;
+        self.input.expect(b':')?;
+        // Actual UTF-8 strings are not a thing in PHP, but we offer this conversion
+        // as a convenience.
+        let lossy = self.options.lossy_utf8;
+        match self.input.read_string_ref()? {
+            Bytes::Borrowed(raw) => match std::str::from_utf8(raw) {
+                Ok(s) => visitor.visit_borrowed_str(s),
+                Err(_) if lossy => visitor.visit_string(String::from_utf8_lossy(raw).into_owned()),
+                Err(e) => Err(Error::NotUtf8String(e)),
+            },
+            Bytes::Copied(raw) => match String::from_utf8(raw) {
+                Ok(s) => visitor.visit_string(s),
+                Err(e) if lossy => {
+                    visitor.visit_string(String::from_utf8_lossy(e.as_bytes()).into_owned())
+                }
+                Err(e) => Err(Error::NotUtf8String(e.utf8_error())),
+            },
+        }
     }
-}
 
-/// Helper to deserialize a PHP array where the keys might be out of order.
-///
-/// ## Caveat
-///
-/// Holes in the array will not be filled in.  The following PHP array
-///
-/// ```php
-/// $arr = array();
-/// $arr[0] = "zero";
-/// $arr[2] = "two";
-/// $arr[1] = "one";
-/// $arr[6] = "six";
-/// ```
-///
-/// will be deserialized to a Rust `Vec` with the four elements
-/// "zero", "one", "two", and "six".
-pub fn deserialize_unordered_array<'de, T, D>(
-    deserializer: D,
-) -> std::result::Result<Vec<T>, D::Error>
-where
-    D: Deserializer<'de>,
-    T: Deserialize<'de>,
-{
-    // Serialize into a map and return a Vec ordered by the keys.
-    let v = BTreeMap::<usize, T>::deserialize(deserializer)?;
-    Ok(v.into_iter().map(|(_, v)| v).collect())
-}
+    #[inline]
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::{deserialize_unordered_array, from_bytes};
-    use serde::Deserialize;
-    use std::collections::HashMap;
+    #[inline]
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_bytes(visitor)
+    }
 
-    macro_rules! assert_deserializes {
-        ($ty:ty, $input:expr, $expected:expr) => {{
-            // TODO: It's not feasible to infer the type here, compare
-            //       `deserialize_php_string` and `deserialize_string`.gi
-            let actual: $ty = from_bytes($input).expect("deserialization failed");
-            assert_eq!(actual, $expected);
-        }};
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.at_reference() {
+            return self.resolve_reference(|de| de.deserialize_bytes(visitor));
+        }
+
+        if self.input.peek()? != Some(b's') {
+            return self.deserialize_any(visitor);
+        }
+
+        self.input.expect(b's')?;
+        self.input.expect(b':')?;
+        match self.input.read_string_ref()? {
+            Bytes::Borrowed(raw) => visitor.visit_borrowed_bytes(raw),
+            Bytes::Copied(raw) => visitor.visit_byte_buf(raw),
+        }
+    }
+
+    #[inline]
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.at_reference() {
+            return self.resolve_reference(|de| de.deserialize_option(visitor));
+        }
+
+        // A `null` value indicates our `None` here.
+        if let Some(b'N') = self.input.peek()? {
+            self.input.expect(b'N')?;
+            self.input.expect(b';')?;
+            visitor.visit_none()
+        } else {
+            // Otherwise, we can parse the actual value.
+            visitor.visit_some(self)
+        }
     }
 
-    #[test]
-    #[allow(clippy::bool_assert_comparison)]
-    fn deserialize_bool() {
-        assert_deserializes!(bool, b"b:0;", false);
-        assert_deserializes!(bool, b"b:1;", true);
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.at_reference() {
+            return self.resolve_reference(|de| de.deserialize_unit(visitor));
+        }
+
+        // PHP code writes "nothing here" as `null` or as an empty array.
+        if self.input.peek()? == Some(b'a') {
+            self.input.expect(b'a')?;
+            self.input.expect(b':')?;
+            let num_elements = self.input.read_array_header()?;
+            if num_elements > 0 {
+                return Err(de::Error::invalid_length(num_elements, &visitor));
+            }
+            self.input.expect(b'}')?;
+            return visitor.visit_unit();
+        }
+
+        self.deserialize_any(visitor)
+    }
+
+    #[inline]
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    #[inline]
+    fn deserialize_struct<V>(
+        self,
+        _name: &str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.positional {
+            self.positional = false;
+            return self.deserialize_seq(visitor);
+        }
+
+        // We need to explicitly implement struct deserialization to be able
+        // to distinguish between empty numeric arrays and empty associative
+        // arrays.
+        let fields = self.options.deny_unknown_keys.then_some(fields);
+        self.deserialize_mapping(fields, visitor)
+    }
+
+    #[inline]
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_mapping(None, visitor)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.skip_value()?;
+        visitor.visit_unit()
+    }
+
+    #[inline]
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if name == value::TOKEN {
+            return self.deserialize_value(visitor);
+        }
+
+        if name == lenient::TOKEN {
+            return self.deserialize_lenient(visitor);
+        }
+
+        if name == positional::TOKEN {
+            self.positional = true;
+            let rval = visitor.visit_newtype_struct(&mut *self)?;
+
+            // The flag is consumed by the next struct.
+            if self.positional {
+                self.positional = false;
+                return Err(Error::DeserializationFailed(
+                    "`Positional` must wrap a struct".to_owned(),
+                ));
+            }
+            return Ok(rval);
+        }
+
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.at_reference() {
+            return self.resolve_reference(|de| de.deserialize_enum(name, variants, visitor));
+        }
+
+        if self.input.peek()? == Some(b'E') {
+            // PHP enum cases select unit variants by their name.
+            self.input.expect(b'E')?;
+            self.input.expect(b':')?;
+            let (_class, case) = self.input.read_enum_case()?;
+            return visitor.visit_enum(case.into_deserializer());
+        }
+
+        if self.input.peek()? == Some(b's') {
+            // So do plain strings, as written for unit variants.
+            self.input.expect(b's')?;
+            self.input.expect(b':')?;
+            let variant = String::from_utf8(self.input.read_raw_string()?)
+                .map_err(|e| Error::NotUtf8String(e.utf8_error()))?;
+            return visitor.visit_enum(variant.into_deserializer());
+        }
+
+        if self.input.peek()? == Some(b'a') {
+            // Other variants are arrays with the variant name as their only
+            // key, holding the fields.
+            self.input.expect(b'a')?;
+            self.input.expect(b':')?;
+            let num_elements = self.input.read_array_header()?;
+            if num_elements != 1 {
+                return Err(de::Error::invalid_length(
+                    num_elements,
+                    &"an array with a single entry",
+                ));
+            }
+            let key = self.read_key()?;
+            let rval = visitor.visit_enum(VariantEntry { de: self, key })?;
+            self.input.expect(b'}')?;
+            return Ok(rval);
+        }
+
+        self.deserialize_any(visitor)
+    }
+
+    deserialize_number! { true =>
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+    }
+
+    fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.at_reference() {
+            return self.resolve_reference(|de| de.deserialize_i128(visitor));
+        }
+
+        match self.read_wide_integer(&visitor)? {
+            Some(digits) => match digits.parse() {
+                Ok(n) => visitor.visit_i128(n),
+                Err(_) => Err(de::Error::invalid_value(Unexpected::Str(&digits), &visitor)),
+            },
+            None => self.deserialize_number(true, visitor),
+        }
+    }
+
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.at_reference() {
+            return self.resolve_reference(|de| de.deserialize_u128(visitor));
+        }
+
+        match self.read_wide_integer(&visitor)? {
+            Some(digits) => match digits.parse() {
+                Ok(n) => visitor.visit_u128(n),
+                Err(_) => Err(de::Error::invalid_value(Unexpected::Str(&digits), &visitor)),
+            },
+            None => self.deserialize_number(true, visitor),
+        }
+    }
+
+    deserialize_number! { false => deserialize_f32 deserialize_f64 }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.at_reference() {
+            return self.resolve_reference(|de| de.deserialize_seq(visitor));
+        }
+
+        match self.input.peek()? {
+            Some(b'a') => (),
+            Some(b's') => {
+                // Strings are sequences of bytes, e.g. for `Vec<u8>`.
+                self.input.expect(b's')?;
+                self.input.expect(b':')?;
+                let data = self.input.read_raw_string()?;
+                return visitor.visit_seq(SeqDeserializer::new(data.into_iter()));
+            }
+            _ => return self.deserialize_any(visitor),
+        }
+
+        // Arrays are read as sequences whatever their keys, so that pairs
+        // of key and value can be collected from any array.
+        self.input.expect(b'a')?;
+        self.input.expect(b':')?;
+        let num_elements = self.input.read_array_header()?;
+        if self.keeps_last_duplicate() {
+            return self.deduplicated(num_elements)?.deserialize_seq(visitor);
+        }
+
+        let rval = visitor.visit_seq(ArraySequence::new(self, num_elements))?;
+        self.input.expect(b'}')?;
+        Ok(rval)
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    forward_to_deserialize_any! {
+        identifier
+    }
+}
+
+/// Numeric array sequence helper.
+#[derive(Debug)]
+struct ArraySequence<'a, R> {
+    de: &'a mut PhpDeserializer<R>,
+    num_elements: usize,
+    index: usize,
+    /// Number of entries skipped for duplicate keys.
+    skipped: usize,
+    /// Keys seen so far, if duplicate keys are checked.
+    seen: Option<HashSet<PhpKey>>,
+}
+
+impl<'a, R> ArraySequence<'a, R> {
+    fn new(de: &'a mut PhpDeserializer<R>, num_elements: usize) -> Self {
+        let seen = de.options.duplicate_keys.map(|_| HashSet::new());
+        ArraySequence {
+            de,
+            num_elements,
+            index: 0,
+            skipped: 0,
+            seen,
+        }
+    }
+}
+
+impl<'a, 'de, R> SeqAccess<'de> for ArraySequence<'a, R>
+where
+    R: Input<'de>,
+{
+    type Error = Error;
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.num_elements - self.index - self.skipped)
+    }
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        loop {
+            if self.num_elements == self.index + self.skipped {
+                return Ok(None);
+            }
+
+            let offset = self.de.byte_offset();
+            let key = self.de.read_key()?;
+            if let Some(seen) = &mut self.seen {
+                if seen.contains(&key) {
+                    if self.de.options.duplicate_keys == Some(DuplicateKeys::Error) {
+                        let key = key.to_string();
+                        return Err(Error::DuplicateKey { key, offset });
+                    }
+
+                    // Only the first value is kept.
+                    self.skipped += 1;
+                    self.de.nested(|de| IgnoredAny::deserialize(de))?;
+                    continue;
+                }
+                seen.insert(key.clone());
+            }
+
+            // Whether the key is the expected index depends on how the
+            // element is deserialized, see `ArrayElement`.
+            let index = self.index;
+            self.index += 1;
+            return self
+                .de
+                .nested(|de| seed.deserialize(ArrayElement { de, key, index }))
+                .map(Some)
+                .map_err(|e| e.in_index(index));
+        }
+    }
+}
+
+/// Element of an array read as a sequence, after its key.
+///
+/// Usually, keys must be the indices of a list: `0`, `1`, `2` and so on, in
+/// order. Elements read as pairs whose first field is a `PhpKey` are the
+/// exception, they take the key as it is. This way arrays with any keys,
+/// including mixed integer and string keys, can be read into a
+/// `Vec<(PhpKey, T)>`, keeping their order.
+struct ArrayElement<'a, R> {
+    de: &'a mut PhpDeserializer<R>,
+    key: PhpKey,
+    index: usize,
+}
+
+impl<R> ArrayElement<'_, R> {
+    fn check_index(&self) -> Result<()> {
+        check_index(&self.key, self.index)
+    }
+}
+
+/// Check that the key of an array element is its index in a list.
+fn check_index(key: &PhpKey, index: usize) -> Result<()> {
+    match *key {
+        PhpKey::Int(n) => match usize::try_from(n) {
+            Ok(actual) if actual == index => Ok(()),
+            Ok(actual) => Err(Error::IndexMismatch {
+                expected: index,
+                actual,
+            }),
+            Err(_) => Err(de::Error::invalid_value(
+                Unexpected::Signed(n),
+                &"an array index",
+            )),
+        },
+        PhpKey::String(_) => Err(Error::UnexpectedType {
+            expected: 'i',
+            actual: 's',
+        }),
+    }
+}
+
+macro_rules! forward_to_element {
+    ($($method:ident($($arg:ident: $ty:ty),*))*) => {
+        $(
+            #[inline]
+            fn $method<V>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value>
+            where
+                V: Visitor<'de>,
+            {
+                self.check_index()?;
+                self.de.$method($($arg,)* visitor)
+            }
+        )*
+    };
+}
+
+impl<'de, R> Deserializer<'de> for ArrayElement<'_, R>
+where
+    R: Input<'de>,
+{
+    type Error = Error;
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if len != 2 {
+            self.check_index()?;
+            return self.de.deserialize_tuple(len, visitor);
+        }
+
+        let mut pair = ArrayPair {
+            de: self.de,
+            key: Some(self.key),
+            index: self.index,
+            state: PairState::Start,
+        };
+        let rval = visitor.visit_seq(&mut pair)?;
+        match pair.state {
+            PairState::Done => Ok(rval),
+            PairState::Tuple(2) => {
+                pair.de.input.expect(b'}')?;
+                Ok(rval)
+            }
+            _ => Err(de::Error::invalid_length(1, &"a pair")),
+        }
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        // Ignored elements may have any key.
+        self.de.deserialize_ignored_any(visitor)
+    }
+
+    forward_to_element! {
+        deserialize_any() deserialize_bool() deserialize_char()
+        deserialize_i8() deserialize_i16() deserialize_i32() deserialize_i64() deserialize_i128()
+        deserialize_u8() deserialize_u16() deserialize_u32() deserialize_u64() deserialize_u128()
+        deserialize_f32() deserialize_f64() deserialize_str() deserialize_string()
+        deserialize_bytes() deserialize_byte_buf() deserialize_option() deserialize_unit()
+        deserialize_seq() deserialize_map() deserialize_identifier()
+        deserialize_unit_struct(name: &'static str)
+        deserialize_newtype_struct(name: &'static str)
+        deserialize_tuple_struct(name: &'static str, len: usize)
+        deserialize_struct(name: &'static str, fields: &'static [&'static str])
+        deserialize_enum(name: &'static str, variants: &'static [&'static str])
+    }
+}
+
+/// Progress through an element read as a pair.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PairState {
+    /// Nothing read yet.
+    Start,
+    /// The key was read, the value is next.
+    Pair,
+    /// Reading a regular tuple stored as an array, after this many elements.
+    Tuple(usize),
+    /// The key and value were read.
+    Done,
+}
+
+/// Sequence access for an element read as a pair.
+///
+/// If the first field of the pair asks for a `PhpKey`, the pair is the key
+/// and value of the element. Otherwise the element is a regular two element
+/// tuple, stored as a nested array.
+struct ArrayPair<'a, R> {
+    de: &'a mut PhpDeserializer<R>,
+    key: Option<PhpKey>,
+    index: usize,
+    state: PairState,
+}
+
+impl<'de, R> ArrayPair<'_, R>
+where
+    R: Input<'de>,
+{
+    /// Start reading the element as a regular tuple, up to its first field.
+    fn open_tuple(&mut self) -> Result<()> {
+        if let Some(key) = self.key.take() {
+            check_index(&key, self.index)?;
+        }
+
+        self.de.input.expect(b'a')?;
+        self.de.input.expect(b':')?;
+        let num_elements = self.de.input.read_array_header()?;
+        if num_elements != 2 {
+            return Err(de::Error::invalid_length(num_elements, &"a pair"));
+        }
+        self.tuple_index(0)
+    }
+
+    /// Read the key of a field of the regular tuple.
+    fn tuple_index(&mut self, index: usize) -> Result<()> {
+        let actual = usize::deserialize(&mut *self.de)?;
+        if actual != index {
+            return Err(Error::IndexMismatch {
+                expected: index,
+                actual,
+            });
+        }
+        self.state = PairState::Tuple(index + 1);
+        Ok(())
+    }
+}
+
+impl<'de, R> SeqAccess<'de> for ArrayPair<'_, R>
+where
+    R: Input<'de>,
+{
+    type Error = Error;
+
+    fn size_hint(&self) -> Option<usize> {
+        match self.state {
+            PairState::Start => Some(2),
+            PairState::Pair | PairState::Tuple(1) => Some(1),
+            _ => Some(0),
+        }
+    }
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.state {
+            PairState::Start => seed.deserialize(PairKey { pair: self }).map(Some),
+            PairState::Pair => {
+                self.state = PairState::Done;
+                seed.deserialize(&mut *self.de).map(Some)
+            }
+            PairState::Tuple(1) => {
+                self.tuple_index(1)?;
+                self.de.nested(|de| seed.deserialize(de)).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+/// First field of an element read as a pair, see `ArrayPair`.
+struct PairKey<'a, 'b, R> {
+    pair: &'a mut ArrayPair<'b, R>,
+}
+
+macro_rules! forward_to_tuple {
+    ($($method:ident($($arg:ident: $ty:ty),*))*) => {
+        $(
+            #[inline]
+            fn $method<V>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value>
+            where
+                V: Visitor<'de>,
+            {
+                self.pair.open_tuple()?;
+                self.pair.de.nested(|de| de.$method($($arg,)* visitor))
+            }
+        )*
+    };
+}
+
+impl<'de, R> Deserializer<'de> for PairKey<'_, '_, R>
+where
+    R: Input<'de>,
+{
+    type Error = Error;
+
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if name != key::TOKEN {
+            self.pair.open_tuple()?;
+            return self
+                .pair
+                .de
+                .nested(|de| de.deserialize_newtype_struct(name, visitor));
+        }
+
+        let key = self.pair.key.take().ok_or(Error::UnexpectedEof)?;
+        self.pair.state = PairState::Pair;
+        visitor.visit_newtype_struct(key.into_deserializer())
+    }
+
+    forward_to_tuple! {
+        deserialize_any() deserialize_bool() deserialize_char()
+        deserialize_i8() deserialize_i16() deserialize_i32() deserialize_i64() deserialize_i128()
+        deserialize_u8() deserialize_u16() deserialize_u32() deserialize_u64() deserialize_u128()
+        deserialize_f32() deserialize_f64() deserialize_str() deserialize_string()
+        deserialize_bytes() deserialize_byte_buf() deserialize_option() deserialize_unit()
+        deserialize_seq() deserialize_map() deserialize_identifier() deserialize_ignored_any()
+        deserialize_unit_struct(name: &'static str)
+        deserialize_tuple(len: usize)
+        deserialize_tuple_struct(name: &'static str, len: usize)
+        deserialize_struct(name: &'static str, fields: &'static [&'static str])
+        deserialize_enum(name: &'static str, variants: &'static [&'static str])
+    }
+}
+
+/// Associative array helper.
+#[derive(Debug)]
+struct ArrayMapping<'a, R> {
+    de: &'a mut PhpDeserializer<R>,
+    num_elements: usize,
+    index: usize,
+    /// Class name to yield as the first entry, for objects read as `Value`.
+    class: Option<String>,
+    /// Whether to strip visibility prefixes from property names.
+    demangle: bool,
+    /// Keys to accept, if unknown keys are an error.
+    fields: Option<&'static [&'static str]>,
+    /// Keys seen so far, if duplicate keys are checked.
+    seen: Option<HashSet<PhpKey>>,
+    /// Key of the current entry, for the path of errors in its value.
+    key: Option<PhpKey>,
+}
+
+impl<'a, R> ArrayMapping<'a, R> {
+    fn new(de: &'a mut PhpDeserializer<R>, num_elements: usize) -> Self {
+        let seen = de.options.duplicate_keys.map(|_| HashSet::new());
+        ArrayMapping {
+            de,
+            num_elements,
+            index: 0,
+            class: None,
+            demangle: false,
+            fields: None,
+            seen,
+            key: None,
+        }
+    }
+
+    /// Map over the properties of an object, with plain property names.
+    fn for_object(de: &'a mut PhpDeserializer<R>, num_properties: usize) -> Self {
+        ArrayMapping {
+            demangle: true,
+            seen: None,
+            ..ArrayMapping::new(de, num_properties)
+        }
+    }
+}
+
+impl<'a, 'de, R> MapAccess<'de> for ArrayMapping<'a, R>
+where
+    R: Input<'de>,
+{
+    type Error = Error;
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.num_elements - self.index + usize::from(self.class.is_some()))
+    }
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        if self.class.is_some() {
+            return seed
+                .deserialize(IntoDeserializer::<Error>::into_deserializer(
+                    value::CLASS_KEY,
+                ))
+                .map(Some);
+        }
+
+        loop {
+            // We are keeping count, so no need to check for end delimiting symbols.
+            if self.index == self.num_elements {
+                return Ok(None);
+            }
+
+            // Keys can be integers or strings. Since PHP turns numeric string
+            // keys into integers, we read the key here and let the key
+            // deserializer offer it in whatever form the target asks for.
+            let offset = self.de.byte_offset();
+            let mut key = self.de.read_key()?;
+            if self.demangle {
+                key = property::demangle_key(key);
+            }
+            if let Some(seen) = &mut self.seen {
+                if seen.contains(&key) {
+                    if self.de.options.duplicate_keys == Some(DuplicateKeys::Error) {
+                        let key = key.to_string();
+                        return Err(Error::DuplicateKey { key, offset });
+                    }
+
+                    // Only the first value is kept.
+                    self.index += 1;
+                    self.de.nested(|de| IgnoredAny::deserialize(de))?;
+                    continue;
+                }
+                seen.insert(key.clone());
+            }
+            if let Some(fields) = self.fields {
+                let key = key.to_string();
+                if !fields.contains(&key.as_str()) {
+                    return Err(Error::UnknownKey { key, offset });
+                }
+            }
+            self.key = Some(key.clone());
+            return seed.deserialize(key.into_deserializer()).map(Some);
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        if let Some(class) = self.class.take() {
+            return seed.deserialize(IntoDeserializer::<Error>::into_deserializer(class));
+        }
+
+        self.index += 1;
+        let key = self.key.take();
+        self.de
+            .nested(|de| seed.deserialize(de))
+            .map_err(|e| match key {
+                Some(key) => e.in_key(&key),
+                None => e,
+            })
+    }
+}
+
+/// Single entry array holding an enum variant, after its key.
+struct VariantEntry<'a, R> {
+    de: &'a mut PhpDeserializer<R>,
+    key: PhpKey,
+}
+
+impl<'de, R> VariantEntry<'_, R>
+where
+    R: Input<'de>,
+{
+    /// Run `f` on the fields of the variant, the value of the entry.
+    fn fields<T, F>(self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut PhpDeserializer<R>) -> Result<T>,
+    {
+        let key = self.key;
+        self.de.nested(f).map_err(|e| e.in_key(&key))
+    }
+}
+
+impl<'de, R> EnumAccess<'de> for VariantEntry<'_, R>
+where
+    R: Input<'de>,
+{
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self)>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(self.key.clone().into_deserializer())?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de, R> VariantAccess<'de> for VariantEntry<'_, R>
+where
+    R: Input<'de>,
+{
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        self.fields(|de| <()>::deserialize(de))
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        self.fields(|de| seed.deserialize(de))
+    }
+
+    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.fields(|de| de.deserialize_tuple(len, visitor))
+    }
+
+    fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.fields(|de| de.deserialize_struct("", fields, visitor))
+    }
+}
+
+/// Single entry map passing a value on to `Lenient`.
+struct LenientAccess<'a, R> {
+    de: &'a mut PhpDeserializer<R>,
+    /// Error the value failed with, replaced by a placeholder for `Lenient`
+    /// to discard.
+    error: &'a mut Option<Error>,
+    done: bool,
+}
+
+impl<'de, R> MapAccess<'de> for LenientAccess<'_, R>
+where
+    R: Input<'de>,
+{
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        if self.done {
+            return Ok(None);
+        }
+        self.done = true;
+        seed.deserialize(BorrowedStrDeserializer::new(lenient::TOKEN))
+            .map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        seed.deserialize(&mut *self.de).map_err(|e| {
+            *self.error = Some(e);
+            Error::DeserializationFailed(String::new())
+        })
+    }
+}
+
+/// Pass a class-based record on as a map of its class and one more entry.
+///
+/// Used for custom serialized objects (`value::PAYLOAD_KEY`, see
+/// `custom::serialize`) and enum cases (`value::CASE_KEY`).
+fn visit_record<'de, V>(class: String, key: &str, data: Vec<u8>, visitor: V) -> Result<V::Value>
+where
+    V: Visitor<'de>,
+{
+    let entries = vec![
+        (value::CLASS_KEY, Value::String(class.into_bytes())),
+        (key, Value::String(data)),
+    ];
+    let mut map = MapDeserializer::new(entries.into_iter());
+    let rval = visitor.visit_map(&mut map)?;
+    map.end()?;
+    Ok(rval)
+}
+
+/// Read the storage array from the payload of a `C:` serialized `ArrayObject`.
+///
+/// The payload has the form `x:<flags>;<storage>;m:<members>`.
+pub fn array_object_storage(payload: &[u8], options: DeserializerOptions) -> Result<Value> {
+    let mut des = PhpDeserializer::with_options(payload, options);
+    des.input.expect(b'x')?;
+    des.input.expect(b':')?;
+    // Flags.
+    IgnoredAny::deserialize(&mut des)?;
+    let storage = Value::deserialize(&mut des)?;
+    des.input.expect(b';')?;
+    des.input.expect(b'm')?;
+    Ok(storage)
+}
+
+/// Helper to deserialize a PHP array where the keys might be out of order.
+///
+/// ## Caveat
+///
+/// Holes in the array will not be filled in.  The following PHP array
+///
+/// ```php
+/// $arr = array();
+/// $arr[0] = "zero";
+/// $arr[2] = "two";
+/// $arr[1] = "one";
+/// $arr[6] = "six";
+/// ```
+///
+/// will be deserialized to a Rust `Vec` with the four elements
+/// "zero", "one", "two", and "six".
+pub fn deserialize_unordered_array<'de, T, D>(
+    deserializer: D,
+) -> std::result::Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    // Serialize into a map and return a Vec ordered by the keys.
+    let v = BTreeMap::<usize, T>::deserialize(deserializer)?;
+    Ok(v.into_iter().map(|(_, v)| v).collect())
+}
+
+/// Helper to deserialize a PHP array where the keys might be out of order,
+/// filling holes with `T::default()`.
+///
+/// For `Option` elements, holes become `None`. The PHP array from the
+/// example of `deserialize_unordered_array` becomes
+/// `["zero", "one", "two", "", "", "", "six"]`.
+///
+/// To keep input from allocating arbitrary amounts of memory, at most
+/// 65536 elements are filled in per array.
+pub fn deserialize_unordered_array_with_defaults<'de, T, D>(
+    deserializer: D,
+) -> std::result::Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    fill_holes(deserializer, Some(T::default))
+}
+
+/// Helper to deserialize a PHP array where the keys might be out of order,
+/// failing on the first hole.
+pub fn deserialize_unordered_array_without_holes<'de, T, D>(
+    deserializer: D,
+) -> std::result::Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    fill_holes(deserializer, None)
+}
+
+/// Helper to deserialize a PHP array where the keys might be out of order,
+/// skipping elements that fail to deserialize.
+///
+/// Like `deserialize_unordered_array`, but each element is read like a
+/// `Lenient<T>`, so that one corrupted element does not fail the whole
+/// array. Their errors are collected by `from_bytes_lenient` and
+/// `PhpDeserializer::take_errors`. Keys must still be array indices.
+///
+/// ```rust
+/// use php_serde::from_bytes_lenient;
+/// use serde::Deserialize;
+///
+/// #[derive(Debug, Deserialize)]
+/// struct Batch {
+///     #[serde(deserialize_with = "php_serde::deserialize_unordered_array_lenient")]
+///     rows: Vec<i64>,
+/// }
+///
+/// let input = br#"a:1:{s:4:"rows";a:3:{i:2;i:30;i:0;i:10;i:1;s:1:"x";}}"#;
+/// let (batch, errors) = from_bytes_lenient::<Batch>(input).unwrap();
+/// assert_eq!(batch.rows, vec![10, 30]);
+/// assert_eq!(errors.len(), 1);
+/// ```
+pub fn deserialize_unordered_array_lenient<'de, T, D>(
+    deserializer: D,
+) -> std::result::Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    let v = BTreeMap::<usize, Lenient<T>>::deserialize(deserializer)?;
+    Ok(v.into_values().filter_map(Lenient::into_inner).collect())
+}
+
+/// Order an array by its keys, filling holes with `fill`, or failing on
+/// them if there is none.
+fn fill_holes<'de, T, D>(
+    deserializer: D,
+    fill: Option<fn() -> T>,
+) -> std::result::Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    const MAX_FILLED: usize = 1 << 16;
+
+    let map = BTreeMap::<usize, T>::deserialize(deserializer)?;
+    let mut v = Vec::with_capacity(map.len());
+    let mut filled = 0;
+
+    for (index, value) in map {
+        if index > v.len() {
+            let fill =
+                fill.ok_or_else(|| de::Error::custom(format!("missing array index {}", v.len())))?;
+
+            filled += index - v.len();
+            if filled > MAX_FILLED {
+                return Err(de::Error::custom(format!(
+                    "array has more than {MAX_FILLED} holes"
+                )));
+            }
+            v.resize_with(index, fill);
+        }
+        v.push(value);
+    }
+
+    Ok(v)
+}
+
+/// Helper to deserialize a PHP array where the keys might be out of order,
+/// keeping the keys.
+///
+/// Like `deserialize_unordered_array`, elements are ordered by their keys,
+/// but each comes with its original index, so holes remain visible. Negative
+/// indices are supported as well. The PHP array from the example of
+/// `deserialize_unordered_array` becomes
+/// `[(0, "zero"), (1, "one"), (2, "two"), (6, "six")]`.
+///
+/// To look elements up by index instead, deserialize into a
+/// `BTreeMap<i64, T>` directly.
+pub fn deserialize_indexed_array<'de, T, D>(
+    deserializer: D,
+) -> std::result::Result<Vec<(i64, T)>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    let v = BTreeMap::<i64, T>::deserialize(deserializer)?;
+    Ok(v.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        deserialize_indexed_array, deserialize_unordered_array,
+        deserialize_unordered_array_lenient, deserialize_unordered_array_with_defaults,
+        deserialize_unordered_array_without_holes, extract, from_bytes, from_bytes_lenient,
+        from_bytes_partial, from_bytes_with_options, from_reader, PhpDeserializer,
+    };
+    use crate::input::BufferInput;
+    use crate::{
+        to_vec, CyclicReferences, DeserializerOptions, DuplicateKeys, Error, ErrorKind, Lenient,
+        PhpKey, Value,
+    };
+    use serde::de::{DeserializeSeed, IgnoredAny};
+    use serde::{Deserialize, Serialize};
+    use std::borrow::Cow;
+    use std::collections::{BTreeMap, HashMap};
+    use std::io;
+
+    macro_rules! assert_deserializes {
+        ($ty:ty, $input:expr, $expected:expr) => {{
+            // TODO: It's not feasible to infer the type here, compare
+            //       `deserialize_php_string` and `deserialize_string`.gi
+            let actual: $ty = from_bytes($input).expect("deserialization failed");
+            assert_eq!(actual, $expected);
+        }};
+    }
+
+    #[test]
+    #[allow(clippy::bool_assert_comparison)]
+    fn deserialize_bool() {
+        assert_deserializes!(bool, b"b:0;", false);
+        assert_deserializes!(bool, b"b:1;", true);
+    }
+
+    #[test]
+    fn deserialize_null() {
+        assert_deserializes!((), b"N;", ());
+        assert_deserializes!(Option<i64>, b"N;", None);
+        assert_deserializes!(Option<String>, b"N;", None);
+        assert_deserializes!(Option<Vec<i64>>, b"N;", None);
+        assert_deserializes!(Option<i64>, b"i:1;", Some(1));
+
+        assert_eq!(from_reader::<_, Option<String>>(&b"N;"[..]).unwrap(), None);
+        assert!(from_bytes::<Option<i64>>(b"N").is_err());
+        assert!(from_bytes::<()>(b"i:0;").is_err());
+    }
+
+    #[test]
+    fn deserialize_unit_struct() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Nothing;
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Data {
+            a: Nothing,
+            b: (),
+        }
+
+        // PHP writes both `null` and empty arrays for "nothing here".
+        assert_deserializes!((), b"a:0:{}", ());
+        assert_deserializes!(Nothing, b"N;", Nothing);
+        assert_deserializes!(Nothing, b"a:0:{}", Nothing);
+        assert_deserializes!(
+            Data,
+            br#"a:2:{s:1:"a";a:0:{}s:1:"b";N;}"#,
+            Data { a: Nothing, b: () }
+        );
+
+        let value: Value = from_bytes(br#"a:2:{s:1:"a";N;s:1:"b";a:0:{}}"#).unwrap();
+        assert_eq!(
+            crate::from_value::<Data>(value).unwrap(),
+            Data { a: Nothing, b: () }
+        );
+
+        assert!(from_bytes::<Nothing>(b"a:1:{i:0;N;}").is_err());
+        assert!(from_bytes::<Nothing>(b"b:0;").is_err());
+    }
+
+    #[test]
+    fn deserialize_integer() {
+        assert_deserializes!(i64, b"i:-1;", -1);
+        assert_deserializes!(i64, b"i:0;", 0);
+        assert_deserializes!(i64, b"i:1;", 1);
+        assert_deserializes!(i64, b"i:123;", 123);
+    }
+
+    #[test]
+    fn deserialize_wide_integer() {
+        assert_deserializes!(i128, b"i:-5;", -5);
+        assert_deserializes!(u128, b"i:5;", 5);
+        assert_deserializes!(
+            i128,
+            br#"s:40:"-170141183460469231731687303715884105728";"#,
+            i128::MIN
+        );
+        assert_deserializes!(
+            u128,
+            br#"s:39:"340282366920938463463374607431768211455";"#,
+            u128::MAX
+        );
+
+        assert!(from_bytes::<u128>(b"i:-1;").is_err());
+        assert!(from_bytes::<u128>(br#"s:2:"-1";"#).is_err());
+        assert!(from_bytes::<i128>(br#"s:3:"1.5";"#).is_err());
+        assert!(from_bytes::<i128>(b"s:1:\"\xff\";").is_err());
+    }
+
+    #[test]
+    fn deserialize_float() {
+        assert_deserializes!(f64, b"d:-1;", -1.0);
+        assert_deserializes!(f64, b"d:0;", 0.0);
+        assert_deserializes!(f64, b"d:1;", 1.0);
+        assert_deserializes!(f64, b"d:-1.9;", -1.9);
+        assert_deserializes!(f64, b"d:0.9;", 0.9);
+        assert_deserializes!(f64, b"d:1.9;", 1.9);
+        assert_deserializes!(f64, b"d:3.0e-15;", 3.0E-15);
+        assert_deserializes!(f64, b"d:3.0e15;", 3.0E15);
+        assert_deserializes!(f64, b"d:3.0e+15;", 3.0E+15);
+        assert_deserializes!(f64, b"d:3.0000000000000004E-5;", 3.000_000_000_000_000_4E-5);
+    }
+
+    #[test]
+    fn deserialize_non_finite_float() {
+        assert_deserializes!(f64, b"d:INF;", f64::INFINITY);
+        assert_deserializes!(f64, b"d:-INF;", f64::NEG_INFINITY);
+        assert_deserializes!(f32, b"d:-INF;", f32::NEG_INFINITY);
+        assert!(from_bytes::<f64>(b"d:NAN;").unwrap().is_nan());
+        assert_eq!(
+            from_bytes::<Value>(b"a:1:{i:0;d:INF;}").unwrap(),
+            Value::Array(vec![(PhpKey::Int(0), Value::Float(f64::INFINITY))])
+        );
+
+        assert!(from_bytes::<f64>(b"d:-NAN;").is_err());
+        assert!(from_bytes::<f64>(b"d:+INF;").is_err());
+        assert!(from_bytes::<f64>(b"d:IN;").is_err());
+        assert!(from_bytes::<f64>(b"d:inf;").is_err());
+    }
+
+    #[test]
+    fn deserialize_php_string() {
+        assert_deserializes!(
+            Vec<u8>,
+            br#"s:14:"single quote '";"#,
+            b"single quote '".to_owned()
+        );
+    }
+
+    #[test]
+    fn deserialize_string() {
+        assert_deserializes!(
+            String,
+            br#"s:14:"single quote '";"#,
+            "single quote '".to_owned()
+        );
+    }
+
+    #[test]
+    fn deserialize_array() {
+        #[derive(Debug, Deserialize, Eq, PartialEq)]
+        struct SubData();
+
+        #[derive(Debug, Deserialize, Eq, PartialEq)]
+        struct Data(Vec<u8>, Vec<u8>, SubData);
+
+        assert_deserializes!(
+            Data,
+            br#"a:3:{i:0;s:4:"user";i:1;s:0:"";i:2;a:0:{}}"#,
+            Data(b"user".to_vec(), b"".to_vec(), SubData())
+        );
+    }
+
+    #[test]
+    fn deserialize_array_unordered() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Data(#[serde(deserialize_with = "deserialize_unordered_array")] Vec<f64>);
+
+        let expected = Data(vec![1.1, 2.2, 3.3, 4.4]);
+
+        assert_deserializes!(
+            Data,
+            br#"a:4:{i:1;d:2.2;i:0;d:1.1;i:3;d:4.4;i:2;d:3.3;}"#,
+            expected
+        );
+    }
+
+    #[test]
+    fn deserialize_array_unordered_with_holes() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Data(#[serde(deserialize_with = "deserialize_unordered_array")] Vec<String>);
+
+        let expected = Data(vec![
+            "zero".to_string(),
+            "one".to_string(),
+            "two".to_string(),
+            "six".to_string(),
+        ]);
+
+        assert_deserializes!(
+            Data,
+            br#"a:4:{i:0;s:4:"zero";i:2;s:3:"two";i:1;s:3:"one";i:6;s:3:"six";}"#,
+            expected
+        );
+    }
+
+    #[test]
+    fn deserialize_array_unordered_hole_policies() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Defaults(
+            #[serde(deserialize_with = "deserialize_unordered_array_with_defaults")] Vec<i64>,
+        );
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Options(
+            #[serde(deserialize_with = "deserialize_unordered_array_with_defaults")]
+            Vec<Option<char>>,
+        );
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Strict(
+            #[serde(deserialize_with = "deserialize_unordered_array_without_holes")] Vec<i64>,
+        );
+
+        let holes = b"a:2:{i:3;i:3;i:1;i:1;}";
+        assert_deserializes!(Defaults, holes, Defaults(vec![0, 1, 0, 3]));
+        assert_deserializes!(
+            Options,
+            b"a:2:{i:3;i:100;i:1;i:97;}",
+            Options(vec![None, Some('a'), None, Some('d')])
+        );
+        assert!(from_bytes::<Strict>(holes).is_err());
+
+        let complete = b"a:2:{i:1;i:1;i:0;i:0;}";
+        assert_deserializes!(Defaults, complete, Defaults(vec![0, 1]));
+        assert_deserializes!(Strict, complete, Strict(vec![0, 1]));
+
+        // Filling is limited.
+        assert!(from_bytes::<Defaults>(b"a:1:{i:4000000000;i:1;}").is_err());
+    }
+
+    #[test]
+    fn deserialize_array_unordered_lenient() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Row {
+            id: i64,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Batch(#[serde(deserialize_with = "deserialize_unordered_array_lenient")] Vec<Row>);
+
+        let input =
+            br#"a:3:{i:1;a:1:{s:2:"id";s:1:"x";}i:2;a:1:{s:2:"id";i:2;}i:0;a:1:{s:2:"id";i:0;}}"#;
+        let (batch, errors) = from_bytes_lenient::<Batch>(input).unwrap();
+        assert_eq!(batch, Batch(vec![Row { id: 0 }, Row { id: 2 }]));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].position(), Some(9));
+        assert_eq!(errors[0].path(), Some("id"));
+
+        // Malformed input and invalid keys still fail the whole array.
+        assert!(from_bytes::<Batch>(b"a:1:{i:0;a:1:{s:2:\"id\";i:1;").is_err());
+        assert!(from_bytes::<Batch>(br#"a:1:{s:1:"a";a:1:{s:2:"id";i:1;}}"#).is_err());
+    }
+
+    #[test]
+    fn deserialize_array_indexed() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Data(#[serde(deserialize_with = "deserialize_indexed_array")] Vec<(i64, String)>);
+
+        let expected = Data(vec![
+            (-3, "minus three".to_string()),
+            (0, "zero".to_string()),
+            (2, "two".to_string()),
+            (6, "six".to_string()),
+        ]);
+
+        assert_deserializes!(
+            Data,
+            br#"a:4:{i:6;s:3:"six";i:0;s:4:"zero";i:-3;s:11:"minus three";i:2;s:3:"two";}"#,
+            expected
+        );
+        assert!(from_bytes::<Data>(br#"a:1:{s:1:"a";i:1;}"#).is_err());
+    }
+
+    #[test]
+    fn deserialize_struct() {
+        // PHP equiv:
+        //
+        // array("foo" => true,
+        //       "bar" => "xyz",
+        //       "sub" => array("x" => 42))
+
+        #[derive(Debug, Deserialize, Eq, PartialEq)]
+        struct Outer {
+            foo: bool,
+            bar: String,
+            sub: Inner,
+        }
+
+        #[derive(Debug, Deserialize, Eq, PartialEq)]
+        struct Inner {
+            x: i64,
+        }
+
+        assert_deserializes!(
+            Outer,
+            br#"a:3:{s:3:"foo";b:1;s:3:"bar";s:3:"xyz";s:3:"sub";a:1:{s:1:"x";i:42;}}"#,
+            Outer {
+                foo: true,
+                bar: "xyz".to_owned(),
+                sub: Inner { x: 42 },
+            }
+        );
+    }
+
+    #[test]
+    fn deserialize_struct_with_optional() {
+        #[derive(Debug, Deserialize, Eq, PartialEq)]
+        struct Location {
+            province: Option<String>,
+            postalcode: Option<String>,
+            country: Option<String>,
+        }
+
+        assert_deserializes!(
+            Location,
+            br#"a:0:{}"#,
+            Location {
+                province: None,
+                postalcode: None,
+                country: None,
+            }
+        );
+        assert_deserializes!(
+            Location,
+            br#"a:1:{s:8:"province";s:29:"Newfoundland and Labrador, CA";}"#,
+            Location {
+                province: Some("Newfoundland and Labrador, CA".to_owned()),
+                postalcode: None,
+                country: None,
+            }
+        );
+        assert_deserializes!(
+            Location,
+            br#"a:2:{s:10:"postalcode";s:5:"90002";s:7:"country";s:24:"United States of America";}"#,
+            Location {
+            province: None,
+            postalcode: Some("90002".to_owned()),
+            country: Some("United States of America".to_owned()),
+        }
+        );
+    }
+
+    #[test]
+    fn deserialize_object_as_struct() {
+        #[derive(Debug, Deserialize, Eq, PartialEq)]
+        struct Point {
+            x: i64,
+            y: i64,
+        }
+
+        #[derive(Debug, Deserialize, Eq, PartialEq)]
+        struct Outer {
+            points: Vec<Point>,
+        }
+
+        assert_deserializes!(
+            Point,
+            br#"O:5:"Point":2:{s:1:"x";i:1;s:1:"y";i:-2;}"#,
+            Point { x: 1, y: -2 }
+        );
+
+        assert_deserializes!(
+            Outer,
+            br#"a:1:{s:6:"points";a:1:{i:0;O:5:"Point":2:{s:1:"x";i:3;s:1:"y";i:4;}}}"#,
+            Outer {
+                points: vec![Point { x: 3, y: 4 }]
+            }
+        );
+    }
+
+    #[test]
+    fn deserialize_mangled_properties() {
+        // PHP:
+        //
+        // class User {
+        //     public $name = "bob";
+        //     protected $id = 7;
+        //     private $secret = true;
+        // }
+        #[derive(Debug, Deserialize, Eq, PartialEq)]
+        struct User {
+            name: String,
+            id: i64,
+            secret: bool,
+        }
+
+        assert_deserializes!(
+            User,
+            b"O:4:\"User\":3:{s:4:\"name\";s:3:\"bob\";s:5:\"\0*\0id\";i:7;s:12:\"\0User\0secret\";b:1;}",
+            User {
+                name: "bob".to_owned(),
+                id: 7,
+                secret: true,
+            }
+        );
+
+        // Arrays are left alone.
+        assert_deserializes!(
+            HashMap<String, i64>,
+            b"a:1:{s:5:\"\0*\0id\";i:7;}",
+            vec![("\0*\0id".to_owned(), 7)].into_iter().collect()
+        );
+    }
+
+    #[test]
+    fn deserialize_nested() {
+        // PHP: array("x" => array("inner" => 1), "y" => array("inner" => 2))
+        #[derive(Debug, Deserialize, Eq, PartialEq)]
+        struct Outer {
+            x: Inner,
+            y: Inner,
+        }
+
+        #[derive(Debug, Deserialize, Eq, PartialEq)]
+        struct Inner {
+            inner: u8,
+        }
+
+        assert_deserializes!(
+            Outer,
+            br#"a:2:{s:1:"x";a:1:{s:5:"inner";i:1;}s:1:"y";a:1:{s:5:"inner";i:2;}}"#,
+            Outer {
+                x: Inner { inner: 1 },
+                y: Inner { inner: 2 },
+            }
+        );
+    }
+
+    #[test]
+    fn deserialize_enum_variants() {
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        enum Shape {
+            Empty,
+            Circle(f64),
+            Line(i64, i64),
+            Rect { w: i64, h: i64 },
+        }
+
+        let shapes = vec![
+            Shape::Empty,
+            Shape::Circle(0.5),
+            Shape::Line(1, 2),
+            Shape::Rect { w: 3, h: 4 },
+        ];
+        let serialized = to_vec(&shapes).unwrap();
+        assert_eq!(from_bytes::<Vec<Shape>>(&serialized).unwrap(), shapes);
+        let value: Value = from_bytes(&serialized).unwrap();
+        assert_eq!(crate::from_value::<Vec<Shape>>(value).unwrap(), shapes);
+
+        // Unit variants may be wrapped as well.
+        assert_deserializes!(Shape, br#"a:1:{s:5:"Empty";N;}"#, Shape::Empty);
+
+        let err = from_bytes::<Shape>(br#"a:1:{s:4:"Rect";a:1:{s:1:"w";s:1:"x";}}"#).unwrap_err();
+        assert_eq!(err.path(), Some("Rect.w"));
+        assert!(from_bytes::<Shape>(br#"a:1:{s:6:"Square";i:1;}"#).is_err());
+        assert!(from_bytes::<Shape>(br#"a:2:{s:6:"Circle";d:1;s:5:"Empty";N;}"#).is_err());
+        assert!(from_bytes::<Shape>(b"a:0:{}").is_err());
+    }
+
+    #[test]
+    fn deserialize_untagged() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        enum Status {
+            Active,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        #[serde(untagged)]
+        enum Field {
+            Null(()),
+            Bool(bool),
+            Int(i64),
+            Float(f64),
+            Status(Status),
+            Text(String),
+            List(Vec<Field>),
+            Bytes(serde_bytes::ByteBuf),
+            ById(BTreeMap<i64, Field>),
+            Map(BTreeMap<String, Field>),
+        }
+
+        let input = [
+            &br#"a:10:{i:0;N;i:1;b:1;i:2;i:7;i:3;d:0.5;i:4;s:6:"Active";i:5;s:3:"abc";i:6;s:1:""#[..],
+            b"\xff",
+            br#"";i:7;a:2:{i:0;i:1;i:1;i:2;}i:8;a:2:{i:0;s:1:"a";i:2;s:1:"b";}i:9;a:1:{s:1:"k";a:0:{}}}"#,
+        ]
+        .concat();
+        let by_id = vec![
+            (0, Field::Text("a".to_owned())),
+            (2, Field::Text("b".to_owned())),
+        ];
+        assert_eq!(
+            from_bytes::<Field>(&input).unwrap(),
+            Field::List(vec![
+                Field::Null(()),
+                Field::Bool(true),
+                Field::Int(7),
+                Field::Float(0.5),
+                Field::Status(Status::Active),
+                Field::Text("abc".to_owned()),
+                Field::Bytes(serde_bytes::ByteBuf::from(vec![0xff])),
+                Field::List(vec![Field::Int(1), Field::Int(2)]),
+                Field::ById(by_id.into_iter().collect()),
+                Field::Map(
+                    vec![("k".to_owned(), Field::List(vec![]))]
+                        .into_iter()
+                        .collect()
+                ),
+            ])
+        );
+
+        // Numeric arrays are read ahead, errors in them still have
+        // positions.
+        let err = from_bytes::<Field>(b"a:2:{i:3;i:1;i:1;x:2;}").unwrap_err();
+        assert_eq!(err.position(), Some(19));
+        let options = DeserializerOptions::new().duplicate_keys(DuplicateKeys::Error);
+        let err =
+            from_bytes_with_options::<Field>(b"a:2:{i:3;i:1;i:3;i:2;}", &options).unwrap_err();
+        assert_eq!(err.position(), Some(13));
+        assert!(matches!(
+            err.into_inner(),
+            Error::DuplicateKey { offset: 13, .. }
+        ));
+    }
+
+    #[test]
+    fn deserialize_flattened() {
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct Meta {
+            version: i64,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct User {
+            name: String,
+            #[serde(flatten)]
+            meta: Meta,
+            #[serde(flatten)]
+            extra: BTreeMap<String, Value>,
+        }
+
+        // Unknown keys are captured, including integer keys.
+        let input = br#"O:4:"User":4:{s:4:"name";s:3:"bob";s:7:"version";i:2;s:4:"tags";a:2:{i:3;s:1:"a";i:1;s:1:"b";}i:7;b:1;}"#;
+        let user: User = from_bytes(input).unwrap();
+        assert_eq!(user.meta, Meta { version: 2 });
+        assert_eq!(
+            user.extra.keys().collect::<Vec<_>>(),
+            vec![&"7".to_owned(), &"tags".to_owned()]
+        );
+        assert_eq!(user.extra["7"], Value::Bool(true));
+        assert_eq!(
+            user.extra["tags"].as_array().unwrap()[0],
+            (PhpKey::Int(3), Value::from("a"))
+        );
+
+        let serialized = to_vec(&user).unwrap();
+        assert_eq!(from_bytes::<User>(&serialized).unwrap(), user);
+    }
+
+    #[test]
+    fn deserialize_variable_length() {
+        // PHP: array(1.1, 2.2, 3.3, 4.4)
+        assert_deserializes!(
+            Vec<f64>,
+            br#"a:4:{i:0;d:1.1;i:1;d:2.2;i:2;d:3.3;i:3;d:4.4;}"#,
+            vec![1.1, 2.2, 3.3, 4.4]
+        );
+    }
+
+    #[test]
+    fn deserialize_hashmap() {
+        // PHP: array("foo" => 1, "bar" => 2)
+        let mut expected = HashMap::new();
+        expected.insert("foo".to_owned(), 1);
+        expected.insert("bar".to_owned(), 2);
+
+        assert_deserializes!(HashMap<String, u16>, br#"a:2:{s:3:"foo";i:1;s:3:"bar";i:2;}"#, expected);
+    }
+
+    #[test]
+    fn deserialize_hashmap_integer_keys_as_strings() {
+        // PHP: array("0" => 1, "foo" => 2), where PHP turned "0" into 0.
+        let mut expected = HashMap::new();
+        expected.insert("0".to_owned(), 1);
+        expected.insert("foo".to_owned(), 2);
+
+        assert_deserializes!(HashMap<String, u16>, br#"a:2:{i:0;i:1;s:3:"foo";i:2;}"#, expected);
+    }
+
+    #[test]
+    fn deserialize_numeric_strings() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Product {
+            id: u32,
+            price: f64,
+            name: String,
+        }
+
+        let input = br#"a:3:{s:2:"id";s:2:"42";s:5:"price";s:5:" 9.5 ";s:4:"name";s:2:"17";}"#;
+        let options = DeserializerOptions::new().numeric_strings(true);
+
+        assert_eq!(
+            from_bytes_with_options::<Product>(input, &options).unwrap(),
+            Product {
+                id: 42,
+                price: 9.5,
+                name: "17".to_owned(),
+            }
+        );
+
+        // Strict by default.
+        assert!(from_bytes::<Product>(input).is_err());
+
+        // Trailing garbage, fractions for integers and overflows are rejected.
+        for input in &[
+            &br#"s:3:"42a";"#[..],
+            br#"s:3:"1.5";"#,
+            br#"s:3:"256";"#,
+            br#"s:0:"";"#,
+        ] {
+            assert!(from_bytes_with_options::<u8>(input, &options).is_err());
+        }
+    }
+
+    #[test]
+    fn deserialize_lossy_utf8() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Row<'a> {
+            #[serde(borrow)]
+            name: Cow<'a, str>,
+            city: String,
+        }
+
+        let input = b"a:2:{s:4:\"name\";s:4:\"Ren\xe9\";s:4:\"city\";s:5:\"Paris\";}";
+        assert!(from_bytes::<Row<'_>>(input).is_err());
+
+        let options = DeserializerOptions::new().lossy_utf8(true);
+        let row: Row<'_> = from_bytes_with_options(input, &options).unwrap();
+        assert_eq!(row.name, "Ren\u{fffd}");
+        assert_eq!(row.city, "Paris");
+
+        // Copied strings as well.
+        let mut de = PhpDeserializer::with_options(&b"s:2:\"\xff!\";"[..], options.clone());
+        assert_eq!(String::deserialize(&mut de).unwrap(), "\u{fffd}!");
+
+        // Valid strings are still borrowed, invalid ones cannot be.
+        let s: &str = from_bytes_with_options(b"s:2:\"ok\";", &options).unwrap();
+        assert_eq!(s, "ok");
+        assert!(from_bytes_with_options::<&str>(b"s:1:\"\xff\";", &options).is_err());
+    }
+
+    #[test]
+    fn deserialize_loose_bools() {
+        let options = DeserializerOptions::new().loose_bools(true);
+
+        let input = br#"a:6:{i:0;b:1;i:1;i:0;i:2;i:1;i:3;s:1:"1";i:4;s:1:"0";i:5;s:0:"";}"#;
+        assert_eq!(
+            from_bytes_with_options::<Vec<bool>>(input, &options).unwrap(),
+            vec![true, false, true, true, false, false]
+        );
+        assert!(from_bytes::<Vec<bool>>(input).is_err());
+
+        for input in &[&b"i:2;"[..], br#"s:4:"true";"#, b"d:1;"] {
+            assert!(from_bytes_with_options::<bool>(input, &options).is_err());
+        }
+    }
+
+    #[test]
+    fn deserialize_float_to_int() {
+        let options = DeserializerOptions::new().float_to_int(true);
+
+        let input = b"a:3:{i:0;d:3;i:1;d:-2;i:2;i:7;}";
+        assert_eq!(
+            from_bytes_with_options::<Vec<i8>>(input, &options).unwrap(),
+            vec![3, -2, 7]
+        );
+        assert!(from_bytes::<Vec<i8>>(input).is_err());
+
+        assert!(from_bytes_with_options::<u64>(b"d:1.8446744073709552E+19;", &options).is_err());
+        assert_eq!(
+            from_bytes_with_options::<u64>(b"d:9.2233720368547758E+18;", &options).unwrap(),
+            1 << 63
+        );
+        for input in &[&b"d:1.5;"[..], b"d:300;", b"d:-1;", b"d:INF;"] {
+            assert!(from_bytes_with_options::<u8>(input, &options).is_err());
+        }
+
+        // Integers are always accepted for floats.
+        assert_eq!(from_bytes::<f64>(b"i:3;").unwrap(), 3.0);
+
+        // Combined with numeric strings.
+        let options = options.numeric_strings(true);
+        assert_eq!(
+            from_bytes_with_options::<i32>(br#"s:3:"4.0";"#, &options).unwrap(),
+            4
+        );
+    }
+
+    #[test]
+    fn deserialize_partial() {
+        let input = br#"a:1:{i:0;s:1:"x";}N;b:1;"#;
+
+        let (first, rest): (Vec<String>, _) = from_bytes_partial(input).unwrap();
+        assert_eq!(first, vec!["x".to_owned()]);
+        assert_eq!(rest, b"N;b:1;");
+
+        let (second, rest): (Option<bool>, _) = from_bytes_partial(rest).unwrap();
+        assert_eq!(second, None);
+        let (third, rest): (bool, _) = from_bytes_partial(rest).unwrap();
+        assert!(third);
+        assert!(rest.is_empty());
+
+        // Integers stop at the first non-digit, which must not be lost.
+        let (n, rest): (i64, _) = from_bytes_partial(b"i:12;3").unwrap();
+        assert_eq!((n, rest), (12, &b"3"[..]));
+    }
+
+    #[test]
+    fn deserialize_buffered_depth_limit() {
+        // Values buffered on the way are limited by the depth the
+        // deserializer allows, not by the default of the serializer.
+        let mut input = b"a:1:{i:0;".repeat(130);
+        input.extend_from_slice(b"i:1;");
+        input.extend(vec![b'}'; 130]);
+
+        // Debug builds need more stack for this than tests get by default.
+        let check = move || {
+            let options = DeserializerOptions::new().max_depth(130);
+            let last = options.clone().duplicate_keys(DuplicateKeys::Last);
+            assert!(from_bytes_with_options::<Vec<Value>>(&input, &last).is_ok());
+            assert!(from_bytes_with_options::<Lenient<Value>>(&input, &options).is_ok());
+            let mut de = PhpDeserializer::with_options(&input[..], options);
+            assert!(serde_json::Value::deserialize(&mut de).is_ok());
+
+            let options = DeserializerOptions::new().max_depth(129);
+            let mut de = PhpDeserializer::with_options(&input[..], options);
+            assert!(serde_json::Value::deserialize(&mut de).is_err());
+        };
+        let thread = std::thread::Builder::new().stack_size(64 << 20);
+        thread.spawn(check).unwrap().join().unwrap();
+    }
+
+    #[test]
+    fn deserialize_depth_limit() {
+        fn nested(depth: usize) -> Vec<u8> {
+            let mut input = b"a:1:{i:0;".repeat(depth);
+            input.extend_from_slice(b"i:1;");
+            input.extend(vec![b'}'; depth]);
+            input
+        }
+
+        // The default is generous enough for regular data.
+        assert!(from_bytes::<Value>(&nested(128)).is_ok());
+        assert!(matches!(
+            from_bytes::<Value>(&nested(129)).map_err(Error::into_inner),
+            Err(Error::DepthLimitExceeded)
+        ));
+        // Fails cleanly instead of overflowing the stack.
+        assert!(matches!(
+            from_bytes::<IgnoredAny>(&nested(100_000)).map_err(Error::into_inner),
+            Err(Error::DepthLimitExceeded)
+        ));
+
+        let options = DeserializerOptions::new().max_depth(2);
+        assert!(from_bytes_with_options::<Vec<Vec<i64>>>(&nested(2), &options).is_ok());
+        assert!(from_bytes_with_options::<Vec<Vec<Vec<i64>>>>(&nested(3), &options).is_err());
+
+        // Objects count, including the payload of custom serialized ones.
+        let input = br#"a:1:{i:0;O:1:"A":1:{s:1:"a";a:0:{}}}"#;
+        assert!(from_bytes_with_options::<Value>(input, &options).is_ok());
+        let input = br#"a:1:{i:0;C:11:"ArrayObject":39:{x:i:0;a:1:{i:0;a:1:{i:0;i:1;}};m:a:0:{}}}"#;
+        assert!(from_bytes_with_options::<Vec<Vec<Vec<i64>>>>(input, &options).is_err());
     }
 
     #[test]
-    fn deserialize_integer() {
-        assert_deserializes!(i64, b"i:-1;", -1);
-        assert_deserializes!(i64, b"i:0;", 0);
-        assert_deserializes!(i64, b"i:1;", 1);
-        assert_deserializes!(i64, b"i:123;", 123);
+    fn deserialize_huge_lengths() {
+        // Declared lengths beyond the input fail without allocating them.
+        for input in &[
+            &br#"s:4294967295:"abc";"#[..],
+            br#"a:1:{i:0;s:18446744073709551615:"";}"#,
+            br#"O:4294967295:"A":0:{}"#,
+            br#"C:1:"A":4294967295:{}"#,
+        ] {
+            assert!(from_bytes::<Value>(input).is_err());
+        }
+        assert!(from_bytes::<Value>(b"a:4294967295:{i:0;i:1;}").is_err());
+
+        let options = DeserializerOptions::new().max_length(3);
+        assert_eq!(
+            from_bytes_with_options::<Vec<u8>>(br#"s:3:"abc";"#, &options).unwrap(),
+            b"abc"
+        );
+        assert!(matches!(
+            from_bytes_with_options::<Vec<u8>>(br#"s:4:"abcd";"#, &options)
+                .map_err(Error::into_inner),
+            Err(Error::LengthLimitExceeded(4))
+        ));
+        assert!(matches!(
+            from_bytes_with_options::<Vec<i64>>(
+                b"a:4:{i:0;i:1;i:1;i:1;i:2;i:1;i:3;i:1;}",
+                &options
+            )
+            .map_err(Error::into_inner),
+            Err(Error::LengthLimitExceeded(4))
+        ));
     }
 
     #[test]
-    fn deserialize_float() {
-        assert_deserializes!(f64, b"d:-1;", -1.0);
-        assert_deserializes!(f64, b"d:0;", 0.0);
-        assert_deserializes!(f64, b"d:1;", 1.0);
-        assert_deserializes!(f64, b"d:-1.9;", -1.9);
-        assert_deserializes!(f64, b"d:0.9;", 0.9);
-        assert_deserializes!(f64, b"d:1.9;", 1.9);
-        assert_deserializes!(f64, b"d:3.0e-15;", 3.0E-15);
-        assert_deserializes!(f64, b"d:3.0e15;", 3.0E15);
-        assert_deserializes!(f64, b"d:3.0e+15;", 3.0E+15);
-        assert_deserializes!(f64, b"d:3.0000000000000004E-5;", 3.000_000_000_000_000_4E-5);
+    fn deserialize_deny_unknown_keys() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct User {
+            name: String,
+            #[serde(rename = "mail")]
+            email: Option<String>,
+        }
+
+        let options = DeserializerOptions::new().deny_unknown_keys(true);
+
+        let input = br#"a:2:{s:4:"name";s:3:"Bob";s:4:"mail";N;}"#;
+        assert!(from_bytes_with_options::<User>(input, &options).is_ok());
+
+        let input = br#"a:2:{s:4:"name";s:3:"Bob";s:5:"admin";b:1;}"#;
+        assert!(from_bytes::<User>(input).is_ok());
+        let err = from_bytes_with_options::<User>(input, &options).unwrap_err();
+        assert!(matches!(err, Error::UnknownKey { ref key, offset: 26 } if key == "admin"));
+
+        // Demangled property names are checked, maps are not affected.
+        let input = b"O:4:\"User\":1:{s:7:\"\0*\0name\";s:3:\"Bob\";}";
+        assert!(from_bytes_with_options::<User>(input, &options).is_ok());
+        let input = br#"a:1:{s:1:"x";i:1;}"#;
+        assert!(from_bytes_with_options::<HashMap<String, i64>>(input, &options).is_ok());
     }
 
     #[test]
-    fn deserialize_php_string() {
-        assert_deserializes!(
-            Vec<u8>,
-            br#"s:14:"single quote '";"#,
-            b"single quote '".to_owned()
+    fn deserialize_duplicate_keys() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Flag {
+            on: bool,
+        }
+
+        let options = |policy| DeserializerOptions::new().duplicate_keys(policy);
+        let first = options(DuplicateKeys::First);
+        let last = options(DuplicateKeys::Last);
+        let error = options(DuplicateKeys::Error);
+
+        let input = br#"a:3:{s:2:"on";b:0;s:1:"x";i:1;s:2:"on";b:1;}"#;
+        assert_eq!(
+            from_bytes_with_options::<Flag>(input, &first).unwrap(),
+            Flag { on: false }
+        );
+        assert_eq!(
+            from_bytes_with_options::<Flag>(input, &last).unwrap(),
+            Flag { on: true }
+        );
+        let err = from_bytes_with_options::<Flag>(input, &error).unwrap_err();
+        assert!(matches!(err, Error::DuplicateKey { ref key, offset: 30 } if key == "on"));
+
+        // Like PHP, the last value takes the position of the first.
+        let value: Value = from_bytes_with_options(input, &last).unwrap();
+        assert_eq!(
+            to_vec(&value).unwrap(),
+            br#"a:2:{s:2:"on";b:1;s:1:"x";i:1;}"#
+        );
+
+        // Numeric keys.
+        let input = br#"a:3:{i:0;s:1:"a";i:1;s:1:"b";i:0;s:1:"c";}"#;
+        assert_eq!(
+            from_bytes_with_options::<Vec<String>>(input, &first).unwrap(),
+            vec!["a".to_owned(), "b".to_owned()]
+        );
+        assert_eq!(
+            from_bytes_with_options::<Vec<String>>(input, &last).unwrap(),
+            vec!["c".to_owned(), "b".to_owned()]
+        );
+        assert!(from_bytes_with_options::<Vec<String>>(input, &error).is_err());
+        assert!(from_bytes_with_options::<Value>(input, &error).is_err());
+
+        // Nested arrays, with other options still applying.
+        let input = br#"a:1:{i:0;a:2:{s:2:"on";i:0;s:2:"on";i:1;}}"#;
+        let options = last.loose_bools(true);
+        assert_eq!(
+            from_bytes_with_options::<Vec<Flag>>(input, &options).unwrap(),
+            vec![Flag { on: true }]
         );
     }
 
     #[test]
-    fn deserialize_string() {
-        assert_deserializes!(
-            String,
-            br#"s:14:"single quote '";"#,
-            "single quote '".to_owned()
+    fn deserialize_many_duplicate_keys() {
+        // Duplicates are skipped one after another, not recursively.
+        let count = 1_000_000;
+        let mut input = format!("a:{}:{{i:0;i:1;", count + 1).into_bytes();
+        for _ in 0..count {
+            input.extend_from_slice(b"i:0;N;");
+        }
+        input.push(b'}');
+
+        let options = DeserializerOptions::new().duplicate_keys(DuplicateKeys::First);
+        assert_eq!(
+            from_bytes_with_options::<Vec<Option<i64>>>(&input, &options).unwrap(),
+            vec![Some(1)]
         );
+        let map: HashMap<i64, Option<i64>> = from_bytes_with_options(&input, &options).unwrap();
+        assert_eq!(map, HashMap::from([(0, Some(1))]));
     }
 
     #[test]
-    fn deserialize_array() {
-        #[derive(Debug, Deserialize, Eq, PartialEq)]
-        struct SubData();
+    fn deserialize_mixed_keys() {
+        #[derive(Debug, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+        #[serde(untagged)]
+        enum Key {
+            Index(i64),
+            Name(String),
+        }
 
-        #[derive(Debug, Deserialize, Eq, PartialEq)]
-        struct Data(Vec<u8>, Vec<u8>, SubData);
+        #[derive(Debug, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+        enum Slot {
+            #[serde(rename = "0")]
+            Zero,
+            #[serde(rename = "k")]
+            Named,
+            #[serde(rename = "5")]
+            Five,
+        }
 
-        assert_deserializes!(
-            Data,
-            br#"a:3:{i:0;s:4:"user";i:1;s:0:"";i:2;a:0:{}}"#,
-            Data(b"user".to_vec(), b"".to_vec(), SubData())
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Item {
+            id: i64,
+        }
+
+        let input = br#"a:3:{i:0;s:1:"a";s:1:"k";s:1:"b";i:5;s:1:"c";}"#;
+
+        let pairs: Vec<(PhpKey, String)> = from_bytes(input).unwrap();
+        assert_eq!(
+            pairs,
+            vec![
+                (PhpKey::Int(0), "a".to_owned()),
+                (PhpKey::from("k"), "b".to_owned()),
+                (PhpKey::Int(5), "c".to_owned()),
+            ]
+        );
+
+        let value: Value = from_bytes(input).unwrap();
+        assert_eq!(value.get("k"), Some(&Value::from("b")));
+        assert_eq!(value.get(5), Some(&Value::from("c")));
+
+        let map: BTreeMap<Key, String> = from_bytes(input).unwrap();
+        assert_eq!(map[&Key::Name("k".to_owned())], "b");
+        assert_eq!(map[&Key::Index(5)], "c");
+
+        let map: BTreeMap<Slot, String> = from_bytes(input).unwrap();
+        assert_eq!(
+            map.keys().collect::<Vec<_>>(),
+            [&Slot::Zero, &Slot::Named, &Slot::Five]
         );
+
+        // Lists still require their indices, tuples stored as arrays work as before.
+        assert!(from_bytes::<Vec<String>>(input).is_err());
+        let tuples: Vec<(i64, String)> =
+            from_bytes(br#"a:1:{i:0;a:2:{i:0;i:7;i:1;s:1:"x";}}"#).unwrap();
+        assert_eq!(tuples, vec![(7, "x".to_owned())]);
+
+        // Ignored mixed arrays are skipped.
+        let input = br#"a:2:{s:4:"meta";a:2:{i:0;b:1;s:1:"k";b:0;}s:2:"id";i:3;}"#;
+        assert_eq!(from_bytes::<Item>(input).unwrap(), Item { id: 3 });
+
+        // Duplicate keys are checked for pairs as well.
+        let options = DeserializerOptions::new().duplicate_keys(DuplicateKeys::Error);
+        let input = br#"a:2:{s:1:"k";i:1;s:1:"k";i:2;}"#;
+        assert!(from_bytes::<Vec<(PhpKey, i64)>>(input).is_ok());
+        assert!(from_bytes_with_options::<Vec<(PhpKey, i64)>>(input, &options).is_err());
     }
 
     #[test]
-    fn deserialize_array_unordered() {
+    fn deserialize_borrowed() {
         #[derive(Debug, Deserialize, PartialEq)]
-        struct Data(#[serde(deserialize_with = "deserialize_unordered_array")] Vec<f64>);
+        struct Blob<'a> {
+            name: &'a str,
+            #[serde(borrow)]
+            label: Cow<'a, str>,
+            #[serde(with = "serde_bytes")]
+            data: &'a [u8],
+        }
 
-        let expected = Data(vec![1.1, 2.2, 3.3, 4.4]);
+        let input =
+            b"a:3:{s:4:\"name\";s:1:\"a\";s:5:\"label\";s:1:\"b\";s:4:\"data\";s:2:\"\xff\x00\";}";
+        let blob: Blob<'_> = from_bytes(input).unwrap();
+        assert_eq!(blob.name, "a");
+        assert!(matches!(blob.label, Cow::Borrowed("b")));
+        assert_eq!(blob.data, b"\xff\x00");
 
-        assert_deserializes!(
-            Data,
-            br#"a:4:{i:1;d:2.2;i:0;d:1.1;i:3;d:4.4;i:2;d:3.3;}"#,
-            expected
-        );
+        // Borrowed from the input, not copied.
+        let range = input.as_ptr_range();
+        assert!(range.contains(&blob.data.as_ptr()));
+
+        // Readers cannot lend out strings, but still fill owned targets.
+        let mut de = PhpDeserializer::new(&br#"s:1:"b";"#[..]);
+        assert!(<&str>::deserialize(&mut de).is_err());
+        let mut de = PhpDeserializer::new(&br#"s:1:"b";"#[..]);
+        let label: Cow<'_, str> = Deserialize::deserialize(&mut de).unwrap();
+        assert!(matches!(label, Cow::Owned(_)));
+
+        assert!(from_bytes::<&str>(b"s:1:\"\xff\";").is_err());
     }
 
     #[test]
-    fn deserialize_array_unordered_with_holes() {
+    fn deserialize_byte_strings() {
         #[derive(Debug, Deserialize, PartialEq)]
-        struct Data(#[serde(deserialize_with = "deserialize_unordered_array")] Vec<String>);
+        #[serde(untagged)]
+        enum Field {
+            Int(i64),
+            Text(String),
+        }
 
-        let expected = Data(vec![
-            "zero".to_string(),
-            "one".to_string(),
-            "two".to_string(),
-            "six".to_string(),
-        ]);
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct File {
+            #[serde(with = "serde_bytes")]
+            data: Vec<u8>,
+        }
 
-        assert_deserializes!(
-            Data,
-            br#"a:4:{i:0;s:4:"zero";i:2;s:3:"two";i:1;s:3:"one";i:6;s:3:"six";}"#,
-            expected
+        let input = b"s:3:\"a\xffb\";";
+        let buf: serde_bytes::ByteBuf = from_bytes(input).unwrap();
+        assert_eq!(buf.as_slice(), b"a\xffb");
+        let buf: serde_bytes::ByteBuf = from_reader(&input[..]).unwrap();
+        assert_eq!(buf.as_slice(), b"a\xffb");
+
+        // Self-describing targets see strings as bytes, not as sequences.
+        assert_eq!(
+            from_bytes::<Vec<Field>>(br#"a:2:{i:0;i:1;i:1;s:1:"x";}"#).unwrap(),
+            vec![Field::Int(1), Field::Text("x".to_owned())]
         );
+
+        // Plain byte vectors still read strings, as sequences.
+        assert_eq!(from_bytes::<Vec<u8>>(input).unwrap(), b"a\xffb");
+
+        let file = File {
+            data: b"\x00\x01".to_vec(),
+        };
+        let serialized = to_vec(&file).unwrap();
+        assert_eq!(serialized, &b"a:1:{s:4:\"data\";s:2:\"\x00\x01\";}"[..]);
+        assert_eq!(from_bytes::<File>(&serialized).unwrap(), file);
     }
 
     #[test]
-    fn deserialize_struct() {
-        // PHP equiv:
-        //
-        // array("foo" => true,
-        //       "bar" => "xyz",
-        //       "sub" => array("x" => 42))
+    fn deserialize_error_position() {
+        #[derive(Debug, Deserialize)]
+        struct Session {
+            #[allow(dead_code)]
+            user: String,
+        }
 
-        #[derive(Debug, Deserialize, Eq, PartialEq)]
-        struct Outer {
-            foo: bool,
-            bar: String,
-            sub: Inner,
+        let position = |input: &[u8]| from_bytes::<Session>(input).unwrap_err().position();
+        for (input, offset) in [
+            // Type mismatch, after the type.
+            (&b"a:1:{s:4:\"user\";i:7;}"[..], 17),
+            // Truncated string, after the bytes read.
+            (br#"a:1:{s:4:"user";s:9:"bob";}"#, 21),
+            (br#"a:1:{s:4:"user";x:1;}"#, 17),
+            // Missing field, at the end of the array.
+            (br#"a:1:{s:4:"name";s:3:"bob";}"#, 26),
+            (b"", 0),
+        ] {
+            assert_eq!(position(input), Some(offset));
         }
+    }
 
-        #[derive(Debug, Deserialize, Eq, PartialEq)]
-        struct Inner {
-            x: i64,
+    #[test]
+    fn deserialize_error_path() {
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Address {
+            zip: i64,
         }
 
-        assert_deserializes!(
-            Outer,
-            br#"a:3:{s:3:"foo";b:1;s:3:"bar";s:3:"xyz";s:3:"sub";a:1:{s:1:"x";i:42;}}"#,
-            Outer {
-                foo: true,
-                bar: "xyz".to_owned(),
-                sub: Inner { x: 42 },
-            }
-        );
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Profile {
+            addresses: Vec<Address>,
+            tags: BTreeMap<i64, bool>,
+        }
+
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct User {
+            profile: Profile,
+        }
+
+        let err = from_bytes::<User>(
+            br#"a:1:{s:7:"profile";a:2:{s:9:"addresses";a:2:{i:0;a:1:{s:3:"zip";i:1;}i:1;a:1:{s:3:"zip";s:1:"2";}}s:4:"tags";a:0:{}}}"#,
+        )
+        .unwrap_err();
+        assert_eq!(err.path(), Some("profile.addresses[1].zip"));
+        assert_eq!(err.position(), Some(96));
+        assert!(err
+            .to_string()
+            .starts_with("profile.addresses[1].zip: PHP Deserialization failed: invalid type"));
+        assert!(matches!(err.into_inner(), Error::DeserializationFailed(_)));
+
+        // Integer keys of maps are written like indices.
+        let err = from_bytes::<User>(
+            br#"a:1:{s:7:"profile";a:2:{s:9:"addresses";a:0:{}s:4:"tags";a:1:{i:3;i:1;}}}"#,
+        )
+        .unwrap_err();
+        assert_eq!(err.path(), Some("profile.tags[3]"));
+
+        // Errors in the top-level value, or detected before reaching a
+        // value, have no path.
+        assert_eq!(from_bytes::<i64>(b"b:1;").unwrap_err().path(), None);
+        let err =
+            from_bytes::<User>(br#"a:1:{s:7:"profile";a:1:{s:9:"addresses";a:0:{}}}"#).unwrap_err();
+        assert_eq!(err.path(), Some("profile"));
     }
 
     #[test]
-    fn deserialize_struct_with_optional() {
-        #[derive(Debug, Deserialize, Eq, PartialEq)]
-        struct Location {
-            province: Option<String>,
-            postalcode: Option<String>,
-            country: Option<String>,
+    fn deserializer_directly() {
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Item {
+            id: i64,
         }
 
-        assert_deserializes!(
-            Location,
-            br#"a:0:{}"#,
-            Location {
-                province: None,
-                postalcode: None,
-                country: None,
+        /// Seed adding up the numbers it reads, to carry state into
+        /// deserialization.
+        struct Sum<'a>(&'a mut i64);
+
+        impl<'de> DeserializeSeed<'de> for Sum<'_> {
+            type Value = ();
+
+            fn deserialize<D>(self, deserializer: D) -> Result<(), D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                *self.0 += Vec::<i64>::deserialize(deserializer)?.iter().sum::<i64>();
+                Ok(())
             }
+        }
+
+        let input = br#"a:2:{i:0;a:1:{s:2:"id";i:1;}i:1;a:1:{s:2:"id";b:1;}}"#;
+        let mut de = PhpDeserializer::from_slice(input);
+        let err = serde_path_to_error::deserialize::<_, Vec<Item>>(&mut de).unwrap_err();
+        assert_eq!(err.path().to_string(), "[1].id");
+
+        let mut total = 0;
+        let mut de = PhpDeserializer::from_slice(b"a:2:{i:0;i:3;i:1;i:4;}");
+        Sum(&mut total).deserialize(&mut de).unwrap();
+        assert_eq!(total, 7);
+    }
+
+    #[test]
+    fn deserialize_unexpected_type() {
+        let err = from_bytes::<Vec<String>>(b"a:2:{i:0;s:1:\"a\";i:1;i:5;}").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[1]: Expected `s` (string) but got `i` (integer) instead at byte 22"
         );
-        assert_deserializes!(
-            Location,
-            br#"a:1:{s:8:"province";s:29:"Newfoundland and Labrador, CA";}"#,
-            Location {
-                province: Some("Newfoundland and Labrador, CA".to_owned()),
-                postalcode: None,
-                country: None,
+        assert!(matches!(
+            err.into_inner(),
+            Error::UnexpectedType {
+                expected: 's',
+                actual: 'i'
             }
+        ));
+
+        let err = from_bytes::<BTreeMap<String, i64>>(b"b:1;").unwrap_err();
+        assert!(matches!(
+            err.into_inner(),
+            Error::UnexpectedType {
+                expected: 'a',
+                actual: 'b'
+            }
+        ));
+        let err = from_bytes::<char>(b"d:0.5;").unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Expected `i` (integer) but got `d` (float) instead"));
+    }
+
+    #[test]
+    fn deserialize_error_context() {
+        let input = b"a:2:{i:0;s:3:\"\x00\xffa\";i:1;b:2;}";
+        let options = DeserializerOptions::new().error_context(6);
+        let err = from_bytes_with_options::<Vec<Value>>(input, &options).unwrap_err();
+        assert_eq!(err.position(), Some(27));
+        assert_eq!(
+            err.to_string(),
+            "[1]: Not a valid value for boolean: 2 at byte 27, \
+             near 31 3b 62 3a 32 3b | 7d (1;b:2;|})"
         );
-        assert_deserializes!(
-            Location,
-            br#"a:2:{s:10:"postalcode";s:5:"90002";s:7:"country";s:24:"United States of America";}"#,
-            Location {
-            province: None,
-            postalcode: Some("90002".to_owned()),
-            country: Some("United States of America".to_owned()),
+        assert!(matches!(err.into_inner(), Error::InvalidBooleanValue('2')));
+
+        // Non-printable bytes are shown as dots, and the excerpt stops at
+        // the start and end of the input.
+        let err = from_bytes_with_options::<bool>(b"s:2:\"\x00\xff\";", &options).unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("near 3a 22 00 ff 22 3b | (:\"..\";|)"));
+        let err = from_bytes_with_options::<bool>(b"x:1;", &options).unwrap_err();
+        assert!(err.to_string().ends_with("near 78 3a | 31 3b (x:|1;)"));
+
+        // Off by default.
+        let err = from_bytes::<Vec<Value>>(input).unwrap_err();
+        assert!(!err.to_string().contains("near"));
+    }
+
+    #[test]
+    fn deserialize_from_reader() {
+        // A reader without buffering of its own, handing out one byte at a time.
+        struct Trickle<'a>(&'a [u8]);
+
+        impl io::Read for Trickle<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                let n = buf.len().min(self.0.len()).min(1);
+                buf[..n].copy_from_slice(&self.0[..n]);
+                self.0 = &self.0[n..];
+                Ok(n)
+            }
         }
+
+        let input = br#"a:2:{s:1:"a";a:1:{i:0;d:0.5;}s:1:"b";N;}"#;
+        let map: BTreeMap<String, Option<Vec<f64>>> = from_reader(Trickle(input)).unwrap();
+        assert_eq!(map["a"], Some(vec![0.5]));
+        assert_eq!(map["b"], None);
+
+        assert!(matches!(
+            from_reader::<_, Vec<i64>>(Trickle(b"a:1:{i:0;i:")).map_err(Error::into_inner),
+            Err(Error::UnexpectedEof)
+        ));
+    }
+
+    #[test]
+    fn deserialize_reader_nested_arrays() {
+        // Arrays read from a reader are buffered once, at the outermost
+        // level, and the levels within are scanned in the buffer.
+        let de = PhpDeserializer::from_input(
+            BufferInput::new(br#":0;i:1;i:2;s:1:"a";}"#.to_vec()),
+            DeserializerOptions::default(),
+        );
+        assert_eq!(de.scan_list(2), Some(false));
+
+        let input = br#"a:2:{i:0;a:2:{i:0;i:1;i:2;i:3;}i:1;a:1:{i:0;a:1:{i:0;i:4;}}}"#;
+        let value: Vec<serde_json::Value> = from_reader(&input[..]).unwrap();
+        assert_eq!(
+            value,
+            vec![
+                serde_json::json!({"0": 1, "2": 3}),
+                serde_json::json!([[4]])
+            ]
         );
     }
 
     #[test]
-    fn deserialize_nested() {
-        // PHP: array("x" => array("inner" => 1), "y" => array("inner" => 2))
-        #[derive(Debug, Deserialize, Eq, PartialEq)]
-        struct Outer {
-            x: Inner,
-            y: Inner,
+    fn deserialize_reader_errors() {
+        /// A reader interrupted before every byte, then failing for good.
+        struct Flaky<'a> {
+            input: &'a [u8],
+            interrupted: bool,
         }
 
-        #[derive(Debug, Deserialize, Eq, PartialEq)]
-        struct Inner {
-            inner: u8,
+        impl io::Read for Flaky<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                self.interrupted = !self.interrupted;
+                if self.interrupted {
+                    return Err(io::ErrorKind::Interrupted.into());
+                }
+                if self.input.is_empty() {
+                    return Err(io::ErrorKind::ConnectionReset.into());
+                }
+                let n = buf.len().min(self.input.len()).min(1);
+                buf[..n].copy_from_slice(&self.input[..n]);
+                self.input = &self.input[n..];
+                Ok(n)
+            }
         }
 
-        assert_deserializes!(
-            Outer,
-            br#"a:2:{s:1:"x";a:1:{s:5:"inner";i:1;}s:1:"y";a:1:{s:5:"inner";i:2;}}"#,
-            Outer {
-                x: Inner { inner: 1 },
-                y: Inner { inner: 2 },
-            }
+        let flaky = |input| Flaky {
+            input,
+            interrupted: false,
+        };
+
+        // Interruptions are retried.
+        let v: Vec<i64> = from_reader(flaky(b"a:1:{i:0;i:5;}")).unwrap();
+        assert_eq!(v, vec![5]);
+
+        // Other errors are passed on, and keep their kind as `io::Error`.
+        let err = from_reader::<_, Vec<i64>>(flaky(b"a:1:{i:0;")).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Io);
+        assert_eq!(
+            err.io_error().map(io::Error::kind),
+            Some(io::ErrorKind::ConnectionReset)
         );
+        assert!(std::error::Error::source(&err).is_some());
+        assert_eq!(io::Error::from(err).kind(), io::ErrorKind::ConnectionReset);
+
+        let err = from_bytes::<Vec<i64>>(b"a:1:{i:0;").unwrap_err();
+        assert_eq!(io::Error::from(err).kind(), io::ErrorKind::UnexpectedEof);
+        let err = from_bytes::<Vec<i64>>(b"a:1:{i:0;b:1;}").unwrap_err();
+        assert_eq!(io::Error::from(err).kind(), io::ErrorKind::InvalidData);
     }
 
     #[test]
-    fn deserialize_variable_length() {
-        // PHP: array(1.1, 2.2, 3.3, 4.4)
-        assert_deserializes!(
-            Vec<f64>,
-            br#"a:4:{i:0;d:1.1;i:1;d:2.2;i:2;d:3.3;i:3;d:4.4;}"#,
-            vec![1.1, 2.2, 3.3, 4.4]
+    fn deserialize_references() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Team {
+            name: String,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Member {
+            team: Team,
+        }
+
+        let options = DeserializerOptions::new().resolve_references(true);
+        let read = |input: &[u8]| from_bytes_with_options::<Vec<Value>>(input, &options);
+
+        // Objects shared in PHP are read as copies.
+        let input = br#"a:2:{i:0;a:2:{s:4:"name";s:3:"bob";s:4:"team";O:4:"Team":1:{s:4:"name";s:3:"red";}}i:1;a:1:{s:4:"team";r:4;}}"#;
+        let members: Vec<Member> = from_bytes_with_options(input, &options).unwrap();
+        assert_eq!(members[0], members[1]);
+        assert_eq!(members[1].team.name, "red");
+
+        // Keys and `R:` are not numbered, `r:` is.
+        let input = br#"a:4:{i:0;s:1:"a";i:1;R:2;i:2;s:1:"b";i:3;r:3;}"#;
+        let strings: Vec<String> = from_bytes_with_options(input, &options).unwrap();
+        assert_eq!(strings, vec!["a", "a", "b", "b"]);
+
+        // References in copies are resolved in turn.
+        let input = b"a:3:{i:0;a:1:{i:0;i:5;}i:1;a:1:{i:0;r:3;}i:2;r:4;}";
+        let lists: Vec<Vec<i64>> = from_bytes_with_options(input, &options).unwrap();
+        assert_eq!(lists, vec![vec![5], vec![5], vec![5]]);
+        assert_eq!(
+            read(b"a:2:{i:0;i:1;i:1;r:3;}").unwrap_err().position(),
+            Some(21)
         );
+
+        // Ignored values are numbered as well.
+        let input = br#"a:2:{s:4:"team";O:4:"Team":1:{s:4:"name";s:3:"red";}s:4:"name";r:3;}"#;
+        let team: Team = from_bytes_with_options(input, &options).unwrap();
+        assert_eq!(team.name, "red");
+        let mut de = PhpDeserializer::with_options(&input[..], options.clone());
+        assert_eq!(Team::deserialize(&mut de).unwrap(), team);
+
+        // Only values read before can be referred to.
+        for input in [&b"a:1:{i:0;r:3;}"[..], b"r:0;"] {
+            let err = read(input).unwrap_err();
+            assert!(matches!(err.into_inner(), Error::InvalidReference(_)));
+        }
+        let err = from_bytes_with_options::<IgnoredAny>(b"a:1:{i:0;r:9;}", &options).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidValue);
+
+        // Rejected by default.
+        let err = from_bytes::<Vec<Value>>(b"a:2:{i:0;i:1;i:1;r:2;}").unwrap_err();
+        assert!(matches!(err.into_inner(), Error::InvalidTypeIndicator('r')));
     }
 
     #[test]
-    fn deserialize_hashmap() {
-        // PHP: array("foo" => 1, "bar" => 2)
-        let mut expected = HashMap::new();
-        expected.insert("foo".to_owned(), 1);
-        expected.insert("bar".to_owned(), 2);
+    fn deserialize_cyclic_references() {
+        let options = DeserializerOptions::new().resolve_references(true);
+        let read = |input: &[u8]| from_bytes_with_options::<Value>(input, &options);
 
-        assert_deserializes!(HashMap<String, u16>, br#"a:2:{s:3:"foo";i:1;s:3:"bar";i:2;}"#, expected);
+        // The array itself, a reference to itself and an array being read.
+        for (input, number, position) in [
+            (&b"a:1:{i:0;r:1;}"[..], 1, 13),
+            (b"a:1:{i:0;r:2;}", 2, 13),
+            (b"a:1:{i:0;a:1:{i:0;R:2;}}", 2, 22),
+        ] {
+            let err = read(input).unwrap_err();
+            assert_eq!(err.position(), Some(position));
+            assert!(matches!(err.into_inner(), Error::CyclicReference(n) if n == number));
+        }
+
+        // Broken with `null`, also when copied.
+        let options = options.cyclic_references(CyclicReferences::Null);
+        let read = |input: &[u8]| from_bytes_with_options::<Value>(input, &options);
+        let value = read(b"a:2:{i:0;a:1:{i:0;r:2;}i:1;r:2;}").unwrap();
+        let inner = Value::from(vec![Value::Null]);
+        assert_eq!(value, Value::from(vec![inner.clone(), inner]));
+        assert_eq!(read(b"r:1;").unwrap(), Value::Null);
+
+        // Errors in copies are positioned at the original.
+        let input = b"a:2:{i:0;a:1:{i:0;i:7;}i:1;r:2;}";
+        let err = from_bytes_with_options::<(Vec<i64>, Vec<bool>)>(input, &options).unwrap_err();
+        assert_eq!(err.position(), Some(22));
+    }
+
+    #[test]
+    fn deserialize_ignored() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Summary {
+            id: i64,
+        }
+
+        let input = br#"a:4:{s:4:"blob";s:5:"x;}"y";s:4:"list";a:2:{i:0;O:1:"A":1:{s:1:"a";d:0.5;}i:1;C:1:"B":3:{a:}}}s:2:"id";i:9;s:4:"case";E:5:"E:Foo";}"#;
+        assert_eq!(from_bytes::<Summary>(input).unwrap(), Summary { id: 9 });
+
+        // Readers without borrowing skip the same way.
+        let mut de = PhpDeserializer::new(&input[..]);
+        assert_eq!(Summary::deserialize(&mut de).unwrap(), Summary { id: 9 });
+        assert_eq!(de.byte_offset(), input.len());
+
+        // The structure is still checked.
+        assert!(from_bytes::<IgnoredAny>(br#"s:5:"x";"#).is_err());
+        assert!(from_bytes::<IgnoredAny>(b"a:1:{a:0:{}i:0;}").is_err());
+        assert!(from_bytes::<IgnoredAny>(b"a:2:{i:0;N;}").is_err());
+    }
+
+    #[test]
+    fn extract_path() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Point {
+            x: i64,
+        }
+
+        let input = b"O:4:\"Page\":3:{s:8:\"\0*\0items\";a:2:{i:0;s:1:\"a\";i:7;O:5:\"Point\":1:{\
+            s:1:\"x\";i:3;}}s:4:\"blob\";C:1:\"B\":2:{a:}s:5:\"title\";s:2:\"hi\";}garbage";
+        assert_eq!(extract(input, "title").unwrap(), Some("hi"));
+        assert_eq!(extract(input, "items.7").unwrap(), Some(Point { x: 3 }));
+        assert_eq!(extract(input, "items.7.x").unwrap(), Some(3));
+        let page: Option<Value> = extract(input, "").unwrap();
+        assert_eq!(page.unwrap().class(), Some("Page"));
+        assert_eq!(extract::<i64>(input, "items.1").unwrap(), None);
+        assert_eq!(extract::<i64>(input, "title.x").unwrap(), None);
+        assert_eq!(extract::<i64>(input, "blob.x").unwrap(), None);
+
+        // Errors on the way are positioned.
+        let err = extract::<i64>(b"a:1:{i:0;x:1;}", "1").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedToken);
+        assert_eq!(err.position(), Some(11));
+        let err = extract::<i64>(br#"a:1:{s:1:"a";s:1:"b";}"#, "a").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Custom);
     }
 }
@@ -1,5 +1,6 @@
 //! Top-level error type for PHP serialization/deserialization.
 
+use crate::key::PhpKey;
 use std::{fmt, io};
 
 /// Result type for PHP serialization/deserialization.
@@ -21,6 +22,13 @@ pub enum Error {
         /// Actual byte found.
         actual: char,
     },
+    /// A value has a different type than the target asks for.
+    UnexpectedType {
+        /// Type indicator expected, e.g. `s` for strings.
+        expected: char,
+        /// Type indicator found.
+        actual: char,
+    },
     /// Expected a digit, but got non-digit value instead.
     ExpectedDigit {
         /// Non-digit found.
@@ -38,6 +46,71 @@ pub enum Error {
     UnsupportedArrayKeyType(char),
     /// Invalid type indicator on value.
     InvalidTypeIndicator(char),
+    /// Value cannot be used as an array key.
+    UnsupportedKey(&'static str),
+    /// Path leads through a value that is not an array.
+    NotAnArray(String),
+    /// Expected a serialized object.
+    ExpectedObject,
+    /// No type is registered for the class of an object.
+    UnknownClass(String),
+    /// Invalid or unsupported date in a `DateTime` object.
+    InvalidDateTime(String),
+    /// A reference refers to a value that does not exist, see
+    /// `DeserializerOptions::resolve_references`.
+    InvalidReference(usize),
+    /// A reference refers to a value that contains the reference itself,
+    /// see `DeserializerOptions::cyclic_references`.
+    CyclicReference(usize),
+    /// Arrays and objects are nested deeper than allowed.
+    DepthLimitExceeded,
+    /// A string length or element count exceeds the configured limit.
+    LengthLimitExceeded(usize),
+    /// An array contains the same key twice.
+    DuplicateKey {
+        /// The duplicated key.
+        key: String,
+        /// Byte offset of the second occurrence in the input.
+        offset: usize,
+    },
+    /// A struct was deserialized from an array or object with a key it
+    /// does not have a field for.
+    UnknownKey {
+        /// The offending key.
+        key: String,
+        /// Byte offset of the key in the input.
+        offset: usize,
+    },
+    /// Deserialization failed at a known position.
+    Positioned {
+        /// Byte offset in the input at which the error was detected.
+        offset: usize,
+        /// The error itself.
+        error: Box<Error>,
+    },
+    /// Deserialization failed, with the input around the failure point, see
+    /// `DeserializerOptions::error_context`.
+    Excerpt {
+        /// Input right before the failure point.
+        before: Vec<u8>,
+        /// Input from the failure point on.
+        after: Vec<u8>,
+        /// The error itself.
+        error: Box<Error>,
+    },
+    /// Deserialization failed inside an array or object, e.g. in
+    /// `addresses[3].zip`.
+    Path {
+        /// Keys and indices leading to the value that failed.
+        path: String,
+        /// The error itself.
+        error: Box<Error>,
+    },
+    /// An integer is out of the range of PHP integers, see
+    /// `SerializerOptions::integer_overflow`.
+    IntegerOutOfRange(String),
+    /// The buffer passed to `to_slice` is too small for the output.
+    BufferTooSmall,
     /// Feature not implemented by `php_serde`.
     MissingFeature(&'static str),
     /// Array-index mismatch: must be in-order and numeric.
@@ -53,12 +126,263 @@ pub enum Error {
     /// sequences of unknown length requires writing these to a memory buffer
     /// with potentially unbounded space requirements and is thus disabled.
     LengthRequired,
-    /// PHP Deserialization failed.
+    /// A `Serialize` implementation failed, e.g. a serialized `Mutex` was
+    /// poisoned.
     SerializationFailed(String),
-    /// PHP Serialization failed.
+    /// A `Deserialize` implementation rejected well-formed input, e.g. for a
+    /// mismatched type or a missing field.
     DeserializationFailed(String),
 }
 
+/// Category of an `Error`, see `Error::kind`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ErrorKind {
+    /// Reading input or writing output failed, including a full buffer
+    /// passed to `to_slice`.
+    Io,
+    /// The input ended in the middle of a value.
+    Eof,
+    /// The input is malformed, e.g. a wrong type indicator or delimiter.
+    UnexpectedToken,
+    /// A string is not valid UTF-8.
+    Utf8,
+    /// A number, boolean, character or date is invalid, or out of range
+    /// for its type.
+    InvalidValue,
+    /// A limit of the options was exceeded, e.g. the nesting depth.
+    LimitExceeded,
+    /// An array key is unsupported, duplicated, unknown or out of order,
+    /// or a path leads through a value that is not an array.
+    Key,
+    /// The class of an object is unknown, or an object was expected.
+    Class,
+    /// The value is not supported by PHP or by this crate.
+    Unsupported,
+    /// An error raised by a `Serialize` or `Deserialize` implementation,
+    /// e.g. for a missing field or a mismatched type.
+    Custom,
+}
+
+impl Error {
+    /// Category of the error, for handling errors without matching on
+    /// every variant.
+    ///
+    /// ```rust
+    /// use php_serde::{from_bytes, ErrorKind};
+    ///
+    /// let err = from_bytes::<String>(b"s:5:\"ab").unwrap_err();
+    /// assert_eq!(err.kind(), ErrorKind::Eof);
+    ///
+    /// let err = from_bytes::<String>(b"i:5;").unwrap_err();
+    /// assert_eq!(err.kind(), ErrorKind::UnexpectedToken);
+    ///
+    /// let err = from_bytes::<Vec<i64>>(b"a:1:{i:0;b:1;}").unwrap_err();
+    /// assert_eq!(err.kind(), ErrorKind::Custom);
+    /// ```
+    pub fn kind(&self) -> ErrorKind {
+        #[allow(clippy::enum_glob_use)]
+        use Error::*;
+
+        match self {
+            WriteSerialized(_) | ReadSerialized(_) | BufferTooSmall => ErrorKind::Io,
+            UnexpectedEof => ErrorKind::Eof,
+            Unexpected { .. }
+            | UnexpectedType { .. }
+            | ExpectedDigit { .. }
+            | InvalidTypeIndicator(_)
+            | UnsupportedArrayKeyType(_) => ErrorKind::UnexpectedToken,
+            NotUtf8String(_) => ErrorKind::Utf8,
+            CharConversionFailed(_)
+            | NotAValidNumber(_)
+            | InvalidBooleanValue(_)
+            | InvalidDateTime(_)
+            | InvalidReference(_)
+            | CyclicReference(_)
+            | IntegerOutOfRange(_) => ErrorKind::InvalidValue,
+            DepthLimitExceeded | LengthLimitExceeded(_) => ErrorKind::LimitExceeded,
+            UnsupportedKey(_)
+            | NotAnArray(_)
+            | DuplicateKey { .. }
+            | UnknownKey { .. }
+            | IndexMismatch { .. } => ErrorKind::Key,
+            ExpectedObject | UnknownClass(_) => ErrorKind::Class,
+            MissingFeature(_) | LengthRequired => ErrorKind::Unsupported,
+            SerializationFailed(_) | DeserializationFailed(_) => ErrorKind::Custom,
+            Positioned { error, .. } | Excerpt { error, .. } | Path { error, .. } => error.kind(),
+        }
+    }
+
+    /// Byte offset in the input at which deserialization failed, if known.
+    ///
+    /// Errors of `from_bytes`, `from_reader` and the other functions
+    /// deserializing a whole value carry their position. When using a
+    /// `PhpDeserializer` directly, see `PhpDeserializer::byte_offset`.
+    ///
+    /// ```rust
+    /// use php_serde::{from_bytes, Error};
+    ///
+    /// let err = from_bytes::<Vec<i64>>(b"a:2:{i:0;i:1;i:1;b:1;}").unwrap_err();
+    /// assert_eq!(err.position(), Some(21));
+    /// assert!(matches!(err.into_inner(), Error::DeserializationFailed(_)));
+    /// ```
+    pub fn position(&self) -> Option<usize> {
+        match self {
+            Error::Positioned { offset, .. }
+            | Error::DuplicateKey { offset, .. }
+            | Error::UnknownKey { offset, .. } => Some(*offset),
+            Error::Excerpt { error, .. } | Error::Path { error, .. } => error.position(),
+            _ => None,
+        }
+    }
+
+    /// Keys and indices leading to the value at which deserialization
+    /// failed, if it failed inside an array or object.
+    ///
+    /// String keys are joined with dots, integer keys and list indices are
+    /// written in brackets.
+    ///
+    /// ```rust
+    /// use php_serde::from_bytes;
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Debug, Deserialize)]
+    /// struct Address {
+    ///     zip: i64,
+    /// }
+    ///
+    /// #[derive(Debug, Deserialize)]
+    /// struct User {
+    ///     addresses: Vec<Address>,
+    /// }
+    ///
+    /// let input = br#"a:1:{s:9:"addresses";a:1:{i:0;a:1:{s:3:"zip";s:5:"12345";}}}"#;
+    /// let err = from_bytes::<User>(input).unwrap_err();
+    /// assert_eq!(err.path(), Some("addresses[0].zip"));
+    /// assert!(err.to_string().starts_with("addresses[0].zip: "));
+    /// ```
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            Error::Path { path, .. } => Some(path),
+            Error::Positioned { error, .. } | Error::Excerpt { error, .. } => error.path(),
+            _ => None,
+        }
+    }
+
+    /// The I/O error of the reader or writer, if that is what failed.
+    ///
+    /// Available as `source` as well. Unlike errors in the data, these may
+    /// be worth retrying, depending on their kind.
+    ///
+    /// ```rust
+    /// use php_serde::to_writer;
+    /// use std::io::{self, Write};
+    ///
+    /// struct Closed;
+    ///
+    /// impl Write for Closed {
+    ///     fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+    ///         Err(io::ErrorKind::BrokenPipe.into())
+    ///     }
+    ///
+    ///     fn flush(&mut self) -> io::Result<()> {
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let err = to_writer(Closed, &1).unwrap_err();
+    /// assert_eq!(err.io_error().map(io::Error::kind), Some(io::ErrorKind::BrokenPipe));
+    ///
+    /// let err = php_serde::from_bytes::<i64>(b"i:x;").unwrap_err();
+    /// assert!(err.io_error().is_none());
+    /// ```
+    pub fn io_error(&self) -> Option<&io::Error> {
+        match self {
+            Error::WriteSerialized(err) | Error::ReadSerialized(err) => Some(err),
+            Error::Positioned { error, .. }
+            | Error::Excerpt { error, .. }
+            | Error::Path { error, .. } => error.io_error(),
+            _ => None,
+        }
+    }
+
+    /// Remove the position, excerpt and path from the error, e.g. to match
+    /// on its kind.
+    #[must_use]
+    pub fn into_inner(self) -> Error {
+        match self {
+            Error::Positioned { error, .. }
+            | Error::Excerpt { error, .. }
+            | Error::Path { error, .. } => error.into_inner(),
+            error => error,
+        }
+    }
+
+    /// Attach the position at which the error was detected, unless it has
+    /// one already.
+    #[must_use]
+    pub(crate) fn at(self, offset: usize) -> Error {
+        if self.position().is_some() {
+            return self;
+        }
+        Error::Positioned {
+            offset,
+            error: Box::new(self),
+        }
+    }
+
+    /// Attach up to `width` bytes of `input` on either side of the position
+    /// of the error, if it has one.
+    #[must_use]
+    pub(crate) fn with_excerpt(self, input: &[u8], width: usize) -> Error {
+        let offset = match self.position() {
+            Some(offset) if width > 0 => offset.min(input.len()),
+            _ => return self,
+        };
+        let start = offset.saturating_sub(width);
+        let end = offset.saturating_add(width).min(input.len());
+        Error::Excerpt {
+            before: input[start..offset].to_vec(),
+            after: input[offset..end].to_vec(),
+            error: Box::new(self),
+        }
+    }
+
+    /// Prepend the index of the list element the error occurred in.
+    #[must_use]
+    pub(crate) fn in_index(self, index: usize) -> Error {
+        self.in_segment(format!("[{index}]"))
+    }
+
+    /// Prepend the key of the array entry or property the error occurred
+    /// in.
+    #[must_use]
+    pub(crate) fn in_key(self, key: &PhpKey) -> Error {
+        match key {
+            PhpKey::Int(n) => self.in_segment(format!("[{n}]")),
+            PhpKey::String(_) => self.in_segment(key.to_string()),
+        }
+    }
+
+    fn in_segment(self, mut segment: String) -> Error {
+        match self {
+            Error::Path { path, error } => {
+                if !path.starts_with('[') {
+                    segment.push('.');
+                }
+                segment.push_str(&path);
+                Error::Path {
+                    path: segment,
+                    error,
+                }
+            }
+            error => Error::Path {
+                path: segment,
+                error: Box::new(error),
+            },
+        }
+    }
+}
+
 // Note: Manual error implementation as opposed to `thiserror`, otherwise
 //       `NotAValidNumber` errors cannot be constructed `Send`.
 impl std::error::Error for Error {
@@ -68,6 +392,9 @@ impl std::error::Error for Error {
             Error::NotUtf8String(ref err) => Some(err),
             Error::CharConversionFailed(ref err) => Some(err),
             Error::NotAValidNumber(ref err) => Some(err.as_ref()),
+            Error::Positioned { ref error, .. }
+            | Error::Excerpt { ref error, .. }
+            | Error::Path { ref error, .. } => error.source(),
             _ => None,
         }
     }
@@ -85,6 +412,12 @@ impl fmt::Display for Error {
             Unexpected { expected, actual } => {
                 write!(f, "Expected `{}` but got `{}` instead", expected, actual)
             }
+            UnexpectedType { expected, actual } => write!(
+                f,
+                "Expected `{expected}` ({}) but got `{actual}` ({}) instead",
+                type_name(*expected),
+                type_name(*actual)
+            ),
             ExpectedDigit { actual } => write!(f, "Expected a digit, but got `{}` instead", actual),
             NotUtf8String(err) => write!(f, "Deserialized bytestring is not valid UTF: {}", err),
             CharConversionFailed(err) => {
@@ -96,6 +429,36 @@ impl fmt::Display for Error {
             InvalidBooleanValue(ch) => write!(f, "Not a valid value for boolean: {}", ch),
             UnsupportedArrayKeyType(ch) => write!(f, "Unsupported array key type: {}", ch),
             InvalidTypeIndicator(ch) => write!(f, "Invalid type indicator on value: {}", ch),
+            UnsupportedKey(kind) => write!(f, "Value cannot be used as an array key: {kind}"),
+            NotAnArray(path) => write!(f, "Cannot use a scalar value as an array: {path}"),
+            ExpectedObject => write!(f, "Expected a serialized object"),
+            UnknownClass(class) => write!(f, "No type registered for class: {class}"),
+            InvalidDateTime(msg) => write!(f, "Invalid date: {msg}"),
+            InvalidReference(number) => write!(f, "Cannot resolve reference to value {number}"),
+            CyclicReference(number) => write!(f, "Reference to value {number} forms a cycle"),
+            DepthLimitExceeded => write!(f, "Arrays and objects are nested too deeply"),
+            LengthLimitExceeded(length) => write!(f, "Declared length is too large: {length}"),
+            DuplicateKey { key, offset } => write!(f, "Duplicate key `{key}` at byte {offset}"),
+            UnknownKey { key, offset } => write!(f, "Unknown key `{key}` at byte {offset}"),
+            Positioned { offset, error } => write!(f, "{error} at byte {offset}"),
+            Excerpt {
+                before,
+                after,
+                error,
+            } => {
+                write!(f, "{error}, near ")?;
+                for b in before {
+                    write!(f, "{b:02x} ")?;
+                }
+                f.write_str("|")?;
+                for b in after {
+                    write!(f, " {b:02x}")?;
+                }
+                write!(f, " ({}|{})", Ascii(before), Ascii(after))
+            }
+            Path { path, error } => write!(f, "{path}: {error}"),
+            IntegerOutOfRange(n) => write!(f, "Integer is out of range for PHP: {n}"),
+            BufferTooSmall => write!(f, "Buffer is too small for the serialized value"),
             MissingFeature(feat) => write!(f, "Feature not implemented by `php_serde`: {}", feat),
             IndexMismatch { expected, actual } => write!(
                 f,
@@ -103,8 +466,58 @@ impl fmt::Display for Error {
                 expected, actual
             ),
             LengthRequired => write!(f, "Attempted to serialize sequence of unknown length"),
-            SerializationFailed(err) => write!(f, "PHP Deserialization failed: {}", err),
-            DeserializationFailed(err) => write!(f, "PHP Serialization failed: {}", err),
+            SerializationFailed(err) => write!(f, "PHP Serialization failed: {err}"),
+            DeserializationFailed(err) => write!(f, "PHP Deserialization failed: {err}"),
+        }
+    }
+}
+
+/// Name of the type of values with the given type indicator.
+fn type_name(indicator: char) -> &'static str {
+    match indicator {
+        'N' => "null",
+        'b' => "boolean",
+        'i' => "integer",
+        'd' => "float",
+        's' => "string",
+        'a' => "array",
+        'O' => "object",
+        'C' => "custom serialized object",
+        'E' => "enum case",
+        'r' | 'R' => "reference",
+        _ => "unknown type",
+    }
+}
+
+/// Bytes shown as ASCII, with `.` for anything else, as in hex dumps.
+struct Ascii<'a>(&'a [u8]);
+
+impl fmt::Display for Ascii<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for &b in self.0 {
+            let c = if b.is_ascii_graphic() || b == b' ' {
+                char::from(b)
+            } else {
+                '.'
+            };
+            write!(f, "{c}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Converts to the I/O error of the reader or writer if that is what
+/// failed, and to an `InvalidData` error wrapping it otherwise, or
+/// `UnexpectedEof` for truncated input.
+impl From<Error> for io::Error {
+    fn from(error: Error) -> io::Error {
+        match error.kind() {
+            ErrorKind::Io if error.io_error().is_some() => match error.into_inner() {
+                Error::WriteSerialized(err) | Error::ReadSerialized(err) => err,
+                _ => unreachable!("I/O errors have an `io::Error`"),
+            },
+            ErrorKind::Eof => io::Error::new(io::ErrorKind::UnexpectedEof, error),
+            _ => io::Error::new(io::ErrorKind::InvalidData, error),
         }
     }
 }
@@ -0,0 +1,839 @@
+//! Writing values as PHP code, like PHP's `var_export`, and reading them
+//! back.
+
+use crate::error::{Error, Result};
+use crate::key::PhpKey;
+use crate::options::{DeserializerOptions, FloatPrecision};
+use crate::property::Visibility;
+use crate::ser::{self, write_float};
+use crate::token::{Key, Lexer, Token};
+use crate::value::{from_value, Value};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+/// Syntax of arrays written by `var_export_with_syntax`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ArraySyntax {
+    /// `array (...)`, exactly like PHP's `var_export`.
+    Long,
+    /// `[...]`, with nested arrays opening on the line of their key.
+    Short,
+}
+
+/// Write a value as PHP code, the way PHP's `var_export` does.
+///
+/// The value is mapped like by `to_vec`, and written like `var_export`
+/// writes the unserialized value, e.g. to generate a PHP configuration
+/// file returning an array. `stdClass` objects are written as arrays cast
+/// to `(object)`, other objects as calls to `__set_state` with their
+/// demangled properties and enum cases as constants.
+///
+/// Fails for custom serialized objects, which have no equivalent in PHP
+/// code, for references and if the output is not valid UTF-8, which can
+/// only happen if byte strings are written.
+///
+/// ```rust
+/// use php_serde::var_export;
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Config {
+///     debug: bool,
+///     hosts: Vec<&'static str>,
+/// }
+///
+/// let config = Config {
+///     debug: false,
+///     hosts: vec!["db1"],
+/// };
+/// assert_eq!(
+///     var_export(&config).unwrap(),
+///     "array (
+///   'debug' => false,
+///   'hosts' => \n  array (
+///     0 => 'db1',
+///   ),
+/// )"
+/// );
+/// ```
+pub fn var_export<T>(value: &T) -> Result<String>
+where
+    T: Serialize + ?Sized,
+{
+    var_export_with_syntax(value, ArraySyntax::Long)
+}
+
+/// Write a value as PHP code, with arrays in the given syntax.
+///
+/// ```rust
+/// use php_serde::{var_export_with_syntax, ArraySyntax};
+///
+/// let code = var_export_with_syntax(&vec![vec![1]], ArraySyntax::Short).unwrap();
+/// assert_eq!(code, "[\n  0 => [\n    0 => 1,\n  ],\n]");
+/// ```
+pub fn var_export_with_syntax<T>(value: &T, syntax: ArraySyntax) -> Result<String>
+where
+    T: Serialize + ?Sized,
+{
+    let serialized = ser::to_vec(value)?;
+    let mut exporter = Exporter {
+        output: Vec::new(),
+        syntax,
+        levels: Vec::new(),
+    };
+    for token in Lexer::new(&serialized) {
+        exporter.write(token?.0)?;
+    }
+    String::from_utf8(exporter.output).map_err(|e| Error::NotUtf8String(e.utf8_error()))
+}
+
+/// Writer of tokens as PHP code.
+struct Exporter {
+    output: Vec<u8>,
+    syntax: ArraySyntax,
+    /// Arrays and objects being written, the innermost last.
+    levels: Vec<Level>,
+}
+
+/// An array or object being written by `Exporter`.
+struct Level {
+    /// Level of the value in `var_export`'s terms, which sets the
+    /// indentation.
+    level: usize,
+    kind: Kind,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Array,
+    StdClass,
+    Object,
+}
+
+impl Exporter {
+    fn write(&mut self, token: Token<'_>) -> Result<()> {
+        let level = self.levels.last().map_or(1, |level| level.level + 2);
+        match token {
+            Token::Null => self.output.extend_from_slice(b"NULL"),
+            Token::Bool(b) => self
+                .output
+                .extend_from_slice(if b { b"true" } else { b"false" }),
+            // The smallest integer is not a literal, its magnitude is a float.
+            Token::Int(i64::MIN) => {
+                self.output.extend_from_slice(b"-9223372036854775807-1");
+            }
+            Token::Int(n) => self
+                .output
+                .extend_from_slice(itoa::Buffer::new().format(n).as_bytes()),
+            Token::Float(v) => {
+                let start = self.output.len();
+                write_float(&mut self.output, v, FloatPrecision::Shortest)
+                    .map_err(Error::WriteSerialized)?;
+                let written = &self.output[start..];
+                if v.is_finite() && !written.iter().any(|b| b"eE.".contains(b)) {
+                    self.output.extend_from_slice(b".0");
+                }
+            }
+            Token::Str(s) => self.write_string(s),
+            // The value comes next, without a separator.
+            Token::Key(key) => return self.write_key(key),
+            Token::ArrayStart(_) => {
+                self.open(level);
+                self.output.extend_from_slice(match self.syntax {
+                    ArraySyntax::Long => b"array (\n",
+                    ArraySyntax::Short => b"[\n",
+                });
+                self.levels.push(Level {
+                    level,
+                    kind: Kind::Array,
+                });
+                return Ok(());
+            }
+            Token::ObjectStart { class, .. } => {
+                self.open_object(level, class);
+                return Ok(());
+            }
+            Token::ArrayEnd | Token::ObjectEnd => {
+                let Level { level, kind } = self
+                    .levels
+                    .pop()
+                    .ok_or_else(|| failed("the end of a value that was not started"))?;
+                if level > 1 {
+                    self.indent(level - 1);
+                }
+                self.output.extend_from_slice(match self.syntax {
+                    ArraySyntax::Long => b")",
+                    ArraySyntax::Short => b"]",
+                });
+                if kind == Kind::Object {
+                    self.output.push(b')');
+                }
+            }
+            Token::Enum { class, case } => {
+                self.output.push(b'\\');
+                self.output.extend_from_slice(class);
+                self.output.extend_from_slice(b"::");
+                self.output.extend_from_slice(case);
+            }
+            Token::Custom { .. } => {
+                return Err(failed("custom serialized objects cannot be exported"))
+            }
+            Token::Reference(_) | Token::MutableReference(_) => {
+                return Err(failed("references cannot be exported"))
+            }
+        }
+
+        // Entries of arrays and objects end with a separator.
+        if !self.levels.is_empty() {
+            self.output.extend_from_slice(b",\n");
+        }
+        Ok(())
+    }
+
+    /// Start a nested array or object on a line of its own, like
+    /// `var_export` does.
+    fn open(&mut self, level: usize) {
+        if level > 1 && self.syntax == ArraySyntax::Long {
+            self.output.push(b'\n');
+            self.indent(level - 1);
+        }
+    }
+
+    fn open_object(&mut self, level: usize, class: &[u8]) {
+        self.open(level);
+        let kind = if class == b"stdClass" {
+            self.output.extend_from_slice(b"(object) ");
+            Kind::StdClass
+        } else {
+            self.output.push(b'\\');
+            self.output.extend_from_slice(class);
+            self.output.extend_from_slice(b"::__set_state(");
+            Kind::Object
+        };
+        self.output.extend_from_slice(match self.syntax {
+            ArraySyntax::Long => b"array(\n",
+            ArraySyntax::Short => b"[\n",
+        });
+        self.levels.push(Level { level, kind });
+    }
+
+    /// Write an array key or property name, indented like `var_export`
+    /// does.
+    fn write_key(&mut self, key: Key<'_>) -> Result<()> {
+        let (indent, object) = match self.levels.last() {
+            Some(top) if top.kind == Kind::Array => (top.level + 1, false),
+            Some(top) => (top.level + 2, true),
+            None => return Err(failed("a key outside of an array")),
+        };
+        self.indent(indent);
+        match key {
+            Key::Int(n) => self
+                .output
+                .extend_from_slice(itoa::Buffer::new().format(n).as_bytes()),
+            Key::Bytes(name) if object => self.write_string(Visibility::demangle(name).1),
+            Key::Bytes(s) => self.write_string(s),
+        }
+        self.output.extend_from_slice(b" => ");
+        Ok(())
+    }
+
+    fn indent(&mut self, width: usize) {
+        self.output.resize(self.output.len() + width, b' ');
+    }
+
+    /// Write a string literal in single quotes, with NUL bytes spliced in
+    /// as double quoted `"\0"`.
+    fn write_string(&mut self, s: &[u8]) {
+        self.output.push(b'\'');
+        for &b in s {
+            match b {
+                b'\'' | b'\\' => self.output.extend_from_slice(&[b'\\', b]),
+                0 => self.output.extend_from_slice(b"' . \"\\0\" . '"),
+                b => self.output.push(b),
+            }
+        }
+        self.output.push(b'\'');
+    }
+}
+
+fn failed(reason: &str) -> Error {
+    Error::SerializationFailed(reason.to_owned())
+}
+
+/// Read PHP code written by `var_export` into a value.
+///
+/// Both array syntaxes are accepted, `array (...)` and `[...]`, as are
+/// `(object)` casts, `__set_state` calls and enum cases written by
+/// `var_export`, strings in single or double quotes and concatenated with
+/// `.`, and sums of numbers such as `-9223372036854775807-1`. Entries
+/// without a key get the next integer key, like in PHP. Comments, a leading
+/// `<?php` and `return` and a trailing `;` are skipped, so that whole
+/// configuration files can be read.
+///
+/// Other PHP code, such as variables or calls of other functions, fails.
+///
+/// ```rust
+/// use php_serde::from_var_export;
+/// use serde::Deserialize;
+///
+/// #[derive(Debug, Deserialize, PartialEq)]
+/// struct Config {
+///     debug: bool,
+///     hosts: Vec<String>,
+/// }
+///
+/// let code = "<?php return ['debug' => true, 'hosts' => array('db1', 'db2')];";
+/// let config: Config = from_var_export(code).unwrap();
+/// assert_eq!(config.hosts, vec!["db1", "db2"]);
+/// ```
+pub fn from_var_export<T>(code: &str) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    let mut parser = Parser {
+        input: code.as_bytes(),
+        offset: 0,
+    };
+    let value = parser
+        .document(DeserializerOptions::default().max_depth)
+        .map_err(|e| e.at(parser.offset))?;
+    from_value(value)
+}
+
+/// Recursive descent parser of the PHP code written by `var_export`.
+struct Parser<'a> {
+    input: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn document(&mut self, max_depth: usize) -> Result<Value> {
+        self.skip_whitespace();
+        if self.input[self.offset..].starts_with(b"<?php") {
+            self.offset += 5;
+            self.skip_whitespace();
+        }
+        self.keyword("return");
+        let value = self.value(max_depth)?;
+        self.skip_whitespace();
+        if self.eat(b';') {
+            self.skip_whitespace();
+        }
+        match self.peek() {
+            None => Ok(value),
+            Some(_) => Err(syntax("expected the end of the code")),
+        }
+    }
+
+    /// Read a value, including concatenated strings and sums of numbers.
+    fn value(&mut self, depth: usize) -> Result<Value> {
+        let mut value = self.term(depth)?;
+        loop {
+            self.skip_whitespace();
+            value = match (value, self.peek()) {
+                (Value::String(mut s), Some(b'.')) => {
+                    self.offset += 1;
+                    match self.term(depth)? {
+                        Value::String(rest) => s.extend_from_slice(&rest),
+                        _ => return Err(syntax("only strings can be concatenated")),
+                    }
+                    Value::String(s)
+                }
+                (lhs @ (Value::Int(_) | Value::Float(_)), Some(op @ (b'+' | b'-'))) => {
+                    self.offset += 1;
+                    let rhs = self.term(depth)?;
+                    arithmetic(&lhs, &rhs, op == b'-')?
+                }
+                (value, _) => return Ok(value),
+            };
+        }
+    }
+
+    fn term(&mut self, depth: usize) -> Result<Value> {
+        self.skip_whitespace();
+        match self.peek().ok_or(Error::UnexpectedEof)? {
+            b'\'' => self.single_quoted().map(Value::String),
+            b'"' => self.double_quoted().map(Value::String),
+            b'[' => {
+                self.offset += 1;
+                self.array(b']', depth).map(Value::Array)
+            }
+            b'(' => {
+                self.offset += 1;
+                self.skip_whitespace();
+                if !self.keyword("object") || !self.eat(b')') {
+                    return Err(syntax("expected an `(object)` cast"));
+                }
+                match self.term(deeper(depth)?)? {
+                    Value::Array(properties) => Ok(Value::Object {
+                        class: "stdClass".to_owned(),
+                        properties,
+                    }),
+                    _ => Err(syntax("expected an array cast to an object")),
+                }
+            }
+            sign @ (b'-' | b'+') => {
+                self.offset += 1;
+                let value = self.term(deeper(depth)?)?;
+                arithmetic(&Value::Int(0), &value, sign == b'-')
+            }
+            b'0'..=b'9' | b'.' => self.number(),
+            b if b.is_ascii_alphabetic() || b == b'_' || b == b'\\' => self.constant(depth),
+            _ => Err(syntax("expected a value")),
+        }
+    }
+
+    /// Read a constant, an array, a `__set_state` call or an enum case.
+    fn constant(&mut self, depth: usize) -> Result<Value> {
+        let name = self.identifier();
+        self.skip_whitespace();
+        if self.input[self.offset..].starts_with(b"::") {
+            self.offset += 2;
+            self.skip_whitespace();
+            let class = name.trim_start_matches('\\').to_owned();
+            let member = self.identifier();
+            if !member.eq_ignore_ascii_case("__set_state") {
+                return Ok(Value::Enum {
+                    class,
+                    case: member.to_owned(),
+                });
+            }
+            self.skip_whitespace();
+            self.expect(b'(')?;
+            let Value::Array(properties) = self.value(deeper(depth)?)? else {
+                return Err(syntax("expected the properties of an object"));
+            };
+            self.skip_whitespace();
+            self.expect(b')')?;
+            return Ok(Value::Object { class, properties });
+        }
+
+        match name.to_ascii_lowercase().as_str() {
+            "null" => Ok(Value::Null),
+            "true" => Ok(Value::Bool(true)),
+            "false" => Ok(Value::Bool(false)),
+            "inf" => Ok(Value::Float(f64::INFINITY)),
+            "nan" => Ok(Value::Float(f64::NAN)),
+            "array" => {
+                self.expect(b'(')?;
+                self.array(b')', depth).map(Value::Array)
+            }
+            _ => Err(syntax("unknown constant")),
+        }
+    }
+
+    /// Read the entries of an array up to its closing bracket.
+    fn array(&mut self, close: u8, depth: usize) -> Result<Vec<(PhpKey, Value)>> {
+        let depth = deeper(depth)?;
+        let mut entries: Vec<(PhpKey, Value)> = Vec::new();
+        // Positions of the keys in `entries`.
+        let mut positions: HashMap<PhpKey, usize> = HashMap::new();
+        let mut next_index = 0;
+        loop {
+            self.skip_whitespace();
+            if self.eat(close) {
+                return Ok(entries);
+            }
+
+            let value = self.value(depth)?;
+            let (key, value) = if self.input[self.offset..].starts_with(b"=>") {
+                self.offset += 2;
+                (array_key(value)?, self.value(depth)?)
+            } else {
+                (PhpKey::Int(next_index), value)
+            };
+            if let PhpKey::Int(n) = key {
+                next_index = next_index.max(n.saturating_add(1));
+            }
+            // Later entries replace earlier ones with the same key.
+            match positions.entry(key) {
+                Entry::Occupied(position) => entries[*position.get()].1 = value,
+                Entry::Vacant(position) => {
+                    entries.push((position.key().clone(), value));
+                    position.insert(entries.len() - 1);
+                }
+            }
+
+            self.skip_whitespace();
+            if !self.eat(b',') {
+                self.expect(close)?;
+                return Ok(entries);
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value> {
+        let start = self.offset;
+        let mut float = false;
+        while let Some(b) = self.peek() {
+            match b {
+                b'0'..=b'9' => (),
+                b'.' | b'e' | b'E' => float = true,
+                b'+' | b'-' if matches!(self.input[self.offset - 1], b'e' | b'E') => (),
+                _ => break,
+            }
+            self.offset += 1;
+        }
+
+        let digits = std::str::from_utf8(&self.input[start..self.offset]).unwrap_or_default();
+        if !float {
+            // Integers beyond `i64` are floats, like in PHP.
+            if let Ok(n) = digits.parse() {
+                return Ok(Value::Int(n));
+            }
+        }
+        digits
+            .parse()
+            .map(Value::Float)
+            .map_err(|_| syntax("invalid number"))
+    }
+
+    fn single_quoted(&mut self) -> Result<Vec<u8>> {
+        self.offset += 1;
+        let mut s = Vec::new();
+        loop {
+            match self.next()? {
+                b'\'' => return Ok(s),
+                b'\\' if matches!(self.peek(), Some(b'\'' | b'\\')) => s.push(self.next()?),
+                b => s.push(b),
+            }
+        }
+    }
+
+    fn double_quoted(&mut self) -> Result<Vec<u8>> {
+        self.offset += 1;
+        let mut s = Vec::new();
+        loop {
+            match self.next()? {
+                b'"' => return Ok(s),
+                b'\\' => self.escape(&mut s)?,
+                b => s.push(b),
+            }
+        }
+    }
+
+    /// Read an escape sequence of a double quoted string.
+    fn escape(&mut self, s: &mut Vec<u8>) -> Result<()> {
+        let b = self.next()?;
+        match b {
+            b'n' => s.push(b'\n'),
+            b't' => s.push(b'\t'),
+            b'r' => s.push(b'\r'),
+            b'v' => s.push(0x0b),
+            b'e' => s.push(0x1b),
+            b'f' => s.push(0x0c),
+            b'\\' | b'$' | b'"' => s.push(b),
+            b'0'..=b'7' => {
+                let mut n = u32::from(b - b'0');
+                for _ in 0..2 {
+                    match self.peek() {
+                        Some(d @ b'0'..=b'7') => n = n * 8 + u32::from(d - b'0'),
+                        _ => break,
+                    }
+                    self.offset += 1;
+                }
+                // Like PHP, octal escapes above `\377` overflow.
+                s.push(n.to_le_bytes()[0]);
+            }
+            b'x' if self.peek().is_some_and(|d| d.is_ascii_hexdigit()) => {
+                let start = self.offset;
+                while self.offset - start < 2 && self.peek().is_some_and(|d| d.is_ascii_hexdigit())
+                {
+                    self.offset += 1;
+                }
+                let hex = std::str::from_utf8(&self.input[start..self.offset]).unwrap_or_default();
+                s.push(u8::from_str_radix(hex, 16).unwrap_or_default());
+            }
+            b'u' if self.peek() == Some(b'{') => {
+                let end = self.input[self.offset..]
+                    .iter()
+                    .position(|&b| b == b'}')
+                    .ok_or(Error::UnexpectedEof)?;
+                let hex = &self.input[self.offset + 1..self.offset + end];
+                let c = std::str::from_utf8(hex)
+                    .ok()
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| syntax("invalid unicode escape"))?;
+                s.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                self.offset += end + 1;
+            }
+            // Unknown escapes are kept as they are.
+            b => s.extend_from_slice(&[b'\\', b]),
+        }
+        Ok(())
+    }
+
+    /// Read a name, possibly qualified with a namespace.
+    fn identifier(&mut self) -> &'a str {
+        let start = self.offset;
+        while self
+            .peek()
+            .is_some_and(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'\\' || b >= 0x80)
+        {
+            self.offset += 1;
+        }
+        std::str::from_utf8(&self.input[start..self.offset]).unwrap_or_default()
+    }
+
+    /// Skip `word` if it comes next, ignoring case.
+    fn keyword(&mut self, word: &str) -> bool {
+        let end = self.offset + word.len();
+        let matches = self
+            .input
+            .get(self.offset..end)
+            .is_some_and(|s| s.eq_ignore_ascii_case(word.as_bytes()))
+            && !self
+                .input
+                .get(end)
+                .is_some_and(|&b| b.is_ascii_alphanumeric() || b == b'_');
+        if matches {
+            self.offset = end;
+            self.skip_whitespace();
+        }
+        matches
+    }
+
+    /// Skip whitespace and comments.
+    fn skip_whitespace(&mut self) {
+        loop {
+            let rest = &self.input[self.offset..];
+            let skipped = match rest {
+                [b, ..] if b.is_ascii_whitespace() => 1,
+                [b'#', ..] | [b'/', b'/', ..] => {
+                    rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len())
+                }
+                [b'/', b'*', ..] => rest
+                    .windows(2)
+                    .position(|w| w == b"*/")
+                    .map_or(rest.len(), |end| end + 2),
+                _ => return,
+            };
+            self.offset += skipped;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.offset).copied()
+    }
+
+    fn next(&mut self) -> Result<u8> {
+        let b = self.peek().ok_or(Error::UnexpectedEof)?;
+        self.offset += 1;
+        Ok(b)
+    }
+
+    fn eat(&mut self, b: u8) -> bool {
+        let eaten = self.peek() == Some(b);
+        if eaten {
+            self.offset += 1;
+        }
+        eaten
+    }
+
+    fn expect(&mut self, b: u8) -> Result<()> {
+        match self.peek() {
+            Some(actual) if actual == b => {
+                self.offset += 1;
+                Ok(())
+            }
+            Some(actual) => Err(Error::Unexpected {
+                expected: char::from(b),
+                actual: char::from(actual),
+            }),
+            None => Err(Error::UnexpectedEof),
+        }
+    }
+}
+
+/// Add or subtract two numbers, with integers overflowing into floats.
+#[allow(clippy::cast_precision_loss)]
+fn arithmetic(lhs: &Value, rhs: &Value, subtract: bool) -> Result<Value> {
+    let float = |v: &Value| match *v {
+        Value::Int(n) => Ok(n as f64),
+        Value::Float(v) => Ok(v),
+        _ => Err(syntax("expected a number")),
+    };
+    if let (Value::Int(a), Value::Int(b)) = (lhs, rhs) {
+        let n = if subtract {
+            a.checked_sub(*b)
+        } else {
+            a.checked_add(*b)
+        };
+        if let Some(n) = n {
+            return Ok(Value::Int(n));
+        }
+    }
+    let (a, b) = (float(lhs)?, float(rhs)?);
+    Ok(Value::Float(if subtract { a - b } else { a + b }))
+}
+
+/// Count one level of nesting against the depth limit.
+fn deeper(depth: usize) -> Result<usize> {
+    depth.checked_sub(1).ok_or(Error::DepthLimitExceeded)
+}
+
+/// Convert a value to an array key, the way PHP does.
+fn array_key(value: Value) -> Result<PhpKey> {
+    match value {
+        Value::Int(n) => Ok(PhpKey::Int(n)),
+        Value::String(s) => Ok(PhpKey::from_bytes(s)),
+        Value::Bool(b) => Ok(PhpKey::from(b)),
+        Value::Float(v) => Ok(PhpKey::from(v)),
+        Value::Null => Ok(PhpKey::String(Vec::new())),
+        _ => Err(syntax("illegal array key")),
+    }
+}
+
+fn syntax(reason: &str) -> Error {
+    Error::DeserializationFailed(format!("invalid PHP code: {reason}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_var_export, var_export, var_export_with_syntax, ArraySyntax};
+    use crate::{from_bytes, CustomSerialized, ErrorKind, PhpKey, Value};
+
+    fn export(input: &[u8]) -> String {
+        var_export(&from_bytes::<Value>(input).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn scalars() {
+        assert_eq!(export(b"N;"), "NULL");
+        assert_eq!(export(b"b:1;"), "true");
+        assert_eq!(export(b"i:-3;"), "-3");
+        assert_eq!(export(b"i:-9223372036854775808;"), "-9223372036854775807-1");
+        assert_eq!(export(b"d:1;"), "1.0");
+        assert_eq!(export(b"d:-0.5;"), "-0.5");
+        assert_eq!(export(b"d:1.0E+25;"), "1.0E+25");
+        assert_eq!(export(b"d:-INF;"), "-INF");
+        assert_eq!(export(b"s:5:\"a'\\\x00b\";"), r#"'a\'\\' . "\0" . 'b'"#);
+        assert_eq!(export(br#"E:11:"Suit:Hearts";"#), r"\Suit::Hearts");
+    }
+
+    #[test]
+    fn nested() {
+        let input = b"a:4:{s:1:\"a\";i:1;s:1:\"b\";a:2:{i:0;b:1;i:1;a:0:{}}\
+            s:1:\"o\";O:8:\"stdClass\":1:{s:1:\"x\";s:1:\"y\";}\
+            s:1:\"p\";O:5:\"Point\":2:{s:4:\"\0*\0x\";i:1;s:1:\"y\";i:2;}}";
+        let value: Value = from_bytes(input).unwrap();
+        assert_eq!(
+            var_export(&value).unwrap(),
+            "array (
+  'a' => 1,
+  'b' => \n  array (
+    0 => true,
+    1 => \n    array (
+    ),
+  ),
+  'o' => \n  (object) array(
+     'x' => 'y',
+  ),
+  'p' => \n  \\Point::__set_state(array(
+     'x' => 1,
+     'y' => 2,
+  )),
+)"
+        );
+        assert_eq!(
+            var_export_with_syntax(&value, ArraySyntax::Short).unwrap(),
+            "[
+  'a' => 1,
+  'b' => [
+    0 => true,
+    1 => [
+    ],
+  ],
+  'o' => (object) [
+     'x' => 'y',
+  ],
+  'p' => \\Point::__set_state([
+     'x' => 1,
+     'y' => 2,
+  ]),
+]"
+        );
+        assert_eq!(export(b"a:0:{}"), "array (\n)");
+    }
+
+    #[test]
+    fn unsupported() {
+        let custom = CustomSerialized {
+            class: "Foo".to_owned(),
+            payload: b"x".to_vec(),
+        };
+        assert_eq!(var_export(&custom).unwrap_err().kind(), ErrorKind::Custom);
+        let err = var_export(&Value::from(&b"\xff"[..])).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Utf8);
+    }
+
+    #[test]
+    fn parse() {
+        let value: Value = from_bytes(
+            b"a:5:{i:0;N;s:1:\"a\";a:2:{i:3;d:1.5;i:4;i:-9223372036854775808;}\
+              s:1:\"o\";O:8:\"stdClass\":1:{s:1:\"x\";s:3:\"y\0'\";}\
+              s:1:\"p\";O:5:\"Point\":1:{s:1:\"x\";b:0;}s:1:\"e\";E:11:\"Suit:Hearts\";}",
+        )
+        .unwrap();
+        for syntax in [ArraySyntax::Long, ArraySyntax::Short] {
+            let code = var_export_with_syntax(&value, syntax).unwrap();
+            assert_eq!(from_var_export::<Value>(&code).unwrap(), value);
+        }
+
+        let code = r#"<?php
+            // Generated.
+            return [
+                'a', 5 => "b\t\x41\101\u{e9}\$", # comment
+                /* keys */ '7' => TRUE, -1 => 1e3, 'n' => - 2 + .5, 'a' => null,
+            ];
+        "#;
+        let value: Value = from_var_export(code).unwrap();
+        assert_eq!(
+            value,
+            Value::Array(vec![
+                (PhpKey::Int(0), Value::from("a")),
+                (PhpKey::Int(5), Value::from("b\tAA\u{e9}$")),
+                (PhpKey::Int(7), Value::Bool(true)),
+                (PhpKey::Int(-1), Value::Float(1000.0)),
+                (PhpKey::from("n"), Value::Float(-1.5)),
+                (PhpKey::from("a"), Value::Null),
+            ])
+        );
+        assert_eq!(
+            from_var_export::<Vec<i64>>("array(1, 2, 3,)").unwrap(),
+            vec![1, 2, 3]
+        );
+
+        // Later entries replace earlier ones in place, also in large arrays.
+        let value: Value = from_var_export("[0 => 'x', 1 => 'y', '0' => 'z']").unwrap();
+        assert_eq!(value, Value::from(vec!["z", "y"]));
+        let code = format!("[{}]", "1, ".repeat(100_000));
+        assert_eq!(from_var_export::<Vec<i64>>(&code).unwrap().len(), 100_000);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let err = from_var_export::<Value>("array (\n  0 => $x,\n)").unwrap_err();
+        assert_eq!(err.position(), Some(15));
+        assert_eq!(err.kind(), ErrorKind::Custom);
+        let err = from_var_export::<Value>("['a' => 1").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Eof);
+        assert!(from_var_export::<Value>("[1] [2]").is_err());
+        assert!(from_var_export::<Value>("[[1] => 2]").is_err());
+        assert!(from_var_export::<Value>("'a' . 1").is_err());
+        assert!(from_var_export::<Value>("foo()").is_err());
+        let nested = "[".repeat(200) + &"]".repeat(200);
+        let err = from_var_export::<Value>(&nested).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::LimitExceeded);
+        // Signs, casts and `__set_state` calls count as nesting as well.
+        for prefix in ["-", "(object)", "A::__set_state("] {
+            let nested = prefix.repeat(1_000_000) + "1";
+            let err = from_var_export::<Value>(&nested).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::LimitExceeded);
+        }
+        assert_eq!(from_var_export::<i64>("- -+1").unwrap(), 1);
+    }
+}
@@ -0,0 +1,534 @@
+//! The binary format of PHP's igbinary extension, with the `igbinary`
+//! feature.
+//!
+//! Many applications store sessions and cache entries with
+//! `igbinary_serialize` rather than `serialize`. This module reads and
+//! writes version 2 of its format, which igbinary has written since its
+//! 1.1 release.
+//!
+//! Values are mapped exactly like in the textual format: serialization
+//! writes the tokens of `to_vec` in their binary form, and deserialization
+//! reads the binary form into the tokens `from_bytes` reads. As the tokens
+//! are held in between, strings cannot be borrowed from the input.
+//!
+//! ```rust
+//! use php_serde::igbinary;
+//!
+//! let encoded = igbinary::to_vec(&vec!["a", "a"]).unwrap();
+//! assert_eq!(encoded, b"\x00\x00\x00\x02\x14\x02\x06\x00\x11\x01a\x06\x01\x0e\x00");
+//! let decoded: Vec<String> = igbinary::from_bytes(&encoded).unwrap();
+//! assert_eq!(decoded, vec!["a", "a"]);
+//! ```
+//!
+//! References, which igbinary writes for PHP references and for objects
+//! that occur more than once, and PHP 8.1 enum cases are not supported.
+
+use crate::de;
+use crate::error::{Error, Result};
+use crate::options::{DeserializerOptions, SerializerOptions};
+use crate::ser;
+use crate::token::{Key, Lexer, Token, TokenWriter};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+/// Header of version 2 of the format.
+const HEADER: [u8; 4] = [0, 0, 0, 2];
+
+const NULL: u8 = 0x00;
+const REF8: u8 = 0x01;
+const REF32: u8 = 0x03;
+const FALSE: u8 = 0x04;
+const TRUE: u8 = 0x05;
+const LONG8P: u8 = 0x06;
+const LONG8N: u8 = 0x07;
+const LONG16P: u8 = 0x08;
+const LONG16N: u8 = 0x09;
+const LONG32P: u8 = 0x0a;
+const LONG32N: u8 = 0x0b;
+const DOUBLE: u8 = 0x0c;
+const STRING_EMPTY: u8 = 0x0d;
+const STRING_ID8: u8 = 0x0e;
+const STRING_ID16: u8 = 0x0f;
+const STRING_ID32: u8 = 0x10;
+const STRING8: u8 = 0x11;
+const STRING16: u8 = 0x12;
+const STRING32: u8 = 0x13;
+const ARRAY8: u8 = 0x14;
+const ARRAY16: u8 = 0x15;
+const ARRAY32: u8 = 0x16;
+const OBJECT8: u8 = 0x17;
+const OBJECT16: u8 = 0x18;
+const OBJECT32: u8 = 0x19;
+const OBJECT_ID8: u8 = 0x1a;
+const OBJECT_ID16: u8 = 0x1b;
+const OBJECT_ID32: u8 = 0x1c;
+const OBJECT_SER8: u8 = 0x1d;
+const OBJECT_SER16: u8 = 0x1e;
+const OBJECT_SER32: u8 = 0x1f;
+const LONG64P: u8 = 0x20;
+const LONG64N: u8 = 0x21;
+const OBJREF8: u8 = 0x22;
+const OBJREF32: u8 = 0x24;
+const REF: u8 = 0x25;
+const STRING64: u8 = 0x26;
+
+/// Serialize a value into igbinary's format.
+pub fn to_vec<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: Serialize + ?Sized,
+{
+    to_vec_with_options(value, &SerializerOptions::default())
+}
+
+/// Serialize a value into igbinary's format, with non-default options.
+pub fn to_vec_with_options<T>(value: &T, options: &SerializerOptions) -> Result<Vec<u8>>
+where
+    T: Serialize + ?Sized,
+{
+    let serialized = ser::to_vec_with_options(value, options)?;
+    encode(&serialized, options.max_depth)
+}
+
+/// Deserialize a value from igbinary's format.
+pub fn from_bytes<T>(input: &[u8]) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    from_bytes_with_options(input, &DeserializerOptions::default())
+}
+
+/// Deserialize a value from igbinary's format, with non-default options.
+///
+/// Error positions refer to the textual form of the value, except for
+/// malformed input.
+pub fn from_bytes_with_options<T>(input: &[u8], options: &DeserializerOptions) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    let serialized = decode(input, options.max_depth)?;
+    de::from_bytes_with_options(&serialized, options)
+}
+
+/// Convert a value from the textual format into igbinary's format.
+///
+/// ```rust
+/// use php_serde::igbinary;
+///
+/// let encoded = igbinary::from_php(b"a:1:{i:0;b:1;}").unwrap();
+/// assert_eq!(igbinary::to_php(&encoded).unwrap(), b"a:1:{i:0;b:1;}");
+/// ```
+pub fn from_php(input: &[u8]) -> Result<Vec<u8>> {
+    encode(input, DeserializerOptions::default().max_depth)
+}
+
+/// Convert a value from igbinary's format into the textual format.
+pub fn to_php(input: &[u8]) -> Result<Vec<u8>> {
+    decode(input, DeserializerOptions::default().max_depth)
+}
+
+fn encode(serialized: &[u8], max_depth: usize) -> Result<Vec<u8>> {
+    let mut encoder = Encoder {
+        output: HEADER.to_vec(),
+        strings: HashMap::new(),
+    };
+    let options = DeserializerOptions::new().max_depth(max_depth);
+    for token in Lexer::with_options(serialized, options) {
+        encoder.write(token?.0)?;
+    }
+    Ok(encoder.output)
+}
+
+fn decode(input: &[u8], max_depth: usize) -> Result<Vec<u8>> {
+    if input.get(..HEADER.len()) != Some(&HEADER[..]) {
+        return Err(Error::DeserializationFailed(
+            "expected the header of igbinary version 2".to_owned(),
+        ));
+    }
+
+    let mut decoder = Decoder {
+        input,
+        offset: HEADER.len(),
+        strings: Vec::new(),
+    };
+    let mut writer = TokenWriter::new(Vec::new());
+    decoder.value(&mut writer, max_depth)?;
+    if decoder.offset < input.len() {
+        return Err(
+            Error::DeserializationFailed("trailing bytes after the value".to_owned())
+                .at(decoder.offset),
+        );
+    }
+    writer.finish()
+}
+
+/// Writer of tokens in igbinary's format.
+struct Encoder<'a> {
+    output: Vec<u8>,
+    /// Strings written so far, by their id. Repeated strings, including
+    /// keys and class names, are written as ids.
+    strings: HashMap<&'a [u8], usize>,
+}
+
+impl<'a> Encoder<'a> {
+    fn write(&mut self, token: Token<'a>) -> Result<()> {
+        match token {
+            Token::Null => self.output.push(NULL),
+            Token::Bool(false) => self.output.push(FALSE),
+            Token::Bool(true) => self.output.push(TRUE),
+            Token::Int(n) | Token::Key(Key::Int(n)) => self.write_int(n),
+            Token::Float(v) => {
+                self.output.push(DOUBLE);
+                self.output.extend_from_slice(&v.to_be_bytes());
+            }
+            Token::Str(s) | Token::Key(Key::Bytes(s)) => self.write_string(s)?,
+            Token::ArrayStart(len) => self.write_array_header(len)?,
+            Token::ObjectStart { class, len } => {
+                self.write_class(class)?;
+                self.write_array_header(len)?;
+            }
+            Token::ArrayEnd | Token::ObjectEnd => (),
+            Token::Custom { class, payload } => {
+                self.write_class(class)?;
+                self.write_length([OBJECT_SER8, OBJECT_SER16, OBJECT_SER32], payload.len())?;
+                self.output.extend_from_slice(payload);
+            }
+            Token::Enum { .. } => return Err(Error::MissingFeature("igbinary enum cases")),
+            Token::Reference(_) | Token::MutableReference(_) => {
+                return Err(Error::MissingFeature("igbinary references"))
+            }
+        }
+        Ok(())
+    }
+
+    fn write_int(&mut self, n: i64) {
+        let magnitude = n.unsigned_abs();
+        let negative = usize::from(n < 0);
+        if let Ok(m) = u8::try_from(magnitude) {
+            self.output.push([LONG8P, LONG8N][negative]);
+            self.output.push(m);
+        } else if let Ok(m) = u16::try_from(magnitude) {
+            self.output.push([LONG16P, LONG16N][negative]);
+            self.output.extend_from_slice(&m.to_be_bytes());
+        } else if let Ok(m) = u32::try_from(magnitude) {
+            self.output.push([LONG32P, LONG32N][negative]);
+            self.output.extend_from_slice(&m.to_be_bytes());
+        } else {
+            self.output.push([LONG64P, LONG64N][negative]);
+            self.output.extend_from_slice(&magnitude.to_be_bytes());
+        }
+    }
+
+    fn write_string(&mut self, s: &'a [u8]) -> Result<()> {
+        if s.is_empty() {
+            self.output.push(STRING_EMPTY);
+            return Ok(());
+        }
+        if let Some(&id) = self.strings.get(s) {
+            return self.write_length([STRING_ID8, STRING_ID16, STRING_ID32], id);
+        }
+
+        self.strings.insert(s, self.strings.len());
+        if u32::try_from(s.len()).is_ok() {
+            self.write_length([STRING8, STRING16, STRING32], s.len())?;
+        } else {
+            self.output.push(STRING64);
+            self.output
+                .extend_from_slice(&(s.len() as u64).to_be_bytes());
+        }
+        self.output.extend_from_slice(s);
+        Ok(())
+    }
+
+    fn write_class(&mut self, class: &'a [u8]) -> Result<()> {
+        if let Some(&id) = self.strings.get(class) {
+            return self.write_length([OBJECT_ID8, OBJECT_ID16, OBJECT_ID32], id);
+        }
+        self.strings.insert(class, self.strings.len());
+        self.write_length([OBJECT8, OBJECT16, OBJECT32], class.len())?;
+        self.output.extend_from_slice(class);
+        Ok(())
+    }
+
+    fn write_array_header(&mut self, len: usize) -> Result<()> {
+        self.write_length([ARRAY8, ARRAY16, ARRAY32], len)
+    }
+
+    /// Write a type followed by a length or id, picking the smallest of
+    /// the 8, 16 and 32 bit variants of the type it fits.
+    fn write_length(&mut self, types: [u8; 3], n: usize) -> Result<()> {
+        if let Ok(n) = u8::try_from(n) {
+            self.output.extend_from_slice(&[types[0], n]);
+        } else if let Ok(n) = u16::try_from(n) {
+            self.output.push(types[1]);
+            self.output.extend_from_slice(&n.to_be_bytes());
+        } else {
+            let n = u32::try_from(n).map_err(|_| Error::LengthLimitExceeded(n))?;
+            self.output.push(types[2]);
+            self.output.extend_from_slice(&n.to_be_bytes());
+        }
+        Ok(())
+    }
+}
+
+/// Reader of values in igbinary's format, passing them on as tokens.
+struct Decoder<'a> {
+    input: &'a [u8],
+    offset: usize,
+    /// Strings read so far, by their id.
+    strings: Vec<&'a [u8]>,
+}
+
+impl<'a> Decoder<'a> {
+    fn value(&mut self, writer: &mut TokenWriter<Vec<u8>>, depth: usize) -> Result<()> {
+        let start = self.offset;
+        let token = match self.byte()? {
+            NULL => Token::Null,
+            FALSE => Token::Bool(false),
+            TRUE => Token::Bool(true),
+            DOUBLE => Token::Float(f64::from_be_bytes(self.array()?)),
+            ty @ (LONG8P..=LONG32N | LONG64P | LONG64N) => Token::Int(self.int(ty)?),
+            ty @ (STRING_EMPTY..=STRING32 | STRING64) => Token::Str(self.string(ty)?),
+            ty @ ARRAY8..=ARRAY32 => {
+                let len = self.length(ty - ARRAY8)?;
+                return self.entries(writer, Token::ArrayStart(len), len, depth);
+            }
+            ty @ OBJECT8..=OBJECT_ID32 => {
+                let class = self.class(ty)?;
+                let offset = self.offset;
+                match self.byte()? {
+                    ty @ ARRAY8..=ARRAY32 => {
+                        let len = self.length(ty - ARRAY8)?;
+                        let token = Token::ObjectStart { class, len };
+                        return self.entries(writer, token, len, depth);
+                    }
+                    ty @ OBJECT_SER8..=OBJECT_SER32 => {
+                        let len = self.length(ty - OBJECT_SER8)?;
+                        let payload = self.bytes(len)?;
+                        Token::Custom { class, payload }
+                    }
+                    ty => return Err(unknown_type(ty).at(offset)),
+                }
+            }
+            // Marks a PHP reference `&`, to which later references refer.
+            REF => return self.value(writer, depth),
+            REF8..=REF32 | OBJREF8..=OBJREF32 => {
+                return Err(Error::MissingFeature("igbinary references").at(start))
+            }
+            ty => return Err(unknown_type(ty).at(start)),
+        };
+        writer.write(token).map_err(|e| e.at(start))
+    }
+
+    /// Read the entries of an array or object, started by `token`.
+    fn entries(
+        &mut self,
+        writer: &mut TokenWriter<Vec<u8>>,
+        token: Token<'a>,
+        len: usize,
+        depth: usize,
+    ) -> Result<()> {
+        // Like in the textual format, only levels with entries count.
+        let depth = match depth.checked_sub(1) {
+            Some(depth) => depth,
+            None if len == 0 => 0,
+            None => return Err(Error::DepthLimitExceeded.at(self.offset)),
+        };
+        writer.write(token)?;
+        for _ in 0..len {
+            let start = self.offset;
+            let key = match self.byte()? {
+                ty @ (LONG8P..=LONG32N | LONG64P | LONG64N) => Key::Int(self.int(ty)?),
+                ty @ (STRING_EMPTY..=STRING32 | STRING64) => Key::Bytes(self.string(ty)?),
+                ty => return Err(unknown_type(ty).at(start)),
+            };
+            writer.write(Token::Key(key))?;
+            self.value(writer, depth)?;
+        }
+        writer.write(match token {
+            Token::ObjectStart { .. } => Token::ObjectEnd,
+            _ => Token::ArrayEnd,
+        })
+    }
+
+    fn int(&mut self, ty: u8) -> Result<i64> {
+        let start = self.offset - 1;
+        let magnitude = match ty {
+            LONG8P | LONG8N => u64::from(self.byte()?),
+            LONG16P | LONG16N => u64::from(u16::from_be_bytes(self.array()?)),
+            LONG32P | LONG32N => u64::from(u32::from_be_bytes(self.array()?)),
+            _ => u64::from_be_bytes(self.array()?),
+        };
+        let n = if matches!(ty, LONG8N | LONG16N | LONG32N | LONG64N) {
+            0_i64.checked_sub_unsigned(magnitude)
+        } else {
+            i64::try_from(magnitude).ok()
+        };
+        n.ok_or_else(|| Error::IntegerOutOfRange(magnitude.to_string()).at(start))
+    }
+
+    fn string(&mut self, ty: u8) -> Result<&'a [u8]> {
+        let s = match ty {
+            STRING_EMPTY => return Ok(b""),
+            STRING_ID8..=STRING_ID32 => return self.string_id(ty - STRING_ID8),
+            STRING64 => {
+                let len = u64::from_be_bytes(self.array()?);
+                let len = usize::try_from(len).map_err(|_| Error::UnexpectedEof)?;
+                self.bytes(len)?
+            }
+            _ => {
+                let len = self.length(ty - STRING8)?;
+                self.bytes(len)?
+            }
+        };
+        self.strings.push(s);
+        Ok(s)
+    }
+
+    fn class(&mut self, ty: u8) -> Result<&'a [u8]> {
+        if ty >= OBJECT_ID8 {
+            return self.string_id(ty - OBJECT_ID8);
+        }
+        let len = self.length(ty - OBJECT8)?;
+        let class = self.bytes(len)?;
+        self.strings.push(class);
+        Ok(class)
+    }
+
+    fn string_id(&mut self, width: u8) -> Result<&'a [u8]> {
+        let start = self.offset - 1;
+        let id = self.length(width)?;
+        self.strings
+            .get(id)
+            .copied()
+            .ok_or_else(|| Error::InvalidReference(id).at(start))
+    }
+
+    /// Read a length or id of 8, 16 or 32 bits, for a `width` of 0, 1 or 2.
+    fn length(&mut self, width: u8) -> Result<usize> {
+        let n = match width {
+            0 => u32::from(self.byte()?),
+            1 => u32::from(u16::from_be_bytes(self.array()?)),
+            _ => u32::from_be_bytes(self.array()?),
+        };
+        usize::try_from(n).map_err(|_| Error::LengthLimitExceeded(usize::MAX))
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        let [b] = self.array()?;
+        Ok(b)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.bytes(N)?);
+        Ok(array)
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .offset
+            .checked_add(len)
+            .filter(|&end| end <= self.input.len())
+            .ok_or_else(|| Error::UnexpectedEof.at(self.input.len()))?;
+        let bytes = &self.input[self.offset..end];
+        self.offset = end;
+        Ok(bytes)
+    }
+}
+
+fn unknown_type(ty: u8) -> Error {
+    Error::DeserializationFailed(format!("unknown igbinary type {ty:#04x}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_bytes, from_php, to_php, to_vec};
+    use crate::{CustomSerialized, Error, ErrorKind, PhpEnum, Value};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct User {
+        name: String,
+        id: i64,
+        tags: Vec<String>,
+    }
+
+    #[test]
+    fn roundtrip() {
+        let user = User {
+            name: "tag".to_owned(),
+            id: -300,
+            tags: vec!["tag".to_owned(), String::new()],
+        };
+        let encoded = to_vec(&user).unwrap();
+        assert_eq!(
+            encoded,
+            &b"\x00\x00\x00\x02\x14\x03\x11\x04name\x11\x03tag\x11\x02id\x09\x01\x2c\
+               \x11\x04tags\x14\x02\x06\x00\x0e\x01\x06\x01\x0d"[..]
+        );
+        assert_eq!(from_bytes::<User>(&encoded).unwrap(), user);
+
+        for input in [
+            &b"N;"[..],
+            b"d:-0.5;",
+            b"a:4:{i:0;i:9223372036854775807;i:1;i:-9223372036854775808;i:2;i:70000;i:3;b:0;}",
+            br#"a:2:{i:0;O:3:"Foo":1:{s:4:" * x";O:3:"Foo":0:{}}i:1;C:3:"Bar":2:{xy}}"#,
+        ] {
+            assert_eq!(to_php(&from_php(input).unwrap()).unwrap(), input);
+        }
+
+        let custom = CustomSerialized {
+            class: "Bar".to_owned(),
+            payload: b"xy".to_vec(),
+        };
+        assert_eq!(
+            to_vec(&custom).unwrap(),
+            b"\x00\x00\x00\x02\x17\x03Bar\x1d\x02xy"
+        );
+    }
+
+    #[test]
+    fn igbinary_output() {
+        // `igbinary_serialize(['a' => 1.5, 'b' => [true, null], 'c' => 'a'])`
+        let input = b"\x00\x00\x00\x02\x14\x03\x11\x01a\x0c\x3f\xf8\x00\x00\x00\x00\x00\x00\
+            \x11\x01b\x14\x02\x06\x00\x05\x06\x01\x00\x11\x01c\x0e\x00";
+        let value: Value = from_bytes(input).unwrap();
+        assert_eq!(
+            to_php(input).unwrap(),
+            &br#"a:3:{s:1:"a";d:1.5;s:1:"b";a:2:{i:0;b:1;i:1;N;}s:1:"c";s:1:"a";}"#[..]
+        );
+        assert_eq!(value.get("c").and_then(Value::as_str), Some("a"));
+
+        // PHP references are read as the values they mark.
+        assert_eq!(
+            from_bytes::<i64>(b"\x00\x00\x00\x02\x25\x07\x05").unwrap(),
+            -5
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        let err = from_bytes::<Value>(b"\x00\x00\x00\x01\x00").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Custom);
+        let err = from_bytes::<Value>(b"\x00\x00\x00\x02\x11\x05ab").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Eof);
+        let err = from_bytes::<Value>(b"\x00\x00\x00\x02\x14\x01\x06\x00\x0e\x00").unwrap_err();
+        assert_eq!(err.position(), Some(8));
+        assert!(matches!(err.into_inner(), Error::InvalidReference(0)));
+        let err = from_bytes::<Value>(b"\x00\x00\x00\x02\x14\x01\x06\x00\x01\x00").unwrap_err();
+        assert!(matches!(err.into_inner(), Error::MissingFeature(_)));
+        let err = from_bytes::<Value>(b"\x00\x00\x00\x02\x00\x00").unwrap_err();
+        assert_eq!(err.position(), Some(5));
+        let err = from_bytes::<String>(b"\x00\x00\x00\x02\x06\x01").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedToken);
+
+        assert!(to_php(b"\x00\x00\x00\x02\x21\x80\x00\x00\x00\x00\x00\x00\x00").is_ok());
+        #[derive(Serialize)]
+        enum Suit {
+            Hearts,
+        }
+        assert!(to_vec(&PhpEnum(Suit::Hearts)).is_err());
+    }
+}
@@ -0,0 +1,132 @@
+//! Sources of serialized data.
+
+use std::io::{self, BufRead};
+
+/// Source of serialized data for a `PhpDeserializer`.
+///
+/// Implemented for all `BufRead` types, whose strings are copied out, and
+/// for `SliceInput`, which lends them out instead. Only the latter can be
+/// deserialized into borrowed types such as `&'de str`.
+///
+/// This trait is sealed and cannot be implemented outside of this crate.
+pub trait Input<'de>: io::Read + private::Sealed {
+    /// Borrow the next `length` bytes from the input.
+    ///
+    /// Returns `None` if the bytes must be read and copied instead, which
+    /// includes inputs with fewer than `length` bytes left.
+    #[doc(hidden)]
+    fn borrow_bytes(&mut self, length: usize) -> Option<&'de [u8]>;
+
+    /// The input left to read, if it is in memory.
+    #[doc(hidden)]
+    fn rest(&self) -> Option<&[u8]>;
+}
+
+impl<'de, R> Input<'de> for R
+where
+    R: BufRead,
+{
+    #[inline]
+    fn borrow_bytes(&mut self, _length: usize) -> Option<&'de [u8]> {
+        None
+    }
+
+    #[inline]
+    fn rest(&self) -> Option<&[u8]> {
+        None
+    }
+}
+
+/// Input read from a byte slice, lending out strings.
+///
+/// Used by `from_bytes` and `PhpDeserializer::from_slice`.
+#[derive(Clone, Debug)]
+pub struct SliceInput<'de> {
+    slice: &'de [u8],
+}
+
+impl<'de> SliceInput<'de> {
+    /// Read from the start of `slice`.
+    pub fn new(slice: &'de [u8]) -> Self {
+        SliceInput { slice }
+    }
+}
+
+impl io::Read for SliceInput<'_> {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.slice.read(buf)
+    }
+
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        self.slice.read_to_end(buf)
+    }
+}
+
+impl<'de> Input<'de> for SliceInput<'de> {
+    #[inline]
+    fn borrow_bytes(&mut self, length: usize) -> Option<&'de [u8]> {
+        if length > self.slice.len() {
+            return None;
+        }
+
+        let (bytes, rest) = self.slice.split_at(length);
+        self.slice = rest;
+        Some(bytes)
+    }
+
+    #[inline]
+    fn rest(&self) -> Option<&[u8]> {
+        Some(self.slice)
+    }
+}
+
+/// Input read from a buffer of its own, for values read once and then
+/// deserialized again.
+///
+/// Unlike a `BufRead` over the buffer, its contents can be scanned ahead.
+#[derive(Clone, Debug)]
+pub struct BufferInput {
+    buf: Vec<u8>,
+    offset: usize,
+}
+
+impl BufferInput {
+    /// Read from the start of `buf`.
+    pub fn new(buf: Vec<u8>) -> Self {
+        BufferInput { buf, offset: 0 }
+    }
+}
+
+impl io::Read for BufferInput {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = (&self.buf[self.offset..]).read(buf)?;
+        self.offset += n;
+        Ok(n)
+    }
+}
+
+impl Input<'_> for BufferInput {
+    #[inline]
+    fn borrow_bytes(&mut self, _length: usize) -> Option<&'static [u8]> {
+        None
+    }
+
+    #[inline]
+    fn rest(&self) -> Option<&[u8]> {
+        Some(&self.buf[self.offset..])
+    }
+}
+
+mod private {
+    use std::io::BufRead;
+
+    pub trait Sealed {}
+
+    impl<R: BufRead> Sealed for R {}
+
+    impl Sealed for super::SliceInput<'_> {}
+
+    impl Sealed for super::BufferInput {}
+}
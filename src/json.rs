@@ -0,0 +1,235 @@
+//! Converting between serialized data and JSON, with the `json` feature.
+
+use crate::custom::CustomSerialized;
+use crate::de::PhpDeserializer;
+use crate::error::{Error, Result};
+use crate::key::PhpKey;
+use crate::property;
+use crate::validate::validate;
+use crate::value::{self, Value};
+use serde_json::Value as JsonValue;
+use std::convert::TryFrom;
+
+/// Convert a serialized value to JSON.
+///
+/// The value is transcoded as it is read, without building it in memory
+/// first. Lists become JSON arrays, other arrays and objects become JSON
+/// objects, with integer keys written as strings and class names dropped.
+/// Non-finite floats become `null`, strings that are not valid UTF-8 arrays
+/// of their bytes. Custom serialized objects and enum cases become JSON
+/// objects of their class and payload or case, like they are passed on to
+/// other formats.
+///
+/// Malformed input fails before anything is written, the same way
+/// `validate` does, and so do references.
+///
+/// ```rust
+/// use php_serde::php_to_json;
+///
+/// let input = br#"a:2:{s:4:"tags";a:2:{i:0;s:1:"a";i:1;s:1:"b";}s:5:"score";d:0.5;}"#;
+/// assert_eq!(php_to_json(input).unwrap(), r#"{"tags":["a","b"],"score":0.5}"#);
+/// ```
+pub fn php_to_json(input: &[u8]) -> Result<String> {
+    validate(input)?;
+
+    let mut de = PhpDeserializer::from_slice(input);
+    let mut output = Vec::new();
+    let mut ser = serde_json::Serializer::new(&mut output);
+    serde_transcode::transcode(&mut de, &mut ser)
+        .map_err(|e| Error::SerializationFailed(e.to_string()).at(de.byte_offset()))?;
+
+    String::from_utf8(output).map_err(|e| Error::NotUtf8String(e.utf8_error()))
+}
+
+/// Convert JSON to a serialized value.
+///
+/// JSON arrays become lists and JSON objects arrays with string keys, with
+/// numeric keys such as `"1"` turned into integers, like PHP's
+/// `json_decode` does with `$associative` set. Integers beyond the range of
+/// PHP integers become floats.
+///
+/// Unlike `php_to_json`, the value is read into memory first, since PHP
+/// writes the number of entries of an array before the entries.
+///
+/// ```rust
+/// use php_serde::json_to_php;
+///
+/// let serialized = json_to_php(r#"{"tags":["a"],"1":null}"#).unwrap();
+/// assert_eq!(serialized, br#"a:2:{s:4:"tags";a:1:{i:0;s:1:"a";}i:1;N;}"#);
+/// ```
+pub fn json_to_php(json: &str) -> Result<Vec<u8>> {
+    let value: Value =
+        serde_json::from_str(json).map_err(|e| Error::DeserializationFailed(e.to_string()))?;
+    crate::to_vec(&value)
+}
+
+/// Convert JSON to a PHP value, like `json_to_php` does.
+impl From<JsonValue> for Value {
+    fn from(json: JsonValue) -> Self {
+        match json {
+            JsonValue::Null => Value::Null,
+            JsonValue::Bool(b) => Value::Bool(b),
+            // Integers beyond `i64` become floats, like in PHP.
+            JsonValue::Number(n) => match n.as_i64() {
+                Some(n) => Value::Int(n),
+                None => Value::from(n.as_f64()),
+            },
+            JsonValue::String(s) => Value::from(s),
+            JsonValue::Array(values) => Value::from(values),
+            JsonValue::Object(entries) => entries.into_iter().collect(),
+        }
+    }
+}
+
+/// Convert a PHP value to JSON, failing for values JSON cannot represent.
+///
+/// Lists become JSON arrays, other arrays JSON objects with integer keys
+/// written as strings, which `From<serde_json::Value>` turns back into
+/// integers. Objects become JSON objects of their properties, without their
+/// class or the prefixes of private and protected properties, the same way
+/// `php_to_json` writes them.
+///
+/// Strings and keys that are not valid UTF-8 cannot be converted, nor can
+/// non-finite floats, custom serialized objects and enum cases.
+impl TryFrom<Value> for JsonValue {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Self> {
+        Ok(match value {
+            Value::Null => JsonValue::Null,
+            Value::Bool(b) => JsonValue::Bool(b),
+            Value::Int(n) => JsonValue::from(n),
+            Value::Float(v) => serde_json::Number::from_f64(v)
+                .map(JsonValue::Number)
+                .ok_or_else(|| Error::SerializationFailed(format!("{v} is not a JSON number")))?,
+            Value::String(s) => JsonValue::String(utf8(s)?),
+            Value::Array(entries) if value::is_list(&entries) => JsonValue::Array(
+                entries
+                    .into_iter()
+                    .map(|(_, v)| JsonValue::try_from(v))
+                    .collect::<Result<_>>()?,
+            ),
+            Value::Array(entries) => json_object(entries)?,
+            Value::Object { properties, .. } => json_object(
+                properties
+                    .into_iter()
+                    .map(|(k, v)| (property::demangle_key(k), v)),
+            )?,
+            Value::Custom(CustomSerialized { class, .. }) | Value::Enum { class, .. } => {
+                return Err(Error::SerializationFailed(format!(
+                    "`{class}` cannot be converted to JSON"
+                )))
+            }
+        })
+    }
+}
+
+/// Convert the entries of an array or object to a JSON object.
+fn json_object<I>(entries: I) -> Result<JsonValue>
+where
+    I: IntoIterator<Item = (PhpKey, Value)>,
+{
+    entries
+        .into_iter()
+        .map(|(key, value)| {
+            let key = match key {
+                PhpKey::Int(n) => n.to_string(),
+                PhpKey::String(s) => utf8(s)?,
+            };
+            Ok((key, JsonValue::try_from(value)?))
+        })
+        .collect::<Result<_>>()
+        .map(JsonValue::Object)
+}
+
+fn utf8(s: Vec<u8>) -> Result<String> {
+    String::from_utf8(s).map_err(|e| Error::NotUtf8String(e.utf8_error()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{json_to_php, php_to_json};
+    use crate::{from_bytes, ErrorKind, PhpKey, Value};
+    use serde_json::json;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn to_json() {
+        let input = br#"a:5:{i:0;N;i:1;b:1;i:2;i:-3;i:3;d:INF;i:4;a:2:{i:5;s:1:"x";s:1:"y";O:3:"Foo":1:{s:1:"z";a:0:{}}}}"#;
+        assert_eq!(
+            php_to_json(input).unwrap(),
+            r#"[null,true,-3,null,{"5":"x","y":{"z":[]}}]"#
+        );
+        assert_eq!(php_to_json(b"s:2:\"\xc3\xa9\";").unwrap(), r#""é""#);
+        assert_eq!(php_to_json(b"s:1:\"\xff\";").unwrap(), "[255]");
+    }
+
+    #[test]
+    fn from_json() {
+        let json = r#"{"a":[1,2.5,"x"],"9223372036854775808":true,"b":{"c":null}}"#;
+        assert_eq!(
+            json_to_php(json).unwrap(),
+            &br#"a:3:{s:1:"a";a:3:{i:0;i:1;i:1;d:2.5;i:2;s:1:"x";}s:19:"9223372036854775808";b:1;s:1:"b";a:1:{s:1:"c";N;}}"#[..]
+        );
+        assert_eq!(
+            json_to_php("18446744073709551615").unwrap(),
+            b"d:1.8446744073709552E+19;"
+        );
+
+        // Converting back and forth keeps the data.
+        let json = r#"{"list":[true,{"0":"a","2":"b"}],"n":-1}"#;
+        assert_eq!(php_to_json(&json_to_php(json).unwrap()).unwrap(), json);
+    }
+
+    #[test]
+    fn errors() {
+        let err = php_to_json(b"a:1:{i:0;b:2;}").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidValue);
+        assert_eq!(err.position(), Some(13));
+
+        // JSON object keys must be strings.
+        let err = php_to_json(b"a:1:{s:1:\"\xff\";N;}").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Custom);
+
+        assert_eq!(json_to_php("[1,").unwrap_err().kind(), ErrorKind::Custom);
+    }
+
+    #[test]
+    fn value_conversions() {
+        let json = json!({"1": null, "9223372036854775808": true, "a": [1, 2.5, "x"]});
+        let value = Value::from(json.clone());
+        assert_eq!(
+            value,
+            vec![
+                (PhpKey::from("1"), Value::Null),
+                (PhpKey::from("9223372036854775808"), Value::Bool(true)),
+                (
+                    PhpKey::from("a"),
+                    Value::from(vec![Value::Int(1), Value::Float(2.5), Value::from("x")])
+                ),
+            ]
+            .into_iter()
+            .collect()
+        );
+        assert_eq!(serde_json::Value::try_from(value).unwrap(), json);
+        assert_eq!(Value::from(json!(u64::MAX)), Value::Float(u64::MAX as f64));
+
+        let value: Value =
+            from_bytes(b"O:1:\"A\":2:{s:4:\"\0*\0x\";i:1;s:1:\"y\";a:0:{}}").unwrap();
+        assert_eq!(
+            serde_json::Value::try_from(value).unwrap(),
+            json!({"x": 1, "y": []})
+        );
+
+        let fails = |value: Value| serde_json::Value::try_from(value).unwrap_err().kind();
+        assert_eq!(fails(Value::String(vec![0xff])), ErrorKind::Utf8);
+        assert_eq!(fails(Value::Float(f64::NAN)), ErrorKind::Custom);
+        assert_eq!(
+            fails(Value::Enum {
+                class: "Suit".to_owned(),
+                case: "Up".to_owned()
+            }),
+            ErrorKind::Custom
+        );
+    }
+}
@@ -0,0 +1,659 @@
+//! PHP array keys.
+
+use crate::error::{Error, Result};
+use crate::numeric;
+use serde::de::{self, IntoDeserializer, Visitor};
+use serde::{forward_to_deserialize_any, ser, Deserialize, Deserializer, Serialize, Serializer};
+use std::convert::TryFrom;
+use std::fmt;
+
+/// Name used to recognize `PhpKey` while deserializing.
+///
+/// Lets pairs of key and value be read from any array, see
+/// `de::ArrayElement`.
+pub const TOKEN: &str = "$php_serde::private::PhpKey";
+
+/// A PHP array key.
+///
+/// PHP arrays only allow integers and strings as keys. Everything else is
+/// coerced into one of the two when used as a key:
+///
+/// * Strings containing a canonical decimal integer (e.g. `"42"` or `"-7"`,
+///   but not `"042"`, `"+7"` or `"-0"`) that fits into an `i64` become
+///   integers.
+/// * Booleans become `0` or `1`.
+/// * Floats are truncated towards zero, like an `(int)` cast in PHP.
+///
+/// All `From` implementations apply these rules, so a `PhpKey` always holds
+/// the key PHP itself would use.
+///
+/// ```rust
+/// use php_serde::PhpKey;
+///
+/// assert_eq!(PhpKey::from("42"), PhpKey::Int(42));
+/// assert_eq!(PhpKey::from("042"), PhpKey::String(b"042".to_vec()));
+/// assert_eq!(PhpKey::from(true), PhpKey::Int(1));
+/// assert_eq!(PhpKey::from(-2.9), PhpKey::Int(-2));
+/// ```
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum PhpKey {
+    /// Integer key.
+    Int(i64),
+    /// String key. Like all PHP strings, this is an arbitrary bytestring.
+    String(Vec<u8>),
+}
+
+impl PhpKey {
+    /// Create a key from a bytestring, applying PHP's numeric string rules.
+    pub fn from_bytes(bytes: impl Into<Vec<u8>>) -> Self {
+        let bytes = bytes.into();
+        match parse_numeric_key(&bytes) {
+            Some(n) => PhpKey::Int(n),
+            None => PhpKey::String(bytes),
+        }
+    }
+
+    /// Returns the integer value, if this is an integer key.
+    pub fn as_int(&self) -> Option<i64> {
+        match *self {
+            PhpKey::Int(n) => Some(n),
+            PhpKey::String(_) => None,
+        }
+    }
+
+    /// Returns the raw bytes, if this is a string key.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            PhpKey::Int(_) => None,
+            PhpKey::String(s) => Some(s),
+        }
+    }
+}
+
+/// Parse a bytestring the way PHP checks for integer-like array keys.
+///
+/// Only canonical representations are accepted: no leading zeros, no plus
+/// sign, no whitespace and no negative zero.
+fn parse_numeric_key(bytes: &[u8]) -> Option<i64> {
+    let digits = bytes.strip_prefix(b"-").unwrap_or(bytes);
+    match digits {
+        [b'0'] => {
+            return if digits.len() == bytes.len() {
+                Some(0)
+            } else {
+                None
+            }
+        }
+        [] | [b'0', ..] => return None,
+        _ => {}
+    }
+
+    if !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+
+    // Digits only, so this is always valid UTF-8. Out-of-range values fail
+    // to parse and remain strings, exactly like in PHP.
+    std::str::from_utf8(bytes).ok()?.parse().ok()
+}
+
+impl fmt::Display for PhpKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PhpKey::Int(n) => write!(f, "{n}"),
+            PhpKey::String(s) => write!(f, "{}", String::from_utf8_lossy(s)),
+        }
+    }
+}
+
+macro_rules! impl_from_int {
+    ($($ty:ty),*) => {
+        $(
+            impl From<$ty> for PhpKey {
+                #[inline]
+                fn from(v: $ty) -> Self {
+                    PhpKey::Int(i64::from(v))
+                }
+            }
+        )*
+    };
+}
+
+impl_from_int!(i8, i16, i32, i64, u8, u16, u32);
+
+impl From<bool> for PhpKey {
+    #[inline]
+    fn from(v: bool) -> Self {
+        PhpKey::Int(i64::from(v))
+    }
+}
+
+impl From<f64> for PhpKey {
+    #[inline]
+    fn from(v: f64) -> Self {
+        PhpKey::Int(numeric::float_to_int(v))
+    }
+}
+
+impl From<f32> for PhpKey {
+    #[inline]
+    fn from(v: f32) -> Self {
+        PhpKey::from(f64::from(v))
+    }
+}
+
+impl From<&str> for PhpKey {
+    #[inline]
+    fn from(v: &str) -> Self {
+        PhpKey::from_bytes(v)
+    }
+}
+
+impl From<String> for PhpKey {
+    #[inline]
+    fn from(v: String) -> Self {
+        PhpKey::from_bytes(v)
+    }
+}
+
+impl From<&[u8]> for PhpKey {
+    #[inline]
+    fn from(v: &[u8]) -> Self {
+        PhpKey::from_bytes(v)
+    }
+}
+
+impl From<Vec<u8>> for PhpKey {
+    #[inline]
+    fn from(v: Vec<u8>) -> Self {
+        PhpKey::from_bytes(v)
+    }
+}
+
+impl Serialize for PhpKey {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            PhpKey::Int(n) => serializer.serialize_i64(*n),
+            PhpKey::String(s) => serializer.serialize_bytes(s),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for PhpKey {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct KeyVisitor;
+
+        impl<'de> Visitor<'de> for KeyVisitor {
+            type Value = PhpKey;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("an integer or string array key")
+            }
+
+            fn visit_newtype_struct<D>(
+                self,
+                deserializer: D,
+            ) -> std::result::Result<PhpKey, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                deserializer.deserialize_any(self)
+            }
+
+            fn visit_bool<E: de::Error>(self, v: bool) -> std::result::Result<PhpKey, E> {
+                Ok(PhpKey::from(v))
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> std::result::Result<PhpKey, E> {
+                Ok(PhpKey::Int(v))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> std::result::Result<PhpKey, E> {
+                Ok(i64::try_from(v)
+                    .map_or_else(|_| PhpKey::String(v.to_string().into_bytes()), PhpKey::Int))
+            }
+
+            fn visit_f64<E: de::Error>(self, v: f64) -> std::result::Result<PhpKey, E> {
+                Ok(PhpKey::from(v))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<PhpKey, E> {
+                Ok(PhpKey::from(v))
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> std::result::Result<PhpKey, E> {
+                Ok(PhpKey::from(v))
+            }
+
+            fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> std::result::Result<PhpKey, E> {
+                Ok(PhpKey::from(v))
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(TOKEN, KeyVisitor)
+    }
+}
+
+/// Deserializer handing a single, already parsed key to a visitor.
+///
+/// Integer keys are offered as strings to targets asking for one, since PHP
+/// turns numeric string keys into integers on its own.
+#[derive(Debug)]
+pub struct PhpKeyDeserializer {
+    key: PhpKey,
+}
+
+impl IntoDeserializer<'_, Error> for PhpKey {
+    type Deserializer = PhpKeyDeserializer;
+
+    fn into_deserializer(self) -> PhpKeyDeserializer {
+        PhpKeyDeserializer { key: self }
+    }
+}
+
+impl<'de> Deserializer<'de> for PhpKeyDeserializer {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.key {
+            PhpKey::Int(n) => visitor.visit_i64(n),
+            PhpKey::String(s) => match String::from_utf8(s) {
+                Ok(s) => visitor.visit_string(s),
+                Err(e) => visitor.visit_byte_buf(e.into_bytes()),
+            },
+        }
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.key {
+            PhpKey::Int(n) => visitor.visit_string(n.to_string()),
+            key @ PhpKey::String(_) => key.into_deserializer().deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        // Integers beyond `i64::MAX` are stored as string keys by PHP.
+        if let PhpKey::String(ref s) = self.key {
+            if let Some(n) = std::str::from_utf8(s).ok().and_then(|s| s.parse().ok()) {
+                return visitor.visit_u64(n);
+            }
+        }
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        // PHP writes `false` and `true` keys as `0` and `1`.
+        match self.key {
+            PhpKey::Int(0) => visitor.visit_bool(false),
+            PhpKey::Int(1) => visitor.visit_bool(true),
+            key => key.into_deserializer().deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        // PHP truncates float keys to integers, which are accepted as they
+        // are. Numeric string keys such as `"1.5"` are parsed.
+        if let PhpKey::String(ref s) = self.key {
+            match numeric::parse_numeric(s) {
+                Some((numeric::Number::Float(v), true)) => return visitor.visit_f64(v),
+                Some((numeric::Number::Int(n), true)) => return visitor.visit_i64(n),
+                _ => (),
+            }
+        }
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_f64(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        // Keys select unit variants by name, integer keys included.
+        visitor.visit_enum(IntoDeserializer::<Error>::into_deserializer(
+            self.key.to_string(),
+        ))
+    }
+
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u128 char
+        bytes byte_buf unit unit_struct seq tuple map struct
+        ignored_any tuple_struct
+    }
+}
+
+/// Serializer converting a map key into a `PhpKey`.
+///
+/// Applies the same coercion rules PHP uses for array keys. Numeric strings
+/// are only turned into integers with `coerce_numeric`.
+#[derive(Debug)]
+pub struct KeySerializer {
+    /// Whether numeric strings become integer keys, see
+    /// `SerializerOptions::coerce_numeric_keys`.
+    pub coerce_numeric: bool,
+}
+
+impl KeySerializer {
+    fn string(&self, v: &[u8]) -> PhpKey {
+        if self.coerce_numeric {
+            PhpKey::from(v)
+        } else {
+            PhpKey::String(v.to_vec())
+        }
+    }
+}
+
+impl ser::Serializer for KeySerializer {
+    type Ok = PhpKey;
+    type Error = Error;
+
+    type SerializeSeq = ser::Impossible<PhpKey, Error>;
+    type SerializeTuple = ser::Impossible<PhpKey, Error>;
+    type SerializeTupleStruct = ser::Impossible<PhpKey, Error>;
+    type SerializeTupleVariant = ser::Impossible<PhpKey, Error>;
+    type SerializeMap = ser::Impossible<PhpKey, Error>;
+    type SerializeStruct = ser::Impossible<PhpKey, Error>;
+    type SerializeStructVariant = ser::Impossible<PhpKey, Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<PhpKey> {
+        Ok(PhpKey::from(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<PhpKey> {
+        Ok(PhpKey::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<PhpKey> {
+        Ok(PhpKey::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<PhpKey> {
+        Ok(PhpKey::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<PhpKey> {
+        Ok(PhpKey::from(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<PhpKey> {
+        Ok(PhpKey::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<PhpKey> {
+        Ok(PhpKey::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<PhpKey> {
+        Ok(PhpKey::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<PhpKey> {
+        // Too large for an integer key, PHP keeps these as strings.
+        Ok(PhpKey::from_bytes(v.to_string()))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<PhpKey> {
+        Ok(PhpKey::from_bytes(v.to_string()))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<PhpKey> {
+        Ok(PhpKey::from_bytes(v.to_string()))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<PhpKey> {
+        Ok(PhpKey::from(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<PhpKey> {
+        Ok(PhpKey::from(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<PhpKey> {
+        let mut buf = [0; 4];
+        self.serialize_str(v.encode_utf8(&mut buf))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<PhpKey> {
+        Ok(self.string(v.as_bytes()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<PhpKey> {
+        Ok(self.string(v))
+    }
+
+    fn serialize_none(self) -> Result<PhpKey> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<PhpKey>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<PhpKey> {
+        // `null` keys become the empty string.
+        Ok(PhpKey::String(Vec::new()))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<PhpKey> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<PhpKey> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<PhpKey>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<PhpKey>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(Error::UnsupportedKey("newtype variant"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Err(Error::UnsupportedKey("sequence"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(Error::UnsupportedKey("tuple"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(Error::UnsupportedKey("tuple struct"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(Error::UnsupportedKey("tuple variant"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(Error::UnsupportedKey("map"))
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Err(Error::UnsupportedKey("struct"))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(Error::UnsupportedKey("struct variant"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PhpKey;
+
+    #[test]
+    fn numeric_strings_become_integers() {
+        assert_eq!(PhpKey::from("0"), PhpKey::Int(0));
+        assert_eq!(PhpKey::from("42"), PhpKey::Int(42));
+        assert_eq!(PhpKey::from("-42"), PhpKey::Int(-42));
+        assert_eq!(PhpKey::from("9223372036854775807"), PhpKey::Int(i64::MAX));
+        assert_eq!(PhpKey::from("-9223372036854775808"), PhpKey::Int(i64::MIN));
+    }
+
+    #[test]
+    fn non_canonical_strings_stay_strings() {
+        for s in &[
+            "",
+            "-",
+            "-0",
+            "00",
+            "042",
+            "+1",
+            " 1",
+            "1 ",
+            "1.0",
+            "1e3",
+            "abc",
+            "9223372036854775808",
+        ] {
+            assert_eq!(PhpKey::from(*s), PhpKey::String(s.as_bytes().to_vec()));
+        }
+    }
+
+    #[test]
+    fn scalars_are_coerced() {
+        assert_eq!(PhpKey::from(false), PhpKey::Int(0));
+        assert_eq!(PhpKey::from(true), PhpKey::Int(1));
+        assert_eq!(PhpKey::from(1.9), PhpKey::Int(1));
+        assert_eq!(PhpKey::from(-1.9), PhpKey::Int(-1));
+        assert_eq!(PhpKey::from(f64::NAN), PhpKey::Int(0));
+        assert_eq!(PhpKey::from(f64::INFINITY), PhpKey::Int(0));
+    }
+
+    #[test]
+    fn bool_and_float_keys() {
+        use crate::{from_bytes, to_vec};
+        use serde::{Deserialize, Serialize};
+        use std::cmp::Ordering;
+        use std::collections::BTreeMap;
+
+        let map: BTreeMap<bool, u8> = vec![(false, 0), (true, 1)].into_iter().collect();
+        let serialized = to_vec(&map).unwrap();
+        assert_eq!(serialized, &b"a:2:{i:0;i:0;i:1;i:1;}"[..]);
+        assert_eq!(from_bytes::<BTreeMap<bool, u8>>(&serialized).unwrap(), map);
+        assert!(from_bytes::<BTreeMap<bool, u8>>(b"a:1:{i:2;i:0;}").is_err());
+
+        #[derive(Debug, Deserialize, PartialEq, PartialOrd, Serialize)]
+        #[serde(transparent)]
+        struct Float(f64);
+
+        impl Eq for Float {}
+
+        impl Ord for Float {
+            fn cmp(&self, other: &Self) -> Ordering {
+                self.0.total_cmp(&other.0)
+            }
+        }
+
+        let map: BTreeMap<Float, u8> = vec![(Float(-2.5), 0), (Float(1.7), 1)]
+            .into_iter()
+            .collect();
+        let serialized = to_vec(&map).unwrap();
+        assert_eq!(serialized, &b"a:2:{i:-2;i:0;i:1;i:1;}"[..]);
+        let map: BTreeMap<Float, u8> = from_bytes(&serialized).unwrap();
+        assert_eq!(
+            map.keys().collect::<Vec<_>>(),
+            vec![&Float(-2.0), &Float(1.0)]
+        );
+
+        let map: BTreeMap<Float, u8> = from_bytes(br#"a:1:{s:3:"1.5";i:0;}"#).unwrap();
+        assert_eq!(map.keys().collect::<Vec<_>>(), vec![&Float(1.5)]);
+    }
+}
@@ -0,0 +1,186 @@
+//! Values skipped instead of failing the whole input.
+
+use serde::de::{MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+/// Newtype name used by `Lenient` to request recovery from failures.
+pub const TOKEN: &str = "$php_serde::private::Lenient";
+
+/// A value that is `None` if it fails to deserialize, rather than failing
+/// the whole input.
+///
+/// Meant for cleaning up data of mixed quality, e.g. a `Vec<Lenient<T>>`
+/// keeps the elements that are valid `T`s and skips the others. The errors
+/// of skipped values are collected, see `from_bytes_lenient` and
+/// `PhpDeserializer::take_errors`. Each is positioned at the start of the
+/// value that failed.
+///
+/// Only values that are read into the target type can be skipped: input
+/// that is malformed, e.g. truncated, still fails as a whole. Wrapped
+/// values are read into a `Value` first, and the target cannot borrow
+/// strings from the input.
+///
+/// With other formats, failures are not recovered from.
+///
+/// ```rust
+/// use php_serde::{from_bytes_lenient, Lenient};
+///
+/// let input = br#"a:3:{i:0;i:1;i:1;s:3:"two";i:2;i:3;}"#;
+/// let (numbers, errors): (Vec<Lenient<i64>>, _) = from_bytes_lenient(input).unwrap();
+///
+/// let numbers: Vec<i64> = numbers.into_iter().flat_map(Lenient::into_inner).collect();
+/// assert_eq!(numbers, vec![1, 3]);
+/// assert_eq!(errors.len(), 1);
+/// assert_eq!(errors[0].position(), Some(17));
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Lenient<T>(pub Option<T>);
+
+impl<T> Lenient<T> {
+    /// Unwrap the inner value, `None` if it was skipped.
+    pub fn into_inner(self) -> Option<T> {
+        self.0
+    }
+}
+
+impl<T> Deref for Lenient<T> {
+    type Target = Option<T>;
+
+    fn deref(&self) -> &Option<T> {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Lenient<T> {
+    fn deref_mut(&mut self) -> &mut Option<T> {
+        &mut self.0
+    }
+}
+
+impl<T> From<T> for Lenient<T> {
+    fn from(inner: T) -> Self {
+        Lenient(Some(inner))
+    }
+}
+
+impl<T: Serialize> Serialize for Lenient<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Lenient<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct LenientVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>> Visitor<'de> for LenientVisitor<T> {
+            type Value = Lenient<T>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("any value")
+            }
+
+            fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Lenient<T>, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                T::deserialize(deserializer).map(Lenient::from)
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Lenient<T>, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                // `PhpDeserializer` passes the value on as the only entry,
+                // after keeping aside the error it may fail with.
+                match map.next_key::<&str>()? {
+                    Some(TOKEN) => Ok(Lenient(map.next_value().ok())),
+                    _ => Err(serde::de::Error::custom(
+                        "expected a value wrapped in `Lenient`",
+                    )),
+                }
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(TOKEN, LenientVisitor(PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Lenient;
+    use crate::{from_bytes, from_bytes_lenient, to_vec, Error, ErrorKind, PhpDeserializer};
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Address {
+        zip: i64,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct User {
+        name: String,
+        addresses: Vec<Lenient<Address>>,
+        tags: BTreeMap<String, Lenient<bool>>,
+    }
+
+    #[test]
+    fn skips_invalid() {
+        let input = br#"a:2:{i:0;a:3:{s:4:"name";s:3:"bob";s:9:"addresses";a:2:{i:0;a:1:{s:3:"zip";s:1:"1";}i:1;a:1:{s:3:"zip";i:2;}}s:4:"tags";a:2:{s:1:"a";b:1;s:1:"b";i:7;}}i:1;a:1:{s:4:"name";i:5;}}"#;
+        let (users, errors): (Vec<Lenient<User>>, _) = from_bytes_lenient(input).unwrap();
+
+        assert_eq!(users.len(), 2);
+        assert_eq!(
+            users[0],
+            Lenient(Some(User {
+                name: "bob".to_owned(),
+                addresses: vec![Lenient(None), Lenient(Some(Address { zip: 2 }))],
+                tags: vec![
+                    ("a".to_owned(), Lenient(Some(true))),
+                    ("b".to_owned(), Lenient(None)),
+                ]
+                .into_iter()
+                .collect(),
+            }))
+        );
+        assert_eq!(users[1], Lenient(None));
+
+        let positions: Vec<_> = errors.iter().map(Error::position).collect();
+        assert_eq!(positions, vec![Some(60), Some(145), Some(155)]);
+        assert_eq!(errors[0].path(), Some("zip"));
+        assert_eq!(errors[2].path(), Some("name"));
+    }
+
+    #[test]
+    fn malformed() {
+        // Syntax errors are not skipped.
+        let err = from_bytes_lenient::<Vec<Lenient<i64>>>(b"a:2:{i:0;i:1;i:1;x:2;}").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedToken);
+
+        // Without collecting errors, values are skipped all the same.
+        let numbers: Vec<Lenient<i64>> = from_bytes(b"a:2:{i:0;b:1;i:1;i:2;}").unwrap();
+        assert_eq!(numbers, vec![Lenient(None), Lenient(Some(2))]);
+
+        let mut de = PhpDeserializer::from_slice(b"a:1:{i:0;b:1;}");
+        let numbers = Vec::<Lenient<i64>>::deserialize(&mut de).unwrap();
+        assert_eq!(numbers, vec![Lenient(None)]);
+        assert_eq!(de.take_errors().len(), 1);
+        assert!(de.take_errors().is_empty());
+    }
+
+    #[test]
+    fn serialize() {
+        let values = vec![Lenient(Some(1)), Lenient(None)];
+        assert_eq!(to_vec(&values).unwrap(), &b"a:2:{i:0;i:1;i:1;N;}"[..]);
+    }
+}
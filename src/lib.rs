@@ -23,11 +23,118 @@
 //!   | integer                 | `i64` (automatic conversion to other types supported) |
 //!   | float                   | `f64` (automatic conversion to `f32` supported)       |
 //!   | strings                 | `Vec<u8>` (PHP strings are not UTF8)                  |
-//!   | null                    | decoded as `None`                                     |
+//!   | null                    | `None` or `()`, also at the top level                 |
 //!   | array (non-associative) | tuple `struct`s or `Vec<_>`                           |
 //!   | array (associative)     | regular `struct`s or `HashMap<_, _>`                  |
 //!
 //! * Rust `String`s are transparently UTF8-converted to PHP bytestrings.
+//!   Strings in other encodings can be decoded lossily, see
+//!   [`DeserializerOptions::lossy_utf8`].
+//! * [`from_bytes`] lends strings out of the input, so borrowed targets such
+//!   as `&str`, `&[u8]` or `Cow` fields marked `#[serde(borrow)]` avoid
+//!   copying large strings.
+//! * Map keys are coerced the same way PHP coerces array keys, e.g. the
+//!   string `"1"` is written as the integer `1`, `true` as `1` and `1.7` as
+//!   `1`. Maps keyed by `bool` or `f64` read such keys back. See [`PhpKey`]
+//!   for details.
+//! * Arrays mixing integer and string keys deserialize into maps, including
+//!   maps keyed by an enum, into a [`Value`], or into a `Vec<(PhpKey, T)>`,
+//!   which takes the keys as they are and keeps their order.
+//! * Data of unknown shape can be deserialized into a [`Value`], which keeps
+//!   array keys and their order intact and supports merging and patching.
+//!   [`diff`] lists the entries added, removed and changed between two
+//!   values, e.g. to audit how an application changed a stored value.
+//!   Self-describing targets such as `#[serde(untagged)]` enums work as
+//!   well: strings are offered as text if they are valid UTF8, and numeric
+//!   arrays as sequences only if their keys are `0..n` in order. So do
+//!   `#[serde(flatten)]` fields, including maps catching unknown keys.
+//!   With the `arbitrary` feature, random `Value`s can be generated through
+//!   `proptest`'s `Arbitrary` trait.
+//! * PHP objects deserialize like associative arrays, keeping their class name
+//!   when read into a [`Value`]. A [`ClassRegistry`] picks the Rust type by
+//!   class name at runtime. [`StdClass`] writes maps and structs as
+//!   generic `stdClass` objects. [`Positional`] maps structs to numeric
+//!   arrays, by field order. Objects with custom serialization (`C:`)
+//!   are carried through as [`CustomSerialized`]. Like PHP's
+//!   `allowed_classes` option, [`AllowedClasses`] turns objects of
+//!   untrusted classes into `__PHP_Incomplete_Class` objects.
+//! * Unit variants of Rust enums are written as strings holding the variant
+//!   name, other variants as arrays with the variant name as their only key,
+//!   like serde's default representation in JSON. PHP 8.1 enum cases
+//!   deserialize into unit variants as well, and are written from Rust enums
+//!   wrapped in [`PhpEnum`].
+//! * `DateTime` objects are represented by [`PhpDateTime`]. The `chrono` and
+//!   `time` features add mappings to the date types of these crates, see
+//!   [`datetime`].
+//! * Names of private and protected properties are demangled when
+//!   deserializing into Rust types, see [`Visibility`].
+//! * The SPL containers `ArrayObject`, `ArrayIterator` and `SplFixedArray`
+//!   deserialize into Rust types like the arrays they contain, in both their
+//!   `O:` and legacy `C:` forms. A [`Value`] keeps the container.
+//! * Deserialization is strict by default. [`DeserializerOptions`], passed to
+//!   [`from_bytes_with_options`], configure it otherwise. Likewise,
+//!   [`SerializerOptions`] are passed to [`to_vec_with_options`], e.g. to
+//!   write structs as PHP objects.
+//! * [`PhpDeserializer`] and [`PhpSerializer`] can be driven directly, e.g.
+//!   with a `DeserializeSeed`, or wrapped by crates such as
+//!   `serde_path_to_error` and `serde_transcode`. Transcoding input from a
+//!   slice into another format passes values on as they are read, without
+//!   holding them in memory. Input from a reader is passed on the same way,
+//!   except for arrays with integer keys: each is held in memory once, at
+//!   its outermost level, to tell whether it is a list.
+//!   With the `json` feature, `php_to_json` and `json_to_php` convert
+//!   between serialized data and JSON, and [`Value`] converts to and from
+//!   `serde_json::Value`. With the `wasm` feature, [`Value`] converts to and
+//!   from `wasm_bindgen::JsValue`.
+//! * Values wrapped in [`Lenient`] are skipped if they fail to deserialize,
+//!   with their errors collected by [`from_bytes_lenient`].
+//! * Untrusted input can be checked with [`validate`] before deserializing
+//!   it, which walks it without building any values. Whether a string
+//!   holds serialized data at all is guessed cheaply with
+//!   [`looks_serialized`] and [`starts_with_valid_token`]. [`analyze`]
+//!   counts values by type and finds the largest strings and entries of
+//!   large values, such as bloated options or sessions. Data whose string
+//!   lengths were broken by search-and-replace, e.g. in SQL dumps, is
+//!   repaired with [`repair::fix_lengths`], and lengths written as character
+//!   counts with [`repair::fix_char_lengths`].
+//! * A [`Lexer`] reads input as [`Token`]s with their byte spans, to inspect
+//!   it without serde. A [`TokenWriter`] writes tokens, e.g. to rewrite
+//!   input token by token. [`rewrite`] replaces values, e.g. to redact them
+//!   before logging, and copies the rest of the input byte for byte.
+//!   [`pretty::format`] renders input as an indented
+//!   tree, to read large values in logs.
+//! * [`var_export`] writes values as PHP code, like PHP's `var_export`,
+//!   e.g. to generate configuration files, and [`from_var_export`] reads
+//!   such code back. Dumps of `print_r` are read, lossily, with
+//!   [`parse_print_r`].
+//! * PHP classes with typed properties matching the serde layout of Rust
+//!   structs are generated with [`php_codegen::class_for`], to keep both
+//!   sides of a shared schema in sync. The other way round,
+//!   [`schema::infer`] proposes Rust types for sample data.
+//! * Values embedded in larger inputs are read with [`from_bytes_partial`],
+//!   concatenated values with a [`StreamDeserializer`]. [`extract`] reads
+//!   a single nested value by its path, passing over the rest, and
+//!   [`splice`] replaces one, leaving the other bytes as they are.
+//! * Session files, such as `userid|i:42;` of PHP's default session
+//!   handler, are read with [`parse_session`] and written with
+//!   [`encode_session`]. The `php_binary` and `php_serialize` handlers are
+//!   supported as well, see [`SessionHandler`], and a [`Session`] gives
+//!   typed access to the variables of any of them.
+//! * With the `igbinary` feature, values are read and written in the binary
+//!   format of PHP's igbinary extension as well, see `igbinary`, and with
+//!   the `wddx` feature as WDDX packets, see `wddx`.
+//! * Files, sockets and other `io::Read` sources are read with
+//!   [`from_reader`], without loading them into memory first.
+//!   With the `tokio` feature, `from_async_reader` reads from a
+//!   `tokio::io::AsyncRead` without blocking, collecting each value in
+//!   memory first, and `to_async_writer` writes to a `tokio::io::AsyncWrite`
+//!   in chunks.
+//! * With the `sqlx` or `mysql` feature, [`Value`] and the `Php` wrapper
+//!   read and write serialized database columns with `sqlx`, or with the
+//!   `mysql` and `mysql_async` crates.
+//! * With the `axum` or `actix` feature, the `PhpSerialized` wrapper reads
+//!   request bodies and writes responses with the
+//!   `application/vnd.php.serialized` content type.
 //!
 //! ### Out-of-order arrays
 //!
@@ -54,15 +161,21 @@
 //!
 //! The helper function `deserialize_unordered_array` can be used with serde's
 //! `deserialize_with` decorator to automatically buffer and order things, as well
-//! as plugging holes by closing any gaps.
+//! as plugging holes by closing any gaps. Its variants
+//! `deserialize_unordered_array_with_defaults` and
+//! `deserialize_unordered_array_without_holes` fill holes with default
+//! values, or reject them instead. Where the gaps matter,
+//! `deserialize_indexed_array` orders things the same way, but keeps the
+//! index of each element. `deserialize_unordered_array_lenient` skips
+//! elements that fail to deserialize, see [`Lenient`].
 //!
 //! ## What is missing?
 //!
-//! * PHP objects
-//! * Non-string/numeric array keys, except when deserializing into a `HashMap`
-//! * Mixed arrays. Array keys are assumed to always have the same key type
-//!   (Note: If this is required, consider extending this library with a variant
-//!    type).
+//! * Sharing values read through PHP references (`r:` and `R:`). They are
+//!   rejected by default, and read as copies of the values they refer to
+//!   with `DeserializerOptions::resolve_references`. With the `rc` feature,
+//!   shared `Rc` and `Arc` values can be written as references, see the
+//!   `shared` module.
 //!
 //! ## Example use
 //!
@@ -252,19 +365,119 @@
     allow(clippy::unwrap_used, clippy::blacklisted_name, clippy::float_cmp)
 )]
 
+mod allowed_classes;
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
+#[cfg(feature = "tokio")]
+mod async_read;
+#[cfg(feature = "tokio")]
+mod async_write;
+#[cfg(any(feature = "sqlx", feature = "mysql"))]
+mod column;
+mod custom;
+pub mod datetime;
 mod de;
 mod error;
+mod export;
+#[cfg(feature = "igbinary")]
+pub mod igbinary;
+mod input;
+#[cfg(feature = "json")]
+mod json;
+mod key;
+mod lenient;
+#[cfg(feature = "mysql")]
+mod mysql;
+mod numeric;
+mod options;
+pub mod php_codegen;
+mod php_enum;
+mod positional;
+pub mod pretty;
+mod print_r;
+mod property;
+mod references;
+mod registry;
+pub mod repair;
+mod rewrite;
+pub mod schema;
 mod ser;
-
-pub use de::{deserialize_unordered_array, from_bytes};
-pub use error::{Error, Result};
-pub use ser::{to_vec, to_writer};
+pub mod session;
+#[cfg(feature = "rc")]
+pub mod shared;
+mod spl;
+#[cfg(feature = "sqlx")]
+mod sql;
+mod stats;
+mod std_class;
+mod stream;
+pub mod token;
+mod validate;
+mod value;
+#[cfg(feature = "wasm")]
+mod wasm;
+#[cfg(feature = "wddx")]
+pub mod wddx;
+#[cfg(any(feature = "axum", feature = "actix"))]
+mod web;
+
+pub use allowed_classes::AllowedClasses;
+#[cfg(feature = "tokio")]
+pub use async_read::{from_async_reader, from_async_reader_with_options};
+#[cfg(feature = "tokio")]
+pub use async_write::{to_async_writer, to_async_writer_with_options};
+#[cfg(any(feature = "sqlx", feature = "mysql"))]
+pub use column::Php;
+pub use custom::CustomSerialized;
+pub use datetime::PhpDateTime;
+pub use de::{
+    deserialize_indexed_array, deserialize_unordered_array, deserialize_unordered_array_lenient,
+    deserialize_unordered_array_with_defaults, deserialize_unordered_array_without_holes, extract,
+    from_bytes, from_bytes_lenient, from_bytes_partial, from_bytes_with_options, from_reader,
+    PhpDeserializer,
+};
+pub use error::{Error, ErrorKind, Result};
+pub use export::{from_var_export, var_export, var_export_with_syntax, ArraySyntax};
+pub use input::{Input, SliceInput};
+#[cfg(feature = "json")]
+pub use json::{json_to_php, php_to_json};
+pub use key::PhpKey;
+pub use lenient::Lenient;
+pub use options::{
+    CyclicReferences, DeserializerOptions, DuplicateKeys, FloatPrecision, IntegerOverflow,
+    SerializerOptions,
+};
+pub use php_enum::PhpEnum;
+pub use positional::Positional;
+pub use print_r::parse_print_r;
+pub use property::Visibility;
+pub use registry::ClassRegistry;
+pub use rewrite::{rewrite, splice};
+pub use ser::{
+    serialized_size, serialized_size_with_options, to_fmt_writer, to_slice, to_string, to_vec,
+    to_vec_in, to_vec_with_options, to_writer, to_writer_with_options, PhpSerializer,
+};
+pub use session::{
+    decode_php_serialize, detect_session_handler, encode_php_serialize, encode_session,
+    encode_session_with_handler, parse_session, parse_session_with_handler, Session,
+    SessionHandler,
+};
+pub use stats::{analyze, Stats};
+pub use std_class::StdClass;
+pub use stream::StreamDeserializer;
+pub use token::{Lexer, Token, TokenWriter};
+pub use validate::{
+    looks_serialized, starts_with_valid_token, validate, validate_with_options, Report,
+};
+pub use value::{diff, from_value, Difference, MergePolicy, PatchOp, Value};
+#[cfg(any(feature = "axum", feature = "actix"))]
+pub use web::PhpSerialized;
 
 #[cfg(test)]
 mod tests {
     use super::{from_bytes, to_vec};
-    use proptest::prelude::any;
-    use proptest::proptest;
+    use proptest::prelude::{any, Strategy};
+    use proptest::{prop_oneof, proptest};
     use serde::{Deserialize, Serialize};
     use std::collections::HashMap;
 
@@ -292,6 +505,37 @@ mod tests {
         roundtrip!(MyNewtype, MyNewtype(-1));
     }
 
+    #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+    enum Variants {
+        Unit,
+        Newtype(i64),
+        Tuple(String, String),
+        Struct { x: f64, y: Option<bool> },
+    }
+
+    fn variants() -> impl Strategy<Value = Variants> {
+        prop_oneof![
+            any::<()>().prop_map(|()| Variants::Unit),
+            any::<i64>().prop_map(Variants::Newtype),
+            any::<(String, String)>().prop_map(|(a, b)| Variants::Tuple(a, b)),
+            any::<(f64, Option<bool>)>().prop_map(|(x, y)| Variants::Struct { x, y }),
+        ]
+    }
+
+    #[test]
+    fn roundtrip_enum() {
+        roundtrip!(Variants, Variants::Unit);
+        roundtrip!(Variants, Variants::Newtype(-1));
+        roundtrip!(Variants, Variants::Tuple("a".to_owned(), String::new()));
+        roundtrip!(
+            Variants,
+            Variants::Struct {
+                x: 0.5,
+                y: Some(true)
+            }
+        );
+    }
+
     proptest! {
         #[test]
         fn roundtrip_unit(v in any::<()>()) {
@@ -387,6 +631,11 @@ mod tests {
         fn roundtrip_string_string_hashmap(v in proptest::collection::hash_map(any::<String>(), any::<String>(), 0..100)) {
             roundtrip!(HashMap<String, String>, v);
         }
+
+        #[test]
+        fn roundtrip_enum_variants(v in proptest::collection::vec(variants(), 0..10)) {
+            roundtrip!(Vec<Variants>, v);
+        }
     }
 
     use std::io::prelude::*;
@@ -488,13 +737,18 @@ mod tests {
             php_roundtrip!((String, i32), v);
         }
 
-        // that'd fail on input: v = {"0": ""} because php
-        // serialized="a:1:{s:1:\"0\";s:0:\"\";}"
-        // output="a:1:{i:0;s:0:\"\";}"
-        // #[test]
-        // #[ignore]
-        // fn php_roundtrip_string_string_hashmap(v in proptest::collection::hash_map(any::<String>(), any::<String>(), 0..100)) {
-        //     php_roundtrip!(HashMap<String, String>, v);
-        // }
+        // Numeric string keys such as `"0"` are written as integers, like PHP
+        // does, and read back into `String` keys.
+        #[test]
+        #[ignore]
+        fn php_roundtrip_string_string_hashmap(v in proptest::collection::hash_map(any::<String>(), any::<String>(), 0..100)) {
+            php_roundtrip!(HashMap<String, String>, v);
+        }
+
+        #[test]
+        #[ignore]
+        fn php_roundtrip_enum_variants(v in variants()) {
+            php_roundtrip!(Variants, v);
+        }
     }
 }
@@ -0,0 +1,134 @@
+//! `mysql_common` conversions for [`Php`] and [`Value`], with the `mysql`
+//! feature.
+
+use crate::column::Php;
+use crate::de::from_bytes;
+use crate::error::Error;
+use crate::ser::to_vec;
+use crate::value::Value;
+use mysql_common::value::convert::{FromValue, FromValueError};
+use mysql_common::value::Value as MySqlValue;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::convert::TryFrom;
+
+/// Deserialize the bytes of a column, handing the column back on failure.
+fn parse<T: DeserializeOwned>(value: MySqlValue) -> Result<T, FromValueError> {
+    match &value {
+        MySqlValue::Bytes(bytes) => from_bytes(bytes).map_err(|_| FromValueError(value)),
+        _ => Err(FromValueError(value)),
+    }
+}
+
+impl<T: DeserializeOwned> TryFrom<MySqlValue> for Php<T> {
+    type Error = FromValueError;
+
+    fn try_from(value: MySqlValue) -> Result<Self, FromValueError> {
+        parse(value).map(Php)
+    }
+}
+
+impl<T: DeserializeOwned> FromValue for Php<T> {
+    type Intermediate = Self;
+}
+
+/// Write a value as bytes.
+///
+/// Fails if the value cannot be serialized. Since `mysql_common` only takes
+/// infallible conversions for parameters, convert it before passing it on.
+impl<T: Serialize> TryFrom<Php<T>> for MySqlValue {
+    type Error = Error;
+
+    fn try_from(Php(value): Php<T>) -> Result<Self, Error> {
+        to_vec(&value).map(MySqlValue::Bytes)
+    }
+}
+
+impl TryFrom<MySqlValue> for Value {
+    type Error = FromValueError;
+
+    fn try_from(value: MySqlValue) -> Result<Self, FromValueError> {
+        parse(value)
+    }
+}
+
+impl FromValue for Value {
+    type Intermediate = Self;
+}
+
+impl TryFrom<Value> for MySqlValue {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Self, Error> {
+        MySqlValue::try_from(Php(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MySqlValue;
+    use crate::{Php, Value};
+    use mysql_common::constants::ColumnType;
+    use mysql_common::packets::Column;
+    use mysql_common::row::convert::from_row_opt;
+    use mysql_common::row::new_row;
+    use mysql_common::value::convert::{from_value, from_value_opt};
+    use serde::{Deserialize, Serialize};
+    use std::convert::TryFrom;
+    use std::sync::Arc;
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Settings {
+        theme: String,
+    }
+
+    #[test]
+    fn values() {
+        let settings = Settings {
+            theme: "dark".to_owned(),
+        };
+        let bytes = br#"a:1:{s:5:"theme";s:4:"dark";}"#.to_vec();
+
+        let value = MySqlValue::try_from(Php(&settings)).unwrap();
+        assert_eq!(value, MySqlValue::Bytes(bytes.clone()));
+        let Php(read): Php<Settings> = from_value(value);
+        assert_eq!(read, settings);
+
+        let value: Value = from_value(MySqlValue::Bytes(b"a:1:{i:0;N;}".to_vec()));
+        assert_eq!(value, Value::from(vec![Value::Null]));
+        assert_eq!(
+            MySqlValue::try_from(value).unwrap(),
+            MySqlValue::Bytes(b"a:1:{i:0;N;}".to_vec())
+        );
+
+        // Values that cannot be written fail instead of panicking.
+        let mut value = Value::Null;
+        for _ in 0..200 {
+            value = Value::from(vec![value]);
+        }
+        assert!(MySqlValue::try_from(value).is_err());
+
+        // Values that cannot be read are handed back.
+        for value in [MySqlValue::Int(1), MySqlValue::Bytes(b"i:1".to_vec())] {
+            let err = from_value_opt::<Php<Settings>>(value.clone()).unwrap_err();
+            assert_eq!(err.0, value);
+        }
+        assert_eq!(from_value::<Option<Value>>(MySqlValue::NULL), None);
+    }
+
+    #[test]
+    fn rows() {
+        let row = new_row(
+            vec![
+                MySqlValue::Int(7),
+                MySqlValue::Bytes(b"s:1:\"x\";".to_vec()),
+            ],
+            Arc::from(vec![
+                Column::new(ColumnType::MYSQL_TYPE_LONGLONG),
+                Column::new(ColumnType::MYSQL_TYPE_BLOB),
+            ]),
+        );
+        let (id, Php(name)): (i64, Php<String>) = from_row_opt(row).unwrap();
+        assert_eq!((id, name.as_str()), (7, "x"));
+    }
+}
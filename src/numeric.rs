@@ -0,0 +1,242 @@
+//! PHP's numeric conversion rules ("type juggling").
+
+use std::convert::TryFrom;
+
+/// A number parsed from a PHP numeric string.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Number {
+    /// Integer-like string, fitting into an `i64`.
+    Int(i64),
+    /// Float-like string, or an integer-like string that overflows `i64`.
+    Float(f64),
+}
+
+/// Whitespace PHP skips around numeric strings.
+fn is_php_whitespace(c: u8) -> bool {
+    matches!(c, b' ' | b'\t' | b'\n' | b'\r' | 0x0b | 0x0c)
+}
+
+/// Parse the leading numeric part of a string, like PHP's
+/// `is_numeric_string()` with errors allowed.
+///
+/// Returns `None` if the string does not start with a number (leading
+/// whitespace is skipped). Otherwise returns the number along with a flag
+/// that is `true` if nothing but whitespace follows it, i.e. the whole string
+/// is numeric.
+pub fn parse_numeric(s: &[u8]) -> Option<(Number, bool)> {
+    let start = s.iter().position(|&c| !is_php_whitespace(c))?;
+    let mut pos = start;
+
+    if let Some(b'+' | b'-') = s.get(pos) {
+        pos += 1;
+    }
+
+    let count_digits = |from: usize| s[from..].iter().take_while(|c| c.is_ascii_digit()).count();
+
+    let int_digits = count_digits(pos);
+    pos += int_digits;
+    let mut is_float = false;
+
+    if s.get(pos) == Some(&b'.') && (int_digits > 0 || count_digits(pos + 1) > 0) {
+        is_float = true;
+        pos += 1 + count_digits(pos + 1);
+    } else if int_digits == 0 {
+        return None;
+    }
+
+    // The exponent is only part of the number if digits follow it.
+    if let Some(b'e' | b'E') = s.get(pos) {
+        let mut exp = pos + 1;
+        if let Some(b'+' | b'-') = s.get(exp) {
+            exp += 1;
+        }
+        let exp_digits = count_digits(exp);
+        if exp_digits > 0 {
+            is_float = true;
+            pos = exp + exp_digits;
+        }
+    }
+
+    let whole = s[pos..].iter().all(|&c| is_php_whitespace(c));
+
+    // Only ASCII was matched, so this cannot fail.
+    let text = std::str::from_utf8(&s[start..pos]).ok()?;
+
+    if !is_float {
+        if let Ok(n) = text.parse() {
+            return Some((Number::Int(n), whole));
+        }
+    }
+
+    // Overflowing integers are treated as floats by PHP as well.
+    text.parse().ok().map(|f| (Number::Float(f), whole))
+}
+
+/// Convert a float to an integer the way PHP's `(int)` cast does.
+///
+/// Non-finite values become `0`, out-of-range values wrap around modulo
+/// 2^64.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_possible_wrap,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+pub fn float_to_int(v: f64) -> i64 {
+    const TWO_POW_64: f64 = 18_446_744_073_709_551_616.0;
+
+    if !v.is_finite() {
+        return 0;
+    }
+
+    // `i64::MAX as f64` rounds up to 2^63, which is already out of range.
+    if v >= i64::MIN as f64 && v < i64::MAX as f64 {
+        return v.trunc() as i64;
+    }
+
+    let mut dmod = v.trunc() % TWO_POW_64;
+    if dmod < 0.0 {
+        dmod += TWO_POW_64;
+    }
+    // `dmod` is now in `[0, 2^64)`, reinterpret it as two's complement.
+    (dmod as u64) as i64
+}
+
+/// Convert a float to an integer, saturating at the bounds of `i64`.
+///
+/// PHP uses this variant for numeric strings that overflow.
+#[allow(clippy::cast_possible_truncation)]
+pub fn float_to_int_saturating(v: f64) -> i64 {
+    if v.is_nan() {
+        0
+    } else {
+        // Float to int casts saturate in Rust.
+        v as i64
+    }
+}
+
+/// Format a float the way PHP does.
+///
+/// `precision` is the number of significant digits, as configured by PHP's
+/// `precision` and `serialize_precision` settings. `None` corresponds to a
+/// setting of `-1`, which picks the shortest representation that round
+/// trips. This is a port of `zend_gcvt()`.
+pub fn format_float(v: f64, precision: Option<usize>) -> String {
+    if v.is_nan() {
+        return "NAN".to_owned();
+    }
+    if v.is_infinite() {
+        return if v > 0.0 { "INF" } else { "-INF" }.to_owned();
+    }
+
+    // Exponential notation gives us the significant digits and the exponent.
+    let formatted = match precision {
+        Some(p) => format!("{:.*e}", p.max(1) - 1, v.abs()),
+        None => format!("{:e}", v.abs()),
+    };
+    let (mantissa, exp) = formatted.split_once('e').unwrap_or((&formatted, "0"));
+    let exp: i32 = exp.parse().unwrap_or(0);
+    let mut digits: String = mantissa.chars().filter(char::is_ascii_digit).collect();
+    while digits.len() > 1 && digits.ends_with('0') {
+        digits.pop();
+    }
+
+    // Position of the decimal point relative to the digits, `0.DIGITS * 10^decpt`.
+    let decpt = if digits == "0" { 1 } else { exp + 1 };
+    let ndigit = i32::try_from(precision.unwrap_or(17)).unwrap_or(i32::MAX);
+
+    let mut out = String::with_capacity(digits.len() + 8);
+    if v.is_sign_negative() {
+        out.push('-');
+    }
+
+    if decpt < -3 || decpt > ndigit {
+        // Exponential format, e.g. `1.0E+25`.
+        let mut rest = digits.chars();
+        out.extend(rest.next());
+        out.push('.');
+        let rest = rest.as_str();
+        out.push_str(if rest.is_empty() { "0" } else { rest });
+        let exp = decpt - 1;
+        out.push_str(if exp < 0 { "E-" } else { "E+" });
+        out.push_str(&exp.abs().to_string());
+    } else if decpt <= 0 {
+        // Standard format `0.000DIGITS`.
+        out.push_str("0.");
+        for _ in decpt..0 {
+            out.push('0');
+        }
+        out.push_str(&digits);
+    } else {
+        // Standard format, padding with zeros up to the decimal point.
+        let decpt = usize::try_from(decpt).unwrap_or(0);
+        if digits.len() <= decpt {
+            out.push_str(&digits);
+            for _ in digits.len()..decpt {
+                out.push('0');
+            }
+        } else {
+            out.push_str(&digits[..decpt]);
+            out.push('.');
+            out.push_str(&digits[decpt..]);
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{float_to_int, format_float, parse_numeric, Number};
+
+    #[test]
+    fn numeric_strings() {
+        assert_eq!(parse_numeric(b"42"), Some((Number::Int(42), true)));
+        assert_eq!(parse_numeric(b" -42 \n"), Some((Number::Int(-42), true)));
+        assert_eq!(parse_numeric(b"42abc"), Some((Number::Int(42), false)));
+        assert_eq!(parse_numeric(b"1.5"), Some((Number::Float(1.5), true)));
+        assert_eq!(parse_numeric(b".5"), Some((Number::Float(0.5), true)));
+        assert_eq!(parse_numeric(b"1."), Some((Number::Float(1.0), true)));
+        assert_eq!(parse_numeric(b"1e3"), Some((Number::Float(1000.0), true)));
+        assert_eq!(parse_numeric(b"1e"), Some((Number::Int(1), false)));
+        assert_eq!(
+            parse_numeric(b"99999999999999999999"),
+            Some((Number::Float(1e20), true))
+        );
+        assert_eq!(parse_numeric(b""), None);
+        assert_eq!(parse_numeric(b"abc"), None);
+        assert_eq!(parse_numeric(b"."), None);
+        assert_eq!(parse_numeric(b"-"), None);
+        assert_eq!(parse_numeric(b"0x1A"), Some((Number::Int(0), false)));
+    }
+
+    #[test]
+    fn float_to_int_conversion() {
+        assert_eq!(float_to_int(1.9), 1);
+        assert_eq!(float_to_int(-1.9), -1);
+        assert_eq!(float_to_int(f64::NAN), 0);
+        assert_eq!(float_to_int(f64::INFINITY), 0);
+        assert_eq!(float_to_int(1e19), -8_446_744_073_709_551_616);
+        assert_eq!(float_to_int(18_446_744_073_709_551_616.0), 0);
+    }
+
+    #[test]
+    fn float_formatting() {
+        assert_eq!(format_float(0.0, None), "0");
+        assert_eq!(format_float(-0.0, None), "-0");
+        assert_eq!(format_float(1.0, None), "1");
+        assert_eq!(format_float(-1.5, None), "-1.5");
+        assert_eq!(format_float(0.1 + 0.2, None), "0.30000000000000004");
+        assert_eq!(format_float(0.1 + 0.2, Some(14)), "0.3");
+        assert_eq!(format_float(0.1, Some(17)), "0.10000000000000001");
+        assert_eq!(format_float(1e15, Some(14)), "1.0E+15");
+        assert_eq!(format_float(1e15, None), "1000000000000000");
+        assert_eq!(format_float(1e25, None), "1.0E+25");
+        assert_eq!(format_float(0.0001, None), "0.0001");
+        assert_eq!(format_float(0.00001, None), "1.0E-5");
+        assert_eq!(format_float(1.5e-7, None), "1.5E-7");
+        assert_eq!(format_float(123.456, None), "123.456");
+        assert_eq!(format_float(f64::NAN, None), "NAN");
+        assert_eq!(format_float(f64::NEG_INFINITY, None), "-INF");
+    }
+}
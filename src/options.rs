@@ -0,0 +1,604 @@
+//! Configuration of the serializer and deserializer.
+
+use crate::allowed_classes::AllowedClasses;
+
+/// Options controlling deserialization.
+///
+/// The defaults match `from_bytes`: input is read strictly and all classes
+/// are allowed. Options are set builder-style and passed to
+/// `from_bytes_with_options` or `PhpDeserializer::with_options`.
+///
+/// ```rust
+/// use php_serde::{from_bytes_with_options, AllowedClasses, DeserializerOptions, Value};
+///
+/// let options = DeserializerOptions::new().allowed_classes(AllowedClasses::None);
+///
+/// let value: Value = from_bytes_with_options(br#"O:3:"Foo":0:{}"#, &options).unwrap();
+/// assert_eq!(value.class(), Some("__PHP_Incomplete_Class"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct DeserializerOptions {
+    pub(crate) allowed_classes: AllowedClasses,
+    pub(crate) numeric_strings: bool,
+    pub(crate) loose_bools: bool,
+    pub(crate) float_to_int: bool,
+    pub(crate) lossy_utf8: bool,
+    pub(crate) max_depth: usize,
+    pub(crate) max_length: usize,
+    pub(crate) max_buffered: usize,
+    pub(crate) deny_unknown_keys: bool,
+    pub(crate) duplicate_keys: Option<DuplicateKeys>,
+    pub(crate) error_context: usize,
+    pub(crate) resolve_references: bool,
+    pub(crate) cyclic_references: CyclicReferences,
+}
+
+impl Default for DeserializerOptions {
+    fn default() -> Self {
+        DeserializerOptions {
+            allowed_classes: AllowedClasses::All,
+            numeric_strings: false,
+            loose_bools: false,
+            float_to_int: false,
+            lossy_utf8: false,
+            max_depth: 128,
+            max_length: usize::MAX,
+            max_buffered: usize::MAX,
+            deny_unknown_keys: false,
+            duplicate_keys: None,
+            error_context: 0,
+            resolve_references: false,
+            cyclic_references: CyclicReferences::Error,
+        }
+    }
+}
+
+impl DeserializerOptions {
+    /// Create the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Restrict the classes of deserialized objects, see `AllowedClasses`.
+    #[must_use]
+    pub fn allowed_classes(mut self, allowed_classes: AllowedClasses) -> Self {
+        self.allowed_classes = allowed_classes;
+        self
+    }
+
+    /// Accept numeric strings such as `s:2:"42";` for integer and float
+    /// fields.
+    ///
+    /// The whole string must be numeric, as defined by PHP: surrounding
+    /// whitespace is allowed, other trailing characters are not.
+    ///
+    /// ```rust
+    /// use php_serde::{from_bytes_with_options, DeserializerOptions};
+    ///
+    /// let options = DeserializerOptions::new().numeric_strings(true);
+    ///
+    /// let n: i64 = from_bytes_with_options(br#"s:2:"42";"#, &options).unwrap();
+    /// assert_eq!(n, 42);
+    /// assert!(from_bytes_with_options::<i64>(br#"s:3:"42a";"#, &options).is_err());
+    /// ```
+    #[must_use]
+    pub fn numeric_strings(mut self, enabled: bool) -> Self {
+        self.numeric_strings = enabled;
+        self
+    }
+
+    /// Accept `i:0;` and `i:1;`, as well as the strings `"0"`, `"1"` and
+    /// `""`, for boolean fields.
+    #[must_use]
+    pub fn loose_bools(mut self, enabled: bool) -> Self {
+        self.loose_bools = enabled;
+        self
+    }
+
+    /// Accept floats without fractional part, such as `d:3;`, for integer
+    /// fields, as long as they are in range.
+    ///
+    /// PHP switches between integers and floats depending on the history of a
+    /// calculation. The other direction, integers for float fields, is always
+    /// accepted.
+    #[must_use]
+    pub fn float_to_int(mut self, enabled: bool) -> Self {
+        self.float_to_int = enabled;
+        self
+    }
+
+    /// Replace invalid UTF-8 in strings deserialized into `String` or
+    /// `Cow<str>` with U+FFFD, instead of failing with
+    /// `Error::NotUtf8String`.
+    ///
+    /// PHP strings are plain bytes, and legacy data often mixes in Latin-1
+    /// or other encodings. Borrowed `&str` targets still fail on invalid
+    /// input, since the replaced string must be copied.
+    ///
+    /// ```rust
+    /// use php_serde::{from_bytes_with_options, DeserializerOptions};
+    ///
+    /// let options = DeserializerOptions::new().lossy_utf8(true);
+    ///
+    /// let s: String = from_bytes_with_options(b"s:4:\"caf\xe9\";", &options).unwrap();
+    /// assert_eq!(s, "caf\u{fffd}");
+    /// ```
+    #[must_use]
+    pub fn lossy_utf8(mut self, enabled: bool) -> Self {
+        self.lossy_utf8 = enabled;
+        self
+    }
+
+    /// Limit how deeply arrays and objects may be nested, 128 by default.
+    ///
+    /// Deeper input fails with `Error::DepthLimitExceeded`, protecting
+    /// against stack overflows on malicious input.
+    #[must_use]
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Limit the declared length of strings and the element count of arrays
+    /// and objects, unlimited by default.
+    ///
+    /// Longer input fails with `Error::LengthLimitExceeded`. Regardless of
+    /// the limit, memory is only allocated as input actually arrives.
+    #[must_use]
+    pub fn max_length(mut self, max_length: usize) -> Self {
+        self.max_length = max_length;
+        self
+    }
+
+    /// Limit how many bytes `from_async_reader` holds in memory for a
+    /// value, unlimited by default.
+    ///
+    /// It collects all bytes of a value before deserializing it, so this
+    /// bounds its memory use on untrusted input, such as a socket. Larger
+    /// values fail with `Error::LengthLimitExceeded` as soon as the limit
+    /// is passed.
+    #[must_use]
+    pub fn max_buffered(mut self, bytes: usize) -> Self {
+        self.max_buffered = bytes;
+        self
+    }
+
+    /// Fail with `Error::UnknownKey` if an array or object deserialized into
+    /// a struct has a key the struct has no field for.
+    ///
+    /// This applies to all structs, whether or not they use
+    /// `#[serde(deny_unknown_fields)]`, and reports the position of the key.
+    /// Structs using `#[serde(flatten)]` are deserialized as maps and not
+    /// checked.
+    #[must_use]
+    pub fn deny_unknown_keys(mut self, enabled: bool) -> Self {
+        self.deny_unknown_keys = enabled;
+        self
+    }
+
+    /// Handle keys occurring more than once in the same array.
+    ///
+    /// Without a policy, duplicates are passed on as they are: maps end up
+    /// with the last value, `Value` keeps all entries and structs fail.
+    #[must_use]
+    pub fn duplicate_keys(mut self, policy: DuplicateKeys) -> Self {
+        self.duplicate_keys = Some(policy);
+        self
+    }
+
+    /// Include up to `width` bytes of input on either side of the failure
+    /// point in errors, shown in hex and ASCII by their `Display`.
+    ///
+    /// Off by default, since input may hold data that should not end up in
+    /// logs. Only applies where the input is at hand, i.e. with
+    /// `from_bytes_with_options` and `from_async_reader_with_options`.
+    ///
+    /// ```rust
+    /// use php_serde::{from_bytes_with_options, DeserializerOptions};
+    ///
+    /// let options = DeserializerOptions::new().error_context(4);
+    ///
+    /// let err = from_bytes_with_options::<Vec<i64>>(b"a:1:{i:0;x:1;}", &options).unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "[0]: Invalid type indicator on value: x at byte 11, near 30 3b 78 3a | 31 3b 7d (0;x:|1;})"
+    /// );
+    /// ```
+    #[must_use]
+    pub fn error_context(mut self, width: usize) -> Self {
+        self.error_context = width;
+        self
+    }
+
+    /// Resolve references, `r:N;` and `R:N;`, to values read before, instead
+    /// of failing with `Error::InvalidTypeIndicator`.
+    ///
+    /// PHP writes them for objects occurring more than once in a graph, and
+    /// for variables bound by reference. Each reference is deserialized
+    /// from a copy of the value it refers to, so the results share nothing,
+    /// and a value referred to many times is deserialized as many times.
+    /// Errors in a copy are positioned at the value it was copied from.
+    /// References to a value that contains them, i.e. cycles, are handled
+    /// as set with `cyclic_references`.
+    ///
+    /// The input of the value being read is kept in memory, to read
+    /// referenced values again.
+    ///
+    /// ```rust
+    /// use php_serde::{from_bytes_with_options, DeserializerOptions};
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Debug, Deserialize, PartialEq)]
+    /// struct Team {
+    ///     name: String,
+    /// }
+    ///
+    /// let input = br#"a:2:{i:0;O:4:"Team":1:{s:4:"name";s:3:"red";}i:1;r:2;}"#;
+    /// let options = DeserializerOptions::new().resolve_references(true);
+    ///
+    /// let teams: Vec<Team> = from_bytes_with_options(input, &options).unwrap();
+    /// assert_eq!(teams[0], teams[1]);
+    /// ```
+    #[must_use]
+    pub fn resolve_references(mut self, enabled: bool) -> Self {
+        self.resolve_references = enabled;
+        self
+    }
+
+    /// Handle references to a value that contains them, which cannot be
+    /// copied, with `resolve_references`. They fail by default.
+    ///
+    /// PHP writes them for arrays holding a reference to themselves, e.g.
+    /// after `$a[0] = &$a;`, and for objects referring back to their parent.
+    ///
+    /// ```rust
+    /// use php_serde::{from_bytes_with_options, CyclicReferences, DeserializerOptions, Value};
+    ///
+    /// let input = b"a:1:{i:0;a:1:{i:0;R:2;}}";
+    /// let options = DeserializerOptions::new().resolve_references(true);
+    /// assert!(from_bytes_with_options::<Value>(input, &options).is_err());
+    ///
+    /// let options = options.cyclic_references(CyclicReferences::Null);
+    /// let value: Value = from_bytes_with_options(input, &options).unwrap();
+    /// assert_eq!(value, Value::from(vec![Value::from(vec![Value::Null])]));
+    /// ```
+    #[must_use]
+    pub fn cyclic_references(mut self, policy: CyclicReferences) -> Self {
+        self.cyclic_references = policy;
+        self
+    }
+}
+
+/// Policy for keys occurring more than once in the same array.
+///
+/// PHP never produces such arrays, but hand-crafted or corrupted input may
+/// contain them.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DuplicateKeys {
+    /// Keep the first value, skip later ones.
+    First,
+    /// Keep the last value, at the position of the first, like `unserialize`.
+    ///
+    /// Arrays are buffered to find the duplicates before they are passed on.
+    Last,
+    /// Fail with `Error::DuplicateKey`.
+    Error,
+}
+
+/// Policy for references forming a cycle, see
+/// `DeserializerOptions::cyclic_references`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CyclicReferences {
+    /// Fail with `Error::CyclicReference`.
+    Error,
+    /// Read the reference as `null`, breaking the cycle.
+    Null,
+}
+
+/// Options controlling serialization.
+///
+/// The defaults match `to_vec` and `to_writer`. Options are set
+/// builder-style and passed to `to_vec_with_options` or
+/// `to_writer_with_options`.
+///
+/// ```rust
+/// use php_serde::{to_vec_with_options, SerializerOptions};
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct User {
+///     id: i64,
+/// }
+///
+/// let options = SerializerOptions::new().structs_as_objects(true);
+///
+/// let serialized = to_vec_with_options(&User { id: 1 }, &options).unwrap();
+/// assert_eq!(serialized, br#"O:4:"User":1:{s:2:"id";i:1;}"#);
+/// ```
+#[derive(Clone, Debug)]
+pub struct SerializerOptions {
+    pub(crate) float_precision: FloatPrecision,
+    pub(crate) integer_overflow: IntegerOverflow,
+    pub(crate) omit_none_fields: bool,
+    pub(crate) sort_map_keys: bool,
+    pub(crate) coerce_numeric_keys: bool,
+    pub(crate) structs_as_lists: bool,
+    pub(crate) structs_as_objects: bool,
+    pub(crate) max_depth: usize,
+    pub(crate) deduplicate_strings: Option<usize>,
+    pub(crate) write_buffer_size: usize,
+}
+
+impl Default for SerializerOptions {
+    fn default() -> Self {
+        SerializerOptions {
+            float_precision: FloatPrecision::default(),
+            integer_overflow: IntegerOverflow::default(),
+            omit_none_fields: false,
+            sort_map_keys: false,
+            coerce_numeric_keys: true,
+            structs_as_lists: false,
+            structs_as_objects: false,
+            max_depth: 128,
+            deduplicate_strings: None,
+            write_buffer_size: 8 * 1024,
+        }
+    }
+}
+
+impl SerializerOptions {
+    /// Create the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Choose how many digits of floats are written, see `FloatPrecision`.
+    #[must_use]
+    pub fn float_precision(mut self, precision: FloatPrecision) -> Self {
+        self.float_precision = precision;
+        self
+    }
+
+    /// Handle integers that PHP cannot represent, see `IntegerOverflow`.
+    #[must_use]
+    pub fn integer_overflow(mut self, policy: IntegerOverflow) -> Self {
+        self.integer_overflow = policy;
+        self
+    }
+
+    /// Leave out struct fields that are `None`, instead of writing them as
+    /// `N;`.
+    ///
+    /// This is like `#[serde(skip_serializing_if = "Option::is_none")]` on
+    /// every optional field. Since the array header holds the number of
+    /// fields, the fields of each struct are buffered until the struct is
+    /// complete. Structs wrapped in `Positional` keep all fields, so that
+    /// positions don't shift.
+    ///
+    /// ```rust
+    /// use php_serde::{to_vec_with_options, SerializerOptions};
+    /// use serde::Serialize;
+    ///
+    /// #[derive(Serialize)]
+    /// struct User {
+    ///     name: String,
+    ///     mail: Option<String>,
+    /// }
+    ///
+    /// let user = User { name: "bob".to_owned(), mail: None };
+    /// let options = SerializerOptions::new().omit_none_fields(true);
+    ///
+    /// let serialized = to_vec_with_options(&user, &options).unwrap();
+    /// assert_eq!(serialized, br#"a:1:{s:4:"name";s:3:"bob";}"#);
+    /// ```
+    #[must_use]
+    pub fn omit_none_fields(mut self, enabled: bool) -> Self {
+        self.omit_none_fields = enabled;
+        self
+    }
+
+    /// Sort the entries of maps by key, so that equal maps always produce
+    /// the same bytes, e.g. for hashing or signing.
+    ///
+    /// Integer keys come first, in numeric order, followed by string keys
+    /// in byte order, as in the `Ord` implementation of `PhpKey`. Keys are
+    /// compared after PHP's key coercion, so `"10"` sorts as the integer
+    /// `10`, unless `coerce_numeric_keys` is disabled. This applies to all
+    /// maps, including the arrays and object properties of a `Value`,
+    /// whose order is otherwise kept. Struct fields keep their declaration
+    /// order.
+    ///
+    /// ```rust
+    /// use php_serde::{to_vec_with_options, SerializerOptions};
+    /// use std::collections::HashMap;
+    ///
+    /// let map: HashMap<&str, i64> = vec![("b", 1), ("a", 2), ("10", 3)].into_iter().collect();
+    /// let options = SerializerOptions::new().sort_map_keys(true);
+    ///
+    /// let serialized = to_vec_with_options(&map, &options).unwrap();
+    /// assert_eq!(serialized, br#"a:3:{i:10;i:3;s:1:"a";i:2;s:1:"b";i:1;}"#);
+    /// ```
+    #[must_use]
+    pub fn sort_map_keys(mut self, enabled: bool) -> Self {
+        self.sort_map_keys = enabled;
+        self
+    }
+
+    /// Write map keys that are numeric strings, such as `"0"` or `"-5"`, as
+    /// integers, enabled by default.
+    ///
+    /// PHP converts such keys when it builds an array, so it writes them as
+    /// integers as well, and this matches its output. Disabling this writes
+    /// them as strings, as they are, e.g. to reproduce input that was not
+    /// written by PHP. Other keys, such as booleans and floats, are always
+    /// converted. Struct fields are always written as strings.
+    ///
+    /// ```rust
+    /// use php_serde::{to_vec_with_options, SerializerOptions};
+    /// use std::collections::BTreeMap;
+    ///
+    /// let map: BTreeMap<&str, i64> = vec![("0", 1), ("x", 2)].into_iter().collect();
+    /// assert_eq!(php_serde::to_vec(&map).unwrap(), br#"a:2:{i:0;i:1;s:1:"x";i:2;}"#);
+    ///
+    /// let options = SerializerOptions::new().coerce_numeric_keys(false);
+    /// let serialized = to_vec_with_options(&map, &options).unwrap();
+    /// assert_eq!(serialized, br#"a:2:{s:1:"0";i:1;s:1:"x";i:2;}"#);
+    /// ```
+    #[must_use]
+    pub fn coerce_numeric_keys(mut self, enabled: bool) -> Self {
+        self.coerce_numeric_keys = enabled;
+        self
+    }
+
+    /// Write the fields of all structs by position, with keys `0`, `1`, ...
+    /// in declaration order, as if each struct was wrapped in `Positional`.
+    ///
+    /// `Positional` does the same for single values, and reads them back.
+    /// `None` fields are kept, even with `omit_none_fields`, so that
+    /// positions don't shift.
+    ///
+    /// ```rust
+    /// use php_serde::{to_vec_with_options, SerializerOptions};
+    /// use serde::Serialize;
+    ///
+    /// #[derive(Serialize)]
+    /// struct User {
+    ///     id: i64,
+    ///     name: &'static str,
+    /// }
+    ///
+    /// let options = SerializerOptions::new().structs_as_lists(true);
+    ///
+    /// let serialized = to_vec_with_options(&User { id: 7, name: "bob" }, &options).unwrap();
+    /// assert_eq!(serialized, br#"a:2:{i:0;i:7;i:1;s:3:"bob";}"#);
+    /// ```
+    #[must_use]
+    pub fn structs_as_lists(mut self, enabled: bool) -> Self {
+        self.structs_as_lists = enabled;
+        self
+    }
+
+    /// Write structs as objects of a class named like the Rust struct,
+    /// instead of as associative arrays.
+    ///
+    /// The name can be changed with `#[serde(rename)]`. Structs wrapped in
+    /// `StdClass` are still written as `stdClass` objects, and tuple structs
+    /// as numeric arrays.
+    #[must_use]
+    pub fn structs_as_objects(mut self, enabled: bool) -> Self {
+        self.structs_as_objects = enabled;
+        self
+    }
+
+    /// Limit how deeply arrays and objects may be nested, 128 by default.
+    ///
+    /// Deeper values fail with `Error::DepthLimitExceeded` instead of
+    /// overflowing the stack, e.g. a tree that accidentally refers back to
+    /// itself. Levels are counted like `DeserializerOptions::max_depth`
+    /// does, so output within the limit reads back with the same limit.
+    #[must_use]
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Write strings of at least `min_length` bytes that occurred before
+    /// as references to their first occurrence, `r:N;`.
+    ///
+    /// This shrinks output repeating long strings, such as URLs. Array keys
+    /// are always written in full. Each distinct string is kept in memory
+    /// until serialization is done. Like the references written for shared
+    /// values, see the `shared` module, this is not done inside parts that
+    /// are buffered, e.g. maps sorted with `sort_map_keys`.
+    ///
+    /// Deserializing them here requires
+    /// `DeserializerOptions::resolve_references`.
+    ///
+    /// ```rust
+    /// use php_serde::{to_vec_with_options, SerializerOptions};
+    ///
+    /// let url = "https://example.com/";
+    /// let options = SerializerOptions::new().deduplicate_strings(8);
+    ///
+    /// let serialized = to_vec_with_options(&vec![url, "short", url, "short"], &options).unwrap();
+    /// assert_eq!(
+    ///     serialized,
+    ///     br#"a:4:{i:0;s:20:"https://example.com/";i:1;s:5:"short";i:2;r:2;i:3;s:5:"short";}"#
+    /// );
+    /// ```
+    #[must_use]
+    pub fn deduplicate_strings(mut self, min_length: usize) -> Self {
+        self.deduplicate_strings = Some(min_length);
+        self
+    }
+
+    /// Set the size of the buffer `to_writer_with_options` collects output
+    /// in, 8 KiB by default.
+    ///
+    /// The serializer produces many small pieces, such as type tags and
+    /// lengths, which would otherwise each be a separate write. With `0`,
+    /// output is written directly, e.g. to a writer that is buffered
+    /// already. Functions writing to memory, like `to_vec`, never buffer.
+    #[must_use]
+    pub fn write_buffer_size(mut self, size: usize) -> Self {
+        self.write_buffer_size = size;
+        self
+    }
+}
+
+/// Digits written for floats, like PHP's `serialize_precision` setting.
+///
+/// Deserialization reads all formats, so this only matters where the
+/// output must match a specific PHP deployment byte for byte.
+///
+/// ```rust
+/// use php_serde::{to_vec_with_options, FloatPrecision, SerializerOptions};
+///
+/// let options = SerializerOptions::new().float_precision(FloatPrecision::Significant(17));
+///
+/// assert_eq!(php_serde::to_vec(&0.1).unwrap(), b"d:0.1;");
+/// assert_eq!(to_vec_with_options(&0.1, &options).unwrap(), b"d:0.10000000000000001;");
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum FloatPrecision {
+    /// The shortest digits reading back as the same value, like a
+    /// `serialize_precision` of `-1`, the default since PHP 7.1.
+    #[default]
+    Shortest,
+    /// A fixed number of significant digits, with trailing zeros removed,
+    /// like a positive `serialize_precision`. Older configurations use 17.
+    ///
+    /// Fewer than 17 digits may lose precision, `0` is treated as `1`.
+    Significant(u8),
+}
+
+/// Policy for integers above `i64::MAX`, which PHP integers cannot hold.
+///
+/// PHP itself turns integers that overflow into floats. Neither replacement
+/// is read back as an integer, by PHP or by this crate: a float keeps only
+/// about 15 significant digits, while a string keeps all digits, so only
+/// strings round trip exactly, as strings.
+///
+/// 128-bit integers are expected to exceed `i64`, so they are never
+/// rejected: with `Error`, they are written as strings as well. Targets of
+/// type `i128` and `u128` read such strings back.
+///
+/// ```rust
+/// use php_serde::{to_vec_with_options, IntegerOverflow, SerializerOptions};
+///
+/// assert!(php_serde::to_vec(&u64::MAX).is_err());
+///
+/// let options = SerializerOptions::new().integer_overflow(IntegerOverflow::String);
+/// let serialized = to_vec_with_options(&u64::MAX, &options).unwrap();
+/// assert_eq!(serialized, br#"s:20:"18446744073709551615";"#);
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum IntegerOverflow {
+    /// Fail with `Error::IntegerOutOfRange`.
+    #[default]
+    Error,
+    /// Write the nearest float, like PHP's arithmetic does on overflow.
+    Float,
+    /// Write the decimal digits as a string.
+    String,
+}
@@ -0,0 +1,490 @@
+//! Generating PHP class definitions from Rust types.
+
+use crate::error::{Error, Result};
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+    VariantAccess, Visitor,
+};
+use std::fmt::Write;
+
+/// Generate a PHP class with a typed property for each field of the struct
+/// `T`, in the layout its serde implementation reads.
+///
+/// The layout is traced through `T`'s `Deserialize` implementation, so
+/// renamed and skipped fields are taken into account. Property types
+/// follow the mapping of this crate: integers are `int`, strings and bytes
+/// `string`, sequences, tuples and maps `array`, `Option`s nullable and
+/// enums `string|array`, for their unit and other variants. Values of any
+/// type, such as a `Value`, are `mixed`. Nested structs become classes of
+/// their own, which follow the class of `T`.
+///
+/// Objects of the generated classes are read and written as structs with
+/// `SerializerOptions::structs_as_objects`.
+///
+/// Structs with flattened fields, which are read like maps, and other types
+/// than structs generate no class, and an empty string is returned.
+/// Properties of implementations that reject the placeholder values used
+/// to trace them are declared `mixed`.
+///
+/// ```rust
+/// use php_serde::php_codegen::class_for;
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct User {
+///     id: u32,
+///     #[serde(rename = "displayName")]
+///     name: Option<String>,
+///     tags: Vec<String>,
+/// }
+///
+/// assert_eq!(
+///     class_for::<User>(),
+///     "class User
+/// {
+///     public int $id;
+///     public ?string $displayName;
+///     public array $tags;
+/// }
+/// "
+/// );
+/// ```
+pub fn class_for<T>() -> String
+where
+    T: DeserializeOwned,
+{
+    let mut tracer = Tracer {
+        classes: Vec::new(),
+        stack: Vec::new(),
+    };
+    let mut ty = None;
+    // A failure leaves the properties traced up to it.
+    let _ = T::deserialize(TypeTracer {
+        tracer: &mut tracer,
+        ty: &mut ty,
+        placeholder: false,
+    });
+
+    let mut output = String::new();
+    for (i, class) in tracer.classes.iter().enumerate() {
+        if i > 0 {
+            output.push('\n');
+        }
+        let _ = writeln!(output, "class {}\n{{", class.name);
+        for (name, ty) in &class.properties {
+            let ty = ty.as_deref().filter(|&ty| ty != "null").unwrap_or("mixed");
+            let _ = writeln!(output, "    public {ty} ${name};");
+        }
+        output.push_str("}\n");
+    }
+    output
+}
+
+/// A class traced from a struct.
+struct Class {
+    name: &'static str,
+    /// Properties with their types, if they could be traced.
+    properties: Vec<(&'static str, Option<String>)>,
+}
+
+/// Classes traced so far.
+struct Tracer {
+    classes: Vec<Class>,
+    /// Structs being traced, the innermost last.
+    stack: Vec<&'static str>,
+}
+
+/// Deserializer handing placeholder values to a `Deserialize`
+/// implementation, recording the PHP type of what it asks for.
+struct TypeTracer<'a> {
+    tracer: &'a mut Tracer,
+    ty: &'a mut Option<String>,
+    /// Whether to only produce a value, e.g. for a struct that has been
+    /// traced before, without tracing anything.
+    placeholder: bool,
+}
+
+impl<'a> TypeTracer<'a> {
+    fn record(&mut self, ty: &str) {
+        *self.ty = Some(ty.to_owned());
+    }
+
+    /// A deserializer for a nested placeholder value.
+    fn placeholder(tracer: &'a mut Tracer, ty: &'a mut Option<String>) -> Self {
+        TypeTracer {
+            tracer,
+            ty,
+            placeholder: true,
+        }
+    }
+}
+
+macro_rules! trace_scalar {
+    ($($method:ident => $ty:literal, $visit:ident($($value:expr)?);)*) => {
+        $(
+            fn $method<V>(mut self, visitor: V) -> Result<V::Value>
+            where
+                V: Visitor<'de>,
+            {
+                self.record($ty);
+                visitor.$visit($($value)?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for TypeTracer<'_> {
+    type Error = Error;
+
+    trace_scalar! {
+        deserialize_any => "mixed", visit_unit();
+        deserialize_bool => "bool", visit_bool(false);
+        deserialize_i8 => "int", visit_i8(0);
+        deserialize_i16 => "int", visit_i16(0);
+        deserialize_i32 => "int", visit_i32(0);
+        deserialize_i64 => "int", visit_i64(0);
+        deserialize_i128 => "int", visit_i128(0);
+        deserialize_u8 => "int", visit_u8(0);
+        deserialize_u16 => "int", visit_u16(0);
+        deserialize_u32 => "int", visit_u32(0);
+        deserialize_u64 => "int", visit_u64(0);
+        deserialize_u128 => "int", visit_u128(0);
+        deserialize_f32 => "float", visit_f32(0.0);
+        deserialize_f64 => "float", visit_f64(0.0);
+        deserialize_char => "string", visit_char(' ');
+        deserialize_str => "string", visit_str("");
+        deserialize_string => "string", visit_str("");
+        deserialize_bytes => "string", visit_bytes(b"");
+        deserialize_byte_buf => "string", visit_bytes(b"");
+        deserialize_unit => "null", visit_unit();
+        deserialize_identifier => "string", visit_str("");
+        deserialize_ignored_any => "mixed", visit_unit();
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.placeholder {
+            return visitor.visit_none();
+        }
+
+        let mut inner = None;
+        let value = visitor.visit_some(TypeTracer {
+            tracer: &mut *self.tracer,
+            ty: &mut inner,
+            placeholder: false,
+        });
+        *self.ty = Some(match inner.as_deref().unwrap_or("mixed") {
+            ty @ ("mixed" | "null") => ty.to_owned(),
+            ty if ty.contains('|') => format!("{ty}|null"),
+            ty => format!("?{ty}"),
+        });
+        value
+    }
+
+    fn deserialize_unit_struct<V>(mut self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.record("null");
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_tuple(0, visitor)
+    }
+
+    fn deserialize_tuple<V>(mut self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.record("array");
+        visitor.visit_seq(Placeholders {
+            tracer: self.tracer,
+            remaining: len,
+        })
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V>(mut self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.record("array");
+        visitor.visit_map(Fields {
+            tracer: self.tracer,
+            names: &[],
+            class: None,
+            index: 0,
+        })
+    }
+
+    fn deserialize_struct<V>(
+        mut self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.record(name);
+        // Structs are traced once, which also ends recursion.
+        let traced = self.tracer.classes.iter().any(|class| class.name == name);
+        let class = if self.placeholder || traced || self.tracer.stack.contains(&name) {
+            None
+        } else {
+            self.tracer.classes.push(Class {
+                name,
+                properties: Vec::new(),
+            });
+            self.tracer.stack.push(name);
+            Some(self.tracer.classes.len() - 1)
+        };
+
+        let value = visitor.visit_map(Fields {
+            tracer: &mut *self.tracer,
+            names: fields,
+            class,
+            index: 0,
+        });
+        if class.is_some() {
+            self.tracer.stack.pop();
+        }
+        value
+    }
+
+    fn deserialize_enum<V>(
+        mut self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.record("string|array");
+        let variant = variants
+            .first()
+            .ok_or_else(|| Error::DeserializationFailed("enum without variants".to_owned()))?;
+        visitor.visit_enum(Variant {
+            tracer: self.tracer,
+            variant,
+        })
+    }
+}
+
+/// Placeholder elements of a sequence or tuple.
+struct Placeholders<'a> {
+    tracer: &'a mut Tracer,
+    remaining: usize,
+}
+
+impl<'de> SeqAccess<'de> for Placeholders<'_> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(TypeTracer::placeholder(self.tracer, &mut None))
+            .map(Some)
+    }
+}
+
+/// Fields of a struct, recording their types into `class` if it is traced.
+struct Fields<'a> {
+    tracer: &'a mut Tracer,
+    names: &'static [&'static str],
+    class: Option<usize>,
+    index: usize,
+}
+
+impl<'de> MapAccess<'de> for Fields<'_> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.names.get(self.index) {
+            Some(&field) => seed.deserialize(field.into_deserializer()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let field = self.names[self.index];
+        self.index += 1;
+        let Some(class) = self.class else {
+            return seed.deserialize(TypeTracer::placeholder(self.tracer, &mut None));
+        };
+
+        let mut ty = None;
+        let value = seed.deserialize(TypeTracer {
+            tracer: &mut *self.tracer,
+            ty: &mut ty,
+            placeholder: false,
+        });
+        self.tracer.classes[class].properties.push((field, ty));
+        value
+    }
+}
+
+/// The first variant of an enum, with placeholder contents.
+struct Variant<'a> {
+    tracer: &'a mut Tracer,
+    variant: &'static str,
+}
+
+impl<'de> EnumAccess<'de> for Variant<'_> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self)>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(self.variant.into_deserializer())?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for Variant<'_> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        seed.deserialize(TypeTracer::placeholder(self.tracer, &mut None))
+    }
+
+    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_tuple(
+            TypeTracer::placeholder(self.tracer, &mut None),
+            len,
+            visitor,
+        )
+    }
+
+    fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_map(Fields {
+            tracer: self.tracer,
+            names: fields,
+            class: None,
+            index: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::class_for;
+    use crate::Value;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Deserialize)]
+    struct Address {
+        _city: String,
+    }
+
+    #[derive(Deserialize)]
+    enum Kind {
+        _Plain,
+        _Numbered(i64),
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename = "User")]
+    struct Account {
+        _id: u64,
+        _score: f64,
+        _pair: (i64, bool),
+        _labels: HashMap<String, i32>,
+        _address: Address,
+        _home: Option<Address>,
+        _kind: Kind,
+        _maybe_kind: Option<Kind>,
+        _extra: Value,
+        _parent: Option<Box<Account>>,
+        #[serde(skip)]
+        _skipped: bool,
+        #[serde(rename = "isAdmin")]
+        _admin: bool,
+    }
+
+    #[test]
+    fn classes() {
+        assert_eq!(
+            class_for::<Account>(),
+            "class User
+{
+    public int $_id;
+    public float $_score;
+    public array $_pair;
+    public array $_labels;
+    public Address $_address;
+    public ?Address $_home;
+    public string|array $_kind;
+    public string|array|null $_maybe_kind;
+    public mixed $_extra;
+    public ?User $_parent;
+    public bool $isAdmin;
+}
+
+class Address
+{
+    public string $_city;
+}
+"
+        );
+        assert_eq!(class_for::<Vec<Address>>(), "");
+
+        // Variants with values are arrays, the others strings.
+        let kind: Kind = crate::from_bytes(br#"a:1:{s:9:"_Numbered";i:5;}"#).unwrap();
+        assert!(matches!(kind, Kind::_Numbered(5)));
+        let kind: Kind = crate::from_bytes(br#"s:6:"_Plain";"#).unwrap();
+        assert!(matches!(kind, Kind::_Plain));
+    }
+}
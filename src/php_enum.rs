@@ -0,0 +1,180 @@
+//! PHP 8.1 enums (`E:` records).
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::ops::{Deref, DerefMut};
+
+/// Newtype name used by `PhpEnum` to request enum case serialization.
+pub const TOKEN: &str = "$php_serde::private::PhpEnum";
+
+/// A Rust enum serialized as a PHP 8.1 enum case.
+///
+/// PHP writes enum cases as `E:<len>:"<class>:<case>";`. Unit variants of
+/// Rust enums deserialize from these directly, with the variant chosen by the
+/// case name. The class name is not checked.
+///
+/// When serializing, unit variants are written as strings holding the
+/// variant name, and only as PHP enum cases when wrapped in `PhpEnum`. The
+/// name of the Rust enum is used as class name, so it
+/// usually needs to be renamed to the fully qualified PHP class name.
+///
+/// ```rust
+/// use php_serde::{from_bytes, to_vec, PhpEnum};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Debug, Deserialize, PartialEq, Serialize)]
+/// #[serde(rename = "App\\Suit")]
+/// enum Suit {
+///     Hearts,
+///     Spades,
+/// }
+///
+/// let serialized = to_vec(&PhpEnum(Suit::Hearts)).unwrap();
+/// assert_eq!(serialized, &br#"E:15:"App\Suit:Hearts";"#[..]);
+///
+/// let suit: Suit = from_bytes(&serialized).unwrap();
+/// assert_eq!(suit, Suit::Hearts);
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct PhpEnum<T>(pub T);
+
+impl<T> PhpEnum<T> {
+    /// Unwrap the inner value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for PhpEnum<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for PhpEnum<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T> From<T> for PhpEnum<T> {
+    fn from(inner: T) -> Self {
+        PhpEnum(inner)
+    }
+}
+
+impl<T: Serialize> Serialize for PhpEnum<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(TOKEN, &self.0)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for PhpEnum<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        T::deserialize(deserializer).map(PhpEnum)
+    }
+}
+
+/// Split the body of an `E:` record into class and case name.
+pub fn split_case(record: &[u8]) -> Option<(&[u8], &[u8])> {
+    let colon = record.iter().position(|&b| b == b':')?;
+    Some((&record[..colon], &record[colon + 1..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PhpEnum;
+    use crate::{from_bytes, to_vec, Value};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+    enum Status {
+        Active,
+        #[serde(rename = "INACTIVE")]
+        Inactive,
+    }
+
+    #[test]
+    fn roundtrip() {
+        let statuses = vec![PhpEnum(Status::Active), PhpEnum(Status::Inactive)];
+        let serialized = to_vec(&statuses).unwrap();
+        assert_eq!(
+            serialized,
+            &br#"a:2:{i:0;E:13:"Status:Active";i:1;E:15:"Status:INACTIVE";}"#[..]
+        );
+        assert_eq!(
+            from_bytes::<Vec<PhpEnum<Status>>>(&serialized).unwrap(),
+            statuses
+        );
+    }
+
+    #[test]
+    fn unit_variants_as_strings() {
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct User {
+            status: Status,
+        }
+
+        let users = vec![
+            User {
+                status: Status::Active,
+            },
+            User {
+                status: Status::Inactive,
+            },
+        ];
+        let serialized = to_vec(&users).unwrap();
+        assert_eq!(
+            serialized,
+            &br#"a:2:{i:0;a:1:{s:6:"status";s:6:"Active";}i:1;a:1:{s:6:"status";s:8:"INACTIVE";}}"#
+                [..]
+        );
+        assert_eq!(from_bytes::<Vec<User>>(&serialized).unwrap(), users);
+
+        let value: Value = from_bytes(&serialized).unwrap();
+        assert_eq!(crate::from_value::<Vec<User>>(value).unwrap(), users);
+
+        assert!(from_bytes::<Status>(br#"s:7:"Pending";"#).is_err());
+        assert!(from_bytes::<Status>(b"s:2:\"\xff\xfe\";").is_err());
+    }
+
+    #[test]
+    fn errors() {
+        assert!(to_vec(&PhpEnum(1)).is_err());
+        assert!(from_bytes::<Status>(br#"E:6:"Status";"#).is_err());
+        assert!(from_bytes::<Status>(br#"E:14:"Status:Pending";"#).is_err());
+    }
+
+    #[test]
+    fn in_value() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Data {
+            status: Status,
+        }
+
+        let input = br#"a:1:{s:6:"status";E:13:"Status:Active";}"#;
+        let value: Value = from_bytes(input).unwrap();
+        assert_eq!(
+            value.get("status"),
+            Some(&Value::Enum {
+                class: "Status".to_owned(),
+                case: "Active".to_owned(),
+            })
+        );
+        assert_eq!(to_vec(&value).unwrap(), &input[..]);
+
+        assert_eq!(
+            crate::from_value::<Data>(value).unwrap(),
+            Data {
+                status: Status::Active
+            }
+        );
+    }
+}
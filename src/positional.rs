@@ -0,0 +1,158 @@
+//! Structs stored as numeric arrays.
+
+use serde::de::Visitor;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+/// Newtype name used by `Positional` to request positional fields.
+pub const TOKEN: &str = "$php_serde::private::Positional";
+
+/// A struct stored as a numeric array, with fields in declaration order.
+///
+/// Some PHP code stores records as plain lists, e.g.
+/// `array($id, $name)`, rather than with named keys. Wrapping a struct in
+/// `Positional` reads its fields from such an array by position, and writes
+/// them back the same way. The array must have exactly one element per
+/// field.
+///
+/// Only the wrapped struct itself is positional. Nested structs keep using
+/// named keys, unless they are wrapped as well.
+///
+/// ```rust
+/// use php_serde::{from_bytes, to_vec, Positional};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Debug, Deserialize, PartialEq, Serialize)]
+/// struct User {
+///     id: i64,
+///     name: String,
+/// }
+///
+/// let input = br#"a:2:{i:0;i:7;i:1;s:3:"bob";}"#;
+/// let user: Positional<User> = from_bytes(input).unwrap();
+/// assert_eq!(user.name, "bob");
+/// assert_eq!(to_vec(&user).unwrap(), &input[..]);
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Positional<T>(pub T);
+
+impl<T> Positional<T> {
+    /// Unwrap the inner value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Positional<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Positional<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T> From<T> for Positional<T> {
+    fn from(inner: T) -> Self {
+        Positional(inner)
+    }
+}
+
+impl<T: Serialize> Serialize for Positional<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(TOKEN, &self.0)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Positional<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct PositionalVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>> Visitor<'de> for PositionalVisitor<T> {
+            type Value = Positional<T>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("a struct stored as a numeric array")
+            }
+
+            fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Positional<T>, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                T::deserialize(deserializer).map(Positional)
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(TOKEN, PositionalVisitor(PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Positional;
+    use crate::{from_bytes, to_vec, StdClass};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Point {
+        x: i64,
+        y: i64,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Shape {
+        name: String,
+        origin: Point,
+        points: Vec<Positional<Point>>,
+    }
+
+    #[test]
+    fn roundtrip() {
+        let shape = Positional(Shape {
+            name: "line".to_owned(),
+            origin: Point { x: 0, y: 0 },
+            points: vec![Positional(Point { x: 1, y: 2 })],
+        });
+
+        let serialized = to_vec(&shape).unwrap();
+        assert_eq!(
+            serialized,
+            &br#"a:3:{i:0;s:4:"line";i:1;a:2:{s:1:"x";i:0;s:1:"y";i:0;}i:2;a:1:{i:0;a:2:{i:0;i:1;i:1;i:2;}}}"#[..]
+        );
+        assert_eq!(from_bytes::<Positional<Shape>>(&serialized).unwrap(), shape);
+    }
+
+    #[test]
+    fn length_mismatch() {
+        assert!(from_bytes::<Positional<Point>>(b"a:1:{i:0;i:1;}").is_err());
+        assert!(from_bytes::<Positional<Point>>(b"a:3:{i:0;i:1;i:1;i:2;i:2;i:3;}").is_err());
+        assert!(from_bytes::<Positional<Point>>(b"a:2:{i:1;i:1;i:0;i:2;}").is_err());
+    }
+
+    #[test]
+    fn not_a_struct() {
+        assert!(to_vec(&Positional(5)).is_err());
+        assert!(from_bytes::<Positional<i64>>(b"i:5;").is_err());
+    }
+
+    #[test]
+    fn std_class() {
+        assert_eq!(
+            to_vec(&StdClass(Positional(Point { x: 1, y: 2 }))).unwrap(),
+            &br#"O:8:"stdClass":2:{i:0;i:1;i:1;i:2;}"#[..]
+        );
+    }
+}
@@ -0,0 +1,243 @@
+//! Pretty-printing serialized data for humans.
+
+use crate::error::Result;
+use crate::options::FloatPrecision;
+use crate::property::Visibility;
+use crate::ser::write_float;
+use crate::token::{Key, Lexer, Token};
+use std::fmt::Write;
+
+/// Render serialized `input` as an indented tree, one value per line, for
+/// logs and debugging.
+///
+/// The layout follows PHP's `var_dump`: every value is annotated with its
+/// type, strings, arrays and objects with their length, and entries are
+/// written as `[key] => value`, with the visibility of private and
+/// protected properties after their name. Strings are quoted, with quotes,
+/// backslashes, control characters and bytes that are not valid UTF-8
+/// escaped, so that each value stays on its line.
+///
+/// Like `from_bytes`, input after the value is not looked at.
+///
+/// ```rust
+/// use php_serde::pretty;
+///
+/// let input = b"a:2:{i:0;s:3:\"a\"b\";s:4:\"user\";\
+///     O:4:\"User\":2:{s:2:\"id\";i:7;s:7:\"\0*\0tags\";a:0:{}}}";
+/// assert_eq!(
+///     pretty::format(input).unwrap(),
+///     r#"array(2) {
+///   [0] => string(3) "a\"b"
+///   ["user"] => object(User) (2) {
+///     ["id"] => int(7)
+///     ["tags":protected] => array(0) {}
+///   }
+/// }
+/// "#
+/// );
+/// ```
+pub fn format(input: &[u8]) -> Result<String> {
+    let mut output = String::new();
+    let mut levels: Vec<Level> = Vec::new();
+    for token in Lexer::new(input) {
+        let (token, _) = token?;
+
+        match token {
+            Token::Null => output.push_str("null\n"),
+            Token::Bool(b) => {
+                let _ = writeln!(output, "bool({b})");
+            }
+            Token::Int(n) => {
+                let _ = writeln!(output, "int({n})");
+            }
+            Token::Float(n) => {
+                let mut float = Vec::new();
+                let _ = write_float(&mut float, n, FloatPrecision::Shortest);
+                let _ = writeln!(output, "float({})", String::from_utf8_lossy(&float));
+            }
+            Token::Str(s) => {
+                let _ = write!(output, "string({}) ", s.len());
+                write_quoted(&mut output, s);
+                output.push('\n');
+            }
+            Token::ArrayStart(len) => {
+                let _ = write!(output, "array({len}) {{");
+                levels.push(Level {
+                    object: false,
+                    empty: true,
+                });
+            }
+            Token::ObjectStart { class, len } => {
+                let _ = write!(
+                    output,
+                    "object({}) ({len}) {{",
+                    String::from_utf8_lossy(class)
+                );
+                levels.push(Level {
+                    object: true,
+                    empty: true,
+                });
+            }
+            Token::ArrayEnd | Token::ObjectEnd => {
+                if levels.pop().is_some_and(|level| !level.empty) {
+                    indent(&mut output, levels.len());
+                }
+                output.push_str("}\n");
+            }
+            Token::Key(key) => {
+                if let Some(level) = levels.last_mut() {
+                    if level.empty {
+                        output.push('\n');
+                    }
+                    level.empty = false;
+                }
+                let object = levels.last().is_some_and(|level| level.object);
+                indent(&mut output, levels.len());
+                write_key(&mut output, key, object);
+                output.push_str(" => ");
+            }
+            Token::Custom { class, payload } => {
+                let _ = write!(
+                    output,
+                    "custom({}) ({}) ",
+                    String::from_utf8_lossy(class),
+                    payload.len()
+                );
+                write_quoted(&mut output, payload);
+                output.push('\n');
+            }
+            Token::Enum { class, case } => {
+                let _ = writeln!(
+                    output,
+                    "enum({}::{})",
+                    String::from_utf8_lossy(class),
+                    String::from_utf8_lossy(case)
+                );
+            }
+            Token::Reference(n) => {
+                let _ = writeln!(output, "reference({n})");
+            }
+            Token::MutableReference(n) => {
+                let _ = writeln!(output, "&reference({n})");
+            }
+        }
+    }
+    Ok(output)
+}
+
+/// An array or object being formatted.
+struct Level {
+    object: bool,
+    /// Whether no entries have been written yet.
+    empty: bool,
+}
+
+fn indent(output: &mut String, level: usize) {
+    output.push_str(&"  ".repeat(level));
+}
+
+/// Write a key as `[0]` or `["name"]`, with the visibility of properties.
+fn write_key(output: &mut String, key: Key<'_>, object: bool) {
+    let name = match key {
+        Key::Int(n) => {
+            let _ = write!(output, "[{n}]");
+            return;
+        }
+        Key::Bytes(name) => name,
+    };
+    let (visibility, plain) = if object {
+        Visibility::demangle(name)
+    } else {
+        (Visibility::Public, name)
+    };
+
+    output.push('[');
+    write_quoted(output, plain);
+    match visibility {
+        Visibility::Public => {}
+        Visibility::Protected => output.push_str(":protected"),
+        Visibility::Private(class) => {
+            output.push(':');
+            write_quoted(output, class.as_bytes());
+            output.push_str(":private");
+        }
+    }
+    output.push(']');
+}
+
+/// Write bytes in double quotes, escaping what would not show as it is.
+fn write_quoted(output: &mut String, mut s: &[u8]) {
+    output.push('"');
+    while !s.is_empty() {
+        let (valid, invalid) = match std::str::from_utf8(s) {
+            Ok(valid) => (valid, &[][..]),
+            Err(e) => {
+                let (valid, rest) = s.split_at(e.valid_up_to());
+                let len = e.error_len().unwrap_or(rest.len());
+                // The prefix was checked to be valid.
+                let valid = std::str::from_utf8(valid).unwrap_or_default();
+                (valid, &rest[..len])
+            }
+        };
+        for c in valid.chars() {
+            match c {
+                '"' | '\\' => {
+                    output.push('\\');
+                    output.push(c);
+                }
+                '\n' => output.push_str("\\n"),
+                '\r' => output.push_str("\\r"),
+                '\t' => output.push_str("\\t"),
+                c if c.is_control() => {
+                    let _ = write!(output, "\\x{:02x}", u32::from(c));
+                }
+                c => output.push(c),
+            }
+        }
+        for b in invalid {
+            let _ = write!(output, "\\x{b:02x}");
+        }
+        s = &s[valid.len() + invalid.len()..];
+    }
+    output.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format;
+    use crate::ErrorKind;
+
+    #[test]
+    fn tree() {
+        let input = b"a:2:{i:0;s:1:\"\xff\";i:1;a:4:{s:1:\"a\";a:1:{i:0;d:0.1;}s:1:\"b\";\
+            O:3:\"Foo\":3:{s:8:\"\0Foo\0pid\";b:1;s:1:\"c\";C:3:\"Bar\":2:{x\0}\
+            s:1:\"e\";E:7:\"Suit:Up\";}s:4:\"\xc3\xa9\0\n\";R:2;i:-1;N;}}";
+        assert_eq!(
+            format(input).unwrap(),
+            "array(2) {
+  [0] => string(1) \"\\xff\"
+  [1] => array(4) {
+    [\"a\"] => array(1) {
+      [0] => float(0.1)
+    }
+    [\"b\"] => object(Foo) (3) {
+      [\"pid\":\"Foo\":private] => bool(true)
+      [\"c\"] => custom(Bar) (2) \"x\\x00\"
+      [\"e\"] => enum(Suit::Up)
+    }
+    [\"\u{e9}\\x00\\n\"] => &reference(2)
+    [-1] => null
+  }
+}
+"
+        );
+        assert_eq!(format(b"i:5;trailing").unwrap(), "int(5)\n");
+    }
+
+    #[test]
+    fn malformed() {
+        let err = format(b"a:1:{i:0;x:1;}").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedToken);
+        assert!(format(b"a:2:{i:0;N;").is_err());
+    }
+}
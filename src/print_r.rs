@@ -0,0 +1,298 @@
+//! Reading the output of PHP's `print_r`, on a best-effort basis.
+
+use crate::error::{Error, Result};
+use crate::key::PhpKey;
+use crate::options::DeserializerOptions;
+use crate::property::Visibility;
+use crate::value::Value;
+
+/// Read the output of PHP's `print_r` into a `Value`, as far as possible.
+///
+/// `print_r` is meant for humans and loses information, so this is lossy:
+///
+/// * All scalars are read as strings, since `print_r` writes `1` for both
+///   `1` and `true`, and nothing for `false`, `null` and `""` alike. Use
+///   `Value::as_i64_loose` and its siblings to read numbers and booleans.
+/// * A string spanning several lines ends before the next line that looks
+///   like an entry `[key] => ` or the `)` closing an array, so strings
+///   containing such lines are cut short.
+/// * Objects keep their class and the visibility of their properties, and
+///   enum cases their class and case name. Backed enums lose their value,
+///   and `*RECURSION*` markers are read as strings.
+///
+/// Indentation is not relied on, so dumps that were re-indented or trimmed
+/// in a log are read as well.
+///
+/// ```rust
+/// use php_serde::{parse_print_r, Value};
+///
+/// let dump = "Array
+/// (
+///     [id] => 7
+///     [tags] => Array
+///         (
+///             [0] => a
+///         )
+///
+/// )
+/// ";
+/// let value = parse_print_r(dump).unwrap();
+/// assert_eq!(value.get("id").map(Value::as_i64_loose), Some(7));
+/// assert_eq!(value.get("tags").and_then(|tags| tags.get(0)), Some(&Value::from("a")));
+/// ```
+pub fn parse_print_r(input: &str) -> Result<Value> {
+    let mut parser = Parser { input, offset: 0 };
+    parser.skip_whitespace();
+    if parser.header().is_none() {
+        // A scalar at the top level is written as it is.
+        return Ok(Value::from(input));
+    }
+
+    let value = parser
+        .value(DeserializerOptions::default().max_depth)
+        .map_err(|e| e.at(parser.offset))?;
+    parser.skip_whitespace();
+    if parser.offset < input.len() {
+        return Err(malformed("expected the end of the dump").at(parser.offset));
+    }
+    Ok(value)
+}
+
+/// Kind of a value that `print_r` writes with its entries in parentheses.
+enum Header<'a> {
+    Array,
+    Object(&'a str),
+    Enum(&'a str),
+}
+
+/// Line-based parser of `print_r` output.
+struct Parser<'a> {
+    input: &'a str,
+    offset: usize,
+}
+
+impl<'a> Parser<'a> {
+    /// Read a value starting at the current position, after a `=> `.
+    fn value(&mut self, depth: usize) -> Result<Value> {
+        let Some((header, open)) = self.header() else {
+            return Ok(Value::from(self.string()));
+        };
+        self.offset = open + 1;
+        self.skip_line();
+
+        let depth = depth.checked_sub(1).ok_or(Error::DepthLimitExceeded)?;
+        let entries = self.entries(depth, !matches!(header, Header::Array))?;
+        Ok(match header {
+            Header::Array => Value::Array(entries),
+            Header::Object(class) => Value::Object {
+                class: class.to_owned(),
+                properties: entries,
+            },
+            Header::Enum(class) => {
+                let case = entries
+                    .into_iter()
+                    .find(|(key, _)| key.as_bytes() == Some(b"name"))
+                    .map(|(_, case)| case.as_string_lossy().into_owned())
+                    .ok_or_else(|| malformed("expected the name of an enum case"))?;
+                Value::Enum {
+                    class: class.to_owned(),
+                    case,
+                }
+            }
+        })
+    }
+
+    /// Recognize `Array`, `Foo Object` or `Foo Enum` at the end of the
+    /// current line, followed by a line opening the entries, and return
+    /// the position of the `(`.
+    fn header(&self) -> Option<(Header<'a>, usize)> {
+        let (line, end) = self.line();
+        let line = line.trim();
+        let header = if line == "Array" {
+            Header::Array
+        } else if let Some(class) = line.strip_suffix(" Object") {
+            Header::Object(class)
+        } else if let Some((class, kind)) = line.split_once(" Enum") {
+            if !(kind.is_empty() || kind.starts_with(':')) {
+                return None;
+            }
+            Header::Enum(class)
+        } else {
+            return None;
+        };
+
+        let rest = &self.input[end..];
+        let open = rest.len() - rest.trim_start().len();
+        rest.trim_start()
+            .starts_with('(')
+            .then_some((header, end + open))
+    }
+
+    /// Read entries up to the closing `)`.
+    fn entries(&mut self, depth: usize, object: bool) -> Result<Vec<(PhpKey, Value)>> {
+        let mut entries = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let (line, _) = self.line();
+            if line.trim_end() == ")" {
+                self.skip_line();
+                return Ok(entries);
+            }
+            if self.offset == self.input.len() {
+                return Err(Error::UnexpectedEof);
+            }
+            let (name, value_start) = entry(line).ok_or_else(|| malformed("expected an entry"))?;
+
+            let key = if object {
+                PhpKey::from_bytes(property(name))
+            } else {
+                PhpKey::from(name)
+            };
+            self.offset += value_start;
+            entries.push((key, self.value(depth)?));
+        }
+    }
+
+    /// Read a string up to the next line starting an entry or ending an
+    /// array.
+    fn string(&mut self) -> &'a str {
+        let start = self.offset;
+        let (_, mut end) = self.line();
+        while end < self.input.len() {
+            let next = &self.input[end + 1..];
+            let line = next.split('\n').next().unwrap_or_default();
+            if line.trim() == ")" || entry(line).is_some() {
+                break;
+            }
+            end += 1 + line.len();
+        }
+        self.offset = end;
+        self.input[start..end].trim_end_matches('\r')
+    }
+
+    /// The rest of the current line, and the position of its end.
+    fn line(&self) -> (&'a str, usize) {
+        let rest = &self.input[self.offset..];
+        let len = rest.find('\n').unwrap_or(rest.len());
+        (&rest[..len], self.offset + len)
+    }
+
+    fn skip_line(&mut self) {
+        let (_, end) = self.line();
+        self.offset = (end + 1).min(self.input.len());
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.input[self.offset..];
+        self.offset += rest.len() - rest.trim_start().len();
+    }
+}
+
+/// Split a line like `    [key] => value` into the key and the position
+/// of the value.
+fn entry(line: &str) -> Option<(&str, usize)> {
+    let indent = line.len() - line.trim_start().len();
+    let rest = line.trim_start().strip_prefix('[')?;
+    let end = rest.find("] =>")?;
+    let value = indent + 1 + end + "] =>".len();
+    // A space separates the value, even an empty one.
+    let value = value + usize::from(line[value..].starts_with(' '));
+    Some((&rest[..end], value))
+}
+
+/// Mangle a property name written like `name:protected` or
+/// `name:Class:private`.
+fn property(name: &str) -> Vec<u8> {
+    if let Some(name) = name.strip_suffix(":protected") {
+        return Visibility::Protected.mangle(name.as_bytes());
+    }
+    if let Some((name, class)) = name
+        .strip_suffix(":private")
+        .and_then(|name| name.split_once(':'))
+    {
+        return Visibility::Private(class.to_owned()).mangle(name.as_bytes());
+    }
+    name.as_bytes().to_vec()
+}
+
+fn malformed(reason: &str) -> Error {
+    Error::DeserializationFailed(format!("invalid print_r output: {reason}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_print_r;
+    use crate::{from_bytes, ErrorKind, Value};
+
+    #[test]
+    fn dumps() {
+        let dump = "Array
+(
+    [0] => 1
+    [name] => two
+lines
+    [empty] =>
+    [list] => Array
+        (
+            [0] => Array
+                (
+                )
+
+        )
+
+    [point] => App\\Point Object
+        (
+            [x] => 1.5
+            [y:protected] =>
+            [z:App\\Point:private] => [not a key]
+        )
+
+    [suit] => Suit Enum:string
+        (
+            [name] => Hearts
+            [value] => H
+        )
+
+)
+";
+        let expected: Value = from_bytes(
+            b"a:5:{i:0;s:1:\"1\";s:4:\"name\";s:9:\"two\nlines\";s:5:\"empty\";s:0:\"\";\
+              s:4:\"list\";a:1:{i:0;a:0:{}}s:5:\"point\";O:9:\"App\\Point\":3:{\
+              s:1:\"x\";s:3:\"1.5\";s:4:\"\0*\0y\";s:0:\"\";s:12:\"\0App\\Point\0z\";\
+              s:11:\"[not a key]\";}}",
+        )
+        .unwrap();
+        let mut value = parse_print_r(dump).unwrap();
+        let suit = value.as_array_mut().unwrap().pop().unwrap();
+        assert_eq!(value, expected);
+        assert_eq!(
+            suit.1,
+            Value::Enum {
+                class: "Suit".to_owned(),
+                case: "Hearts".to_owned()
+            }
+        );
+
+        // Trimmed lines and CRLF line breaks.
+        let dump = "Array\r\n(\r\n[a] => x\r\n[b] => Array\r\n(\r\n)\r\n\r\n)\r\n";
+        let value = parse_print_r(dump).unwrap();
+        assert_eq!(value.get("a"), Some(&Value::from("x")));
+        assert_eq!(value.get("b"), Some(&Value::Array(Vec::new())));
+
+        assert_eq!(
+            parse_print_r("plain\ntext").unwrap(),
+            Value::from("plain\ntext")
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        let err = parse_print_r("Array\n(\n    [a] => 1\n").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Eof);
+        let err = parse_print_r("Array\n(\n    a => 1\n)\n").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Custom);
+        assert_eq!(err.position(), Some(12));
+        assert!(parse_print_r("Array\n(\n)\ntrailing").is_err());
+        assert!(parse_print_r("Suit Enum\n(\n)\n").is_err());
+    }
+}
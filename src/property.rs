@@ -0,0 +1,134 @@
+//! Mangled names of private and protected object properties.
+
+use crate::key::PhpKey;
+
+/// Visibility of an object property.
+///
+/// PHP encodes the visibility of a property in its serialized name:
+/// protected properties are prefixed with `\0*\0`, private ones with the
+/// name of their declaring class between two NUL bytes.
+///
+/// When deserializing objects into Rust types, these prefixes are stripped,
+/// so a struct field `prop` matches all of `public $prop`, `protected $prop`
+/// and `private $prop`. Deserializing into a `Value` retains the serialized
+/// names; use `Visibility::demangle` to recover the visibility.
+///
+/// ```rust
+/// use php_serde::Visibility;
+///
+/// assert_eq!(
+///     Visibility::demangle(b"\0App\\User\0email"),
+///     (Visibility::Private("App\\User".to_owned()), &b"email"[..])
+/// );
+/// assert_eq!(
+///     Visibility::demangle(b"\0*\0id"),
+///     (Visibility::Protected, &b"id"[..])
+/// );
+/// assert_eq!(Visibility::demangle(b"name"), (Visibility::Public, &b"name"[..]));
+/// ```
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Visibility {
+    /// `public`, the name is not mangled.
+    Public,
+    /// `protected`, the name is prefixed with `\0*\0`.
+    Protected,
+    /// `private`, the name is prefixed with the declaring class.
+    Private(String),
+}
+
+impl Visibility {
+    /// Split a serialized property name into its visibility and plain name.
+    ///
+    /// Names that are not validly mangled are treated as public.
+    pub fn demangle(name: &[u8]) -> (Visibility, &[u8]) {
+        let Some((0, mangled)) = name.split_first() else {
+            return (Visibility::Public, name);
+        };
+
+        let Some(end) = mangled.iter().position(|&b| b == 0) else {
+            return (Visibility::Public, name);
+        };
+
+        let (class, plain) = (&mangled[..end], &mangled[end + 1..]);
+        let visibility = if class == b"*" {
+            Visibility::Protected
+        } else {
+            Visibility::Private(String::from_utf8_lossy(class).into_owned())
+        };
+
+        (visibility, plain)
+    }
+
+    /// Build the serialized name of a property with this visibility.
+    ///
+    /// ```rust
+    /// use php_serde::Visibility;
+    ///
+    /// assert_eq!(Visibility::Protected.mangle(b"id"), b"\0*\0id");
+    /// ```
+    pub fn mangle(&self, name: &[u8]) -> Vec<u8> {
+        let class: &[u8] = match self {
+            Visibility::Public => return name.to_vec(),
+            Visibility::Protected => b"*",
+            Visibility::Private(class) => class.as_bytes(),
+        };
+
+        let mut mangled = Vec::with_capacity(class.len() + name.len() + 2);
+        mangled.push(0);
+        mangled.extend_from_slice(class);
+        mangled.push(0);
+        mangled.extend_from_slice(name);
+        mangled
+    }
+}
+
+/// Strip the visibility prefix from a property key.
+pub fn demangle_key(key: PhpKey) -> PhpKey {
+    match key {
+        PhpKey::String(name) if name.first() == Some(&0) => {
+            let (_, plain) = Visibility::demangle(&name);
+            PhpKey::String(plain.to_vec())
+        }
+        key => key,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{demangle_key, Visibility};
+    use crate::key::PhpKey;
+
+    #[test]
+    fn roundtrip() {
+        for visibility in &[
+            Visibility::Public,
+            Visibility::Protected,
+            Visibility::Private("Foo".to_owned()),
+        ] {
+            let mangled = visibility.mangle(b"bar");
+            assert_eq!(
+                Visibility::demangle(&mangled),
+                (visibility.clone(), &b"bar"[..])
+            );
+        }
+    }
+
+    #[test]
+    fn malformed() {
+        assert_eq!(
+            Visibility::demangle(b"\0oops"),
+            (Visibility::Public, &b"\0oops"[..])
+        );
+        assert_eq!(Visibility::demangle(b""), (Visibility::Public, &b""[..]));
+    }
+
+    #[test]
+    fn keys() {
+        assert_eq!(demangle_key(PhpKey::from("\0*\0a")), PhpKey::from("a"));
+        assert_eq!(
+            demangle_key(PhpKey::from("\0A\0b")),
+            PhpKey::String(b"b".to_vec())
+        );
+        assert_eq!(demangle_key(PhpKey::Int(3)), PhpKey::Int(3));
+    }
+}
@@ -0,0 +1,81 @@
+//! Numbering of written values, to refer back to earlier ones.
+
+use std::collections::HashMap;
+
+/// Values written so far, to refer back to earlier ones.
+///
+/// PHP numbers the values it serializes, all except array keys, starting
+/// with `1` for the value itself. `r:N;` refers back to value `N`.
+#[derive(Debug)]
+pub struct References {
+    /// Number of values written, including the one being written.
+    values: usize,
+    /// Values that can be referred back to.
+    ///
+    /// `None` in parts that are buffered, whose values may still be
+    /// reordered or dropped.
+    seen: Option<Seen>,
+}
+
+/// First occurrences of values that can be referred back to.
+#[derive(Debug, Default)]
+struct Seen {
+    /// Shared values by address, see `shared`.
+    #[cfg(feature = "rc")]
+    shared: HashMap<usize, usize>,
+    /// Strings, see `SerializerOptions::deduplicate_strings`.
+    strings: HashMap<Vec<u8>, usize>,
+}
+
+impl References {
+    /// Start with the value at the top.
+    pub fn new() -> Self {
+        References {
+            values: 1,
+            seen: Some(Seen::default()),
+        }
+    }
+
+    /// Start a buffered part, which only counts its values.
+    pub fn buffered() -> Self {
+        References {
+            values: 0,
+            seen: None,
+        }
+    }
+
+    /// Count the values of a buffered part, once it is written.
+    pub fn add(&mut self, buffered: &References) {
+        self.values += buffered.values;
+    }
+
+    /// Count a value about to be written.
+    pub fn next_value(&mut self) {
+        self.values += 1;
+    }
+
+    /// Number of the first occurrence of the shared value at `address`, if
+    /// it was written before. Otherwise, the value being written is
+    /// remembered as its first occurrence.
+    #[cfg(feature = "rc")]
+    pub fn find_shared(&mut self, address: usize) -> Option<usize> {
+        let values = self.values;
+        let seen = self.seen.as_mut()?;
+        if let Some(&number) = seen.shared.get(&address) {
+            return Some(number);
+        }
+        seen.shared.insert(address, values);
+        None
+    }
+
+    /// Number of the first occurrence of string `s`, like `find_shared`.
+    pub fn find_string(&mut self, s: &[u8]) -> Option<usize> {
+        let values = self.values;
+        let seen = self.seen.as_mut()?;
+        if let Some(&number) = seen.strings.get(s) {
+            return Some(number);
+        }
+        seen.strings.insert(s.to_vec(), values);
+        None
+    }
+}
@@ -0,0 +1,304 @@
+//! Choosing the Rust type of a PHP object by its class name at runtime.
+
+use crate::error::{Error, Result};
+use crate::value::{from_value, Value};
+use serde::de::{self, DeserializeOwned, DeserializeSeed};
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Constructor turning the properties of an object into a `T`.
+type Constructor<T> = Box<dyn Fn(Value) -> Result<T> + Send + Sync>;
+
+/// Registry of PHP classes and the Rust types they deserialize into.
+///
+/// Payloads may contain objects of several different classes in the same
+/// place, e.g. a list of events. A `ClassRegistry<T>` maps each class name to
+/// a constructor producing a common type `T`, typically an enum or a
+/// `Box<dyn Trait>`.
+///
+/// Like in PHP, class names are matched case-insensitively and a leading
+/// backslash is ignored.
+///
+/// ```rust
+/// use php_serde::ClassRegistry;
+/// use serde::Deserialize;
+///
+/// #[derive(Debug, Deserialize, PartialEq)]
+/// struct UserCreated {
+///     name: String,
+/// }
+///
+/// #[derive(Debug, Deserialize, PartialEq)]
+/// struct UserDeleted {
+///     id: i64,
+/// }
+///
+/// #[derive(Debug, PartialEq)]
+/// enum Event {
+///     Created(UserCreated),
+///     Deleted(UserDeleted),
+/// }
+///
+/// let mut registry = ClassRegistry::new();
+/// registry
+///     .register_with("App\\Events\\UserCreated", |value| {
+///         php_serde::from_value(value).map(Event::Created)
+///     })
+///     .register_with("App\\Events\\UserDeleted", |value| {
+///         php_serde::from_value(value).map(Event::Deleted)
+///     });
+///
+/// let input = br#"O:22:"App\Events\UserDeleted":1:{s:2:"id";i:7;}"#;
+/// assert_eq!(
+///     registry.from_bytes(input).unwrap(),
+///     Event::Deleted(UserDeleted { id: 7 })
+/// );
+/// ```
+///
+/// The registry can also be used as a `DeserializeSeed`, e.g. to deserialize
+/// a list of objects of mixed classes.
+pub struct ClassRegistry<T> {
+    constructors: HashMap<String, Constructor<T>>,
+}
+
+/// Normalize a class name for lookup.
+fn normalize(class: &str) -> String {
+    class.trim_start_matches('\\').to_ascii_lowercase()
+}
+
+impl<T> ClassRegistry<T> {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        ClassRegistry {
+            constructors: HashMap::new(),
+        }
+    }
+
+    /// Register type `U` for objects of class `class`.
+    ///
+    /// The object is deserialized into a `U`, which is then converted into a
+    /// `T`. Registering the same class twice replaces the previous entry.
+    pub fn register<U>(&mut self, class: &str) -> &mut Self
+    where
+        U: DeserializeOwned + Into<T>,
+    {
+        self.register_with(class, |value| from_value::<U>(value).map(Into::into))
+    }
+
+    /// Register a custom constructor for objects of class `class`.
+    ///
+    /// The constructor receives the whole object as a `Value::Object`, which
+    /// can be passed on to `from_value`. This is required where no `Into<T>`
+    /// conversion exists, e.g. for trait objects.
+    pub fn register_with<F>(&mut self, class: &str, constructor: F) -> &mut Self
+    where
+        F: Fn(Value) -> Result<T> + Send + Sync + 'static,
+    {
+        self.constructors
+            .insert(normalize(class), Box::new(constructor));
+        self
+    }
+
+    /// Returns `true` if a type is registered for `class`.
+    pub fn contains(&self, class: &str) -> bool {
+        self.constructors.contains_key(&normalize(class))
+    }
+
+    /// Construct a `T` from an object, based on its class.
+    pub fn from_value(&self, value: Value) -> Result<T> {
+        let class = match &value {
+            Value::Object { class, .. } => normalize(class),
+            _ => return Err(Error::ExpectedObject),
+        };
+
+        match self.constructors.get(&class) {
+            Some(constructor) => constructor(value),
+            None => Err(Error::UnknownClass(
+                value.class().unwrap_or_default().to_owned(),
+            )),
+        }
+    }
+
+    /// Deserialize a serialized object into a `T`, based on its class.
+    pub fn from_bytes(&self, s: &[u8]) -> Result<T> {
+        self.from_value(crate::from_bytes(s)?)
+    }
+}
+
+impl<T> Default for ClassRegistry<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> fmt::Debug for ClassRegistry<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.constructors.keys()).finish()
+    }
+}
+
+impl<'de, T> DeserializeSeed<'de> for &ClassRegistry<T> {
+    type Value = T;
+
+    fn deserialize<D>(self, deserializer: D) -> std::result::Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = Value::deserialize(deserializer)?;
+        self.from_value(value).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ClassRegistry;
+    use crate::{from_value, Error, Value};
+    use serde::de::{DeserializeSeed, SeqAccess, Visitor};
+    use serde::{Deserialize, Deserializer};
+    use std::fmt;
+
+    trait Shape {
+        fn area(&self) -> f64;
+    }
+
+    #[derive(Deserialize)]
+    struct Square {
+        side: f64,
+    }
+
+    impl Shape for Square {
+        fn area(&self) -> f64 {
+            self.side * self.side
+        }
+    }
+
+    #[derive(Deserialize)]
+    struct Rect {
+        w: f64,
+        h: f64,
+    }
+
+    impl Shape for Rect {
+        fn area(&self) -> f64 {
+            self.w * self.h
+        }
+    }
+
+    fn shapes() -> ClassRegistry<Box<dyn Shape>> {
+        let mut registry = ClassRegistry::new();
+        registry
+            .register_with("Geometry\\Square", |v| {
+                let shape: Box<dyn Shape> = Box::new(from_value::<Square>(v)?);
+                Ok(shape)
+            })
+            .register_with("Geometry\\Rect", |v| {
+                let shape: Box<dyn Shape> = Box::new(from_value::<Rect>(v)?);
+                Ok(shape)
+            });
+        registry
+    }
+
+    #[test]
+    fn trait_objects() {
+        let registry = shapes();
+
+        let square = registry
+            .from_bytes(br#"O:15:"Geometry\Square":1:{s:4:"side";d:2;}"#)
+            .unwrap();
+        assert_eq!(square.area(), 4.0);
+
+        // Class names are case-insensitive, leading backslashes are ignored.
+        let rect = registry
+            .from_bytes(br#"O:14:"\geometry\rect":2:{s:1:"w";d:2;s:1:"h";d:3;}"#)
+            .unwrap();
+        assert_eq!(rect.area(), 6.0);
+    }
+
+    #[test]
+    fn register_into() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Ping {
+            seq: i64,
+        }
+
+        #[derive(Debug, PartialEq)]
+        enum Message {
+            Ping(Ping),
+        }
+
+        impl From<Ping> for Message {
+            fn from(ping: Ping) -> Self {
+                Message::Ping(ping)
+            }
+        }
+
+        let mut registry = ClassRegistry::<Message>::new();
+        registry.register::<Ping>("Ping");
+        assert!(registry.contains("PING"));
+
+        assert_eq!(
+            registry
+                .from_bytes(br#"O:4:"Ping":1:{s:3:"seq";i:5;}"#)
+                .unwrap(),
+            Message::Ping(Ping { seq: 5 })
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let registry = shapes();
+
+        assert!(matches!(
+            registry.from_bytes(br#"O:6:"Circle":1:{s:1:"r";d:1;}"#),
+            Err(Error::UnknownClass(class)) if class == "Circle"
+        ));
+        assert!(matches!(
+            registry.from_value(Value::Int(1)),
+            Err(Error::ExpectedObject)
+        ));
+    }
+
+    #[test]
+    fn seed_in_sequence() {
+        struct Shapes<'a>(&'a ClassRegistry<Box<dyn Shape>>);
+
+        impl<'de> Visitor<'de> for Shapes<'_> {
+            type Value = Vec<Box<dyn Shape>>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("a list of shapes")
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let mut shapes = Vec::new();
+                while let Some(shape) = seq.next_element_seed(self.0)? {
+                    shapes.push(shape);
+                }
+                Ok(shapes)
+            }
+        }
+
+        impl<'de> DeserializeSeed<'de> for Shapes<'_> {
+            type Value = Vec<Box<dyn Shape>>;
+
+            fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                deserializer.deserialize_seq(self)
+            }
+        }
+
+        let registry = shapes();
+        let input = br#"a:2:{i:0;O:15:"Geometry\Square":1:{s:4:"side";d:3;}i:1;O:13:"Geometry\Rect":2:{s:1:"w";d:1;s:1:"h";d:5;}}"#;
+        let mut de = crate::de::PhpDeserializer::new(&input[..]);
+        let shapes = Shapes(&registry).deserialize(&mut de).unwrap();
+
+        let areas: Vec<f64> = shapes.iter().map(|s| s.area()).collect();
+        assert_eq!(areas, vec![9.0, 5.0]);
+    }
+}
@@ -0,0 +1,375 @@
+//! Repairing serialized data that was edited without updating its lengths.
+
+use crate::error::{Error, Result};
+use crate::options::DeserializerOptions;
+use std::io::Write;
+
+/// Rewrite the length headers of strings in `input` to their actual byte
+/// lengths.
+///
+/// Search-and-replace on serialized data, e.g. of a domain name in an SQL
+/// dump, changes strings without changing the lengths written before them,
+/// and PHP refuses to unserialize the result. The repaired value reads
+/// back as intended.
+///
+/// Where a length header is wrong, it is tried as a count of UTF-8
+/// characters, see [`fix_char_lengths`]. Failing that, the string is taken
+/// to end at the first `";` that is followed by the end of the input, a `}` or the start of
+/// another value. Strings that contain such a sequence themselves, and
+/// whose length is wrong, are cut short there. Class names are repaired
+/// the same way, up to the first `":` followed by a digit. Element counts
+/// of arrays and objects are rewritten to the number of entries found.
+/// Payloads of custom serialized objects (`C:`) are copied as they are.
+///
+/// Input after the value is copied as it is.
+///
+/// ```rust
+/// use php_serde::repair::fix_lengths;
+///
+/// // `http://old.test` replaced by `https://new.example`.
+/// let broken = br#"a:1:{s:7:"siteurl";s:15:"https://new.example";}"#;
+/// assert_eq!(
+///     fix_lengths(broken).unwrap(),
+///     &br#"a:1:{s:7:"siteurl";s:19:"https://new.example";}"#[..]
+/// );
+/// ```
+pub fn fix_lengths(input: &[u8]) -> Result<Vec<u8>> {
+    repair(input, false)
+}
+
+/// Rewrite the length headers of strings in `input` from counts of UTF-8
+/// characters to byte lengths.
+///
+/// Some tools outside of PHP write the number of characters of a string
+/// instead of its number of bytes, which differ for strings that are not
+/// ASCII. Lengths are read as character counts first, with bytes that are
+/// not valid UTF-8 counting as one character each, like `mb_strlen` does.
+/// Lengths that are correct byte counts are kept, and others are repaired
+/// like [`fix_lengths`] does.
+///
+/// ```rust
+/// use php_serde::repair::fix_char_lengths;
+///
+/// let input = r#"a:1:{i:0;s:5:"Grüße";}"#;
+/// assert_eq!(
+///     fix_char_lengths(input.as_bytes()).unwrap(),
+///     r#"a:1:{i:0;s:7:"Grüße";}"#.as_bytes()
+/// );
+/// ```
+pub fn fix_char_lengths(input: &[u8]) -> Result<Vec<u8>> {
+    repair(input, true)
+}
+
+fn repair(input: &[u8], chars_first: bool) -> Result<Vec<u8>> {
+    let mut repairer = Repairer {
+        input,
+        offset: 0,
+        output: Vec::with_capacity(input.len()),
+        chars_first,
+    };
+    repairer
+        .value(DeserializerOptions::default().max_depth)
+        .map_err(|e| e.at(repairer.offset))?;
+    repairer.output.extend_from_slice(&input[repairer.offset..]);
+    Ok(repairer.output)
+}
+
+/// Copies a serialized value, rewriting lengths on the way.
+struct Repairer<'a> {
+    input: &'a [u8],
+    offset: usize,
+    output: Vec<u8>,
+    /// Whether lengths are read as character counts before byte counts.
+    chars_first: bool,
+}
+
+impl<'a> Repairer<'a> {
+    fn value(&mut self, depth: usize) -> Result<()> {
+        let c = self.next()?;
+        match c {
+            b'N' => {
+                self.output.push(c);
+                self.expect(b';')
+            }
+            b'b' | b'i' | b'd' | b'r' | b'R' => {
+                self.output.push(c);
+                self.expect(b':')?;
+                let len = self.input[self.offset..]
+                    .iter()
+                    .position(|&b| b == b';')
+                    .ok_or(Error::UnexpectedEof)?;
+                self.copy(len + 1);
+                Ok(())
+            }
+            b's' | b'E' => {
+                self.skip(b':')?;
+                let s = self.string(b';')?;
+                self.write_string(c, s);
+                self.output.push(b';');
+                Ok(())
+            }
+            b'S' => {
+                self.skip(b':')?;
+                let s = self.string(b';')?;
+                self.write_escaped(s);
+                self.output.push(b';');
+                Ok(())
+            }
+            b'a' => {
+                self.output.push(c);
+                self.expect(b':')?;
+                self.entries(depth)
+            }
+            b'O' | b'C' => {
+                self.skip(b':')?;
+                let class = self.string(b':')?;
+                self.write_string(c, class);
+                self.output.push(b':');
+                if c == b'O' {
+                    return self.entries(depth);
+                }
+
+                let len = self.length()?;
+                let end = len.checked_add(self.offset + 1);
+                if self.input.get(self.offset) != Some(&b'{')
+                    || end.and_then(|end| self.input.get(end)) != Some(&b'}')
+                {
+                    return Err(malformed("custom serialized payload"));
+                }
+                let _ = write!(self.output, "{len}:");
+                self.copy(len + 2);
+                Ok(())
+            }
+            c => Err(Error::InvalidTypeIndicator(char::from(c))),
+        }
+    }
+
+    /// Copy `<count>:{...}`, with the count of the entries found.
+    fn entries(&mut self, depth: usize) -> Result<()> {
+        let depth = depth.checked_sub(1).ok_or(Error::DepthLimitExceeded)?;
+        self.length()?;
+        self.skip(b'{')?;
+
+        let mut entries = std::mem::take(&mut self.output);
+        let mut count = 0_usize;
+        while self.input.get(self.offset) != Some(&b'}') {
+            self.value(depth)?;
+            self.value(depth)?;
+            count += 1;
+        }
+        self.offset += 1;
+        let _ = write!(entries, "{count}:{{");
+        entries.append(&mut self.output);
+        entries.push(b'}');
+        self.output = entries;
+        Ok(())
+    }
+
+    /// Read `<length>:"...."` and the byte `end` after the closing quote,
+    /// and return the contents.
+    fn string(&mut self, end: u8) -> Result<&'a [u8]> {
+        let len = self.length()?;
+        self.skip(b'"')?;
+        let start = self.offset;
+        let input = self.input;
+        let rest = &input[start..];
+
+        // Lengths beyond the input, up to `usize::MAX`, are not the end.
+        let ends_at = |at: usize| {
+            rest.get(at..)
+                .is_some_and(|tail| tail.starts_with(&[b'"', end]) && follows(end, &tail[2..]))
+        };
+        let chars = char_bytes(rest, len).filter(|&at| ends_at(at));
+        let bytes = Some(len).filter(|&at| ends_at(at));
+        let len = if self.chars_first {
+            chars.or(bytes)
+        } else {
+            bytes.or(chars)
+        };
+        let len = match len {
+            Some(len) => len,
+            None => (0..rest.len())
+                .find(|&at| ends_at(at))
+                .ok_or(Error::UnexpectedEof)?,
+        };
+        self.offset = start + len + 2;
+        Ok(&input[start..start + len])
+    }
+
+    /// Write a string as `<c>:<length>:"...."`, without the byte after it.
+    fn write_string(&mut self, c: u8, s: &[u8]) {
+        let _ = write!(self.output, "{}:{}:\"", char::from(c), s.len());
+        self.output.extend_from_slice(s);
+        self.output.push(b'"');
+    }
+
+    /// Write an escaped string (`S:`), whose length counts `\xx` escapes
+    /// as one byte.
+    fn write_escaped(&mut self, s: &[u8]) {
+        let mut len = 0;
+        let mut rest = s;
+        while let Some((&b, tail)) = rest.split_first() {
+            let escape =
+                b == b'\\' && tail.len() >= 2 && tail[..2].iter().all(u8::is_ascii_hexdigit);
+            rest = if escape { &tail[2..] } else { tail };
+            len += 1;
+        }
+        let _ = write!(self.output, "S:{len}:\"");
+        self.output.extend_from_slice(s);
+        self.output.push(b'"');
+    }
+
+    /// Read a length followed by `:`.
+    fn length(&mut self) -> Result<usize> {
+        let digits = self.input[self.offset..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        if digits == 0 {
+            let actual = self.next()?;
+            return Err(Error::ExpectedDigit {
+                actual: char::from(actual),
+            });
+        }
+        let len = std::str::from_utf8(&self.input[self.offset..self.offset + digits])
+            .ok()
+            .and_then(|digits| digits.parse().ok())
+            .ok_or_else(|| malformed("length"))?;
+        self.offset += digits;
+        self.skip(b':')?;
+        Ok(len)
+    }
+
+    fn next(&mut self) -> Result<u8> {
+        let c = *self.input.get(self.offset).ok_or(Error::UnexpectedEof)?;
+        self.offset += 1;
+        Ok(c)
+    }
+
+    /// Read an expected byte without copying it.
+    fn skip(&mut self, expected: u8) -> Result<()> {
+        let actual = self.next()?;
+        if actual == expected {
+            Ok(())
+        } else {
+            self.offset -= 1;
+            Err(Error::Unexpected {
+                expected: char::from(expected),
+                actual: char::from(actual),
+            })
+        }
+    }
+
+    /// Copy an expected byte.
+    fn expect(&mut self, expected: u8) -> Result<()> {
+        self.skip(expected)?;
+        self.output.push(expected);
+        Ok(())
+    }
+
+    fn copy(&mut self, len: usize) {
+        let end = (self.offset + len).min(self.input.len());
+        self.output.extend_from_slice(&self.input[self.offset..end]);
+        self.offset = end;
+    }
+}
+
+/// Number of bytes taken up by the first `chars` UTF-8 characters of `s`.
+fn char_bytes(s: &[u8], chars: usize) -> Option<usize> {
+    let mut len = 0;
+    for _ in 0..chars {
+        let width = match s.get(len)? {
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => 1,
+        };
+        // Invalid sequences count one byte at a time.
+        let valid = s
+            .get(len + 1..len + width)
+            .is_some_and(|tail| tail.iter().all(|&b| b & 0xC0 == 0x80));
+        len += if valid { width } else { 1 };
+    }
+    Some(len)
+}
+
+/// Whether `rest` can follow a string ending in `"` and `end`.
+fn follows(end: u8, rest: &[u8]) -> bool {
+    if end == b':' {
+        return rest.first().is_some_and(u8::is_ascii_digit);
+    }
+    match rest {
+        [] | [b'}', ..] | [b'N', b';', ..] => true,
+        [c, b':', ..] => b"bidsSaOCrRE".contains(c),
+        _ => false,
+    }
+}
+
+fn malformed(what: &str) -> Error {
+    Error::DeserializationFailed(format!("invalid serialized data: malformed {what}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fix_char_lengths, fix_lengths};
+    use crate::{from_bytes, ErrorKind, Value};
+
+    #[test]
+    fn repairs() {
+        let broken: &[u8] =
+            r#"a:3:{s:3:"url";s:3:"a";x";s:4:"user";O:4:"App\User":1:{s:4:"name";s:1:"Zoë";}s:1:"x";a:1:{i:0;E:3:"App\Suit:Hearts";}}"#.as_bytes();
+        let fixed = fix_lengths(broken).unwrap();
+        assert_eq!(
+            fixed,
+            r#"a:3:{s:3:"url";s:4:"a";x";s:4:"user";O:8:"App\User":1:{s:4:"name";s:4:"Zoë";}s:1:"x";a:1:{i:0;E:15:"App\Suit:Hearts";}}"#.as_bytes()
+        );
+        let value: Value = from_bytes(&fixed).unwrap();
+        assert_eq!(value.get("url"), Some(&Value::from("a\";x")));
+
+        // Valid input is left alone, including strings containing `";`.
+        let valid: &[u8] = br#"a:2:{i:0;s:5:"a";}x";i:1;C:3:"Foo":2:{ab}}"#;
+        assert_eq!(fix_lengths(valid).unwrap(), valid);
+        assert_eq!(fix_lengths(br#"S:1:"\41";"#).unwrap(), br#"S:1:"\41";"#);
+
+        // Character counts.
+        let chars = r#"a:2:{i:0;s:2:"é";i:1;s:4:"a";é";}"#;
+        let fixed = r#"a:2:{i:0;s:2:"é";i:1;s:5:"a";é";}"#;
+        assert_eq!(
+            fix_char_lengths(chars.as_bytes()).unwrap(),
+            fixed.as_bytes()
+        );
+        assert_eq!(
+            fix_char_lengths(b"s:3:\"\xff\xc3a\";").unwrap(),
+            b"s:3:\"\xff\xc3a\";"
+        );
+
+        // Counts are rewritten, trailing input is kept.
+        assert_eq!(
+            fix_lengths(br#"a:5:{i:0;N;i:1;b:1;}rest"#).unwrap(),
+            &br#"a:2:{i:0;N;i:1;b:1;}rest"#[..]
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        assert_eq!(
+            fix_lengths(br#"s:3:"abc"#).unwrap_err().kind(),
+            ErrorKind::Eof
+        );
+        let err = fix_lengths(b"a:1:{i:0;x:1;}").unwrap_err();
+        assert_eq!(err.position(), Some(10));
+        assert!(fix_lengths(br#"C:3:"Foo":9:{ab}"#).is_err());
+
+        // Lengths too large to add to are wrong like any other.
+        assert_eq!(
+            fix_lengths(br#"s:18446744073709551615:"a";"#).unwrap(),
+            br#"s:1:"a";"#
+        );
+        assert!(fix_lengths(br#"C:3:"Foo":18446744073709551615:{a}"#).is_err());
+        assert_eq!(
+            fix_char_lengths("s:18446744073709551615:\"\u{e9}\";".as_bytes()).unwrap(),
+            "s:2:\"\u{e9}\";".as_bytes()
+        );
+        assert!(fix_char_lengths(br#"C:3:"Foo":18446744073709551615:{a}"#).is_err());
+    }
+}
@@ -0,0 +1,361 @@
+//! Rewriting parts of serialized data, copying the rest byte for byte.
+
+use crate::error::{Error, Result};
+use crate::key::PhpKey;
+use crate::property::Visibility;
+use crate::ser::to_vec;
+use crate::token::{Key, Lexer, Token};
+use crate::validate::validate;
+use crate::value::Value;
+use std::ops::Range;
+
+/// Copy serialized `input`, replacing the values that `replace` returns a
+/// replacement for.
+///
+/// `replace` is called for every value, with the keys leading to it and its
+/// token, which for arrays and objects is their start. If it returns a
+/// value, that value is written instead, and for arrays and objects their
+/// contents are passed over. Everything else is copied byte for byte, so
+/// the output only differs from the input where values were replaced, and
+/// is valid if the input is. This makes it fit for redacting values before
+/// shipping them to logs or exporting them, without deserializing them.
+///
+/// Replacing values changes the numbers that references (`r:` and `R:`)
+/// after them refer to values by, unless one value is replaced by another
+/// that is not an array or object, e.g. a string by a string. Input after
+/// the value is copied as it is.
+///
+/// ```rust
+/// use php_serde::{rewrite, Value};
+///
+/// let input = br#"a:2:{s:4:"user";s:3:"bob";s:4:"auth";a:2:{s:8:"Password";s:6:"secret";s:5:"token";a:1:{i:0;s:1:"x";}}}"#;
+/// let redacted = rewrite(input, |path, _| {
+///     let key = String::from_utf8_lossy(path.last()?.as_bytes()?).to_lowercase();
+///     (key.contains("password") || key.contains("token")).then(|| Value::from("***"))
+/// })
+/// .unwrap();
+/// assert_eq!(
+///     redacted,
+///     &br#"a:2:{s:4:"user";s:3:"bob";s:4:"auth";a:2:{s:8:"Password";s:3:"***";s:5:"token";s:3:"***";}}"#[..]
+/// );
+/// ```
+pub fn rewrite<F>(input: &[u8], mut replace: F) -> Result<Vec<u8>>
+where
+    F: FnMut(&[PhpKey], &Token<'_>) -> Option<Value>,
+{
+    let mut output = Vec::with_capacity(input.len());
+    // Keys leading to the current value, one for each open array or object
+    // once its entry's key has been read.
+    let mut path: Vec<PhpKey> = Vec::new();
+    let mut depth = 0_usize;
+    // Arrays and objects open in a replaced value, whose tokens are skipped.
+    let mut skipping = 0_usize;
+
+    let mut lexer = Lexer::new(input);
+    for token in lexer.by_ref() {
+        let (token, span) = token?;
+        let opens = matches!(token, Token::ArrayStart(_) | Token::ObjectStart { .. });
+        let closes = matches!(token, Token::ArrayEnd | Token::ObjectEnd);
+
+        if skipping > 0 {
+            if opens {
+                skipping += 1;
+            } else if closes {
+                skipping -= 1;
+            }
+            if skipping > 0 {
+                continue;
+            }
+        } else if let Token::Key(key) = token {
+            output.extend_from_slice(&input[span]);
+            path.push(PhpKey::from(key));
+            continue;
+        } else if closes {
+            output.extend_from_slice(&input[span]);
+            depth -= 1;
+        } else if let Some(value) = replace(&path, &token) {
+            output.extend_from_slice(&to_vec(&value)?);
+            if opens {
+                skipping = 1;
+                continue;
+            }
+        } else {
+            output.extend_from_slice(&input[span]);
+            if opens {
+                depth += 1;
+                continue;
+            }
+        }
+
+        // A value has been written completely, ending its entry.
+        if depth > 0 && path.len() == depth {
+            path.pop();
+        }
+    }
+
+    output.extend_from_slice(&input[lexer.byte_offset()..]);
+    Ok(output)
+}
+
+/// Replace the value at `path` in serialized `input` with the serialized
+/// value `subtree`, leaving all other bytes as they are.
+///
+/// `path` is written like for `extract`: keys separated by `.`, with
+/// integer keys in decimal and properties by their plain names. The empty
+/// path replaces the whole value. If the array or object at the path lacks
+/// the last key, the entry is added at its end, and its element count is
+/// increased. `subtree` is written as it is, after checking that it is a
+/// single well-formed value.
+///
+/// This allows editing values whose other parts must not be written anew,
+/// e.g. because they hold floats or objects this crate would not write the
+/// same way. Like with `rewrite`, replacing an array or object changes the
+/// numbers that references after it refer to values by.
+///
+/// ```rust
+/// use php_serde::splice;
+///
+/// let input = br#"a:2:{s:4:"data";a:1:{s:4:"user";a:1:{s:5:"email";s:5:"a@b.c";}}s:1:"f";d:0.1000000000000000055511151231257827;}"#;
+/// let output = splice(input, "data.user.email", br#"s:5:"x@y.z";"#).unwrap();
+/// assert_eq!(
+///     output,
+///     &br#"a:2:{s:4:"data";a:1:{s:4:"user";a:1:{s:5:"email";s:5:"x@y.z";}}s:1:"f";d:0.1000000000000000055511151231257827;}"#[..]
+/// );
+///
+/// let output = splice(input, "data.user.name", br#"s:3:"Bob";"#).unwrap();
+/// assert_eq!(
+///     output,
+///     &br#"a:2:{s:4:"data";a:1:{s:4:"user";a:2:{s:5:"email";s:5:"a@b.c";s:4:"name";s:3:"Bob";}}s:1:"f";d:0.1000000000000000055511151231257827;}"#[..]
+/// );
+/// ```
+pub fn splice(input: &[u8], path: &str, subtree: &[u8]) -> Result<Vec<u8>> {
+    let report = validate(subtree)?;
+    if report.length < subtree.len() {
+        return Err(Error::DeserializationFailed(
+            "expected a single serialized value to splice in".to_owned(),
+        ));
+    }
+
+    let segments: Vec<&str> = if path.is_empty() {
+        Vec::new()
+    } else {
+        path.split('.').collect()
+    };
+    let target = find(input, &segments)?;
+
+    let mut output = Vec::with_capacity(input.len() + subtree.len());
+    match target {
+        Target::Value(range) => {
+            output.extend_from_slice(&input[..range.start]);
+            output.extend_from_slice(subtree);
+            output.extend_from_slice(&input[range.end..]);
+        }
+        Target::Parent { header, len, end } => {
+            // The header ends in `<count>:{`.
+            let digits = input[header.start..header.end - 2]
+                .iter()
+                .rev()
+                .take_while(|b| b.is_ascii_digit())
+                .count();
+            let count = header.end - 2 - digits;
+            output.extend_from_slice(&input[..count]);
+            output.extend_from_slice((len + 1).to_string().as_bytes());
+            output.extend_from_slice(&input[header.end - 2..end]);
+            let key = segments.last().map_or(&[][..], |key| key.as_bytes());
+            output.extend_from_slice(&to_vec(&PhpKey::from_bytes(key))?);
+            output.extend_from_slice(subtree);
+            output.extend_from_slice(&input[end..]);
+        }
+    }
+    Ok(output)
+}
+
+/// Where `splice` writes the new value.
+enum Target {
+    /// The range of the value at the path.
+    Value(Range<usize>),
+    /// The array or object that lacks the last key of the path, with the
+    /// range of its header, its element count and the position of its `}`.
+    Parent {
+        header: Range<usize>,
+        len: usize,
+        end: usize,
+    },
+}
+
+/// An array or object open while looking for the value at a path.
+struct Level {
+    object: bool,
+    header: Range<usize>,
+    len: usize,
+    /// Whether the keys leading to this array or object are on the path.
+    on_path: bool,
+    /// Whether the key of the current entry is the next one on the path.
+    matched: bool,
+}
+
+/// Find the value at the path `segments` in `input`.
+fn find(input: &[u8], segments: &[&str]) -> Result<Target> {
+    let mut levels: Vec<Level> = Vec::new();
+    let mut start = None;
+    let mut parent = None;
+
+    for token in Lexer::new(input) {
+        let (token, span) = token?;
+        match token {
+            Token::Key(key) => {
+                let depth = levels.len() - 1;
+                if let Some(level) = levels.last_mut() {
+                    level.matched = level.on_path
+                        && segments
+                            .get(depth)
+                            .is_some_and(|segment| key_matches(key, segment, level.object));
+                }
+                continue;
+            }
+            Token::ArrayEnd | Token::ObjectEnd => {
+                let Some(level) = levels.pop() else {
+                    continue;
+                };
+                if level.on_path && levels.len() + 1 == segments.len() {
+                    parent = Some(Target::Parent {
+                        header: level.header,
+                        len: level.len,
+                        end: span.start,
+                    });
+                }
+            }
+            Token::ArrayStart(len) | Token::ObjectStart { len, .. } => {
+                let on_path = levels.last().is_none_or(|level| level.matched);
+                if on_path && levels.len() == segments.len() {
+                    start = Some((span.start, levels.len()));
+                }
+                levels.push(Level {
+                    object: matches!(token, Token::ObjectStart { .. }),
+                    header: span,
+                    len,
+                    on_path,
+                    matched: false,
+                });
+                continue;
+            }
+            _ => {
+                let on_path = levels.last().is_none_or(|level| level.matched);
+                if on_path && levels.len() == segments.len() {
+                    return Ok(Target::Value(span));
+                }
+            }
+        }
+
+        // A value at the path ends with its array or object.
+        if let Some((value_start, depth)) = start {
+            if levels.len() == depth {
+                return Ok(Target::Value(value_start..span.end));
+            }
+        }
+    }
+
+    parent.ok_or_else(|| {
+        let parent = segments[..segments.len().saturating_sub(1)].join(".");
+        Error::DeserializationFailed(format!("no array or object at `{parent}`"))
+    })
+}
+
+/// Whether an array key or property name is written as `segment` in a path.
+fn key_matches(key: Key<'_>, segment: &str, object: bool) -> bool {
+    match key {
+        Key::Int(n) => n.to_string() == segment,
+        Key::Bytes(name) if object => Visibility::demangle(name).1 == segment.as_bytes(),
+        Key::Bytes(name) => name == segment.as_bytes(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rewrite, splice};
+    use crate::{ErrorKind, PhpKey, Token, Value};
+
+    #[test]
+    fn rewrites() {
+        let input = br#"O:3:"Foo":3:{s:1:"a";a:1:{s:1:"b";a:1:{i:0;i:1;}}s:1:"c";d:0.5;s:1:"d";s:1:"x";}rest"#;
+
+        // Nothing replaced.
+        let mut paths = Vec::new();
+        let output = rewrite(input, |path, token| {
+            paths.push((path.to_vec(), matches!(token, Token::ArrayStart(_))));
+            None
+        })
+        .unwrap();
+        assert_eq!(output, &input[..]);
+        let key = PhpKey::from;
+        assert_eq!(
+            paths,
+            [
+                (vec![], false),
+                (vec![key("a")], true),
+                (vec![key("a"), key("b")], true),
+                (vec![key("a"), key("b"), PhpKey::Int(0)], false),
+                (vec![key("c")], false),
+                (vec![key("d")], false),
+            ]
+        );
+
+        // Replacing an array, and values after it.
+        let output = rewrite(input, |path, token| match (path.first(), token) {
+            (Some(PhpKey::String(k)), Token::ArrayStart(_)) if k == b"a" => Some(Value::Null),
+            (Some(_), Token::Float(f)) => Some(Value::Float(f * 2.0)),
+            (Some(_), Token::Str(_)) => Some(Value::from(vec![1])),
+            _ => None,
+        })
+        .unwrap();
+        assert_eq!(
+            output,
+            &br#"O:3:"Foo":3:{s:1:"a";N;s:1:"c";d:1;s:1:"d";a:1:{i:0;i:1;}}rest"#[..]
+        );
+
+        // The whole value.
+        let output = rewrite(input, |_, _| Some(Value::Int(0))).unwrap();
+        assert_eq!(output, b"i:0;rest");
+    }
+
+    #[test]
+    fn invalid() {
+        let err = rewrite(b"a:2:{i:0;N;}", |_, _| None).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedToken);
+    }
+
+    #[test]
+    fn splices() {
+        let input = b"O:4:\"Page\":2:{s:8:\"\0*\0items\";a:2:{i:0;s:1:\"a\";i:7;a:0:{}}\
+            s:5:\"title\";d:0.1000000000000000055511151231257827;}rest";
+        let splice = |path, subtree: &[u8]| splice(input, path, subtree).map(|o| o.to_vec());
+
+        assert_eq!(
+            splice("items.0", b"N;").unwrap(),
+            &b"O:4:\"Page\":2:{s:8:\"\0*\0items\";a:2:{i:0;N;i:7;a:0:{}}\
+            s:5:\"title\";d:0.1000000000000000055511151231257827;}rest"[..]
+        );
+        assert_eq!(
+            splice("items", b"b:1;").unwrap(),
+            &b"O:4:\"Page\":2:{s:8:\"\0*\0items\";b:1;\
+            s:5:\"title\";d:0.1000000000000000055511151231257827;}rest"[..]
+        );
+        // Adding entries to empty and non-empty arrays and objects.
+        assert_eq!(
+            splice("items.7.x", b"i:1;").unwrap(),
+            &b"O:4:\"Page\":2:{s:8:\"\0*\0items\";a:2:{i:0;s:1:\"a\";i:7;a:1:{s:1:\"x\";i:1;}}\
+            s:5:\"title\";d:0.1000000000000000055511151231257827;}rest"[..]
+        );
+        assert_eq!(
+            splice("size", b"i:10;").unwrap(),
+            &b"O:4:\"Page\":3:{s:8:\"\0*\0items\";a:2:{i:0;s:1:\"a\";i:7;a:0:{}}\
+            s:5:\"title\";d:0.1000000000000000055511151231257827;s:4:\"size\";i:10;}rest"[..]
+        );
+        assert_eq!(splice("", b"N;").unwrap(), b"N;rest");
+
+        assert!(splice("items.0.x", b"N;").is_err());
+        assert!(splice("missing.x", b"N;").is_err());
+        assert!(splice("title", b"N;N;").is_err());
+        assert!(splice("title", b"x").is_err());
+    }
+}
@@ -0,0 +1,643 @@
+//! Inferring Rust type definitions from sample data.
+
+use crate::de::from_bytes;
+use crate::error::{Error, Result};
+use crate::key::PhpKey;
+use crate::property::Visibility;
+use crate::value::{is_list, Value};
+use std::fmt;
+
+/// Infer a Rust struct that the serialized `input` deserializes into.
+///
+/// See [`infer_all`] for how types are inferred.
+///
+/// ```rust
+/// use php_serde::schema::infer;
+///
+/// let def = infer(br#"O:8:"App\User":2:{s:2:"id";i:7;s:8:"nickName";N;}"#).unwrap();
+/// assert_eq!(def.name, "User");
+/// assert_eq!(def.fields[1].name, "nick_name");
+/// assert_eq!(def.fields[1].ty, "Option<php_serde::Value>");
+/// ```
+pub fn infer(input: &[u8]) -> Result<RustStructDef> {
+    infer_all([input])
+}
+
+/// Infer a Rust struct that all of the serialized `samples` deserialize
+/// into, along with the nested types it needs.
+///
+/// Arrays with string keys and objects become structs, named after the
+/// class of objects and after the field holding them otherwise. Lists
+/// become `Vec`s, other arrays with integer keys `BTreeMap<i64, _>`s, and
+/// PHP 8.1 enum cases enums with the cases seen. Values of different types
+/// are merged where a type holds both, e.g. integers and floats into `f64`
+/// and anything with `null` into an `Option`. Fields missing from some of
+/// the samples are `Option`s as well. Values that fit no single type, such
+/// as arrays mixing integer and string keys, are kept as a `Value`.
+///
+/// A list at the top level, e.g. of rows, is inferred from its elements.
+/// Its elements, or the top-level values, need to be arrays or objects.
+///
+/// The result is only a proposal, based on the samples: a field that was
+/// always an integer may hold strings in other data.
+///
+/// ```rust
+/// use php_serde::schema::infer_all;
+///
+/// let def = infer_all([
+///     &br#"a:2:{s:4:"name";s:3:"Bob";s:4:"tags";a:1:{i:0;a:1:{s:5:"label";s:1:"x";}}}"#[..],
+///     &br#"a:1:{s:4:"name";s:5:"Alice";}"#[..],
+/// ])
+/// .unwrap();
+/// assert_eq!(
+///     def.to_string(),
+///     "use serde::{Deserialize, Serialize};
+///
+/// #[derive(Debug, Deserialize, Serialize)]
+/// pub struct Root {
+///     pub name: String,
+///     pub tags: Option<Vec<Tag>>,
+/// }
+///
+/// #[derive(Debug, Deserialize, Serialize)]
+/// pub struct Tag {
+///     pub label: String,
+/// }
+/// "
+/// );
+/// ```
+pub fn infer_all<I>(samples: I) -> Result<RustStructDef>
+where
+    I: IntoIterator,
+    I::Item: AsRef<[u8]>,
+{
+    let mut shape = None;
+    for sample in samples {
+        let sample = Shape::of(&from_bytes::<Value>(sample.as_ref())?);
+        shape = Some(match shape {
+            Some(shape) => merge(shape, sample),
+            None => sample,
+        });
+    }
+
+    let shape = match shape {
+        Some(Shape::List(element)) => *element,
+        Some(shape) => shape,
+        None => return Err(unsupported("no samples")),
+    };
+    let (class, fields) = match shape {
+        Shape::Struct { class, fields } => (class, fields),
+        Shape::EmptyArray => (None, Vec::new()),
+        _ => return Err(unsupported("expected arrays or objects")),
+    };
+
+    let mut builder = Builder {
+        types: Vec::new(),
+        root: type_name("Root", class.as_deref()).0,
+    };
+    let mut root = builder.struct_def("Root", class.as_deref(), &fields);
+    root.types = builder.types;
+    Ok(root)
+}
+
+/// A Rust struct definition, inferred by [`infer`].
+///
+/// It is displayed as derive-ready Rust code, followed by the nested types
+/// in [`RustStructDef::types`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RustStructDef {
+    /// Name of the struct.
+    pub name: String,
+    /// Name to rename the struct to, the PHP class name if it differs.
+    pub rename: Option<String>,
+    /// Fields, in the order of the samples.
+    pub fields: Vec<RustFieldDef>,
+    /// Nested types the fields refer to. Only the root struct lists them,
+    /// it is empty for the nested structs themselves.
+    pub types: Vec<RustTypeDef>,
+}
+
+/// A field of a [`RustStructDef`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RustFieldDef {
+    /// Name of the field, a raw identifier for keywords.
+    pub name: String,
+    /// Name to rename the field to, the PHP key if it differs.
+    pub rename: Option<String>,
+    /// The Rust type, with paths for types outside the definitions.
+    pub ty: String,
+}
+
+/// A Rust enum definition for PHP 8.1 enum cases.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RustEnumDef {
+    /// Name of the enum.
+    pub name: String,
+    /// Name to rename the enum to, the PHP class name if it differs.
+    pub rename: Option<String>,
+    /// Names of the unit variants, the cases seen.
+    pub variants: Vec<String>,
+}
+
+/// A nested type of a [`RustStructDef`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RustTypeDef {
+    /// A struct, for arrays with string keys and objects.
+    Struct(RustStructDef),
+    /// An enum, for PHP 8.1 enum cases.
+    Enum(RustEnumDef),
+}
+
+impl RustTypeDef {
+    fn name(&self) -> &str {
+        match self {
+            RustTypeDef::Struct(def) => &def.name,
+            RustTypeDef::Enum(def) => &def.name,
+        }
+    }
+
+    fn name_mut(&mut self) -> &mut String {
+        match self {
+            RustTypeDef::Struct(def) => &mut def.name,
+            RustTypeDef::Enum(def) => &mut def.name,
+        }
+    }
+}
+
+impl fmt::Display for RustStructDef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "use serde::{{Deserialize, Serialize}};")?;
+        self.write(f)?;
+        for def in &self.types {
+            match def {
+                RustTypeDef::Struct(def) => def.write(f)?,
+                RustTypeDef::Enum(def) => def.write(f)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl RustStructDef {
+    /// Write the struct itself, preceded by an empty line.
+    fn write(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_header(f, self.rename.as_deref())?;
+        writeln!(f, "pub struct {} {{", self.name)?;
+        for field in &self.fields {
+            if let Some(rename) = &field.rename {
+                writeln!(f, "    #[serde(rename = {rename:?})]")?;
+            }
+            writeln!(f, "    pub {}: {},", field.name, field.ty)?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+impl RustEnumDef {
+    /// Write the enum, preceded by an empty line.
+    fn write(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_header(f, self.rename.as_deref())?;
+        writeln!(f, "pub enum {} {{", self.name)?;
+        for variant in &self.variants {
+            writeln!(f, "    {variant},")?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+fn write_header(f: &mut fmt::Formatter<'_>, rename: Option<&str>) -> fmt::Result {
+    writeln!(f, "\n#[derive(Debug, Deserialize, Serialize)]")?;
+    if let Some(rename) = rename {
+        writeln!(f, "#[serde(rename = {rename:?})]")?;
+    }
+    Ok(())
+}
+
+/// Type inferred from sample values.
+#[derive(Clone, Debug, PartialEq)]
+enum Shape {
+    /// Only `null` was seen.
+    Null,
+    Bool,
+    Int,
+    Float,
+    String,
+    /// An empty array, which may be a list or have keys.
+    EmptyArray,
+    List(Box<Shape>),
+    IntMap(Box<Shape>),
+    Struct {
+        class: Option<String>,
+        /// PHP names of the fields, their types and whether they were
+        /// present in all samples.
+        fields: Vec<(String, Shape, bool)>,
+    },
+    Enum {
+        class: String,
+        cases: Vec<String>,
+    },
+    DateTime,
+    /// Values of no single type.
+    Any,
+    Optional(Box<Shape>),
+}
+
+impl Shape {
+    fn of(value: &Value) -> Shape {
+        match value {
+            Value::Null => Shape::Null,
+            Value::Bool(_) => Shape::Bool,
+            Value::Int(_) => Shape::Int,
+            Value::Float(_) => Shape::Float,
+            Value::String(s) if std::str::from_utf8(s).is_ok() => Shape::String,
+            Value::Array(entries) if entries.is_empty() => Shape::EmptyArray,
+            Value::Array(entries) if is_list(entries) => {
+                Shape::List(Box::new(Shape::elements(entries.iter().map(|(_, v)| v))))
+            }
+            Value::Array(entries) => {
+                if entries.iter().all(|(k, _)| matches!(k, PhpKey::Int(_))) {
+                    Shape::IntMap(Box::new(Shape::elements(entries.iter().map(|(_, v)| v))))
+                } else if entries.iter().all(|(k, _)| matches!(k, PhpKey::String(_))) {
+                    Shape::structure(None, entries)
+                } else {
+                    Shape::Any
+                }
+            }
+            Value::Object { class, .. } if class == "DateTime" || class == "DateTimeImmutable" => {
+                Shape::DateTime
+            }
+            Value::Object { class, properties } => Shape::structure(Some(class), properties),
+            Value::Enum { class, case } => Shape::Enum {
+                class: class.clone(),
+                cases: vec![case.clone()],
+            },
+            Value::String(_) | Value::Custom(_) => Shape::Any,
+        }
+    }
+
+    fn elements<'a>(values: impl Iterator<Item = &'a Value>) -> Shape {
+        values
+            .map(Shape::of)
+            .reduce(merge)
+            .unwrap_or(Shape::EmptyArray)
+    }
+
+    fn structure(class: Option<&str>, entries: &[(PhpKey, Value)]) -> Shape {
+        let fields = entries
+            .iter()
+            .map(|(key, value)| {
+                let name = match key {
+                    PhpKey::Int(n) => n.to_string(),
+                    PhpKey::String(name) => {
+                        String::from_utf8_lossy(Visibility::demangle(name).1).into_owned()
+                    }
+                };
+                (name, Shape::of(value), true)
+            })
+            .collect();
+        Shape::Struct {
+            class: class.map(str::to_owned),
+            fields,
+        }
+    }
+
+    fn optional(self) -> Shape {
+        match self {
+            Shape::Null | Shape::Any | Shape::Optional(_) => self,
+            shape => Shape::Optional(Box::new(shape)),
+        }
+    }
+
+    /// Mark all fields of a struct as missing from some samples.
+    fn missing_fields(mut self) -> Shape {
+        if let Shape::Struct { fields, .. } = &mut self {
+            for (_, _, present) in fields {
+                *present = false;
+            }
+        }
+        self
+    }
+}
+
+/// Merge the types of values found in the same place of different samples.
+fn merge(a: Shape, b: Shape) -> Shape {
+    match (a, b) {
+        (a, b) if a == b => a,
+        (Shape::Any, _) | (_, Shape::Any) => Shape::Any,
+        (Shape::Null, other) | (other, Shape::Null) => other.optional(),
+        (Shape::Optional(a), b) | (b, Shape::Optional(a)) => merge(*a, b).optional(),
+        (Shape::Int, Shape::Float) | (Shape::Float, Shape::Int) => Shape::Float,
+        (Shape::EmptyArray, other @ (Shape::List(_) | Shape::IntMap(_)))
+        | (other @ (Shape::List(_) | Shape::IntMap(_)), Shape::EmptyArray) => other,
+        (Shape::EmptyArray, other @ Shape::Struct { .. })
+        | (other @ Shape::Struct { .. }, Shape::EmptyArray) => other.missing_fields(),
+        (Shape::List(a), Shape::List(b)) => Shape::List(Box::new(merge(*a, *b))),
+        (Shape::IntMap(a), Shape::IntMap(b) | Shape::List(b))
+        | (Shape::List(a), Shape::IntMap(b)) => Shape::IntMap(Box::new(merge(*a, *b))),
+        (
+            Shape::Struct { class, fields: a },
+            Shape::Struct {
+                class: other,
+                fields: b,
+            },
+        ) if class == other => Shape::Struct {
+            class,
+            fields: merge_fields(a, b),
+        },
+        (
+            Shape::Enum { class, mut cases },
+            Shape::Enum {
+                class: other,
+                cases: more,
+            },
+        ) if class == other => {
+            for case in more {
+                if !cases.contains(&case) {
+                    cases.push(case);
+                }
+            }
+            Shape::Enum { class, cases }
+        }
+        _ => Shape::Any,
+    }
+}
+
+fn merge_fields(
+    a: Vec<(String, Shape, bool)>,
+    b: Vec<(String, Shape, bool)>,
+) -> Vec<(String, Shape, bool)> {
+    let mut b: Vec<_> = b.into_iter().map(Some).collect();
+    let mut fields: Vec<_> = a
+        .into_iter()
+        .map(|(name, shape, present)| {
+            let other = b
+                .iter_mut()
+                .find(|field| field.as_ref().is_some_and(|(other, ..)| *other == name))
+                .and_then(Option::take);
+            match other {
+                Some((_, other, other_present)) => {
+                    (name, merge(shape, other), present && other_present)
+                }
+                None => (name, shape, false),
+            }
+        })
+        .collect();
+    fields.extend(
+        b.into_iter()
+            .flatten()
+            .map(|(name, shape, _)| (name, shape, false)),
+    );
+    fields
+}
+
+/// Collects the nested types of a struct definition.
+struct Builder {
+    types: Vec<RustTypeDef>,
+    /// Name of the root struct, which nested types don't take.
+    root: String,
+}
+
+impl Builder {
+    /// Define a struct, named `name` unless it is an object.
+    fn struct_def(
+        &mut self,
+        name: &str,
+        class: Option<&str>,
+        fields: &[(String, Shape, bool)],
+    ) -> RustStructDef {
+        let (name, rename) = type_name(name, class);
+        let mut names: Vec<String> = Vec::new();
+        let fields = fields
+            .iter()
+            .map(|(php_name, shape, present)| {
+                let mut ty = self.ty(shape, &pascal_case(php_name));
+                if !present && !matches!(shape, Shape::Null | Shape::Any | Shape::Optional(_)) {
+                    ty = format!("Option<{ty}>");
+                }
+
+                let base = field_name(php_name);
+                let mut name = base.clone();
+                for n in 2.. {
+                    if !names.contains(&name) {
+                        break;
+                    }
+                    name = format!("{base}_{n}");
+                }
+                names.push(name.clone());
+                RustFieldDef {
+                    rename: (name.trim_start_matches("r#") != php_name).then(|| php_name.clone()),
+                    name,
+                    ty,
+                }
+            })
+            .collect();
+        RustStructDef {
+            name,
+            rename,
+            fields,
+            types: Vec::new(),
+        }
+    }
+
+    /// The Rust type of a shape, defining the types it needs and naming
+    /// them after `hint` if they are not objects.
+    fn ty(&mut self, shape: &Shape, hint: &str) -> String {
+        match shape {
+            Shape::Null => "Option<php_serde::Value>".to_owned(),
+            Shape::Bool => "bool".to_owned(),
+            Shape::Int => "i64".to_owned(),
+            Shape::Float => "f64".to_owned(),
+            Shape::String => "String".to_owned(),
+            Shape::EmptyArray => "Vec<php_serde::Value>".to_owned(),
+            Shape::Any => "php_serde::Value".to_owned(),
+            Shape::List(element) => format!("Vec<{}>", self.ty(element, &singular(hint))),
+            Shape::IntMap(element) => format!(
+                "std::collections::BTreeMap<i64, {}>",
+                self.ty(element, &singular(hint))
+            ),
+            Shape::Struct { class, fields } => {
+                let def = self.struct_def(hint, class.as_deref(), fields);
+                self.define(RustTypeDef::Struct(def))
+            }
+            Shape::Enum { class, cases } => {
+                let (name, rename) = type_name(hint, Some(class));
+                let def = RustEnumDef {
+                    name,
+                    rename,
+                    variants: cases.clone(),
+                };
+                self.define(RustTypeDef::Enum(def))
+            }
+            Shape::DateTime => "php_serde::PhpDateTime".to_owned(),
+            Shape::Optional(shape) => format!("Option<{}>", self.ty(shape, hint)),
+        }
+    }
+
+    /// Add a type definition, renamed if another type has its name, and
+    /// return its name.
+    fn define(&mut self, mut def: RustTypeDef) -> String {
+        let base = def.name().to_owned();
+        let mut n = 1;
+        loop {
+            match self.types.iter().find(|other| other.name() == def.name()) {
+                Some(other) if *other == def => return def.name().to_owned(),
+                None if def.name() != self.root => break,
+                _ => {}
+            }
+            n += 1;
+            *def.name_mut() = format!("{base}{n}");
+        }
+        let name = def.name().to_owned();
+        self.types.push(def);
+        name
+    }
+}
+
+/// Name of a struct or enum, named `name` unless it is for a PHP class,
+/// and the class name to rename it to.
+fn type_name(name: &str, class: Option<&str>) -> (String, Option<String>) {
+    let Some(class) = class else {
+        return (name.to_owned(), None);
+    };
+    let name = pascal_case(class.rsplit('\\').next().unwrap_or(class));
+    let rename = (name != class).then(|| class.to_owned());
+    (name, rename)
+}
+
+/// Convert a PHP name to a Rust type name.
+fn pascal_case(name: &str) -> String {
+    let mut out = String::new();
+    for word in name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+    {
+        let mut chars = word.chars();
+        out.extend(chars.next().map(|c| c.to_ascii_uppercase()));
+        out.push_str(chars.as_str());
+    }
+    if out.is_empty() || out.starts_with(|c: char| c.is_ascii_digit()) {
+        out.insert_str(0, "Type");
+    }
+    out
+}
+
+/// Name of the elements of a list named `name`.
+fn singular(name: &str) -> String {
+    match name.strip_suffix('s') {
+        Some(stem) if stem.len() > 1 && !stem.ends_with('s') => stem.to_owned(),
+        _ => format!("{name}Item"),
+    }
+}
+
+/// Convert a PHP name to a Rust field name.
+fn field_name(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut out = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_ascii_alphanumeric() {
+            if !out.is_empty() && !out.ends_with('_') {
+                out.push('_');
+            }
+            continue;
+        }
+        if c.is_ascii_uppercase() && i > 0 {
+            let prev = chars[i - 1];
+            let next_lower = chars.get(i + 1).is_some_and(char::is_ascii_lowercase);
+            if (prev.is_ascii_lowercase()
+                || prev.is_ascii_digit()
+                || (prev.is_ascii_uppercase() && next_lower))
+                && !out.is_empty()
+                && !out.ends_with('_')
+            {
+                out.push('_');
+            }
+        }
+        out.push(c.to_ascii_lowercase());
+    }
+    let mut out = out.trim_end_matches('_').to_owned();
+    if out.is_empty() || out.starts_with(|c: char| c.is_ascii_digit()) {
+        out.insert_str(0, "field_");
+    }
+
+    match out.as_str() {
+        "crate" | "self" | "super" => out.push('_'),
+        "as" | "async" | "await" | "break" | "const" | "continue" | "dyn" | "else" | "enum"
+        | "extern" | "false" | "fn" | "for" | "if" | "impl" | "in" | "let" | "loop" | "match"
+        | "mod" | "move" | "mut" | "pub" | "ref" | "return" | "static" | "struct" | "trait"
+        | "true" | "type" | "unsafe" | "use" | "where" | "while" => out.insert_str(0, "r#"),
+        _ => {}
+    }
+    out
+}
+
+fn unsupported(reason: &str) -> Error {
+    Error::DeserializationFailed(format!("cannot infer a struct: {reason}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{field_name, infer, infer_all};
+    use crate::ErrorKind;
+
+    #[test]
+    fn definitions() {
+        let first: &[u8] = br#"a:1:{i:0;O:8:"App\User":7:{s:2:"id";i:1;s:8:"userName";s:3:"bob";s:4:"type";i:3;s:5:"score";i:1;s:4:"suit";E:15:"App\Suit:Hearts";s:5:"roles";a:1:{i:5;s:5:"admin";}s:7:"address";a:1:{s:4:"city";s:5:"Paris";}}}"#;
+        let second: &[u8] = br#"a:1:{i:0;O:8:"App\User":6:{s:2:"id";i:2;s:8:"userName";N;s:5:"score";d:1.5;s:4:"suit";E:15:"App\Suit:Spades";s:5:"roles";a:0:{}s:7:"address";a:0:{}}}"#;
+        let def = infer_all([first, second]).unwrap();
+        assert_eq!(
+            def.to_string(),
+            r#"use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename = "App\\User")]
+pub struct User {
+    pub id: i64,
+    #[serde(rename = "userName")]
+    pub user_name: Option<String>,
+    pub r#type: Option<i64>,
+    pub score: f64,
+    pub suit: Suit,
+    pub roles: std::collections::BTreeMap<i64, String>,
+    pub address: Address,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename = "App\\Suit")]
+pub enum Suit {
+    Hearts,
+    Spades,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Address {
+    pub city: Option<String>,
+}
+"#
+        );
+
+        // Types of different shapes under the same name.
+        let def = infer(br#"a:2:{s:1:"a";a:1:{s:4:"item";a:1:{s:1:"x";i:1;}}s:1:"b";a:1:{s:4:"item";a:1:{s:1:"y";b:1;}}}"#).unwrap();
+        let names: Vec<_> = def.types.iter().map(super::RustTypeDef::name).collect();
+        assert_eq!(names, ["Item", "A", "Item2", "B"]);
+
+        assert_eq!(infer(b"a:0:{}").unwrap().fields, []);
+        assert_eq!(infer(b"i:1;").unwrap_err().kind(), ErrorKind::Custom);
+        assert!(infer_all(Vec::<Vec<u8>>::new()).is_err());
+    }
+
+    #[test]
+    fn field_names() {
+        for (php, rust) in [
+            ("id", "id"),
+            ("userName", "user_name"),
+            ("HTTPStatus", "http_status"),
+            ("user-id 2", "user_id_2"),
+            ("42", "field_42"),
+            ("", "field_"),
+            ("type", "r#type"),
+            ("self", "self_"),
+        ] {
+            assert_eq!(field_name(php), rust);
+        }
+    }
+}
@@ -1,52 +1,601 @@
 use crate::error::{Error, Result};
-use ryu::Buffer;
+use crate::key::{KeySerializer, PhpKey};
+use crate::options::{FloatPrecision, IntegerOverflow, SerializerOptions};
+use crate::references::References;
+#[cfg(feature = "rc")]
+use crate::shared;
+use crate::token::{Key, Token};
+use crate::{php_enum, positional, std_class, value};
 use serde::{ser, Serialize};
-use std::io::Write;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, BufWriter, Write};
 
 /// Write out serialization of value.
+///
+/// Output is collected in a buffer and passed on in large chunks, so that
+/// writers such as `File` or `TcpStream` don't need to be wrapped in a
+/// `BufWriter`. See `SerializerOptions::write_buffer_size`.
 #[inline]
 pub fn to_writer<W, T>(writer: W, value: &T) -> Result<()>
 where
     W: Write,
     T: Serialize + ?Sized,
 {
-    let mut ser = Serializer::new(writer);
+    to_writer_with_options(writer, value, &SerializerOptions::default())
+}
+
+/// Write out serialization of value, with non-default options.
+pub fn to_writer_with_options<W, T>(writer: W, value: &T, options: &SerializerOptions) -> Result<()>
+where
+    W: Write,
+    T: Serialize + ?Sized,
+{
+    if options.write_buffer_size == 0 {
+        return serialize_into(writer, value, options);
+    }
+
+    let mut buffered = BufWriter::with_capacity(options.write_buffer_size, writer);
+    serialize_into(&mut buffered, value, options)?;
+    buffered
+        .into_inner()
+        .map(|_| ())
+        .map_err(|e| Error::WriteSerialized(e.into_error()))
+}
+
+/// Write out serialization of value, without buffering.
+pub fn serialize_into<W, T>(writer: W, value: &T, options: &SerializerOptions) -> Result<()>
+where
+    W: Write,
+    T: Serialize + ?Sized,
+{
+    let mut ser = PhpSerializer::with_options(writer, options.clone());
     value.serialize(&mut ser)
 }
 
 /// Write serialization of value into byte vector.
 #[inline]
 pub fn to_vec<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: Serialize + ?Sized,
+{
+    to_vec_with_options(value, &SerializerOptions::default())
+}
+
+/// Write serialization of value into byte vector, with non-default options.
+pub fn to_vec_with_options<T>(value: &T, options: &SerializerOptions) -> Result<Vec<u8>>
 where
     T: Serialize + ?Sized,
 {
     let mut buf = Vec::new();
-    to_writer(&mut buf, value)?;
+    serialize_into(&mut buf, value, options)?;
     Ok(buf)
 }
 
-/// Central serializer structure.
+/// Write serialization of value into a buffer, returning the number of bytes
+/// written.
+///
+/// Fails with `Error::BufferTooSmall` if the output does not fit, leaving
+/// the buffer partially written. With default options, nothing is
+/// allocated.
+///
+/// ```rust
+/// let mut buf = [0; 32];
+/// let len = php_serde::to_slice(&(1, 2), &mut buf).unwrap();
+/// assert_eq!(&buf[..len], b"a:2:{i:0;i:1;i:1;i:2;}");
+/// ```
+pub fn to_slice<T>(value: &T, buf: &mut [u8]) -> Result<usize>
+where
+    T: Serialize + ?Sized,
+{
+    let capacity = buf.len();
+    let mut rest = buf;
+    match serialize_into(&mut rest, value, &SerializerOptions::default()) {
+        Ok(()) => Ok(capacity - rest.len()),
+        Err(Error::WriteSerialized(e)) if e.kind() == io::ErrorKind::WriteZero => {
+            Err(Error::BufferTooSmall)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Write serialization of value into a string.
+///
+/// Fails if the output is not valid UTF-8, which can only happen if byte
+/// strings are serialized.
+///
+/// ```rust
+/// let s = php_serde::to_string(&vec!["a"]).unwrap();
+/// assert_eq!(s, r#"a:1:{i:0;s:1:"a";}"#);
+/// ```
+pub fn to_string<T>(value: &T) -> Result<String>
+where
+    T: Serialize + ?Sized,
+{
+    String::from_utf8(to_vec(value)?).map_err(|_| not_utf8())
+}
+
+/// Write out serialization of value into a `fmt::Write` target, such as a
+/// `fmt::Formatter`.
+///
+/// Fails if the output is not valid UTF-8, in which case the valid part
+/// before the offending string may already have been written.
+pub fn to_fmt_writer<W, T>(writer: W, value: &T) -> Result<()>
+where
+    W: fmt::Write,
+    T: Serialize + ?Sized,
+{
+    let mut adapter = FmtWriter {
+        inner: writer,
+        not_utf8: false,
+    };
+    match serialize_into(&mut adapter, value, &SerializerOptions::default()) {
+        Err(_) if adapter.not_utf8 => Err(not_utf8()),
+        result => result,
+    }
+}
+
+fn not_utf8() -> Error {
+    Error::SerializationFailed("serialized output is not valid UTF-8".to_owned())
+}
+
+/// Adapter writing to a `fmt::Write` target.
+///
+/// The serializer writes each string in one piece, and everything else is
+/// ASCII, so every write is checked for UTF-8 on its own.
 #[derive(Debug)]
-struct Serializer<W> {
-    output: W,
+struct FmtWriter<W> {
+    inner: W,
+    /// Whether a write failed because it was not valid UTF-8.
+    not_utf8: bool,
+}
+
+impl<W: fmt::Write> Write for FmtWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let s = std::str::from_utf8(buf).map_err(|e| {
+            self.not_utf8 = true;
+            io::Error::new(io::ErrorKind::InvalidData, e)
+        })?;
+        self.inner.write_str(s).map_err(io::Error::other)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
 }
 
-impl<W> Serializer<W> {
-    /// Create new serializer on writer.
+/// Write serialization of value into `buf`, replacing its contents.
+///
+/// The capacity of `buf` is kept, so reusing it for many values avoids
+/// allocating each time. On error, `buf` holds the partial output.
+///
+/// ```rust
+/// let mut buf = Vec::new();
+/// for i in 0..3 {
+///     php_serde::to_vec_in(&i, &mut buf).unwrap();
+///     assert_eq!(buf, format!("i:{i};").as_bytes());
+/// }
+/// ```
+pub fn to_vec_in<T>(value: &T, buf: &mut Vec<u8>) -> Result<()>
+where
+    T: Serialize + ?Sized,
+{
+    buf.clear();
+    serialize_into(buf, value, &SerializerOptions::default())
+}
+
+/// Compute the length of the serialization of value, without writing it.
+///
+/// This runs the whole serialization, only without output. Serializing
+/// into a vector allocated with this size, see `to_vec_in`, avoids growing
+/// it, at the cost of serializing twice.
+///
+/// ```rust
+/// use php_serde::{serialized_size, to_vec, to_vec_in};
+///
+/// let v = vec!["a", "bc"];
+/// assert_eq!(serialized_size(&v).unwrap(), to_vec(&v).unwrap().len());
+///
+/// let mut buf = Vec::with_capacity(serialized_size(&v).unwrap());
+/// to_vec_in(&v, &mut buf).unwrap();
+/// assert_eq!(buf.capacity(), buf.len());
+/// ```
+pub fn serialized_size<T>(value: &T) -> Result<usize>
+where
+    T: Serialize + ?Sized,
+{
+    serialized_size_with_options(value, &SerializerOptions::default())
+}
+
+/// Compute the length of the serialization of value with non-default
+/// options, without writing it.
+pub fn serialized_size_with_options<T>(value: &T, options: &SerializerOptions) -> Result<usize>
+where
+    T: Serialize + ?Sized,
+{
+    let mut counter = ByteCounter(0);
+    serialize_into(&mut counter, value, options)?;
+    Ok(counter.0)
+}
+
+/// Writer discarding its input, counting the bytes.
+#[derive(Debug)]
+struct ByteCounter(usize);
+
+impl Write for ByteCounter {
     #[inline]
-    fn new(output: W) -> Self {
-        Serializer { output }
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
     }
 }
 
-/// Not implemented helper struct.
+/// PHP serializer.
 ///
-/// "Implements" various traits required for serialization that are not supported. Always fails
-/// with an error message if called.
+/// Writes the format read by PHP's `unserialize` function to any
+/// `io::Write`. The functions of this crate use it internally, it can also
+/// be driven directly, e.g. to wrap it with `serde_path_to_error`.
+///
+/// The output is not buffered, see `to_writer` for the buffered variant.
+///
+/// ```rust
+/// use php_serde::PhpSerializer;
+/// use serde::Serialize;
+///
+/// let mut serializer = PhpSerializer::new(Vec::new());
+/// vec![1, 2].serialize(&mut serializer).unwrap();
+/// assert_eq!(serializer.into_inner(), b"a:2:{i:0;i:1;i:1;i:2;}");
+/// ```
 #[derive(Debug)]
-struct NotImplemented;
+pub struct PhpSerializer<W> {
+    output: W,
+    options: SerializerOptions,
+    /// Class name for the map or struct serialized next, see `StdClass`.
+    class: Option<&'static str>,
+    /// Whether the unit variant serialized next is a PHP enum case, see
+    /// `PhpEnum`.
+    enum_case: bool,
+    /// Whether the struct serialized next is written as a numeric array,
+    /// see `Positional`.
+    positional: bool,
+    /// Whether a `None` was serialized, to leave out `None` fields, see
+    /// `SerializerOptions::omit_none_fields`.
+    none: bool,
+    /// Nesting depth of the value being written.
+    depth: usize,
+    /// Values written so far, to refer back to earlier ones.
+    references: References,
+}
+
+impl<W> PhpSerializer<W> {
+    /// Create a serializer writing to `output`, with default options.
+    pub fn new(output: W) -> Self {
+        PhpSerializer::with_options(output, SerializerOptions::default())
+    }
+
+    /// Create a serializer writing to `output`.
+    #[inline]
+    pub fn with_options(output: W, options: SerializerOptions) -> Self {
+        PhpSerializer {
+            output,
+            options,
+            class: None,
+            enum_case: false,
+            positional: false,
+            none: false,
+            depth: 0,
+            references: References::new(),
+        }
+    }
+
+    /// Unwrap the writer.
+    pub fn into_inner(self) -> W {
+        self.output
+    }
+
+    /// Create a serializer writing to `output` instead, at the same depth,
+    /// for parts that are buffered before they are written.
+    fn buffered<V>(&self, output: V) -> PhpSerializer<V> {
+        let mut serializer = PhpSerializer::with_options(output, self.options.clone());
+        serializer.depth = self.depth;
+        serializer.references = References::buffered();
+        serializer
+    }
+}
+
+impl<W> PhpSerializer<W>
+where
+    W: Write,
+{
+    /// Write a string as is, without referring back to an earlier copy.
+    fn write_string(&mut self, v: &[u8]) -> Result<()> {
+        write_integer(&mut self.output, b"s:", v.len(), b":\"")?;
+        self.output.write_all(v).map_err(Error::WriteSerialized)?;
+        self.output
+            .write_all(b"\";")
+            .map_err(Error::WriteSerialized)
+    }
+
+    /// Write an array key, which PHP does not count as a value.
+    fn write_key(&mut self, key: &PhpKey) -> Result<()> {
+        match key {
+            PhpKey::Int(n) => write_integer(&mut self.output, b"i:", *n, b";"),
+            PhpKey::String(s) => self.write_string(s),
+        }
+    }
+
+    /// Serialize an element of an array or object, one level deeper,
+    /// enforcing `SerializerOptions::max_depth`.
+    fn nested<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        if self.depth >= self.options.max_depth {
+            return Err(Error::DepthLimitExceeded);
+        }
+
+        self.references.next_value();
+
+        self.depth += 1;
+        let rval = value.serialize(&mut *self);
+        self.depth -= 1;
+        rval
+    }
+
+    /// Write an enum case record.
+    fn write_enum_case(&mut self, class: &[u8], case: &[u8]) -> Result<()> {
+        write!(self.output, "E:{}:\"", class.len() + case.len() + 1)
+            .map_err(Error::WriteSerialized)?;
+        self.output
+            .write_all(class)
+            .map_err(Error::WriteSerialized)?;
+        self.output
+            .write_all(b":")
+            .map_err(Error::WriteSerialized)?;
+        self.output
+            .write_all(case)
+            .map_err(Error::WriteSerialized)?;
+        self.output
+            .write_all(b"\";")
+            .map_err(Error::WriteSerialized)
+    }
+
+    /// Serializer for map keys, with the coercion of the options.
+    fn key_serializer(&self) -> KeySerializer {
+        KeySerializer {
+            coerce_numeric: self.options.coerce_numeric_keys,
+        }
+    }
+
+    /// Write the header of the single entry array holding an enum variant,
+    /// and the variant name as its key.
+    fn write_variant_key(&mut self, variant: &'static str) -> Result<()> {
+        if self.enum_case {
+            self.enum_case = false;
+            return Err(Error::SerializationFailed(
+                "`PhpEnum` must wrap a unit variant".to_owned(),
+            ));
+        }
+        self.write_map_header(1)?;
+        self.write_key(&variant.serialize(self.key_serializer())?)
+    }
+
+    /// Enter the fields of a tuple or struct variant, like `nested`.
+    fn enter_variant(&mut self, variant: &'static str) -> Result<()> {
+        self.write_variant_key(variant)?;
+        if self.depth >= self.options.max_depth {
+            return Err(Error::DepthLimitExceeded);
+        }
+        self.references.next_value();
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// Leave the fields of a tuple or struct variant, closing the array
+    /// around them.
+    fn leave_variant(&mut self) -> Result<()> {
+        self.depth -= 1;
+        self.output.write_all(b"}").map_err(Error::WriteSerialized)
+    }
+
+    /// Write an integer PHP cannot represent, given as its decimal digits
+    /// and as the nearest float, see `IntegerOverflow`.
+    fn serialize_out_of_range(&mut self, digits: String, approx: f64) -> Result<()> {
+        match self.options.integer_overflow {
+            IntegerOverflow::Error => Err(Error::IntegerOutOfRange(digits)),
+            IntegerOverflow::Float => ser::Serializer::serialize_f64(self, approx),
+            IntegerOverflow::String => ser::Serializer::serialize_str(self, &digits),
+        }
+    }
+
+    /// Write a 128-bit integer PHP cannot represent.
+    ///
+    /// Unlike other integers these are not rejected, since values beyond
+    /// `i64` are expected: they are written as strings, or as floats if
+    /// `IntegerOverflow::Float` is chosen.
+    fn serialize_wide_integer(&mut self, digits: String, approx: f64) -> Result<()> {
+        match self.options.integer_overflow {
+            IntegerOverflow::Float => ser::Serializer::serialize_f64(self, approx),
+            IntegerOverflow::Error | IntegerOverflow::String => {
+                ser::Serializer::serialize_str(self, &digits)
+            }
+        }
+    }
+
+    /// Write a single token for `TokenWriter`, which checks that tokens
+    /// come in a valid order.
+    pub(crate) fn write_token(&mut self, token: Token<'_>) -> Result<()> {
+        match token {
+            Token::Null => ser::Serializer::serialize_unit(self),
+            Token::Bool(v) => ser::Serializer::serialize_bool(self, v),
+            Token::Int(v) | Token::Key(Key::Int(v)) => ser::Serializer::serialize_i64(self, v),
+            Token::Float(v) => ser::Serializer::serialize_f64(self, v),
+            Token::Str(v) | Token::Key(Key::Bytes(v)) => self.write_string(v),
+            Token::ArrayStart(len) => write_integer(&mut self.output, b"a:", len, b":{"),
+            Token::ObjectStart { class, len } => write_object_header(&mut self.output, class, len),
+            Token::ArrayEnd | Token::ObjectEnd => {
+                self.output.write_all(b"}").map_err(Error::WriteSerialized)
+            }
+            Token::Custom { class, payload } => {
+                write!(self.output, "C:{}:\"", class.len()).map_err(Error::WriteSerialized)?;
+                self.output
+                    .write_all(class)
+                    .map_err(Error::WriteSerialized)?;
+                write_integer(&mut self.output, b"\":", payload.len(), b":{")?;
+                self.output
+                    .write_all(payload)
+                    .map_err(Error::WriteSerialized)?;
+                self.output.write_all(b"}").map_err(Error::WriteSerialized)
+            }
+            Token::Enum { class, case } => self.write_enum_case(class, case),
+            Token::Reference(number) => write_integer(&mut self.output, b"r:", number, b";"),
+            Token::MutableReference(number) => write_integer(&mut self.output, b"R:", number, b";"),
+        }
+    }
+
+    /// Write the header of an array, or of an object if a class is pending.
+    fn write_map_header(&mut self, len: usize) -> Result<()> {
+        match self.class.take() {
+            Some(class) => write!(self.output, "O:{}:\"{class}\":{len}:{{", class.len())
+                .map_err(Error::WriteSerialized),
+            None => write_integer(&mut self.output, b"a:", len, b":{"),
+        }
+    }
+}
+
+/// Write a float the way PHP formats it for `serialize`.
+///
+/// Digits are placed like PHP's `php_gcvt`: in exponential notation such as
+/// `1.0E+25` if the exponent is below -4 or at least the precision,
+/// otherwise in plain notation without a trailing `.0`.
+pub fn write_float<W: Write>(output: &mut W, v: f64, precision: FloatPrecision) -> io::Result<()> {
+    if v.is_nan() {
+        return output.write_all(b"NAN");
+    }
+    if v.is_infinite() {
+        return output.write_all(if v > 0.0 { b"INF" } else { b"-INF" });
+    }
+
+    // The digits are formatted on the stack: the shortest ones by `ryu`,
+    // a fixed number in scientific notation, where 255 digits take up to
+    // 262 bytes.
+    let mut shortest = ryu::Buffer::new();
+    let mut buf = [0_u8; 272];
+    let (formatted, max_point) = match precision {
+        FloatPrecision::Shortest => (shortest.format_finite(v), 17),
+        FloatPrecision::Significant(digits) => {
+            let digits = digits.max(1);
+            let mut cursor = io::Cursor::new(&mut buf[..]);
+            write!(cursor, "{v:.*e}", usize::from(digits - 1))?;
+            let len = usize::try_from(cursor.position()).expect("position within buffer");
+            let formatted = std::str::from_utf8(&buf[..len]).expect("ASCII float");
+            (formatted, i32::from(digits))
+        }
+    };
+
+    let formatted = match formatted.strip_prefix('-') {
+        Some(rest) => {
+            output.write_all(b"-")?;
+            rest
+        }
+        None => formatted,
+    };
+    let (mantissa, exponent) = formatted.split_once('e').unwrap_or((formatted, "0"));
+    let exponent: i32 = exponent.parse().expect("integer exponent");
+    let (integral, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+
+    // Collect the digits without leading zeros, keeping track of where the
+    // decimal point goes.
+    let mut digits = [0_u8; 272];
+    let mut n = 0;
+    let mut point = i32::try_from(integral.len()).expect("short integral part") + exponent;
+    for &c in integral.as_bytes().iter().chain(fraction.as_bytes()) {
+        if n == 0 && c == b'0' {
+            point -= 1;
+        } else {
+            digits[n] = c;
+            n += 1;
+        }
+    }
+
+    if n == 0 {
+        return output.write_all(b"0");
+    }
+    let digits = std::str::from_utf8(&digits[..n]).expect("ASCII digits");
+    write_digits(output, digits, point, max_point)
+}
+
+/// Write significant `digits` of a float, with the decimal point at `point`
+/// counted from the first digit.
+fn write_digits<W: Write>(
+    output: &mut W,
+    digits: &str,
+    point: i32,
+    max_point: i32,
+) -> io::Result<()> {
+    let digits = digits.trim_end_matches('0');
+
+    if point < -3 || point > max_point {
+        let (first, rest) = digits.split_at(1);
+        let rest = if rest.is_empty() { "0" } else { rest };
+        let exponent = point - 1;
+        let sign = if exponent < 0 { '-' } else { '+' };
+        write!(output, "{first}.{rest}E{sign}{}", exponent.unsigned_abs())
+    } else if point <= 0 {
+        output.write_all(b"0.")?;
+        write_zeros(output, point.unsigned_abs())?;
+        output.write_all(digits.as_bytes())
+    } else {
+        let point = usize::try_from(point).expect("positive decimal point");
+        if digits.len() <= point {
+            output.write_all(digits.as_bytes())?;
+            write_zeros(
+                output,
+                u32::try_from(point - digits.len()).expect("few zeros"),
+            )
+        } else {
+            let (integral, fraction) = digits.split_at(point);
+            output.write_all(integral.as_bytes())?;
+            output.write_all(b".")?;
+            output.write_all(fraction.as_bytes())
+        }
+    }
+}
+
+/// Write `count` zeros.
+fn write_zeros<W: Write>(output: &mut W, count: u32) -> io::Result<()> {
+    const ZEROS: &[u8; 32] = b"00000000000000000000000000000000";
+
+    let mut left = usize::try_from(count).expect("few zeros");
+    while left > 0 {
+        let chunk = left.min(ZEROS.len());
+        output.write_all(&ZEROS[..chunk])?;
+        left -= chunk;
+    }
+    Ok(())
+}
+
+/// Write an integer between `prefix` and `suffix`, e.g. `i:42;`.
+#[inline]
+fn write_integer<W, I>(output: &mut W, prefix: &[u8], n: I, suffix: &[u8]) -> Result<()>
+where
+    W: Write,
+    I: itoa::Integer,
+{
+    output
+        .write_all(prefix)
+        .and_then(|()| output.write_all(itoa::Buffer::new().format(n).as_bytes()))
+        .and_then(|()| output.write_all(suffix))
+        .map_err(Error::WriteSerialized)
+}
 
-impl<'a, W> ser::Serializer for &'a mut Serializer<W>
+impl<'a, W> ser::Serializer for &'a mut PhpSerializer<W>
 where
     W: Write,
 {
@@ -57,10 +606,10 @@ where
     type SerializeSeq = NumericArraySerializer<'a, W>;
     type SerializeTuple = NumericArraySerializer<'a, W>;
     type SerializeTupleStruct = NumericArraySerializer<'a, W>;
-    type SerializeTupleVariant = NotImplemented;
-    type SerializeMap = Self;
-    type SerializeStruct = Self;
-    type SerializeStructVariant = NotImplemented;
+    type SerializeTupleVariant = NumericArraySerializer<'a, W>;
+    type SerializeMap = MapSerializer<'a, W>;
+    type SerializeStruct = StructSerializer<'a, W>;
+    type SerializeStructVariant = StructSerializer<'a, W>;
 
     #[inline]
     fn serialize_bool(self, v: bool) -> Result<()> {
@@ -89,9 +638,15 @@ where
 
     #[inline]
     fn serialize_i64(self, v: i64) -> Result<()> {
-        // We rely on Rust having a "standard" display implementation for
-        // `i64` types, which is a reasonable assumption.
-        write!(self.output, "i:{};", v).map_err(Error::WriteSerialized)
+        write_integer(&mut self.output, b"i:", v, b";")
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn serialize_i128(self, v: i128) -> Result<()> {
+        match i64::try_from(v) {
+            Ok(v) => self.serialize_i64(v),
+            Err(_) => self.serialize_wide_integer(v.to_string(), v as f64),
+        }
     }
 
     #[inline]
@@ -111,7 +666,19 @@ where
 
     #[inline]
     fn serialize_u64(self, v: u64) -> Result<()> {
-        write!(self.output, "i:{};", v).map_err(Error::WriteSerialized)
+        match i64::try_from(v) {
+            Ok(v) => self.serialize_i64(v),
+            #[allow(clippy::cast_precision_loss)]
+            Err(_) => self.serialize_out_of_range(v.to_string(), v as f64),
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn serialize_u128(self, v: u128) -> Result<()> {
+        match i64::try_from(v) {
+            Ok(v) => self.serialize_i64(v),
+            Err(_) => self.serialize_wide_integer(v.to_string(), v as f64),
+        }
     }
 
     #[inline]
@@ -121,10 +688,11 @@ where
 
     #[inline]
     fn serialize_f64(self, v: f64) -> Result<()> {
-        // For some floats php `serialize` output differs, for example
-        // php will serialize 0 as "d:0;", not "d:0.0;"
-        // but deserialization must always produce the correct value
-        write!(self.output, "d:{};", Buffer::new().format(v)).map_err(Error::WriteSerialized)
+        self.output
+            .write_all(b"d:")
+            .and_then(|()| write_float(&mut self.output, v, self.options.float_precision))
+            .and_then(|()| self.output.write_all(b";"))
+            .map_err(Error::WriteSerialized)
     }
 
     #[inline]
@@ -139,13 +707,19 @@ where
 
     #[inline]
     fn serialize_bytes(self, v: &[u8]) -> Result<()> {
-        write!(self.output, "s:{}:\"", v.len()).map_err(Error::WriteSerialized)?;
-        self.output.write_all(v).map_err(Error::WriteSerialized)?;
-        write!(self.output, "\";").map_err(Error::WriteSerialized)
+        if let Some(min_length) = self.options.deduplicate_strings {
+            if v.len() >= min_length {
+                if let Some(number) = self.references.find_string(v) {
+                    return write_integer(&mut self.output, b"r:", number, b";");
+                }
+            }
+        }
+        self.write_string(v)
     }
 
     #[inline]
     fn serialize_none(self) -> Result<()> {
+        self.none = true;
         self.serialize_unit()
     }
 
@@ -172,20 +746,77 @@ where
     #[inline]
     fn serialize_unit_variant(
         self,
-        _name: &'static str,
+        name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
     ) -> Result<()> {
-        Err(Error::MissingFeature(
-            "Serialization of enums is not supported. If you need C-style enums serialized, look at `serde_repr`.",
-        ))
+        if self.enum_case {
+            self.enum_case = false;
+            return self.write_enum_case(name.as_bytes(), variant.as_bytes());
+        }
+
+        // Like PHP code storing e.g. a status as `"active"`.
+        self.serialize_str(variant)
     }
 
     #[inline]
-    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
+        if name == std_class::TOKEN {
+            self.class = Some(std_class::STD_CLASS);
+            value.serialize(&mut *self)?;
+
+            // The class is consumed by the next map or struct header.
+            return match self.class.take() {
+                Some(_) => Err(Error::SerializationFailed(
+                    "`StdClass` must wrap a map, struct or sequence".to_owned(),
+                )),
+                None => Ok(()),
+            };
+        }
+
+        if name == positional::TOKEN {
+            self.positional = true;
+            value.serialize(&mut *self)?;
+
+            // The flag is consumed by the next struct header.
+            if self.positional {
+                self.positional = false;
+                return Err(Error::SerializationFailed(
+                    "`Positional` must wrap a struct".to_owned(),
+                ));
+            }
+            return Ok(());
+        }
+
+        if name == php_enum::TOKEN {
+            self.enum_case = true;
+            value.serialize(&mut *self)?;
+
+            // The flag is consumed by the next unit variant.
+            if self.enum_case {
+                self.enum_case = false;
+                return Err(Error::SerializationFailed(
+                    "`PhpEnum` must wrap a unit variant".to_owned(),
+                ));
+            }
+            return Ok(());
+        }
+
+        #[cfg(feature = "rc")]
+        if name == shared::TOKEN {
+            let address = std::ptr::from_ref(value).cast::<u8>() as usize;
+            if let Some(number) = self.references.find_shared(address) {
+                // Wrappers such as `StdClass` apply to the first occurrence.
+                self.class = None;
+                self.positional = false;
+                self.enum_case = false;
+                return write_integer(&mut self.output, b"r:", number, b";");
+            }
+        }
+
         // We just "unpack" newtypes when deserializing.
         value.serialize(self)
     }
@@ -195,15 +826,17 @@ where
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-        _value: &T,
+        variant: &'static str,
+        value: &T,
     ) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        Err(Error::MissingFeature(
-            "Serialization of enums is not supported. If you need C-style enums serialized, look at `serde_repr`.",
-        ))
+        // Like serde's default representation elsewhere, e.g. in JSON: an
+        // array with the variant name as its only key.
+        self.write_variant_key(variant)?;
+        self.nested(value)?;
+        self.output.write_all(b"}").map_err(Error::WriteSerialized)
     }
 
     #[inline]
@@ -217,7 +850,7 @@ where
         // accident.
         if let Some(n) = len {
             // We can assume sequences are all of the same type.
-            write!(self.output, "a:{}:{{", n).map_err(Error::WriteSerialized)?;
+            self.write_map_header(n)?;
             Ok(NumericArraySerializer::new(self))
         } else {
             Err(Error::LengthRequired)
@@ -243,28 +876,55 @@ where
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-        _len: usize,
+        variant: &'static str,
+        len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
-        Err(Error::MissingFeature(
-            "Serialization of enums is not supported. If you need C-style enums serialized, look at `serde_repr`.",
-        ))
+        self.enter_variant(variant)?;
+        let mut fields = self.serialize_tuple(len)?;
+        fields.variant = true;
+        Ok(fields)
     }
 
     #[inline]
     fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
-        if let Some(n) = len {
-            write!(self.output, "a:{}:{{", n).map_err(Error::WriteSerialized)?;
-            // No need to count elements, thus no added state.
-            Ok(self)
-        } else {
-            Err(Error::LengthRequired)
-        }
+        // The header is written once the first key is known, since it
+        // might turn the map into an object. Maps of unknown length, e.g.
+        // structs with flattened fields, are buffered to count the entries.
+        Ok(MapSerializer::new(self, len))
     }
 
     #[inline]
-    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
-        self.serialize_map(Some(len))
+    fn serialize_struct(self, name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
+        // A pending class, e.g. from `StdClass`, takes precedence.
+        if self.options.structs_as_objects && self.class.is_none() {
+            self.class = Some(name);
+        }
+        let index = if self.positional || self.options.structs_as_lists {
+            self.positional = false;
+            Some(0)
+        } else {
+            None
+        };
+
+        // Leaving out fields changes the length in the header, so it is
+        // written once all fields are known.
+        let buffered = if self.options.omit_none_fields && index.is_none() {
+            Some(BufferedFields {
+                class: self.class.take(),
+                entries: Vec::new(),
+                len: 0,
+            })
+        } else {
+            self.write_map_header(len)?;
+            None
+        };
+
+        Ok(StructSerializer {
+            index,
+            buffered,
+            variant: false,
+            serializer: self,
+        })
     }
 
     #[inline]
@@ -272,12 +932,13 @@ where
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-        _len: usize,
+        variant: &'static str,
+        len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        Err(Error::MissingFeature(
-            "Serialization of enums is not supported. If you need C-style enums serialized, look at `serde_repr`.",
-        ))
+        self.enter_variant(variant)?;
+        let mut fields = self.serialize_struct(variant, len)?;
+        fields.variant = true;
+        Ok(fields)
     }
 }
 
@@ -288,14 +949,17 @@ pub struct NumericArraySerializer<'a, W> {
     // and carry their own terminator. However, we still need to count
     // the elements.
     index: usize,
-    serializer: &'a mut Serializer<W>,
+    /// Whether these are the fields of a tuple variant.
+    variant: bool,
+    serializer: &'a mut PhpSerializer<W>,
 }
 
 impl<'a, W> NumericArraySerializer<'a, W> {
     /// Create new numeric array helper.
-    fn new(serializer: &'a mut Serializer<W>) -> Self {
+    fn new(serializer: &'a mut PhpSerializer<W>) -> Self {
         NumericArraySerializer {
             index: 0,
+            variant: false,
             serializer,
         }
     }
@@ -314,7 +978,7 @@ where
     {
         // Output-format is just index directly followed by value.
         self.index.serialize(&mut *self.serializer)?;
-        value.serialize(&mut *self.serializer)?;
+        self.serializer.nested(value)?;
         self.index += 1;
         Ok(())
     }
@@ -323,7 +987,11 @@ where
         self.serializer
             .output
             .write_all(b"}")
-            .map_err(Error::WriteSerialized)
+            .map_err(Error::WriteSerialized)?;
+        if self.variant {
+            self.serializer.leave_variant()?;
+        }
+        Ok(())
     }
 }
 
@@ -365,27 +1033,130 @@ where
     }
 }
 
-impl ser::SerializeTupleVariant for NotImplemented {
+impl<W> ser::SerializeTupleVariant for NumericArraySerializer<'_, W>
+where
+    W: Write,
+{
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T>(&mut self, _value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        Err(Error::MissingFeature(
-            "Serialization of enums is not supported. If you need C-style enums serialized, look at `serde_repr`.",
-        ))
+        ser::SerializeSeq::serialize_element(self, value)
     }
 
     fn end(self) -> Result<()> {
-        Err(Error::MissingFeature(
-            "Serialization of enums is not supported. If you need C-style enums serialized, look at `serde_repr`.",
-        ))
+        ser::SerializeSeq::end(self)
     }
 }
 
-impl<'a, W> ser::SerializeMap for &'a mut Serializer<W>
+/// Progress of a `MapSerializer`.
+#[derive(Debug, Eq, PartialEq)]
+enum MapState {
+    /// Nothing written yet, not even the header.
+    Start,
+    /// The class key was seen, the next value is the class name.
+    Class,
+    /// The class name is known, the header is not written yet.
+    Object(Vec<u8>),
+    /// The payload key was seen, the next value is the custom payload.
+    Payload(Vec<u8>),
+    /// The case key was seen, the next value is the enum case name.
+    Case(Vec<u8>),
+    /// Header written, writing entries.
+    Entries,
+    /// A complete custom serialized object or enum case was written.
+    Done,
+}
+
+/// Helper structure for associative arrays and objects.
+///
+/// A map starting with an entry keyed `value::CLASS_KEY` is written as an
+/// object of that class, with the remaining entries as its properties. If the
+/// class is followed by an entry keyed `value::PAYLOAD_KEY` or
+/// `value::CASE_KEY`, a custom serialized object or an enum case is written
+/// instead.
+#[derive(Debug)]
+pub struct MapSerializer<'a, W> {
+    serializer: &'a mut PhpSerializer<W>,
+    /// Number of entries, if known up front.
+    len: Option<usize>,
+    state: MapState,
+    /// Entries with their serialized values, if they are written once
+    /// complete: sorted, see `SerializerOptions::sort_map_keys`, or after a
+    /// header counting them if the length is not known.
+    buffered: Option<Vec<(PhpKey, Vec<u8>)>>,
+    /// Class of an object whose header is written once complete.
+    class: Option<Vec<u8>>,
+}
+
+/// Serialize a string-like value into raw bytes, for class names and payloads.
+fn raw_bytes<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: ?Sized + Serialize,
+{
+    // The key serializer accepts all string-like values.
+    let keys = KeySerializer {
+        coerce_numeric: false,
+    };
+    Ok(match value.serialize(keys)? {
+        PhpKey::String(s) => s,
+        PhpKey::Int(n) => n.to_string().into_bytes(),
+    })
+}
+
+impl<'a, W> MapSerializer<'a, W>
+where
+    W: Write,
+{
+    /// Create new map helper for `len` entries, if known.
+    fn new(serializer: &'a mut PhpSerializer<W>, len: Option<usize>) -> Self {
+        let buffered = if serializer.options.sort_map_keys || len.is_none() {
+            Some(Vec::with_capacity(len.unwrap_or(0)))
+        } else {
+            None
+        };
+        MapSerializer {
+            serializer,
+            len,
+            state: MapState::Start,
+            buffered,
+            class: None,
+        }
+    }
+
+    /// Write the array or object header, if not done yet.
+    ///
+    /// Without a known length, the header is left to `end`.
+    fn write_header(&mut self) -> Result<()> {
+        match (
+            std::mem::replace(&mut self.state, MapState::Entries),
+            self.len,
+        ) {
+            (MapState::Start, Some(len)) => self.serializer.write_map_header(len)?,
+            (MapState::Start, None) => {
+                self.class = self.serializer.class.take().map(|c| c.as_bytes().to_vec());
+            }
+            (MapState::Object(class), Some(len)) => {
+                write_object_header(&mut self.serializer.output, &class, len.saturating_sub(1))?;
+            }
+            (MapState::Object(class), None) => self.class = Some(class),
+            (state, _) => self.state = state,
+        }
+        Ok(())
+    }
+}
+
+/// Write the header of an object with `len` properties.
+fn write_object_header<W: Write>(output: &mut W, class: &[u8], len: usize) -> Result<()> {
+    write!(output, "O:{}:\"", class.len()).map_err(Error::WriteSerialized)?;
+    output.write_all(class).map_err(Error::WriteSerialized)?;
+    write!(output, "\":{len}:{{").map_err(Error::WriteSerialized)
+}
+
+impl<W> ser::SerializeMap for MapSerializer<'_, W>
 where
     W: Write,
 {
@@ -396,22 +1167,146 @@ where
     where
         T: ?Sized + Serialize,
     {
-        key.serialize(&mut **self)
+        // Keys are coerced the same way PHP would, e.g. `"1"` becomes `1`.
+        let key = key.serialize(self.serializer.key_serializer())?;
+
+        match &mut self.state {
+            MapState::Start if key.as_bytes() == Some(value::CLASS_KEY.as_bytes()) => {
+                // An explicit class takes precedence over a pending one.
+                self.serializer.class = None;
+                self.state = MapState::Class;
+                return Ok(());
+            }
+            MapState::Object(class) if key.as_bytes() == Some(value::PAYLOAD_KEY.as_bytes()) => {
+                self.state = MapState::Payload(std::mem::take(class));
+                return Ok(());
+            }
+            MapState::Object(class) if key.as_bytes() == Some(value::CASE_KEY.as_bytes()) => {
+                self.state = MapState::Case(std::mem::take(class));
+                return Ok(());
+            }
+            MapState::Done => {
+                return Err(Error::SerializationFailed(
+                    "custom serialized objects and enum cases cannot have properties".to_owned(),
+                ))
+            }
+            _ => (),
+        }
+
+        self.write_header()?;
+        match &mut self.buffered {
+            Some(buffered) => {
+                buffered.push((key, Vec::new()));
+                Ok(())
+            }
+            None => self.serializer.write_key(&key),
+        }
     }
 
     fn serialize_value<T>(&mut self, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        match std::mem::replace(&mut self.state, MapState::Entries) {
+            MapState::Class => {
+                self.state = MapState::Object(raw_bytes(value)?);
+                Ok(())
+            }
+            MapState::Payload(class) => {
+                let payload = raw_bytes(value)?;
+
+                let output = &mut self.serializer.output;
+                write!(output, "C:{}:\"", class.len()).map_err(Error::WriteSerialized)?;
+                output.write_all(&class).map_err(Error::WriteSerialized)?;
+                write!(output, "\":{}:{{", payload.len()).map_err(Error::WriteSerialized)?;
+                output.write_all(&payload).map_err(Error::WriteSerialized)?;
+                output.write_all(b"}").map_err(Error::WriteSerialized)?;
+
+                self.state = MapState::Done;
+                Ok(())
+            }
+            MapState::Case(class) => {
+                self.serializer
+                    .write_enum_case(&class, &raw_bytes(value)?)?;
+                self.state = MapState::Done;
+                Ok(())
+            }
+            state => {
+                self.state = state;
+                match self
+                    .buffered
+                    .as_mut()
+                    .and_then(|buffered| buffered.last_mut())
+                {
+                    Some((_, buf)) => {
+                        let mut buffered = self.serializer.buffered(buf);
+                        buffered.nested(value)?;
+                        self.serializer.references.add(&buffered.references);
+                        Ok(())
+                    }
+                    None => self.serializer.nested(value),
+                }
+            }
+        }
     }
 
-    fn end(self) -> Result<()> {
-        self.output.write_all(b"}").map_err(Error::WriteSerialized)
+    fn end(mut self) -> Result<()> {
+        if self.state == MapState::Done {
+            return Ok(());
+        }
+
+        self.write_header()?;
+        if let Some(mut buffered) = self.buffered.take() {
+            if self.serializer.options.sort_map_keys {
+                // Stable, so duplicate keys keep their order.
+                buffered.sort_by(|a, b| a.0.cmp(&b.0));
+            }
+            if self.len.is_none() {
+                let output = &mut self.serializer.output;
+                match &self.class {
+                    Some(class) => write_object_header(output, class, buffered.len())?,
+                    None => write_integer(output, b"a:", buffered.len(), b":{")?,
+                }
+            }
+            for (key, value) in buffered {
+                self.serializer.write_key(&key)?;
+                self.serializer
+                    .output
+                    .write_all(&value)
+                    .map_err(Error::WriteSerialized)?;
+            }
+        }
+
+        self.serializer
+            .output
+            .write_all(b"}")
+            .map_err(Error::WriteSerialized)
     }
 }
 
-impl<'a, W> ser::SerializeStruct for &'a mut Serializer<W>
+/// Helper structure for structs.
+#[derive(Debug)]
+pub struct StructSerializer<'a, W> {
+    /// Index of the next field, if fields are written by position instead
+    /// of by name.
+    index: Option<usize>,
+    /// Fields written so far, if `None` fields are left out.
+    buffered: Option<BufferedFields>,
+    /// Whether these are the fields of a struct variant.
+    variant: bool,
+    serializer: &'a mut PhpSerializer<W>,
+}
+
+/// Fields of a struct, collected before its header is written.
+#[derive(Debug)]
+struct BufferedFields {
+    /// Pending class for the header.
+    class: Option<&'static str>,
+    entries: Vec<u8>,
+    len: usize,
+}
+
+impl<W> ser::SerializeStruct for StructSerializer<'_, W>
 where
     W: Write,
 {
@@ -422,41 +1317,86 @@ where
     where
         T: ?Sized + Serialize,
     {
-        key.serialize(&mut **self)?;
-        value.serialize(&mut **self)?;
-        Ok(())
+        if let Some(buffered) = &mut self.buffered {
+            let start = buffered.entries.len();
+            let mut serializer = self.serializer.buffered(&mut buffered.entries);
+            serializer.write_string(key.as_bytes())?;
+            let value_start = serializer.output.len();
+            serializer.nested(value)?;
+
+            if serializer.none && serializer.output[value_start..] == *b"N;" {
+                buffered.entries.truncate(start);
+            } else {
+                self.serializer.references.add(&serializer.references);
+                buffered.len += 1;
+            }
+            return Ok(());
+        }
+
+        match &mut self.index {
+            Some(index) => {
+                index.serialize(&mut *self.serializer)?;
+                *index += 1;
+            }
+            None => self.serializer.write_string(key.as_bytes())?,
+        }
+        self.serializer.nested(value)
     }
 
     fn end(self) -> Result<()> {
-        self.output.write_all(b"}").map_err(Error::WriteSerialized)
+        if let Some(buffered) = self.buffered {
+            self.serializer.class = buffered.class;
+            self.serializer.write_map_header(buffered.len)?;
+            self.serializer
+                .output
+                .write_all(&buffered.entries)
+                .map_err(Error::WriteSerialized)?;
+        }
+
+        self.serializer
+            .output
+            .write_all(b"}")
+            .map_err(Error::WriteSerialized)?;
+        if self.variant {
+            self.serializer.leave_variant()?;
+        }
+        Ok(())
     }
 }
 
-impl ser::SerializeStructVariant for NotImplemented {
+impl<W> ser::SerializeStructVariant for StructSerializer<'_, W>
+where
+    W: Write,
+{
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T>(&mut self, _key: &'static str, _value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        Err(Error::MissingFeature(
-            "Serialization of enums is not supported. If you need C-style enums serialized, look at `serde_repr`.",
-        ))
+        ser::SerializeStruct::serialize_field(self, key, value)
     }
 
     fn end(self) -> Result<()> {
-        Err(Error::MissingFeature(
-            "Serialization of enums is not supported. If you need C-style enums serialized, look at `serde_repr`.",
-        ))
+        ser::SerializeStruct::end(self)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::to_vec;
-    use serde::Serialize;
-    use std::collections::BTreeMap;
+    use super::{
+        serialized_size, serialized_size_with_options, to_fmt_writer, to_slice, to_string, to_vec,
+        to_vec_in, to_vec_with_options, to_writer, to_writer_with_options, PhpSerializer,
+    };
+    use crate::{
+        Error, FloatPrecision, IntegerOverflow, PhpEnum, Positional, SerializerOptions, StdClass,
+        Value,
+    };
+    use serde::{Deserialize, Serialize};
+    use std::collections::{BTreeMap, HashMap};
+    use std::fmt;
+    use std::io::{self, Write};
 
     macro_rules! assert_serializes {
         ($v:expr, $expected:expr) => {
@@ -469,11 +1409,197 @@ mod tests {
         };
     }
 
+    #[test]
+    fn serialize_size() {
+        let mut map = HashMap::new();
+        map.insert("key", vec![Some(1.5), None]);
+        map.insert("other", vec![]);
+        let options = SerializerOptions::new().structs_as_objects(true);
+
+        assert_eq!(serialized_size(&map).unwrap(), to_vec(&map).unwrap().len());
+        assert_eq!(
+            serialized_size_with_options(&StdClass(&map), &options).unwrap(),
+            to_vec_with_options(&StdClass(&map), &options)
+                .unwrap()
+                .len()
+        );
+        assert!(serialized_size(&u64::MAX).is_err());
+    }
+
+    #[test]
+    fn serialize_to_string() {
+        struct Failing;
+
+        impl fmt::Write for Failing {
+            fn write_str(&mut self, _s: &str) -> fmt::Result {
+                Err(fmt::Error)
+            }
+        }
+
+        assert_eq!(
+            to_string(&("ä", 1)).unwrap(),
+            "a:2:{i:0;s:2:\"ä\";i:1;i:1;}"
+        );
+        assert!(matches!(
+            to_string(&serde_bytes::Bytes::new(b"\xff")),
+            Err(Error::SerializationFailed(_))
+        ));
+
+        let mut s = String::new();
+        to_fmt_writer(&mut s, &vec!["ä"]).unwrap();
+        assert_eq!(s, "a:1:{i:0;s:2:\"ä\";}");
+        assert!(matches!(
+            to_fmt_writer(String::new(), &serde_bytes::Bytes::new(b"\xc3")),
+            Err(Error::SerializationFailed(_))
+        ));
+
+        // Formatter errors are passed on.
+        assert!(matches!(
+            to_fmt_writer(Failing, &1),
+            Err(Error::WriteSerialized(_))
+        ));
+    }
+
+    #[test]
+    fn serialize_deduplicate_strings() {
+        let options = SerializerOptions::new().deduplicate_strings(4);
+
+        // Keys are neither replaced nor counted.
+        let map: BTreeMap<&str, &str> = vec![("http://a", "http://a")].into_iter().collect();
+        assert_eq!(
+            to_vec_with_options(&(map, "http://a", "abc", "abc"), &options).unwrap(),
+            &br#"a:4:{i:0;a:1:{s:8:"http://a";s:8:"http://a";}i:1;r:3;i:2;s:3:"abc";i:3;s:3:"abc";}"#[..]
+        );
+
+        assert_eq!(
+            to_vec_with_options(&Value::from(vec!["long", "long"]), &options).unwrap(),
+            &br#"a:2:{i:0;s:4:"long";i:1;r:2;}"#[..]
+        );
+        assert_eq!(
+            to_vec(&vec!["long", "long"]).unwrap(),
+            &br#"a:2:{i:0;s:4:"long";i:1;s:4:"long";}"#[..]
+        );
+    }
+
+    #[test]
+    fn serialize_depth_limit() {
+        /// A value containing itself, endlessly.
+        struct Cycle;
+
+        impl Serialize for Cycle {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_seq(std::iter::once(&Cycle))
+            }
+        }
+
+        fn nested(depth: usize) -> Value {
+            (0..depth).fold(Value::Int(1), |v, _| Value::from(vec![v]))
+        }
+
+        // Fails cleanly instead of overflowing the stack.
+        assert!(matches!(to_vec(&Cycle), Err(Error::DepthLimitExceeded)));
+
+        // Counted like the deserializer does.
+        assert!(to_vec(&nested(128)).is_ok());
+        assert!(to_vec(&nested(129)).is_err());
+        let options = SerializerOptions::new().max_depth(2);
+        assert!(to_vec_with_options(&vec![vec![1]], &options).is_ok());
+        assert!(to_vec_with_options(&vec![vec![vec![1]]], &options).is_err());
+        assert!(to_vec_with_options(&vec![vec![Vec::<i64>::new()]], &options).is_ok());
+
+        // Also for parts that are buffered.
+        let options = options.sort_map_keys(true);
+        let map: BTreeMap<i64, Vec<Vec<i64>>> = vec![(1, vec![vec![1]])].into_iter().collect();
+        assert!(to_vec_with_options(&map, &options).is_err());
+    }
+
+    #[test]
+    fn serialize_buffered() {
+        /// Writer recording the size of each write.
+        struct Writes(Vec<usize>);
+
+        impl Write for Writes {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.push(buf.len());
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let value: Vec<i64> = (0..100).collect();
+        let len = to_vec(&value).unwrap().len();
+
+        let mut writes = Writes(Vec::new());
+        to_writer(&mut writes, &value).unwrap();
+        assert_eq!(writes.0, vec![len]);
+
+        let mut writes = Writes(Vec::new());
+        let options = SerializerOptions::new().write_buffer_size(100);
+        to_writer_with_options(&mut writes, &value, &options).unwrap();
+        assert!(writes.0.len() > 1 && writes.0.iter().all(|&n| n <= 100));
+        assert_eq!(writes.0.iter().sum::<usize>(), len);
+
+        let mut writes = Writes(Vec::new());
+        let options = SerializerOptions::new().write_buffer_size(0);
+        to_writer_with_options(&mut writes, &value, &options).unwrap();
+        assert!(writes.0.len() > 200);
+    }
+
+    #[test]
+    fn serialize_to_vec_in() {
+        let mut buf = Vec::with_capacity(64);
+        let ptr = buf.as_ptr();
+
+        to_vec_in(&vec!["abc"], &mut buf).unwrap();
+        assert_eq!(buf, br#"a:1:{i:0;s:3:"abc";}"#);
+        to_vec_in(&true, &mut buf).unwrap();
+        assert_eq!(buf, b"b:1;");
+        assert_eq!(buf.as_ptr(), ptr);
+
+        assert!(to_vec_in(&u64::MAX, &mut buf).is_err());
+    }
+
+    #[test]
+    fn serialize_to_slice() {
+        let value = (1.5_f64, "abc", Some(-7_i64));
+        let expected = br#"a:3:{i:0;d:1.5;i:1;s:3:"abc";i:2;i:-7;}"#;
+
+        let mut buf = [0_u8; 64];
+        let len = to_slice(&value, &mut buf).unwrap();
+        assert_eq!(&buf[..len], &expected[..]);
+
+        let mut exact = vec![0_u8; expected.len()];
+        assert_eq!(to_slice(&value, &mut exact).unwrap(), expected.len());
+
+        let mut short = vec![0_u8; expected.len() - 1];
+        assert!(matches!(
+            to_slice(&value, &mut short),
+            Err(Error::BufferTooSmall)
+        ));
+        assert!(matches!(
+            to_slice(&value, &mut []),
+            Err(Error::BufferTooSmall)
+        ));
+    }
+
     #[test]
     fn serialize_unit() {
         assert_serializes!((), b"N;");
     }
 
+    #[test]
+    fn serialize_none() {
+        assert_serializes!(None::<i64>, b"N;");
+        assert_serializes!(Some(1_i64), b"i:1;");
+
+        let none = to_vec(&None::<String>).unwrap();
+        assert_eq!(crate::from_bytes::<Option<String>>(&none).unwrap(), None);
+        crate::from_bytes::<()>(&to_vec(&()).unwrap()).unwrap();
+    }
+
     #[test]
     fn serialize_bool() {
         assert_serializes!(false, b"b:0;");
@@ -488,6 +1614,51 @@ mod tests {
         assert_serializes!(123_i64, b"i:123;");
     }
 
+    #[test]
+    fn serialize_integer_overflow() {
+        let serialize = |v: u64, policy| {
+            let options = SerializerOptions::new().integer_overflow(policy);
+            to_vec_with_options(&v, &options)
+        };
+
+        let max = i64::MAX as u64;
+        assert_serializes!(max, b"i:9223372036854775807;");
+        assert!(matches!(
+            to_vec(&(max + 1)),
+            Err(Error::IntegerOutOfRange(n)) if n == "9223372036854775808"
+        ));
+        assert_eq!(
+            serialize(max + 1, IntegerOverflow::Float).unwrap(),
+            b"d:9.223372036854776E+18;"
+        );
+        assert_eq!(
+            serialize(u64::MAX, IntegerOverflow::String).unwrap(),
+            br#"s:20:"18446744073709551615";"#
+        );
+        assert_eq!(serialize(1, IntegerOverflow::String).unwrap(), b"i:1;");
+    }
+
+    #[test]
+    fn serialize_wide_integer() {
+        assert_serializes!(-5_i128, b"i:-5;");
+        assert_serializes!(5_u128, b"i:5;");
+        assert_serializes!(
+            i128::MIN,
+            br#"s:40:"-170141183460469231731687303715884105728";"#
+        );
+        assert_serializes!(u128::from(u64::MAX), br#"s:20:"18446744073709551615";"#);
+
+        let options = SerializerOptions::new().integer_overflow(IntegerOverflow::Float);
+        assert_eq!(
+            to_vec_with_options(&u128::MAX, &options).unwrap(),
+            b"d:3.402823669209385E+38;"
+        );
+
+        for v in [i128::MIN, -1, i128::from(i64::MAX) + 1, i128::MAX] {
+            assert_eq!(crate::from_bytes::<i128>(&to_vec(&v).unwrap()).unwrap(), v);
+        }
+    }
+
     #[test]
     fn serialize_float() {
         assert_serializes!(-1_f64, b"d:-1;");
@@ -496,6 +1667,54 @@ mod tests {
         assert_serializes!(-1.9_f64, b"d:-1.9;");
         assert_serializes!(0.9_f64, b"d:0.9;");
         assert_serializes!(1.9_f64, b"d:1.9;");
+        assert_serializes!(-0.0_f64, b"d:-0;");
+        assert_serializes!(100.0_f64, b"d:100;");
+        assert_serializes!(1e15_f64, b"d:1000000000000000;");
+        assert_serializes!(1e17_f64, b"d:1.0E+17;");
+        assert_serializes!(1.5e300_f64, b"d:1.5E+300;");
+        assert_serializes!(0.0001_f64, b"d:0.0001;");
+        assert_serializes!(0.000_012_5_f64, b"d:1.25E-5;");
+        assert_serializes!(0.1_f32, b"d:0.10000000149011612;");
+    }
+
+    #[test]
+    fn serialize_non_finite_float() {
+        assert_serializes!(f64::NAN, b"d:NAN;");
+        assert_serializes!(f64::INFINITY, b"d:INF;");
+        assert_serializes!(f64::NEG_INFINITY, b"d:-INF;");
+        assert_serializes!(f32::NEG_INFINITY, b"d:-INF;");
+
+        for v in [f64::INFINITY, f64::NEG_INFINITY] {
+            assert_eq!(crate::from_bytes::<f64>(&to_vec(&v).unwrap()).unwrap(), v);
+        }
+        assert!(crate::from_bytes::<f64>(&to_vec(&f64::NAN).unwrap())
+            .unwrap()
+            .is_nan());
+    }
+
+    #[test]
+    fn serialize_float_precision() {
+        let serialize = |v: f64, digits| {
+            let options =
+                SerializerOptions::new().float_precision(FloatPrecision::Significant(digits));
+            to_vec_with_options(&v, &options).unwrap()
+        };
+
+        assert_eq!(serialize(0.1, 17), b"d:0.10000000000000001;");
+        assert_eq!(serialize(1.0 / 3.0, 17), b"d:0.33333333333333331;");
+        assert_eq!(serialize(1.0, 17), b"d:1;");
+        assert_eq!(serialize(-2.5, 17), b"d:-2.5;");
+        assert_eq!(serialize(1e17, 17), b"d:1.0E+17;");
+        assert_eq!(serialize(123_456.0, 4), b"d:1.235E+5;");
+        // Ties are rounded to even, like PHP does.
+        assert_eq!(serialize(0.25, 1), b"d:0.2;");
+        assert_eq!(serialize(0.35, 0), b"d:0.3;");
+
+        // Both read back.
+        for digits in [1, 17] {
+            let serialized = serialize(1e-20, digits);
+            assert_eq!(crate::from_bytes::<f64>(&serialized).unwrap(), 1e-20);
+        }
     }
 
     #[test]
@@ -565,6 +1784,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn serialize_structs_as_objects() {
+        #[derive(Serialize)]
+        struct Inner {
+            x: i64,
+        }
+
+        #[derive(Serialize)]
+        #[serde(rename = "App\\Outer")]
+        struct Outer {
+            inner: Inner,
+            plain: StdClass<Inner>,
+            pair: (i64, i64),
+        }
+
+        let outer = Outer {
+            inner: Inner { x: 1 },
+            plain: StdClass(Inner { x: 2 }),
+            pair: (3, 4),
+        };
+        let options = SerializerOptions::new().structs_as_objects(true);
+        assert_eq!(
+            to_vec_with_options(&outer, &options).unwrap(),
+            &br#"O:9:"App\Outer":3:{s:5:"inner";O:5:"Inner":1:{s:1:"x";i:1;}s:5:"plain";O:8:"stdClass":1:{s:1:"x";i:2;}s:4:"pair";a:2:{i:0;i:3;i:1;i:4;}}"#[..]
+        );
+
+        // Disabled by default.
+        assert_serializes!(Inner { x: 1 }, br#"a:1:{s:1:"x";i:1;}"#);
+    }
+
     #[test]
     fn serialize_struct_with_optional() {
         #[derive(Debug, Serialize, Eq, PartialEq)]
@@ -605,6 +1854,109 @@ mod tests {
         );
     }
 
+    #[test]
+    fn serialize_structs_as_lists() {
+        #[derive(Deserialize, Serialize)]
+        struct Inner {
+            x: Option<i64>,
+        }
+
+        #[derive(Serialize)]
+        struct Outer {
+            name: &'static str,
+            inner: Inner,
+            map: BTreeMap<&'static str, i64>,
+        }
+
+        let mut map = BTreeMap::new();
+        map.insert("a", 1);
+        let outer = Outer {
+            name: "n",
+            inner: Inner { x: None },
+            map,
+        };
+
+        let options = SerializerOptions::new()
+            .structs_as_lists(true)
+            .omit_none_fields(true);
+        assert_eq!(
+            to_vec_with_options(&outer, &options).unwrap(),
+            &br#"a:3:{i:0;s:1:"n";i:1;a:1:{i:0;N;}i:2;a:1:{s:1:"a";i:1;}}"#[..]
+        );
+
+        // Reads back through `Positional`.
+        let options = SerializerOptions::new().structs_as_lists(true);
+        let serialized = to_vec_with_options(&Inner { x: Some(2) }, &options).unwrap();
+        let inner: Positional<Inner> = crate::from_bytes(&serialized).unwrap();
+        assert_eq!(inner.x, Some(2));
+    }
+
+    #[test]
+    fn serialize_omit_none_fields() {
+        #[derive(Serialize)]
+        struct Inner {
+            a: Option<i64>,
+            b: Vec<Option<i64>>,
+            d: (),
+        }
+
+        #[derive(Serialize)]
+        struct Outer {
+            first: Option<i64>,
+            inner: StdClass<Inner>,
+            pos: Positional<Inner>,
+        }
+
+        let inner = || Inner {
+            a: None,
+            b: vec![None],
+            d: (),
+        };
+        let outer = Outer {
+            first: None,
+            inner: StdClass(inner()),
+            pos: Positional(inner()),
+        };
+
+        let options = SerializerOptions::new().omit_none_fields(true);
+        assert_eq!(
+            to_vec_with_options(&outer, &options).unwrap(),
+            &br#"a:2:{s:5:"inner";O:8:"stdClass":2:{s:1:"b";a:1:{i:0;N;}s:1:"d";N;}s:3:"pos";a:3:{i:0;N;i:1;a:1:{i:0;N;}i:2;N;}}"#[..]
+        );
+
+        let options = options.structs_as_objects(true);
+        assert_eq!(
+            to_vec_with_options(&Some(inner()), &options).unwrap(),
+            &br#"O:5:"Inner":2:{s:1:"b";a:1:{i:0;N;}s:1:"d";N;}"#[..]
+        );
+    }
+
+    #[test]
+    fn serialize_sorted_map_keys() {
+        let options = SerializerOptions::new().sort_map_keys(true);
+
+        let mut map = HashMap::new();
+        for (i, key) in ["b", "a", "-1", "10", "2", "010"].iter().enumerate() {
+            let mut inner = HashMap::new();
+            inner.insert(*key, i);
+            inner.insert("", i);
+            map.insert(*key, inner);
+        }
+        let serialized = to_vec_with_options(&map, &options).unwrap();
+        assert_eq!(
+            serialized,
+            &br#"a:6:{i:-1;a:2:{i:-1;i:2;s:0:"";i:2;}i:2;a:2:{i:2;i:4;s:0:"";i:4;}i:10;a:2:{i:10;i:3;s:0:"";i:3;}s:3:"010";a:2:{s:0:"";i:5;s:3:"010";i:5;}s:1:"a";a:2:{s:0:"";i:1;s:1:"a";i:1;}s:1:"b";a:2:{s:0:"";i:0;s:1:"b";i:0;}}"#[..]
+        );
+
+        // Objects keep their class, only the properties are sorted.
+        let value =
+            crate::from_bytes::<Value>(br#"O:3:"Foo":2:{s:1:"y";i:1;s:1:"x";i:2;}"#).unwrap();
+        assert_eq!(
+            to_vec_with_options(&value, &options).unwrap(),
+            &br#"O:3:"Foo":2:{s:1:"x";i:2;s:1:"y";i:1;}"#[..]
+        );
+    }
+
     #[test]
     fn serialize_nested() {
         // PHP: array("x" => array("inner" => 1), "y" => array("inner" => 2))
@@ -629,6 +1981,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn serialize_enum_variants() {
+        #[derive(Serialize)]
+        enum Shape {
+            Empty,
+            Circle(f64),
+            Line(i64, i64),
+            Rect { w: i64, h: i64 },
+        }
+
+        assert_serializes!(
+            vec![
+                Shape::Empty,
+                Shape::Circle(0.5),
+                Shape::Line(1, 2),
+                Shape::Rect { w: 3, h: 4 },
+            ],
+            br#"a:4:{i:0;s:5:"Empty";i:1;a:1:{s:6:"Circle";d:0.5;}i:2;a:1:{s:4:"Line";a:2:{i:0;i:1;i:1;i:2;}}i:3;a:1:{s:4:"Rect";a:2:{s:1:"w";i:3;s:1:"h";i:4;}}}"#
+        );
+
+        // The fields count towards the depth limit.
+        let options = SerializerOptions::new().max_depth(1);
+        assert!(to_vec_with_options(&Shape::Circle(0.5), &options).is_ok());
+        assert!(matches!(
+            to_vec_with_options(&vec![Shape::Line(1, 2)], &options),
+            Err(Error::DepthLimitExceeded)
+        ));
+        assert!(to_vec(&PhpEnum(Shape::Circle(0.5))).is_err());
+    }
+
     #[test]
     fn serialize_variable_length() {
         // PHP: array(1.1, 2.2, 3.3, 4.4)
@@ -648,6 +2030,94 @@ mod tests {
         assert_serializes!(input, br#"a:2:{s:3:"bar";i:7;s:3:"foo";i:42;}"#);
     }
 
+    #[test]
+    fn serialize_map_key_coercion() {
+        // PHP: array("1" => "a", "01" => "b", "-0" => "c")
+        let mut input: BTreeMap<&str, &str> = BTreeMap::new();
+        input.insert("1", "a");
+        input.insert("01", "b");
+        input.insert("-0", "c");
+
+        assert_serializes!(
+            input,
+            br#"a:3:{s:2:"-0";s:1:"c";s:2:"01";s:1:"b";i:1;s:1:"a";}"#
+        );
+
+        let mut input: BTreeMap<bool, u8> = BTreeMap::new();
+        input.insert(false, 0);
+        input.insert(true, 1);
+
+        assert_serializes!(input, b"a:2:{i:0;i:0;i:1;i:1;}");
+
+        // Matches what PHP writes for `unserialize('a:1:{s:1:"0";s:0:"";}')`.
+        let mut input: HashMap<String, String> = HashMap::new();
+        input.insert("0".to_owned(), String::new());
+        assert_serializes!(input, br#"a:1:{i:0;s:0:"";}"#);
+
+        // Numeric strings are kept without coercion, other keys are not.
+        let options = SerializerOptions::new().coerce_numeric_keys(false);
+        assert_eq!(
+            to_vec_with_options(&input, &options).unwrap(),
+            br#"a:1:{s:1:"0";s:0:"";}"#
+        );
+        let input: BTreeMap<bool, &str> = vec![(true, "7")].into_iter().collect();
+        assert_eq!(
+            to_vec_with_options(&input, &options).unwrap(),
+            br#"a:1:{i:1;s:1:"7";}"#
+        );
+        let input: HashMap<char, i64> = vec![('7', 1)].into_iter().collect();
+        assert_eq!(
+            to_vec_with_options(&input, &options).unwrap(),
+            br#"a:1:{s:1:"7";i:1;}"#
+        );
+        assert_serializes!(input, b"a:1:{i:7;i:1;}");
+    }
+
+    #[test]
+    fn serialize_flattened() {
+        #[derive(Serialize)]
+        struct Meta {
+            version: u8,
+        }
+
+        #[derive(Serialize)]
+        struct User {
+            name: &'static str,
+            #[serde(flatten)]
+            meta: Meta,
+            #[serde(flatten)]
+            extra: BTreeMap<&'static str, Value>,
+        }
+
+        // Flattened fields are written as a map of unknown length.
+        let mut extra = BTreeMap::new();
+        extra.insert("tags", Value::from(vec![Value::from("a")]));
+        let user = User {
+            name: "bob",
+            meta: Meta { version: 2 },
+            extra,
+        };
+        assert_serializes!(
+            user,
+            br#"a:3:{s:4:"name";s:3:"bob";s:7:"version";i:2;s:4:"tags";a:1:{i:0;s:1:"a";}}"#
+        );
+        assert_serializes!(
+            StdClass(user),
+            br#"O:8:"stdClass":3:{s:4:"name";s:3:"bob";s:7:"version";i:2;s:4:"tags";a:1:{i:0;s:1:"a";}}"#
+        );
+
+        let options = SerializerOptions::new().sort_map_keys(true);
+        let user = User {
+            name: "bob",
+            meta: Meta { version: 2 },
+            extra: BTreeMap::new(),
+        };
+        assert_eq!(
+            to_vec_with_options(&user, &options).unwrap(),
+            &br#"a:2:{s:4:"name";s:3:"bob";s:7:"version";i:2;}"#[..]
+        );
+    }
+
     #[test]
     fn unaffected_by_recursive_type_error() {
         // The following code will not compile, but fail with an infinite type recursion instead,
@@ -663,4 +2133,68 @@ mod tests {
         let val: bson::Bson = bson::Document::new().into();
         to_vec(&val).unwrap();
     }
+
+    #[test]
+    fn serializer_directly() {
+        #[derive(Serialize)]
+        struct Item {
+            id: i64,
+            price: u64,
+        }
+
+        let items = vec![
+            Item { id: 1, price: 5 },
+            Item {
+                id: 2,
+                price: u64::MAX,
+            },
+        ];
+        let mut serializer = PhpSerializer::new(Vec::new());
+        let err = serde_path_to_error::serialize(&items, &mut serializer).unwrap_err();
+        assert_eq!(err.path().to_string(), "[1].price");
+        assert!(matches!(err.inner(), Error::IntegerOutOfRange(_)));
+
+        let options = SerializerOptions::new().structs_as_lists(true);
+        let mut serializer = PhpSerializer::with_options(Vec::new(), options);
+        items[0].serialize(&mut serializer).unwrap();
+        assert_eq!(serializer.into_inner(), b"a:2:{i:0;i:1;i:1;i:5;}");
+    }
+
+    #[test]
+    fn transcode() {
+        // Re-encoding from one format to another, without an intermediate
+        // value.
+        let input =
+            br#"a:4:{s:1:"a";a:1:{i:0;d:0.5;}s:1:"b";N;i:5;E:7:"Suit:Up";i:9;C:3:"Foo":1:{x}}"#;
+        let mut deserializer = crate::PhpDeserializer::from_slice(input);
+        let mut serializer = PhpSerializer::new(Vec::new());
+        serde_transcode::transcode(&mut deserializer, &mut serializer).unwrap();
+        assert_eq!(serializer.into_inner(), &input[..]);
+
+        // Lists stay lists, other arrays and objects become maps.
+        let input = br#"a:4:{s:4:"list";a:2:{i:0;i:1;i:1;a:1:{i:0;b:1;}}s:6:"sparse";a:2:{i:0;s:1:"a";i:2;N;}s:3:"obj";O:3:"Foo":1:{s:1:"x";d:0.5;}s:3:"big";i:-9007199254740993;}"#;
+        let expected = r#"{"list":[1,[true]],"sparse":{"0":"a","2":null},"obj":{"x":0.5},"big":-9007199254740993}"#;
+        let mut deserializer = crate::PhpDeserializer::from_slice(input);
+        let mut serializer = serde_json::Serializer::new(Vec::new());
+        serde_transcode::transcode(&mut deserializer, &mut serializer).unwrap();
+        assert_eq!(serializer.into_inner(), expected.as_bytes());
+
+        // The same goes for readers, like those of `from_reader`, whose
+        // arrays with integer keys are buffered at the outermost level.
+        let reader = std::io::BufReader::new(std::io::Cursor::new(input.to_vec()));
+        let mut deserializer = crate::PhpDeserializer::new(reader);
+        let mut serializer = serde_json::Serializer::new(Vec::new());
+        serde_transcode::transcode(&mut deserializer, &mut serializer).unwrap();
+        assert_eq!(serializer.into_inner(), expected.as_bytes());
+
+        let mut input = b"a:2:{i:0;i:1;i:1;".repeat(100);
+        input.extend_from_slice(b"N;");
+        input.extend(vec![b'}'; 100]);
+        let reader = std::io::BufReader::new(std::io::Cursor::new(input));
+        let mut deserializer = crate::PhpDeserializer::new(reader);
+        let mut serializer = serde_json::Serializer::new(Vec::new());
+        serde_transcode::transcode(&mut deserializer, &mut serializer).unwrap();
+        let expected = format!("{}null{}", "[1,".repeat(100), "]".repeat(100));
+        assert_eq!(serializer.into_inner(), expected.as_bytes());
+    }
 }
@@ -0,0 +1,577 @@
+//! Session data written by PHP's session serialization handlers.
+//!
+//! PHP stores sessions in one of three formats, chosen with
+//! `session.serialize_handler`, see [`SessionHandler`]. The default `php`
+//! handler stores each session variable as its name, a `|` and its
+//! serialized value, one after another without separators:
+//!
+//! ```text
+//! userid|i:42;cart|a:1:{i:0;s:4:"book";}
+//! ```
+//!
+//! A [`Session`] reads and writes any of them, with typed access to its
+//! variables.
+
+use crate::de::{from_bytes, PhpDeserializer};
+use crate::error::{Error, Result};
+use crate::ser::{to_vec, PhpSerializer};
+use crate::value::{from_value, Value};
+use serde::de::{DeserializeOwned, Deserializer, MapAccess, Visitor};
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+
+/// Separator between the name and the value of a session variable.
+const DELIMITER: u8 = b'|';
+
+/// Marker before the name of an unset variable, written by PHP 5.
+const UNDEF_MARKER: char = '!';
+
+/// Flag in the length byte of an unset variable in the `php_binary` format.
+const BINARY_UNDEF: u8 = 0x80;
+
+/// Format of session data, named like the `session.serialize_handler`
+/// setting writing it.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum SessionHandler {
+    /// The default format, `name|value` for each variable.
+    Php,
+    /// A byte holding the length of the name, the name and the value for
+    /// each variable. Names are limited to 127 bytes.
+    PhpBinary,
+    /// A single serialized array of all variables.
+    PhpSerialize,
+}
+
+/// Parse session data into its variables, detecting its format.
+///
+/// Data starting with `a:` that is a single serialized array is read as
+/// `php_serialize`, then `php` and `php_binary` are tried in turn. If none
+/// of them fits, the error of the `php` format is returned. Use
+/// `parse_session_with_handler` to skip the detection.
+///
+/// A variable set twice keeps its last value, as in PHP. Variables marked as
+/// unset, which PHP 5 writes as `!name|` without a value, are left out.
+/// References between variables are not supported.
+///
+/// ```rust
+/// use php_serde::{parse_session, Value};
+///
+/// let session = parse_session(br#"userid|i:42;name|s:3:"bob";"#).unwrap();
+/// assert_eq!(session["userid"], Value::Int(42));
+/// assert_eq!(session["name"], Value::from("bob"));
+/// ```
+pub fn parse_session(input: &[u8]) -> Result<HashMap<String, Value>> {
+    Session::parse(input).map(|session| session.into_variables().into_iter().collect())
+}
+
+/// Detect the format of session data, returning it along with the
+/// variables, or `None` if no format fits.
+///
+/// Detection works like in `parse_session`. Empty data is read as `php`.
+pub fn detect_session_handler(input: &[u8]) -> Option<(SessionHandler, HashMap<String, Value>)> {
+    detect(input).map(|(handler, variables)| (handler, variables.into_iter().collect()))
+}
+
+/// Detect the format of session data, returning the variables in order.
+fn detect(input: &[u8]) -> Option<(SessionHandler, Vec<(String, Value)>)> {
+    let mut handlers = vec![SessionHandler::Php, SessionHandler::PhpBinary];
+    if input.starts_with(b"a:") {
+        handlers.insert(0, SessionHandler::PhpSerialize);
+    }
+    handlers.into_iter().find_map(|handler| {
+        parse_variables(input, handler)
+            .ok()
+            .map(|variables| (handler, variables))
+    })
+}
+
+/// Parse session data of a known format into its variables.
+pub fn parse_session_with_handler(
+    input: &[u8],
+    handler: SessionHandler,
+) -> Result<HashMap<String, Value>> {
+    parse_variables(input, handler).map(|variables| variables.into_iter().collect())
+}
+
+/// Parse session data of a known format into its variables, in the order
+/// they were first set.
+fn parse_variables(input: &[u8], handler: SessionHandler) -> Result<Vec<(String, Value)>> {
+    if handler == SessionHandler::PhpSerialize {
+        return from_bytes(input).map(Variables::into_vec);
+    }
+
+    let mut variables = Variables::default();
+    let mut offset = 0;
+    while offset < input.len() {
+        let (name, unset) = if handler == SessionHandler::Php {
+            let length = input[offset..]
+                .iter()
+                .position(|&c| c == DELIMITER)
+                .ok_or_else(|| Error::UnexpectedEof.at(input.len()))?;
+            let name = utf8(input, offset, length)?;
+            offset += length + 1;
+            match name.strip_prefix(UNDEF_MARKER) {
+                Some(name) => (name, true),
+                None => (name, false),
+            }
+        } else {
+            let header = input[offset];
+            let length = usize::from(header & !BINARY_UNDEF);
+            offset += 1;
+            if input.len() - offset < length {
+                return Err(Error::UnexpectedEof.at(input.len()));
+            }
+            let name = utf8(input, offset, length)?;
+            offset += length;
+            (name, header & BINARY_UNDEF != 0)
+        };
+
+        if unset {
+            variables.remove(name);
+            continue;
+        }
+        let mut de = PhpDeserializer::from_slice_at(&input[offset..], offset);
+        let value = Value::deserialize(&mut de).map_err(|e| e.at(de.byte_offset()))?;
+        offset = de.byte_offset();
+        variables.insert(name.to_owned(), value);
+    }
+    Ok(variables.into_vec())
+}
+
+/// Set a variable, keeping its place if it was set before, as PHP does.
+fn insert(variables: &mut Vec<(String, Value)>, name: String, value: Value) -> Option<Value> {
+    if let Some((_, previous)) = variables.iter_mut().find(|(other, _)| *other == name) {
+        return Some(std::mem::replace(previous, value));
+    }
+    variables.push((name, value));
+    None
+}
+
+/// Unset a variable, returning its value if it was set.
+fn remove(variables: &mut Vec<(String, Value)>, name: &str) -> Option<Value> {
+    let index = variables.iter().position(|(other, _)| other == name)?;
+    Some(variables.remove(index).1)
+}
+
+/// Variables being read, in the order they were first set.
+///
+/// Unset variables leave a gap, to find the others by their index.
+#[derive(Default)]
+struct Variables {
+    entries: Vec<Option<(String, Value)>>,
+    positions: HashMap<String, usize>,
+}
+
+impl Variables {
+    fn insert(&mut self, name: String, value: Value) {
+        if let Some(&index) = self.positions.get(&name) {
+            self.entries[index] = Some((name, value));
+            return;
+        }
+        self.positions.insert(name.clone(), self.entries.len());
+        self.entries.push(Some((name, value)));
+    }
+
+    fn remove(&mut self, name: &str) {
+        if let Some(index) = self.positions.remove(name) {
+            self.entries[index] = None;
+        }
+    }
+
+    fn into_vec(self) -> Vec<(String, Value)> {
+        self.entries.into_iter().flatten().collect()
+    }
+}
+
+impl<'de> Deserialize<'de> for Variables {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct VariablesVisitor;
+
+        impl<'de> Visitor<'de> for VariablesVisitor {
+            type Value = Variables;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("an array of session variables")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> std::result::Result<Variables, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut variables = Variables::default();
+                while let Some((name, value)) = map.next_entry()? {
+                    variables.insert(name, value);
+                }
+                Ok(variables)
+            }
+        }
+
+        deserializer.deserialize_map(VariablesVisitor)
+    }
+}
+
+/// The name of a session variable, `length` bytes at `offset`.
+fn utf8(input: &[u8], offset: usize, length: usize) -> Result<&str> {
+    std::str::from_utf8(&input[offset..offset + length])
+        .map_err(|e| Error::NotUtf8String(e).at(offset))
+}
+
+/// Write session variables in the default `php` format.
+///
+/// Variables are written in the order they are passed in. Names must not
+/// contain a `|` or start with a `!`, which PHP refuses as well.
+///
+/// ```rust
+/// use php_serde::encode_session;
+///
+/// let session = encode_session(vec![("userid", 42), ("visits", 3)]).unwrap();
+/// assert_eq!(session, b"userid|i:42;visits|i:3;");
+/// ```
+pub fn encode_session<I, K, V>(variables: I) -> Result<Vec<u8>>
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<str>,
+    V: Serialize,
+{
+    encode_session_with_handler(variables, SessionHandler::Php)
+}
+
+/// Write session variables in the given format.
+///
+/// Names of the `php_binary` format must not be longer than 127 bytes.
+pub fn encode_session_with_handler<I, K, V>(
+    variables: I,
+    handler: SessionHandler,
+) -> Result<Vec<u8>>
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<str>,
+    V: Serialize,
+{
+    let mut output = Vec::new();
+    if handler == SessionHandler::PhpSerialize {
+        let variables: Vec<_> = variables.into_iter().collect();
+        let entries = variables.iter().map(|(name, value)| (name.as_ref(), value));
+        (&mut PhpSerializer::new(&mut output)).collect_map(entries)?;
+        return Ok(output);
+    }
+
+    for (name, value) in variables {
+        let name = name.as_ref();
+        let invalid =
+            || Error::SerializationFailed(format!("invalid session variable name {name:?}"));
+        if handler == SessionHandler::Php {
+            if name.as_bytes().contains(&DELIMITER) || name.starts_with(UNDEF_MARKER) {
+                return Err(invalid());
+            }
+            output.extend_from_slice(name.as_bytes());
+            output.push(DELIMITER);
+        } else {
+            let length = u8::try_from(name.len())
+                .ok()
+                .filter(|length| length & BINARY_UNDEF == 0)
+                .ok_or_else(invalid)?;
+            output.push(length);
+            output.extend_from_slice(name.as_bytes());
+        }
+        value.serialize(&mut PhpSerializer::new(&mut output))?;
+    }
+    Ok(output)
+}
+
+/// Deserialize session data of the `php_serialize` format, a single
+/// serialized array, e.g. into a struct with a field for each variable.
+///
+/// ```rust
+/// use php_serde::decode_php_serialize;
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Session {
+///     userid: i64,
+/// }
+///
+/// let session: Session = decode_php_serialize(br#"a:1:{s:6:"userid";i:42;}"#).unwrap();
+/// assert_eq!(session.userid, 42);
+/// ```
+pub fn decode_php_serialize<'de, T>(input: &'de [u8]) -> Result<T>
+where
+    T: Deserialize<'de>,
+{
+    from_bytes(input)
+}
+
+/// Serialize session data in the `php_serialize` format.
+///
+/// `value` has to serialize as an array or map with the variable names as
+/// keys, such as a struct.
+pub fn encode_php_serialize<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: Serialize + ?Sized,
+{
+    to_vec(value)
+}
+
+/// Session variables, along with the format they are stored in.
+///
+/// ```rust
+/// use php_serde::Session;
+///
+/// let mut session = Session::parse(br#"userid|i:42;cart|a:1:{i:0;s:4:"book";}"#).unwrap();
+/// let cart: Vec<String> = session.get("cart").unwrap().unwrap_or_default();
+/// assert_eq!(cart, vec!["book"]);
+///
+/// session.set("visits", 3);
+/// session.remove("cart");
+/// assert_eq!(session.encode().unwrap(), b"userid|i:42;visits|i:3;");
+/// ```
+///
+/// Variables keep the order they were first set in, as in PHP, so that
+/// sessions are written back the way they were read.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session {
+    handler: SessionHandler,
+    variables: Vec<(String, Value)>,
+}
+
+impl Session {
+    /// Create an empty session, to be stored in the given format.
+    pub fn new(handler: SessionHandler) -> Self {
+        Session {
+            handler,
+            variables: Vec::new(),
+        }
+    }
+
+    /// Parse session data, detecting its format like `parse_session`.
+    pub fn parse(input: &[u8]) -> Result<Self> {
+        match detect(input) {
+            Some((handler, variables)) => Ok(Session { handler, variables }),
+            None => Session::parse_with_handler(input, SessionHandler::Php),
+        }
+    }
+
+    /// Parse session data of a known format.
+    pub fn parse_with_handler(input: &[u8], handler: SessionHandler) -> Result<Self> {
+        Ok(Session {
+            handler,
+            variables: parse_variables(input, handler)?,
+        })
+    }
+
+    /// Format the session is stored in.
+    pub fn handler(&self) -> SessionHandler {
+        self.handler
+    }
+
+    /// Deserialize a variable, or return `None` if it is not set.
+    pub fn get<T>(&self, name: &str) -> Result<Option<T>>
+    where
+        T: DeserializeOwned,
+    {
+        self.value(name)
+            .map(|value| from_value(value.clone()))
+            .transpose()
+    }
+
+    /// The value of a variable, or `None` if it is not set.
+    pub fn value(&self, name: &str) -> Option<&Value> {
+        self.variables
+            .iter()
+            .find(|(other, _)| other == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Set a variable, replacing its previous value in place, and returning
+    /// it if there was one.
+    pub fn set(&mut self, name: impl Into<String>, value: impl Into<Value>) -> Option<Value> {
+        insert(&mut self.variables, name.into(), value.into())
+    }
+
+    /// Set a variable to a value of any serializable type, such as a
+    /// struct, which is serialized and read back as a `Value` for that.
+    pub fn set_serialized<T>(&mut self, name: impl Into<String>, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        let value: Value = from_bytes(&to_vec(value)?)?;
+        self.set(name, value);
+        Ok(())
+    }
+
+    /// Unset a variable, returning its value if it was set.
+    pub fn remove(&mut self, name: &str) -> Option<Value> {
+        remove(&mut self.variables, name)
+    }
+
+    /// All variables, in the order they were first set.
+    pub fn variables(&self) -> &[(String, Value)] {
+        &self.variables
+    }
+
+    /// Take the variables out of the session.
+    pub fn into_variables(self) -> Vec<(String, Value)> {
+        self.variables
+    }
+
+    /// Write the session in its format, with its variables in order.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let variables = self.variables.iter().map(|(name, value)| (name, value));
+        encode_session_with_handler(variables, self.handler)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        decode_php_serialize, detect_session_handler, encode_php_serialize, encode_session,
+        encode_session_with_handler, parse_session, parse_session_with_handler, Session,
+        SessionHandler,
+    };
+    use crate::{Error, Value};
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn roundtrip() {
+        let input: &[u8] =
+            br#"userid|i:42;cart|a:2:{i:0;s:4:"book";i:1;s:3:"pen";}flash|N;user|O:4:"User":1:{s:4:"name";s:3:"bob";}"#;
+        let session = parse_session(input).unwrap();
+        assert_eq!(session.len(), 4);
+        assert_eq!(
+            session["cart"],
+            Value::from(vec![Value::from("book"), Value::from("pen")])
+        );
+
+        let sorted: BTreeMap<_, _> = session.iter().collect();
+        let encoded = encode_session(&sorted).unwrap();
+        assert_eq!(
+            encoded,
+            &br#"cart|a:2:{i:0;s:4:"book";i:1;s:3:"pen";}flash|N;user|O:4:"User":1:{s:4:"name";s:3:"bob";}userid|i:42;"#[..]
+        );
+        assert_eq!(parse_session(&encoded).unwrap(), session);
+
+        assert!(parse_session(b"").unwrap().is_empty());
+        assert_eq!(encode_session(Vec::<(&str, i64)>::new()).unwrap(), b"");
+    }
+
+    #[test]
+    fn handlers() {
+        let variables = vec![("userid", Value::Int(42)), ("name", Value::from("bob"))];
+        for (handler, expected) in [
+            (SessionHandler::Php, &br#"userid|i:42;name|s:3:"bob";"#[..]),
+            (
+                SessionHandler::PhpBinary,
+                b"\x06useridi:42;\x04names:3:\"bob\";",
+            ),
+            (
+                SessionHandler::PhpSerialize,
+                br#"a:2:{s:6:"userid";i:42;s:4:"name";s:3:"bob";}"#,
+            ),
+        ] {
+            let encoded = encode_session_with_handler(variables.clone(), handler).unwrap();
+            assert_eq!(encoded, expected);
+
+            let session = parse_session_with_handler(&encoded, handler).unwrap();
+            assert_eq!(session["userid"], Value::Int(42));
+            assert_eq!(session["name"], Value::from("bob"));
+            let (detected, detected_session) = detect_session_handler(&encoded).unwrap();
+            assert_eq!(detected, handler);
+            assert_eq!(detected_session, session);
+        }
+
+        // Unset variables in the binary format.
+        let session =
+            parse_session_with_handler(b"\x01ai:1;\x01bi:2;\x81a", SessionHandler::PhpBinary)
+                .unwrap();
+        assert_eq!(session.len(), 1);
+
+        let long = "x".repeat(128);
+        assert!(encode_session_with_handler(vec![(long, 1)], SessionHandler::PhpBinary).is_err());
+        assert!(detect_session_handler(b"\x05ab").is_none());
+    }
+
+    #[test]
+    fn unset_and_repeated() {
+        let session = parse_session(b"a|i:1;b|i:2;!a|b|i:3;").unwrap();
+        assert_eq!(session.len(), 1);
+        assert_eq!(session["b"], Value::Int(3));
+    }
+
+    #[test]
+    fn malformed() {
+        // Positions count from the start of the session data.
+        let err = parse_session(b"a|i:1;b|i:x;").unwrap_err();
+        assert_eq!(err.position(), Some(11));
+        let err = parse_session(b"a|i:1;b").unwrap_err();
+        assert!(matches!(err.into_inner(), Error::UnexpectedEof));
+        let err = parse_session(b"\xff|N;").unwrap_err();
+        assert!(matches!(err.into_inner(), Error::NotUtf8String(_)));
+
+        assert!(encode_session(vec![("a|b", 1)]).is_err());
+        assert!(encode_session(vec![("!a", 1)]).is_err());
+    }
+
+    #[test]
+    fn typed() {
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct Cart {
+            items: Vec<String>,
+        }
+
+        let input: &[u8] =
+            br#"a:2:{s:6:"userid";i:42;s:4:"cart";a:1:{s:5:"items";a:1:{i:0;s:4:"book";}}}"#;
+        let mut session = Session::parse(input).unwrap();
+        assert_eq!(session.handler(), SessionHandler::PhpSerialize);
+        assert_eq!(session.get::<i64>("userid").unwrap(), Some(42));
+        assert_eq!(session.get::<i64>("missing").unwrap(), None);
+        assert!(session.get::<String>("userid").is_err());
+
+        let mut cart: Cart = session.get("cart").unwrap().unwrap();
+        cart.items.push("pen".to_owned());
+        session.set_serialized("cart", &cart).unwrap();
+        assert_eq!(session.remove("userid"), Some(Value::Int(42)));
+        let encoded = session.encode().unwrap();
+        assert_eq!(
+            encoded,
+            &br#"a:1:{s:4:"cart";a:1:{s:5:"items";a:2:{i:0;s:4:"book";i:1;s:3:"pen";}}}"#[..]
+        );
+
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct Variables {
+            cart: Cart,
+        }
+        let variables: Variables = decode_php_serialize(&encoded).unwrap();
+        assert_eq!(variables.cart, cart);
+        assert_eq!(encode_php_serialize(&variables).unwrap(), encoded);
+
+        let mut session = Session::new(SessionHandler::PhpBinary);
+        assert_eq!(session.set("n", "x"), None);
+        assert_eq!(session.encode().unwrap(), b"\x01ns:1:\"x\";");
+    }
+
+    #[test]
+    fn order() {
+        // Variables are written back in the order they were read, with
+        // repeated ones in their first place.
+        let input: &[u8] = br#"z|i:1;a|i:2;!z|m|i:3;a|i:4;"#;
+        let mut session = Session::parse(input).unwrap();
+        let names: Vec<_> = session.variables().iter().map(|(name, _)| name).collect();
+        assert_eq!(names, ["a", "m"]);
+        assert_eq!(session.encode().unwrap(), b"a|i:4;m|i:3;");
+
+        assert_eq!(session.set("z", 5), None);
+        assert_eq!(session.set("a", "b"), Some(Value::Int(4)));
+        assert_eq!(session.value("a"), Some(&Value::from("b")));
+        assert_eq!(session.encode().unwrap(), br#"a|s:1:"b";m|i:3;z|i:5;"#);
+
+        let input: &[u8] = br#"a:2:{s:1:"z";i:1;s:1:"a";i:2;}"#;
+        let session = Session::parse(input).unwrap();
+        assert_eq!(session.encode().unwrap(), input);
+    }
+}
@@ -0,0 +1,166 @@
+//! Shared values, written as PHP references, with the `rc` feature.
+//!
+//! PHP numbers the values it serializes, all except array keys, starting
+//! with `1` for the value itself. A value that occurs again is written as
+//! `r:N;`, referring back to its first occurrence, so that e.g. an object
+//! held by two others is unserialized as one object again.
+//!
+//! Serde passes `Rc` and `Arc` on like the values they point to, losing
+//! their identity. Fields marked `#[serde(with = "php_serde::shared")]`
+//! keep it: the first occurrence of each shared value is written in full,
+//! later ones as references. Other formats are not affected and write the
+//! value in full each time.
+//!
+//! References are not written inside maps sorted with
+//! `SerializerOptions::sort_map_keys`, maps of unknown length such as
+//! structs with flattened fields, or structs with
+//! `SerializerOptions::omit_none_fields`, since their entries are buffered
+//! and reordered, counted or dropped. Values there are written in full.
+//!
+//! Deserializing reads a value as usual and wraps it. References in the
+//! input are only read with `DeserializerOptions::resolve_references`,
+//! each into a separate copy.
+//!
+//! ```rust
+//! use php_serde::to_vec;
+//! use serde::Serialize;
+//! use std::rc::Rc;
+//!
+//! #[derive(Serialize)]
+//! struct Team {
+//!     name: &'static str,
+//! }
+//!
+//! #[derive(Serialize)]
+//! struct User {
+//!     #[serde(with = "php_serde::shared")]
+//!     team: Rc<Team>,
+//! }
+//!
+//! let team = Rc::new(Team { name: "red" });
+//! let users = vec![User { team: team.clone() }, User { team }];
+//!
+//! assert_eq!(
+//!     to_vec(&users).unwrap(),
+//!     &br#"a:2:{i:0;a:1:{s:4:"team";a:1:{s:4:"name";s:3:"red";}}i:1;a:1:{s:4:"team";r:3;}}"#[..]
+//! );
+//! ```
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::ops::Deref;
+
+/// Newtype name used to pass on the value a shared pointer points to.
+pub(crate) const TOKEN: &str = "$php_serde::private::Shared";
+
+/// Serialize the value `pointer` points to, as a reference if it was
+/// written before.
+///
+/// Values are identified by their address, so this is meant for `Rc`,
+/// `Arc` and other pointers to separate allocations.
+pub fn serialize<P, S>(pointer: &P, serializer: S) -> Result<S::Ok, S::Error>
+where
+    P: Deref,
+    P::Target: Serialize,
+    S: Serializer,
+{
+    serializer.serialize_newtype_struct(TOKEN, &**pointer)
+}
+
+/// Deserialize a value and wrap it, e.g. in a new `Rc`.
+pub fn deserialize<'de, P, D>(deserializer: D) -> Result<P, D::Error>
+where
+    P: Deref + From<<P as Deref>::Target>,
+    P::Target: Deserialize<'de> + Sized,
+    D: Deserializer<'de>,
+{
+    P::Target::deserialize(deserializer).map(P::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{from_bytes, to_vec, to_vec_with_options, SerializerOptions, StdClass};
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Node {
+        id: i64,
+        #[serde(with = "crate::shared")]
+        child: Arc<Vec<i64>>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Child(#[serde(with = "crate::shared")] Arc<Vec<i64>>);
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Wrapper(#[serde(with = "crate::shared")] Rc<Node>);
+
+    #[test]
+    fn references() {
+        let child = Arc::new(vec![1]);
+        let nodes = vec![
+            Node {
+                id: 1,
+                child: child.clone(),
+            },
+            Node {
+                id: 2,
+                child: Arc::new(vec![1]),
+            },
+            Node { id: 3, child },
+        ];
+
+        // Only the same allocation is shared, not equal values.
+        assert_eq!(
+            to_vec(&nodes).unwrap(),
+            &br#"a:3:{i:0;a:2:{s:2:"id";i:1;s:5:"child";a:1:{i:0;i:1;}}i:1;a:2:{s:2:"id";i:2;s:5:"child";a:1:{i:0;i:1;}}i:2;a:2:{s:2:"id";i:3;s:5:"child";r:4;}}"#[..]
+        );
+
+        // Objects are numbered the same way, as are values after a
+        // reference.
+        let node = Rc::new(Node {
+            id: 1,
+            child: Arc::new(vec![]),
+        });
+        let list = (
+            StdClass(Wrapper(node.clone())),
+            Wrapper(node.clone()),
+            Child(node.child.clone()),
+        );
+        assert_eq!(
+            to_vec(&list).unwrap(),
+            &br#"a:3:{i:0;O:8:"stdClass":2:{s:2:"id";i:1;s:5:"child";a:0:{}}i:1;r:2;i:2;r:4;}"#[..]
+        );
+
+        let list = vec![Wrapper(node)];
+        let serialized = to_vec(&list).unwrap();
+        assert_eq!(from_bytes::<Vec<Wrapper>>(&serialized).unwrap(), list);
+    }
+
+    #[test]
+    fn buffered() {
+        let child = Arc::new(vec![1]);
+        let node = |id| Node {
+            id,
+            child: child.clone(),
+        };
+
+        // Sorted maps are written in full, but still counted.
+        let map: BTreeMap<&str, Node> = vec![("b", node(1)), ("a", node(2))].into_iter().collect();
+        let options = SerializerOptions::new().sort_map_keys(true);
+        let value = (map, Child(child.clone()), Child(child.clone()));
+        assert_eq!(
+            to_vec_with_options(&value, &options).unwrap(),
+            &br#"a:3:{i:0;a:2:{s:1:"a";a:2:{s:2:"id";i:2;s:5:"child";a:1:{i:0;i:1;}}s:1:"b";a:2:{s:2:"id";i:1;s:5:"child";a:1:{i:0;i:1;}}}i:1;a:1:{i:0;i:1;}i:2;r:11;}"#[..]
+        );
+
+        let options = SerializerOptions::new().omit_none_fields(true);
+        let value = (node(1), Child(child.clone()), Child(child.clone()));
+        assert_eq!(
+            to_vec_with_options(&value, &options).unwrap(),
+            &br#"a:3:{i:0;a:2:{s:2:"id";i:1;s:5:"child";a:1:{i:0;i:1;}}i:1;a:1:{i:0;i:1;}i:2;r:6;}"#[..]
+        );
+    }
+}
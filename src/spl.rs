@@ -0,0 +1,172 @@
+//! SPL containers wrapping plain arrays.
+//!
+//! `ArrayObject`, `ArrayIterator` and `SplFixedArray` are little more than
+//! arrays in an object. When deserializing into Rust types they are unwrapped,
+//! so a `Vec` or a map accepts them just like the array they contain.
+//! Deserializing into a `Value` keeps the objects as they are.
+
+use crate::de;
+use crate::error::{Error, Result};
+use crate::key::PhpKey;
+use crate::options::DeserializerOptions;
+use crate::value::Value;
+
+/// Kind of SPL container, as far as unwrapping is concerned.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Container {
+    /// `ArrayObject` and its iterators, which hold a storage array.
+    ArrayObject,
+    /// `SplFixedArray`, whose properties are the elements.
+    FixedArray,
+}
+
+impl Container {
+    /// Look up the container kind of a class, if any.
+    pub fn from_class(class: &str) -> Option<Container> {
+        let class = class.strip_prefix('\\').unwrap_or(class);
+        if ["ArrayObject", "ArrayIterator", "RecursiveArrayIterator"]
+            .iter()
+            .any(|c| c.eq_ignore_ascii_case(class))
+        {
+            Some(Container::ArrayObject)
+        } else if class.eq_ignore_ascii_case("SplFixedArray") {
+            Some(Container::FixedArray)
+        } else {
+            None
+        }
+    }
+}
+
+/// Returns `true` if the value is an SPL container that `unwrap` would open.
+pub fn is_container(value: &Value) -> bool {
+    match value {
+        Value::Object { class, .. } => Container::from_class(class).is_some(),
+        Value::Custom(custom) => {
+            Container::from_class(&custom.class) == Some(Container::ArrayObject)
+        }
+        _ => false,
+    }
+}
+
+/// Replace an SPL container by its contents.
+///
+/// `ArrayObject` is serialized either as `C:` record, with a payload of
+/// `x:<flags>;<storage>;m:<members>` (before PHP 7.4), or as an object with
+/// the properties `0 => flags`, `1 => storage`, `2 => members`. Other values
+/// are returned unchanged.
+pub fn unwrap(value: Value) -> Result<Value> {
+    match value {
+        Value::Object { class, properties } => match Container::from_class(&class) {
+            Some(Container::FixedArray) => Ok(Value::Array(properties)),
+            Some(Container::ArrayObject) => properties
+                .into_iter()
+                .find(|(k, _)| *k == PhpKey::Int(1))
+                .map(|(_, storage)| storage)
+                .ok_or_else(|| missing_storage(&class)),
+            None => Ok(Value::Object { class, properties }),
+        },
+        Value::Custom(custom)
+            if Container::from_class(&custom.class) == Some(Container::ArrayObject) =>
+        {
+            de::array_object_storage(&custom.payload, DeserializerOptions::default())
+        }
+        other => Ok(other),
+    }
+}
+
+fn missing_storage(class: &str) -> Error {
+    Error::DeserializationFailed(format!("{class} without a storage array"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{unwrap, Container};
+    use crate::{from_bytes, to_vec, Value};
+    use serde::Deserialize;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn classes() {
+        assert_eq!(
+            Container::from_class("\\arrayobject"),
+            Some(Container::ArrayObject)
+        );
+        assert_eq!(
+            Container::from_class("SplFixedArray"),
+            Some(Container::FixedArray)
+        );
+        assert_eq!(Container::from_class("SplObjectStorage"), None);
+    }
+
+    #[test]
+    fn array_object() {
+        // PHP 7.4+.
+        let modern =
+            br#"O:11:"ArrayObject":4:{i:0;i:0;i:1;a:2:{i:0;s:1:"a";i:1;s:1:"b";}i:2;a:0:{}i:3;N;}"#;
+        // PHP 5 and 7.0 to 7.3.
+        let legacy = br#"C:11:"ArrayObject":45:{x:i:0;a:2:{i:0;s:1:"a";i:1;s:1:"b";};m:a:0:{}}"#;
+
+        for input in &[&modern[..], &legacy[..]] {
+            assert_eq!(
+                from_bytes::<Vec<String>>(input).unwrap(),
+                vec!["a".to_owned(), "b".to_owned()]
+            );
+
+            let mut map = BTreeMap::new();
+            map.insert(0, "a".to_owned());
+            map.insert(1, "b".to_owned());
+            assert_eq!(from_bytes::<BTreeMap<i64, String>>(input).unwrap(), map);
+
+            // `Value` keeps the container.
+            let value: Value = from_bytes(input).unwrap();
+            assert_eq!(value.class(), Some("ArrayObject"));
+            assert_eq!(to_vec(&value).unwrap(), *input);
+            assert_eq!(
+                crate::from_value::<Vec<String>>(value).unwrap(),
+                vec!["a".to_owned(), "b".to_owned()]
+            );
+        }
+    }
+
+    #[test]
+    fn array_iterator_struct() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Config {
+            debug: bool,
+        }
+
+        let input = br#"O:13:"ArrayIterator":3:{i:0;i:0;i:1;a:1:{s:5:"debug";b:1;}i:2;a:0:{}}"#;
+        assert_eq!(from_bytes::<Config>(input).unwrap(), Config { debug: true });
+    }
+
+    #[test]
+    fn fixed_array() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Data {
+            ids: Vec<i64>,
+        }
+
+        let input = br#"a:1:{s:3:"ids";O:13:"SplFixedArray":3:{i:0;i:4;i:1;i:5;i:2;i:6;}}"#;
+        assert_eq!(
+            from_bytes::<Data>(input).unwrap(),
+            Data { ids: vec![4, 5, 6] }
+        );
+
+        let value: Value = from_bytes(input).unwrap();
+        assert_eq!(
+            value.get("ids").and_then(Value::class),
+            Some("SplFixedArray")
+        );
+        assert_eq!(
+            crate::from_value::<Data>(value).unwrap(),
+            Data { ids: vec![4, 5, 6] }
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        let value: Value = from_bytes(br#"O:11:"ArrayObject":1:{i:0;i:0;}"#).unwrap();
+        assert!(unwrap(value).is_err());
+        assert!(from_bytes::<Vec<i64>>(br#"C:11:"ArrayObject":3:{x:i}"#).is_err());
+    }
+}
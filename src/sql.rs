@@ -0,0 +1,151 @@
+//! `sqlx` column types for [`Php`] and [`Value`], with the `sqlx` feature.
+
+use crate::column::Php;
+use crate::de::from_bytes;
+use crate::ser::to_vec;
+use crate::value::Value;
+use serde::{Deserialize, Serialize};
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
+use sqlx::{Database, Decode, Encode, Type};
+
+impl<T, DB> Type<DB> for Php<T>
+where
+    DB: Database,
+    Vec<u8>: Type<DB>,
+{
+    fn type_info() -> DB::TypeInfo {
+        <Vec<u8> as Type<DB>>::type_info()
+    }
+
+    fn compatible(ty: &DB::TypeInfo) -> bool {
+        <Vec<u8> as Type<DB>>::compatible(ty)
+    }
+}
+
+impl<'r, T, DB> Decode<'r, DB> for Php<T>
+where
+    T: Deserialize<'r>,
+    DB: Database,
+    &'r [u8]: Decode<'r, DB>,
+{
+    fn decode(value: <DB as Database>::ValueRef<'r>) -> Result<Self, BoxDynError> {
+        let bytes = <&'r [u8] as Decode<'r, DB>>::decode(value)?;
+        Ok(Php(from_bytes(bytes)?))
+    }
+}
+
+impl<'q, T, DB> Encode<'q, DB> for Php<T>
+where
+    T: Serialize,
+    DB: Database,
+    Vec<u8>: Encode<'q, DB>,
+{
+    fn encode_by_ref(
+        &self,
+        buf: &mut <DB as Database>::ArgumentBuffer<'q>,
+    ) -> Result<IsNull, BoxDynError> {
+        to_vec(&self.0)?.encode(buf)
+    }
+}
+
+impl<DB> Type<DB> for Value
+where
+    DB: Database,
+    Vec<u8>: Type<DB>,
+{
+    fn type_info() -> DB::TypeInfo {
+        <Vec<u8> as Type<DB>>::type_info()
+    }
+
+    fn compatible(ty: &DB::TypeInfo) -> bool {
+        <Vec<u8> as Type<DB>>::compatible(ty)
+    }
+}
+
+impl<'r, DB> Decode<'r, DB> for Value
+where
+    DB: Database,
+    &'r [u8]: Decode<'r, DB>,
+{
+    fn decode(value: <DB as Database>::ValueRef<'r>) -> Result<Self, BoxDynError> {
+        <Php<Value> as Decode<'r, DB>>::decode(value).map(|Php(value)| value)
+    }
+}
+
+impl<'q, DB> Encode<'q, DB> for Value
+where
+    DB: Database,
+    Vec<u8>: Encode<'q, DB>,
+{
+    fn encode_by_ref(
+        &self,
+        buf: &mut <DB as Database>::ArgumentBuffer<'q>,
+    ) -> Result<IsNull, BoxDynError> {
+        Php(self).encode_by_ref(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Php;
+    use crate::Value;
+    use serde::{Deserialize, Serialize};
+    use sqlx::{Connection, Row, SqliteConnection};
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Settings {
+        theme: String,
+        sizes: Vec<i64>,
+    }
+
+    #[test]
+    fn columns() {
+        block_on(async {
+            let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+            sqlx::query("CREATE TABLE users (settings TEXT, extra BLOB)")
+                .execute(&mut conn)
+                .await
+                .unwrap();
+
+            let settings = Settings {
+                theme: "dark".to_owned(),
+                sizes: vec![1, 2],
+            };
+            sqlx::query("INSERT INTO users VALUES (?, ?)")
+                .bind(Php(&settings))
+                .bind(Value::from(vec![Value::Null]))
+                .execute(&mut conn)
+                .await
+                .unwrap();
+
+            let (stored,): (Vec<u8>,) = sqlx::query_as("SELECT settings FROM users")
+                .fetch_one(&mut conn)
+                .await
+                .unwrap();
+            assert_eq!(
+                stored,
+                &br#"a:2:{s:5:"theme";s:4:"dark";s:5:"sizes";a:2:{i:0;i:1;i:1;i:2;}}"#[..]
+            );
+
+            let row = sqlx::query("SELECT settings, extra FROM users")
+                .fetch_one(&mut conn)
+                .await
+                .unwrap();
+            let Php(read): Php<Settings> = row.get(0);
+            assert_eq!(read, settings);
+            let extra: Value = row.get(1);
+            assert_eq!(extra, Value::from(vec![Value::Null]));
+
+            let err = row.try_get::<Php<Vec<i64>>, _>(0).unwrap_err();
+            assert!(matches!(err, sqlx::Error::ColumnDecode { .. }));
+        });
+    }
+}
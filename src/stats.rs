@@ -0,0 +1,188 @@
+//! Statistics about serialized data, to find out what makes it large.
+
+use crate::error::Result;
+use crate::key::PhpKey;
+use crate::token::{Lexer, Token};
+
+/// Number of strings listed in `Stats::largest_strings`.
+const LARGEST_STRINGS: usize = 10;
+
+/// Statistics about a serialized value, see `analyze`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Stats {
+    /// Number of bytes the value takes up in the input.
+    pub length: usize,
+    /// Number of `null` values.
+    pub nulls: usize,
+    /// Number of booleans.
+    pub bools: usize,
+    /// Number of integers.
+    pub ints: usize,
+    /// Number of floats.
+    pub floats: usize,
+    /// Number of strings, not counting array keys.
+    pub strings: usize,
+    /// Number of arrays.
+    pub arrays: usize,
+    /// Number of objects, not counting custom serialized ones.
+    pub objects: usize,
+    /// Number of custom serialized objects (`C:`).
+    pub custom_objects: usize,
+    /// Number of enum cases.
+    pub enums: usize,
+    /// Number of references, of both kinds.
+    pub references: usize,
+    /// Deepest nesting of arrays and objects with elements. `0` for a
+    /// scalar or an empty array.
+    pub max_depth: usize,
+    /// Paths and byte lengths of the largest strings, longest first. Up to
+    /// ten are listed, the first found of strings of the same length.
+    pub largest_strings: Vec<(Vec<PhpKey>, usize)>,
+    /// Keys of the entries of the top-level array or object with the number
+    /// of bytes each takes up, key included, largest first.
+    pub top_level_sizes: Vec<(PhpKey, usize)>,
+}
+
+/// Analyze serialized `input`, counting its values by type and finding its
+/// largest parts.
+///
+/// This helps finding out what bloats large values, such as options or
+/// sessions stored in a database. Like `validate`, it walks the input
+/// without building any values, and input after the value is not looked
+/// at.
+///
+/// ```rust
+/// use php_serde::{analyze, PhpKey};
+///
+/// let input = br#"a:2:{s:4:"logs";a:2:{i:0;s:10:"0123456789";i:1;s:3:"abc";}s:2:"id";i:7;}"#;
+/// let stats = analyze(input).unwrap();
+/// assert_eq!(stats.strings, 2);
+/// assert_eq!(stats.ints, 1);
+/// assert_eq!(stats.max_depth, 2);
+/// assert_eq!(
+///     stats.largest_strings[0],
+///     (vec![PhpKey::from("logs"), PhpKey::Int(0)], 10)
+/// );
+/// assert_eq!(stats.top_level_sizes[0], (PhpKey::from("logs"), 53));
+/// ```
+pub fn analyze(input: &[u8]) -> Result<Stats> {
+    let mut stats = Stats::default();
+    // Keys leading to the current value, one for each open array or object
+    // once its entry's key has been read.
+    let mut path: Vec<PhpKey> = Vec::new();
+    let mut depth = 0_usize;
+    // Key and start of the top-level entry being read.
+    let mut top_level = None;
+
+    let mut lexer = Lexer::new(input);
+    for token in lexer.by_ref() {
+        let (token, span) = token?;
+        let mut done = true;
+        match token {
+            Token::Null => stats.nulls += 1,
+            Token::Bool(_) => stats.bools += 1,
+            Token::Int(_) => stats.ints += 1,
+            Token::Float(_) => stats.floats += 1,
+            Token::Str(s) => {
+                stats.strings += 1;
+                let largest = &mut stats.largest_strings;
+                if largest.len() < LARGEST_STRINGS || largest.last().map_or(0, |l| l.1) < s.len() {
+                    let at = largest.partition_point(|(_, len)| *len >= s.len());
+                    largest.insert(at, (path.clone(), s.len()));
+                    largest.truncate(LARGEST_STRINGS);
+                }
+            }
+            Token::ArrayStart(_) | Token::ObjectStart { .. } => {
+                if matches!(token, Token::ArrayStart(_)) {
+                    stats.arrays += 1;
+                } else {
+                    stats.objects += 1;
+                }
+                depth += 1;
+                done = false;
+            }
+            Token::ArrayEnd | Token::ObjectEnd => depth -= 1,
+            Token::Key(key) => {
+                stats.max_depth = stats.max_depth.max(depth);
+                let key = PhpKey::from(key);
+                if depth == 1 {
+                    top_level = Some((key.clone(), span.start));
+                }
+                path.push(key);
+                done = false;
+            }
+            Token::Custom { .. } => stats.custom_objects += 1,
+            Token::Enum { .. } => stats.enums += 1,
+            Token::Reference(_) | Token::MutableReference(_) => stats.references += 1,
+        }
+
+        // A value has been read completely, ending its entry.
+        if done && depth > 0 && path.len() == depth {
+            path.pop();
+            if depth == 1 {
+                if let Some((key, start)) = top_level.take() {
+                    stats.top_level_sizes.push((key, span.end - start));
+                }
+            }
+        }
+    }
+
+    stats.length = lexer.byte_offset();
+    stats.top_level_sizes.sort_by(|(_, a), (_, b)| b.cmp(a));
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{analyze, Stats};
+    use crate::{ErrorKind, PhpKey};
+
+    #[test]
+    fn stats() {
+        let input = br#"O:4:"User":4:{s:2:"id";i:1;s:4:"data";a:3:{i:0;N;i:1;d:0.5;i:2;a:0:{}}s:4:"misc";a:4:{i:0;b:1;i:1;C:3:"Foo":1:{x}i:2;E:7:"Suit:Up";i:3;r:1;}s:4:"name";s:5:"Alice";}rest"#;
+        let stats = analyze(input).unwrap();
+        let key = PhpKey::from;
+        assert_eq!(
+            stats,
+            Stats {
+                length: input.len() - 4,
+                nulls: 1,
+                bools: 1,
+                ints: 1,
+                floats: 1,
+                strings: 1,
+                arrays: 3,
+                objects: 1,
+                custom_objects: 1,
+                enums: 1,
+                references: 1,
+                max_depth: 2,
+                largest_strings: vec![(vec![key("name")], 5)],
+                top_level_sizes: vec![
+                    (key("misc"), 70),
+                    (key("data"), 43),
+                    (key("name"), 23),
+                    (key("id"), 13),
+                ],
+            }
+        );
+
+        let stats = analyze(b"i:1;").unwrap();
+        assert_eq!(stats.ints, 1);
+        assert_eq!(stats.max_depth, 0);
+        assert!(stats.top_level_sizes.is_empty());
+
+        // Only the largest strings are listed.
+        let strings: Vec<String> = (0..12).map(|n| "x".repeat(n)).collect();
+        let stats = analyze(&crate::to_vec(&strings).unwrap()).unwrap();
+        let lengths: Vec<_> = stats.largest_strings.iter().map(|(_, len)| *len).collect();
+        assert_eq!(lengths, [11, 10, 9, 8, 7, 6, 5, 4, 3, 2]);
+        assert_eq!(stats.largest_strings[0].0, [PhpKey::Int(11)]);
+    }
+
+    #[test]
+    fn invalid() {
+        let err = analyze(b"a:1:{i:0;").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Eof);
+    }
+}
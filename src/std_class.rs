@@ -0,0 +1,153 @@
+//! Generic `stdClass` objects.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::ops::{Deref, DerefMut};
+
+/// Newtype name used by `StdClass` to request object serialization.
+pub const TOKEN: &str = "$php_serde::private::StdClass";
+
+/// Name of PHP's generic object class.
+pub const STD_CLASS: &str = "stdClass";
+
+/// A map, struct or sequence serialized as a PHP `stdClass` object.
+///
+/// Objects of class `stdClass` are little more than associative arrays, and
+/// are what PHP produces when casting an array to an object, or when decoding
+/// JSON objects. Any object deserializes like an associative array already,
+/// so `StdClass` is only needed when writing: the wrapped value is serialized
+/// as `O:8:"stdClass":...` instead of as an array.
+///
+/// When deserializing, both arrays and objects are accepted.
+///
+/// ```rust
+/// use php_serde::{from_bytes, to_vec, StdClass};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Debug, Deserialize, PartialEq, Serialize)]
+/// struct Options {
+///     theme: String,
+/// }
+///
+/// let options = StdClass(Options {
+///     theme: "dark".to_owned(),
+/// });
+///
+/// let serialized = to_vec(&options).unwrap();
+/// assert_eq!(
+///     serialized,
+///     &br#"O:8:"stdClass":1:{s:5:"theme";s:4:"dark";}"#[..]
+/// );
+///
+/// let deserialized: StdClass<Options> = from_bytes(&serialized).unwrap();
+/// assert_eq!(deserialized, options);
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct StdClass<T>(pub T);
+
+impl<T> StdClass<T> {
+    /// Unwrap the inner value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for StdClass<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for StdClass<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T> From<T> for StdClass<T> {
+    fn from(inner: T) -> Self {
+        StdClass(inner)
+    }
+}
+
+impl<T: Serialize> Serialize for StdClass<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(TOKEN, &self.0)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for StdClass<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        T::deserialize(deserializer).map(StdClass)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StdClass;
+    use crate::{from_bytes, to_vec, Value};
+    use serde::{Deserialize, Serialize};
+    use std::collections::{BTreeMap, HashMap};
+
+    #[test]
+    fn serialize_map() {
+        let mut map = BTreeMap::new();
+        map.insert("a", StdClass(BTreeMap::<u8, u8>::new()));
+        map.insert("b", StdClass(BTreeMap::new()));
+        map.get_mut("b").unwrap().insert(7, 8);
+
+        assert_eq!(
+            to_vec(&StdClass(map)).unwrap(),
+            &br#"O:8:"stdClass":2:{s:1:"a";O:8:"stdClass":0:{}s:1:"b";O:8:"stdClass":1:{i:7;i:8;}}"#[..]
+        );
+    }
+
+    #[test]
+    fn serialize_seq() {
+        // PHP: (object) array("x", "y")
+        assert_eq!(
+            to_vec(&StdClass(vec!["x", "y"])).unwrap(),
+            &br#"O:8:"stdClass":2:{i:0;s:1:"x";i:1;s:1:"y";}"#[..]
+        );
+    }
+
+    #[test]
+    fn serialize_scalar_fails() {
+        assert!(to_vec(&StdClass(1)).is_err());
+    }
+
+    #[test]
+    fn deserialize_std_class() {
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct Post {
+            title: String,
+            meta: HashMap<String, Value>,
+        }
+
+        // `meta` is a `stdClass` object, as produced by `json_decode`.
+        let input = br#"a:2:{s:5:"title";s:5:"Hello";s:4:"meta";O:8:"stdClass":2:{s:5:"views";i:10;s:5:"draft";b:0;}}"#;
+        let post: Post = from_bytes(input).unwrap();
+
+        let mut meta = HashMap::new();
+        meta.insert("views".to_owned(), Value::Int(10));
+        meta.insert("draft".to_owned(), Value::Bool(false));
+        assert_eq!(
+            post,
+            Post {
+                title: "Hello".to_owned(),
+                meta,
+            }
+        );
+
+        // Plain arrays are accepted as well.
+        let wrapped: StdClass<HashMap<String, i64>> = from_bytes(br#"a:1:{s:1:"x";i:1;}"#).unwrap();
+        assert_eq!(wrapped.get("x"), Some(&1));
+    }
+}
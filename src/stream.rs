@@ -0,0 +1,148 @@
+//! Iterating over concatenated serialized values.
+
+use crate::de::PhpDeserializer;
+use crate::error::Result;
+use crate::input::Input;
+use serde::Deserialize;
+use std::io::BufRead;
+use std::marker::PhantomData;
+
+/// Iterator deserializing successive values from one input.
+///
+/// Values may follow each other directly or be separated by whitespace, e.g.
+/// one per line. Iteration ends at the end of the input, or after the first
+/// error.
+///
+/// ```rust
+/// use php_serde::{StreamDeserializer, Value};
+///
+/// let input = b"i:1;\ns:1:\"a\";\nN;\n";
+/// let values: Vec<Value> = StreamDeserializer::new(&input[..])
+///     .collect::<Result<_, _>>()
+///     .unwrap();
+/// assert_eq!(values, vec![Value::Int(1), Value::from("a"), Value::Null]);
+/// ```
+#[derive(Debug)]
+pub struct StreamDeserializer<'de, R, T> {
+    de: PhpDeserializer<R>,
+    failed: bool,
+    output: PhantomData<fn() -> T>,
+    lifetime: PhantomData<&'de ()>,
+}
+
+impl<'de, R, T> StreamDeserializer<'de, R, T>
+where
+    R: BufRead,
+    T: Deserialize<'de>,
+{
+    /// Create a stream reading from `input`, with default options.
+    ///
+    /// Use `PhpDeserializer::into_stream` to read with other options.
+    pub fn new(input: R) -> Self {
+        PhpDeserializer::new(input).into_stream()
+    }
+}
+
+impl<R, T> StreamDeserializer<'_, R, T> {
+    /// Number of bytes consumed from the input so far.
+    pub fn byte_offset(&self) -> usize {
+        self.de.byte_offset()
+    }
+}
+
+impl<R> PhpDeserializer<R> {
+    /// Turn the deserializer into an iterator over successive values.
+    pub fn into_stream<'de, T>(self) -> StreamDeserializer<'de, R, T>
+    where
+        R: Input<'de>,
+        T: Deserialize<'de>,
+    {
+        StreamDeserializer {
+            de: self,
+            failed: false,
+            output: PhantomData,
+            lifetime: PhantomData,
+        }
+    }
+}
+
+impl<'de, R, T> Iterator for StreamDeserializer<'de, R, T>
+where
+    R: Input<'de>,
+    T: Deserialize<'de>,
+{
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        if self.failed {
+            return None;
+        }
+
+        let rval = match self.de.skip_whitespace() {
+            Ok(true) => T::deserialize(&mut self.de),
+            Ok(false) => return None,
+            Err(e) => Err(e),
+        }
+        .map_err(|e| e.at(self.de.byte_offset()));
+
+        self.failed = rval.is_err();
+        Some(rval)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StreamDeserializer;
+    use crate::{from_bytes, DeserializerOptions, PhpDeserializer};
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Row {
+        id: i64,
+    }
+
+    #[test]
+    fn concatenated() {
+        let input = br#"a:1:{s:2:"id";i:1;}a:1:{s:2:"id";i:2;}a:1:{s:2:"id";i:3;}"#;
+
+        let mut stream = StreamDeserializer::<_, Row>::new(&input[..]);
+        assert_eq!(stream.next().unwrap().unwrap(), Row { id: 1 });
+        assert_eq!(stream.byte_offset(), 19);
+        assert_eq!(
+            stream.map(Result::unwrap).collect::<Vec<_>>(),
+            vec![Row { id: 2 }, Row { id: 3 }]
+        );
+    }
+
+    #[test]
+    fn empty() {
+        assert!(StreamDeserializer::<_, i64>::new(&b""[..]).next().is_none());
+        assert!(StreamDeserializer::<_, i64>::new(&b" \n"[..])
+            .next()
+            .is_none());
+    }
+
+    #[test]
+    fn stops_after_error() {
+        let mut stream = StreamDeserializer::<_, i64>::new(&b"i:1;\nb:1;\ni:3;"[..]);
+        assert_eq!(stream.next().unwrap().unwrap(), 1);
+        assert!(stream.next().unwrap().is_err());
+        assert!(stream.next().is_none());
+
+        // A truncated value at the end is an error as well.
+        let results: Vec<_> = StreamDeserializer::<_, i64>::new(&b"i:1;i:"[..]).collect();
+        assert_eq!(results.len(), 2);
+        assert!(results[1].is_err());
+    }
+
+    #[test]
+    fn with_options() {
+        let options = DeserializerOptions::new().numeric_strings(true);
+        let values: Vec<i64> = PhpDeserializer::with_options(&br#"s:1:"1";i:2;"#[..], options)
+            .into_stream()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(values, vec![1, 2]);
+        assert!(from_bytes::<i64>(br#"s:1:"1";"#).is_err());
+    }
+}
@@ -0,0 +1,573 @@
+//! Reading and writing serialized data as tokens, without serde.
+
+use crate::de::PhpDeserializer;
+use crate::error::{Error, Result};
+use crate::input::SliceInput;
+use crate::key::PhpKey;
+use crate::options::{DeserializerOptions, SerializerOptions};
+use crate::ser::PhpSerializer;
+use std::io::Write;
+use std::ops::Range;
+
+/// A token of serialized data, read by `Lexer`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Token<'a> {
+    /// `N;`
+    Null,
+    /// `b:0;` or `b:1;`
+    Bool(bool),
+    /// `i:42;`
+    Int(i64),
+    /// `d:0.5;`
+    Float(f64),
+    /// `s:3:"abc";`, with the bytes of the string.
+    Str(&'a [u8]),
+    /// `a:2:{`, the start of an array with the given number of entries.
+    ArrayStart(usize),
+    /// The `}` closing an array.
+    ArrayEnd,
+    /// `O:3:"Foo":2:{`, the start of an object with the given number of
+    /// properties.
+    ObjectStart {
+        /// Class name.
+        class: &'a [u8],
+        /// Number of properties.
+        len: usize,
+    },
+    /// The `}` closing an object.
+    ObjectEnd,
+    /// An array key or property name, read before each value of an array
+    /// or object.
+    Key(Key<'a>),
+    /// `C:3:"Foo":5:{...}`, a custom serialized object with its opaque
+    /// payload.
+    Custom {
+        /// Class name.
+        class: &'a [u8],
+        /// Payload, as written by the class.
+        payload: &'a [u8],
+    },
+    /// `E:8:"Suit:Up";`, a case of a PHP enum.
+    Enum {
+        /// Name of the enum.
+        class: &'a [u8],
+        /// Name of the case.
+        case: &'a [u8],
+    },
+    /// `r:2;`, a reference to a copy of the value numbered `2`.
+    Reference(usize),
+    /// `R:2;`, a PHP reference `&` to the value numbered `2`.
+    MutableReference(usize),
+}
+
+/// An array key or property name, see `Token::Key`.
+///
+/// Property names are passed on as they are, including the prefixes of
+/// private and protected properties.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Key<'a> {
+    /// `i:0;`
+    Int(i64),
+    /// `s:1:"a";`
+    Bytes(&'a [u8]),
+}
+
+impl From<Key<'_>> for PhpKey {
+    fn from(key: Key<'_>) -> Self {
+        match key {
+            Key::Int(n) => PhpKey::Int(n),
+            Key::Bytes(bytes) => PhpKey::String(bytes.to_vec()),
+        }
+    }
+}
+
+/// Pull parser reading serialized data as tokens, with their byte spans.
+///
+/// Meant for tools that inspect or rewrite serialized data without
+/// deserializing it, e.g. to look for strings or rename classes. Tokens
+/// follow the structure of the input: arrays and objects are read as a
+/// start token, a key and a value for each entry, and an end token. Strings
+/// are lent out from the input.
+///
+/// Like `validate`, reading checks that the input is well-formed, with the
+/// length and depth limits of the options. Other options do not apply, and
+/// references are passed on as they are. Iteration ends after a single
+/// value, or after the first error.
+///
+/// ```rust
+/// use php_serde::token::Key;
+/// use php_serde::{Lexer, Token};
+///
+/// let input = br#"a:1:{s:1:"a";O:3:"Foo":0:{}}"#;
+/// let tokens: Vec<_> = Lexer::new(input).collect::<Result<_, _>>().unwrap();
+/// assert_eq!(
+///     tokens,
+///     vec![
+///         (Token::ArrayStart(1), 0..5),
+///         (Token::Key(Key::Bytes(b"a")), 5..13),
+///         (Token::ObjectStart { class: b"Foo", len: 0 }, 13..26),
+///         (Token::ObjectEnd, 26..27),
+///         (Token::ArrayEnd, 27..28),
+///     ]
+/// );
+/// ```
+#[derive(Debug)]
+pub struct Lexer<'a> {
+    input: &'a [u8],
+    de: PhpDeserializer<SliceInput<'a>>,
+    /// Arrays and objects being read, the innermost last.
+    levels: Vec<Level>,
+    max_depth: usize,
+    error_context: usize,
+    /// Whether the value has been read, or reading it failed.
+    done: bool,
+}
+
+/// An array or object being read by `Lexer`.
+#[derive(Debug)]
+struct Level {
+    /// Keys and values left to read.
+    remaining: usize,
+    object: bool,
+}
+
+impl<'a> Lexer<'a> {
+    /// Create a lexer reading from `input`, with default options.
+    pub fn new(input: &'a [u8]) -> Self {
+        Lexer::with_options(input, DeserializerOptions::default())
+    }
+
+    /// Create a lexer reading from `input`, with non-default options.
+    pub fn with_options(input: &'a [u8], options: DeserializerOptions) -> Self {
+        Lexer {
+            input,
+            max_depth: options.max_depth,
+            error_context: options.error_context,
+            de: PhpDeserializer::from_slice_with_options(input, options),
+            levels: Vec::new(),
+            done: false,
+        }
+    }
+
+    /// Number of bytes consumed from the input so far.
+    pub fn byte_offset(&self) -> usize {
+        self.de.byte_offset()
+    }
+
+    fn read_token(&mut self) -> Result<Token<'a>> {
+        let token = match self.levels.last_mut() {
+            Some(level) if level.remaining == 0 => {
+                self.de.read_end_token()?;
+                match self.levels.pop() {
+                    Some(Level { object: true, .. }) => Token::ObjectEnd,
+                    _ => Token::ArrayEnd,
+                }
+            }
+            // Keys and values alternate, starting with a key.
+            Some(level) if level.remaining % 2 == 0 => {
+                level.remaining -= 1;
+                Token::Key(self.de.read_key_token(self.input)?)
+            }
+            level => {
+                if let Some(level) = level {
+                    level.remaining -= 1;
+                }
+                let token = self.de.read_token(self.input)?;
+                match token {
+                    Token::ArrayStart(len) => self.open(len, false)?,
+                    Token::ObjectStart { len, .. } => self.open(len, true)?,
+                    _ => (),
+                }
+                token
+            }
+        };
+
+        self.done = self.levels.is_empty();
+        Ok(token)
+    }
+
+    /// Start reading an array or object with `len` entries.
+    fn open(&mut self, len: usize, object: bool) -> Result<()> {
+        // Like when deserializing, only levels with entries count.
+        if len > 0 && self.levels.len() >= self.max_depth {
+            return Err(Error::DepthLimitExceeded);
+        }
+        let remaining = len.checked_mul(2).ok_or(Error::LengthLimitExceeded(len))?;
+        self.levels.push(Level { remaining, object });
+        Ok(())
+    }
+}
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Result<(Token<'a>, Range<usize>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let start = self.byte_offset();
+        let rval = self.read_token().map_err(|e| {
+            self.done = true;
+            e.at(self.byte_offset())
+                .with_excerpt(self.input, self.error_context)
+        });
+        Some(rval.map(|token| (token, start..self.byte_offset())))
+    }
+}
+
+/// Writer of serialized data from tokens, the counterpart of `Lexer`.
+///
+/// Tokens are written as they come, so that data can be rewritten as it is
+/// read, e.g. by passing the tokens of a `Lexer` on to a `TokenWriter`
+/// after filtering or changing them. The writer only keeps track of the
+/// arrays and objects being written, not of their contents.
+///
+/// Each token is checked to come in a valid order: keys and values
+/// alternate, arrays and objects end after the number of entries given by
+/// their start and with their own end token, and only a single value is
+/// written. Out of order tokens are rejected before anything is written.
+///
+/// Floats are written with the precision of the options, the other options
+/// do not apply. The output is not buffered.
+///
+/// ```rust
+/// use php_serde::{Lexer, Token, TokenWriter};
+///
+/// // Uppercase all strings, except for keys.
+/// let input = br#"a:2:{i:0;s:1:"a";s:1:"b";s:1:"c";}"#;
+/// let mut writer = TokenWriter::new(Vec::new());
+/// for token in Lexer::new(input) {
+///     let (token, _) = token.unwrap();
+///     match token {
+///         Token::Str(s) => writer.write(Token::Str(&s.to_ascii_uppercase())),
+///         token => writer.write(token),
+///     }
+///     .unwrap();
+/// }
+/// let output = writer.finish().unwrap();
+/// assert_eq!(output, br#"a:2:{i:0;s:1:"A";s:1:"b";s:1:"C";}"#);
+/// ```
+#[derive(Debug)]
+pub struct TokenWriter<W> {
+    ser: PhpSerializer<W>,
+    /// Arrays and objects being written, the innermost last.
+    levels: Vec<Level>,
+    max_depth: usize,
+    /// Whether the value has been written.
+    done: bool,
+}
+
+impl<W> TokenWriter<W>
+where
+    W: Write,
+{
+    /// Create a writer writing to `output`, with default options.
+    pub fn new(output: W) -> Self {
+        TokenWriter::with_options(output, SerializerOptions::default())
+    }
+
+    /// Create a writer writing to `output`, with non-default options.
+    pub fn with_options(output: W, options: SerializerOptions) -> Self {
+        TokenWriter {
+            max_depth: options.max_depth,
+            ser: PhpSerializer::with_options(output, options),
+            levels: Vec::new(),
+            done: false,
+        }
+    }
+
+    /// Write a single token.
+    ///
+    /// If writing to the output fails, the token counts as not written, so
+    /// that it can be written again.
+    pub fn write(&mut self, token: Token<'_>) -> Result<()> {
+        let step = self.check(&token)?;
+        self.ser.write_token(token)?;
+        self.advance(step);
+        Ok(())
+    }
+
+    /// Unwrap the writer, failing if the value is incomplete.
+    pub fn finish(self) -> Result<W> {
+        if !self.done {
+            return Err(failed("the value is incomplete"));
+        }
+        Ok(self.ser.into_inner())
+    }
+
+    /// Check that `token` may come next, and work out where it leaves the
+    /// value.
+    fn check(&self, token: &Token<'_>) -> Result<Step> {
+        if self.done {
+            return Err(failed("the value is already complete"));
+        }
+
+        match (self.levels.last(), token) {
+            (Some(level), Token::ArrayEnd | Token::ObjectEnd) => {
+                if level.remaining > 0 {
+                    return Err(failed("expected more entries"));
+                }
+                if level.object != matches!(token, Token::ObjectEnd) {
+                    return Err(failed("ends a different kind of value than started"));
+                }
+                Ok(Step::End)
+            }
+            (None, Token::ArrayEnd | Token::ObjectEnd) => Err(failed("no array or object to end")),
+            (Some(level), Token::Key(_)) if level.remaining % 2 == 0 && level.remaining > 0 => {
+                Ok(Step::Entry(None))
+            }
+            (_, Token::Key(_)) => Err(failed("expected a value, not a key")),
+            (Some(level), _) if level.remaining % 2 == 0 => Err(failed(if level.remaining == 0 {
+                "expected the end of the array or object"
+            } else {
+                "expected a key"
+            })),
+            _ => match *token {
+                Token::ArrayStart(len) => self.open(len, false).map(Some).map(Step::Entry),
+                Token::ObjectStart { len, .. } => self.open(len, true).map(Some).map(Step::Entry),
+                _ => Ok(Step::Entry(None)),
+            },
+        }
+    }
+
+    /// Keep track of where a written token leaves the value.
+    fn advance(&mut self, step: Step) {
+        match step {
+            Step::End => {
+                self.levels.pop();
+            }
+            Step::Entry(open) => {
+                if let Some(level) = self.levels.last_mut() {
+                    level.remaining -= 1;
+                }
+                self.levels.extend(open);
+            }
+        }
+        self.done = self.levels.is_empty();
+    }
+
+    /// Check that an array or object with `len` entries may start here.
+    fn open(&self, len: usize, object: bool) -> Result<Level> {
+        if len > 0 && self.levels.len() >= self.max_depth {
+            return Err(Error::DepthLimitExceeded);
+        }
+        let remaining = len.checked_mul(2).ok_or(Error::LengthLimitExceeded(len))?;
+        Ok(Level { remaining, object })
+    }
+}
+
+/// How a token passed to `TokenWriter` changes where it is in the value.
+enum Step {
+    /// The innermost array or object ends.
+    End,
+    /// A key or value is written, which starts an array or object if given.
+    Entry(Option<Level>),
+}
+
+fn failed(reason: &str) -> Error {
+    Error::SerializationFailed(format!("invalid token order: {reason}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Key, Lexer, Token, TokenWriter};
+    use crate::{DeserializerOptions, Error, ErrorKind, PhpKey, SerializerOptions};
+    use std::io;
+
+    fn tokens(input: &[u8]) -> Vec<Token<'_>> {
+        Lexer::new(input).map(|token| token.unwrap().0).collect()
+    }
+
+    #[test]
+    fn scalars() {
+        assert_eq!(tokens(b"N;"), vec![Token::Null]);
+        assert_eq!(tokens(b"b:1;"), vec![Token::Bool(true)]);
+        assert_eq!(tokens(b"i:-7;"), vec![Token::Int(-7)]);
+        assert_eq!(tokens(b"d:0.5;"), vec![Token::Float(0.5)]);
+        assert_eq!(tokens(br#"s:3:"a;b";"#), vec![Token::Str(b"a;b")]);
+        assert_eq!(
+            tokens(br#"E:7:"Suit:Up";"#),
+            vec![Token::Enum {
+                class: b"Suit",
+                case: b"Up"
+            }]
+        );
+        assert_eq!(
+            tokens(br#"C:3:"Foo":5:{a:0:}}"#),
+            vec![Token::Custom {
+                class: b"Foo",
+                payload: b"a:0:}"
+            }]
+        );
+    }
+
+    #[test]
+    fn nested() {
+        let input =
+            br#"a:3:{i:0;a:0:{}s:1:"o";O:8:"stdClass":1:{s:4:" * x";r:2;}i:5;R:3;}trailing"#;
+        let mut lexer = Lexer::new(input);
+        let spans: Vec<_> = lexer.by_ref().map(Result::unwrap).collect();
+        assert_eq!(
+            spans,
+            vec![
+                (Token::ArrayStart(3), 0..5),
+                (Token::Key(Key::Int(0)), 5..9),
+                (Token::ArrayStart(0), 9..14),
+                (Token::ArrayEnd, 14..15),
+                (Token::Key(Key::Bytes(b"o")), 15..23),
+                (
+                    Token::ObjectStart {
+                        class: b"stdClass",
+                        len: 1
+                    },
+                    23..41
+                ),
+                (Token::Key(Key::Bytes(b" * x")), 41..52),
+                (Token::Reference(2), 52..56),
+                (Token::ObjectEnd, 56..57),
+                (Token::Key(Key::Int(5)), 57..61),
+                (Token::MutableReference(3), 61..65),
+                (Token::ArrayEnd, 65..66),
+            ]
+        );
+        assert_eq!(lexer.byte_offset(), input.len() - 8);
+
+        // Spans cover the input without gaps.
+        assert!(spans.windows(2).all(|w| w[0].1.end == w[1].1.start));
+        assert_eq!(
+            PhpKey::from(Key::Bytes(b"1")),
+            PhpKey::String(b"1".to_vec())
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        let last = |input: &'static [u8]| Lexer::new(input).last().unwrap();
+
+        let err = last(b"a:2:{i:0;b:1;i:1;b:2;}").unwrap_err();
+        assert_eq!(err.position(), Some(21));
+        assert_eq!(err.kind(), ErrorKind::InvalidValue);
+        assert_eq!(last(b"a:1:{i:0;N;").unwrap_err().kind(), ErrorKind::Eof);
+        assert!(matches!(
+            last(b"a:1:{d:0.5;N;}").unwrap_err().into_inner(),
+            Error::UnsupportedArrayKeyType('d')
+        ));
+
+        // Iteration ends after an error.
+        let mut lexer = Lexer::new(b"a:1:{x:0;N;}");
+        assert!(lexer.next().unwrap().is_ok());
+        assert!(lexer.next().unwrap().is_err());
+        assert!(lexer.next().is_none());
+
+        let options = DeserializerOptions::new().max_depth(1).max_length(3);
+        let last = |input: &'static [u8]| {
+            Lexer::with_options(input, options.clone())
+                .last()
+                .unwrap()
+                .map_err(Error::into_inner)
+        };
+        assert!(last(b"a:1:{i:0;a:0:{}}").is_ok());
+        assert!(matches!(
+            last(b"a:1:{i:0;a:1:{i:0;N;}}"),
+            Err(Error::DepthLimitExceeded)
+        ));
+        assert!(matches!(
+            last(br#"s:4:"abcd";"#),
+            Err(Error::LengthLimitExceeded(4))
+        ));
+    }
+
+    #[test]
+    fn rewrite() {
+        for input in [
+            &b"N;"[..],
+            b"d:-0.5;",
+            br#"a:3:{i:0;a:0:{}s:1:"o";O:8:"stdClass":1:{s:1:"x";r:2;}i:5;R:3;}"#,
+            br#"a:2:{i:0;C:3:"Foo":5:{a:0:}}i:1;E:7:"Suit:Up";}"#,
+        ] {
+            let mut writer = TokenWriter::new(Vec::new());
+            for token in Lexer::new(input) {
+                writer.write(token.unwrap().0).unwrap();
+            }
+            assert_eq!(writer.finish().unwrap(), input);
+        }
+    }
+
+    #[test]
+    fn invalid_order() {
+        let write = |tokens: &[Token<'_>]| {
+            let mut writer = TokenWriter::new(Vec::new());
+            for &token in tokens {
+                writer.write(token)?;
+            }
+            writer.finish()
+        };
+
+        assert!(write(&[Token::ArrayStart(1), Token::Int(1)]).is_err());
+        assert!(write(&[Token::ArrayStart(1), Token::ArrayEnd]).is_err());
+        assert!(write(&[Token::ArrayStart(0), Token::ObjectEnd]).is_err());
+        assert!(write(&[Token::Key(Key::Int(0))]).is_err());
+        assert!(write(&[Token::Null, Token::Null]).is_err());
+        assert!(write(&[Token::ArrayStart(1), Token::Key(Key::Int(0))]).is_err());
+        assert!(write(&[
+            Token::ArrayStart(1),
+            Token::Key(Key::Int(0)),
+            Token::Key(Key::Int(1))
+        ])
+        .is_err());
+
+        // Nothing is written for a rejected token.
+        let mut writer = TokenWriter::new(Vec::new());
+        writer.write(Token::ArrayStart(0)).unwrap();
+        assert!(writer.write(Token::Null).is_err());
+        writer.write(Token::ArrayEnd).unwrap();
+        assert_eq!(writer.finish().unwrap(), b"a:0:{}");
+
+        let options = SerializerOptions::new().max_depth(1);
+        let mut writer = TokenWriter::with_options(Vec::new(), options);
+        writer.write(Token::ArrayStart(1)).unwrap();
+        writer.write(Token::Key(Key::Int(0))).unwrap();
+        assert!(matches!(
+            writer.write(Token::ArrayStart(1)),
+            Err(Error::DepthLimitExceeded)
+        ));
+    }
+
+    #[test]
+    fn failed_write() {
+        /// Output failing once, when it has received `fail_at` bytes.
+        struct Flaky {
+            output: Vec<u8>,
+            fail_at: Option<usize>,
+        }
+
+        impl io::Write for Flaky {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                if self.fail_at == Some(self.output.len()) {
+                    self.fail_at = None;
+                    return Err(io::ErrorKind::BrokenPipe.into());
+                }
+                self.output.write(buf)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut writer = TokenWriter::new(Flaky {
+            output: Vec::new(),
+            fail_at: Some(5),
+        });
+        writer.write(Token::ArrayStart(1)).unwrap();
+        let err = writer.write(Token::Key(Key::Int(0))).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Io);
+
+        // The failed token is written again, as if it was never passed.
+        writer.write(Token::Key(Key::Int(0))).unwrap();
+        writer.write(Token::Null).unwrap();
+        writer.write(Token::ArrayEnd).unwrap();
+        assert_eq!(writer.finish().unwrap().output, b"a:1:{i:0;N;}");
+    }
+}
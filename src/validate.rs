@@ -0,0 +1,265 @@
+//! Checking input without deserializing it.
+
+use crate::de::PhpDeserializer;
+use crate::error::Result;
+use crate::options::DeserializerOptions;
+use crate::token::Lexer;
+
+/// Summary of a valid serialized value, see `validate`.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Report {
+    /// Number of bytes the value takes up in the input.
+    pub length: usize,
+    /// Number of values, including the value itself and all nested values,
+    /// but not array keys.
+    pub values: usize,
+    /// Number of arrays.
+    pub arrays: usize,
+    /// Number of objects, including custom serialized objects.
+    pub objects: usize,
+    /// Number of array elements and object properties.
+    pub elements: usize,
+    /// Number of strings, including string keys.
+    pub strings: usize,
+    /// Total length of all strings, including string keys.
+    pub string_bytes: usize,
+    /// Deepest nesting of arrays and objects with elements, as limited by
+    /// `DeserializerOptions::max_depth`. `0` for a scalar or an empty array.
+    pub max_depth: usize,
+}
+
+/// Check that `input` starts with a well-formed serialized value, without
+/// deserializing it.
+///
+/// Type indicators, length headers, delimiters and nesting are checked, as
+/// are integers, floats and booleans. Strings and the payloads of custom
+/// serialized objects are passed over without copying them. Nothing is
+/// allocated, which makes this a cheap check of untrusted input before
+/// deserializing it, e.g. with limits set in `validate_with_options`.
+///
+/// Like `from_bytes`, input after the value is not looked at. Compare
+/// `Report::length` to the length of the input to reject it.
+///
+/// ```rust
+/// use php_serde::validate;
+///
+/// let report = validate(br#"a:2:{i:0;s:3:"abc";i:1;a:1:{s:1:"x";d:0.5;}}"#).unwrap();
+/// assert_eq!(report.values, 4);
+/// assert_eq!(report.elements, 3);
+/// assert_eq!(report.string_bytes, 4);
+/// assert_eq!(report.max_depth, 2);
+///
+/// let err = validate(b"a:2:{i:0;b:1;i:1;b:2;}").unwrap_err();
+/// assert_eq!(err.position(), Some(21));
+/// ```
+pub fn validate(input: &[u8]) -> Result<Report> {
+    validate_with_options(input, &DeserializerOptions::default())
+}
+
+/// Check that `input` starts with a well-formed serialized value, enforcing
+/// the length and depth limits of `options`.
+///
+/// Other options do not apply, since no Rust values are built.
+pub fn validate_with_options(input: &[u8], options: &DeserializerOptions) -> Result<Report> {
+    let mut des = PhpDeserializer::from_slice_with_options(input, options.clone());
+    let mut report = Report::default();
+    des.validate_value(&mut report).map_err(|e| {
+        e.at(des.byte_offset())
+            .with_excerpt(input, options.error_context)
+    })?;
+    report.length = des.byte_offset();
+    Ok(report)
+}
+
+/// Guess whether `data` holds a serialized value, like WordPress's
+/// `is_serialized()`.
+///
+/// Only the first bytes and the last byte are looked at, after trimming
+/// whitespace: the type indicator, the `:` after it, a length or a scalar
+/// of the right form, and the `;` or `}` a value ends with. This is meant
+/// for columns holding either serialized values or plain strings, to
+/// choose between them without parsing. Plain strings that happen to look
+/// like this are taken for serialized values, and values with other type
+/// indicators, such as references, are not recognized.
+///
+/// ```rust
+/// use php_serde::looks_serialized;
+///
+/// assert!(looks_serialized(br#"a:1:{i:0;s:1:"x";}"#));
+/// assert!(looks_serialized(b" i:42; "));
+/// assert!(!looks_serialized(b"hello"));
+/// assert!(!looks_serialized(b"s:5:not quoted;"));
+/// ```
+pub fn looks_serialized(data: &[u8]) -> bool {
+    let is_space = |b: &u8| b" \t\n\r\0\x0B".contains(b);
+    let start = data.iter().position(|b| !is_space(b)).unwrap_or(data.len());
+    let end = data
+        .iter()
+        .rposition(|b| !is_space(b))
+        .map_or(start, |end| end + 1);
+    let data = &data[start..end];
+
+    if data == b"N;" {
+        return true;
+    }
+    let ([token, b':', rest @ ..], Some(b';' | b'}')) = (data, data.last()) else {
+        return false;
+    };
+    if data.len() < 4 {
+        return false;
+    }
+
+    match token {
+        b's' if data[data.len() - 2] != b'"' => false,
+        b's' | b'a' | b'O' | b'E' => {
+            let digits = rest.iter().take_while(|b| b.is_ascii_digit()).count();
+            digits > 0 && rest.get(digits) == Some(&b':')
+        }
+        b'b' | b'i' | b'd' => {
+            let Some((b';', scalar)) = rest.split_last() else {
+                return false;
+            };
+            !scalar.is_empty()
+                && scalar
+                    .iter()
+                    .all(|b| b.is_ascii_digit() || b".E+-".contains(b))
+        }
+        _ => false,
+    }
+}
+
+/// Check that `input` starts with a well-formed token, i.e. a scalar or
+/// the start of an array or object.
+///
+/// This is stricter than `looks_serialized`, reading the first token like
+/// `Lexer` does, but still cheap: the contents of arrays and objects are
+/// not looked at. Use `validate` to check the whole value.
+///
+/// ```rust
+/// use php_serde::starts_with_valid_token;
+///
+/// assert!(starts_with_valid_token(br#"s:3:"abc";"#));
+/// assert!(starts_with_valid_token(b"a:2:{i:0;"));
+/// assert!(!starts_with_valid_token(br#"s:9:"abc";"#));
+/// assert!(!starts_with_valid_token(b"b:2;"));
+/// ```
+pub fn starts_with_valid_token(input: &[u8]) -> bool {
+    matches!(Lexer::new(input).next(), Some(Ok(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        looks_serialized, starts_with_valid_token, validate, validate_with_options, Report,
+    };
+    use crate::{to_vec, DeserializerOptions, Error, ErrorKind, Value};
+
+    #[test]
+    fn report() {
+        let input = br#"a:3:{s:1:"a";O:3:"Foo":1:{s:1:"b";a:0:{}}i:5;C:3:"Bar":4:{abcd}s:1:"c";E:7:"Suit:Up";}trailing"#;
+        assert_eq!(
+            validate(input).unwrap(),
+            Report {
+                length: input.len() - 8,
+                values: 5,
+                arrays: 2,
+                objects: 2,
+                elements: 4,
+                strings: 3,
+                string_bytes: 3,
+                max_depth: 2,
+            }
+        );
+
+        for input in [
+            &b"N;"[..],
+            b"b:0;",
+            b"i:-12;",
+            b"d:-1.5E-3;",
+            b"d:INF;",
+            b"a:0:{}",
+        ] {
+            let report = validate(input).unwrap();
+            assert_eq!(report.values, 1);
+            assert_eq!(report.length, input.len());
+            assert_eq!(report.max_depth, 0);
+        }
+
+        // Everything this crate writes is valid.
+        let value = Value::from(vec![Value::from("x"), Value::Float(0.1)]);
+        assert!(validate(&to_vec(&value).unwrap()).is_ok());
+    }
+
+    #[test]
+    fn invalid() {
+        let kind = |input: &[u8]| validate(input).unwrap_err().kind();
+        assert_eq!(kind(b""), ErrorKind::Eof);
+        assert_eq!(kind(br#"s:2:"abc";"#), ErrorKind::UnexpectedToken);
+        assert_eq!(kind(b"a:2:{i:0;N;}"), ErrorKind::UnexpectedToken);
+        assert_eq!(kind(b"a:1:{d:0.5;N;}"), ErrorKind::UnexpectedToken);
+        assert_eq!(kind(b"i:99999999999999999999;"), ErrorKind::InvalidValue);
+        assert_eq!(kind(b"b:2;"), ErrorKind::InvalidValue);
+        assert_eq!(kind(b"r:1;"), ErrorKind::UnexpectedToken);
+
+        let err = validate(br#"a:1:{i:0;a:1:{s:1:"x";x:1;}}"#).unwrap_err();
+        assert_eq!(err.position(), Some(24));
+        assert!(matches!(err.into_inner(), Error::InvalidTypeIndicator('x')));
+    }
+
+    #[test]
+    fn sniffing() {
+        for data in [
+            &b"N;"[..],
+            b"b:1;",
+            b"i:-12;",
+            b"d:1.5E+3;",
+            br#"s:1:"x";"#,
+            b"a:0:{}",
+            br#"O:3:"Foo":0:{}"#,
+            br#"E:7:"Suit:Up";"#,
+            b"\n\t a:0:{}\0",
+            // Only looked at superficially.
+            b"a:9:{garbage;",
+        ] {
+            assert!(looks_serialized(data), "{:?}", data);
+        }
+        for data in [
+            &b""[..],
+            b"N",
+            b"i:;",
+            b"i:1",
+            b"i:0x1;",
+            b"b:1;;x",
+            br#"s:1:"x"#,
+            br#"s:1:x;"#,
+            b"a:x:{}",
+            b"r:1;",
+            b"plain text;",
+        ] {
+            assert!(!looks_serialized(data), "{:?}", data);
+        }
+
+        assert!(starts_with_valid_token(b"d:INF;"));
+        assert!(starts_with_valid_token(br#"O:3:"Foo":1:{"#));
+        assert!(!starts_with_valid_token(b""));
+        assert!(starts_with_valid_token(b"a:9:{garbage;"));
+        assert!(!starts_with_valid_token(b"i:1"));
+    }
+
+    #[test]
+    fn limits() {
+        let options = DeserializerOptions::new().max_depth(1).max_length(3);
+        let validate =
+            |input: &[u8]| validate_with_options(input, &options).map_err(Error::into_inner);
+
+        assert!(validate(b"a:1:{i:0;a:0:{}}").is_ok());
+        assert!(matches!(
+            validate(b"a:1:{i:0;a:1:{i:0;N;}}"),
+            Err(Error::DepthLimitExceeded)
+        ));
+        assert!(matches!(
+            validate(br#"s:4:"abcd";"#),
+            Err(Error::LengthLimitExceeded(4))
+        ));
+    }
+}
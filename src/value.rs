@@ -0,0 +1,686 @@
+//! Dynamically typed PHP values.
+
+use crate::allowed_classes;
+use crate::custom::CustomSerialized;
+use crate::key::PhpKey;
+use crate::numeric::{self, Number};
+use crate::property::Visibility;
+use serde::de::{self, MapAccess, SeqAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::fmt;
+
+mod deserializer;
+mod merge;
+
+pub use deserializer::{from_value, is_list};
+pub use merge::{diff, Difference, MergePolicy, PatchOp};
+
+/// Newtype name used by `Value` to request untyped deserialization.
+///
+/// When deserializing a `Value`, the PHP deserializer presents every array as
+/// a map (instead of guessing between sequence and map) and strings as
+/// bytestrings, so no information is lost.
+pub const TOKEN: &str = "$php_serde::private::Value";
+
+/// Key of the extra first map entry carrying the class name of an object.
+///
+/// Objects are passed through serde as maps of their properties. Where the
+/// class name must be preserved (i.e. for `Value`), it is prepended as an
+/// entry with this key. Other formats will see it as a regular map entry.
+pub const CLASS_KEY: &str = "$php_serde::private::class";
+
+/// Key of the map entry carrying the raw payload of a custom serialized
+/// object, following the `CLASS_KEY` entry. See `CustomSerialized`.
+pub const PAYLOAD_KEY: &str = "$php_serde::private::payload";
+
+/// Key of the map entry carrying the case name of an enum, following the
+/// `CLASS_KEY` entry. See `PhpEnum`.
+pub const CASE_KEY: &str = "$php_serde::private::case";
+
+/// Any PHP value.
+///
+/// Arrays keep their entries in the order they were serialized in, along
+/// with their original keys.
+///
+/// ```rust
+/// use php_serde::{from_bytes, PhpKey, Value};
+///
+/// let value: Value = from_bytes(br#"a:2:{i:3;s:3:"foo";s:1:"x";b:1;}"#).unwrap();
+/// assert_eq!(
+///     value,
+///     Value::Array(vec![
+///         (PhpKey::Int(3), Value::from("foo")),
+///         (PhpKey::from("x"), Value::Bool(true)),
+///     ])
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    /// `null`.
+    Null,
+    /// Boolean.
+    Bool(bool),
+    /// Integer.
+    Int(i64),
+    /// Float.
+    Float(f64),
+    /// String. PHP strings are bytestrings and not necessarily UTF-8.
+    String(Vec<u8>),
+    /// Array, as an ordered list of key-value pairs.
+    Array(Vec<(PhpKey, Value)>),
+    /// Object, with its class name and properties in order.
+    Object {
+        /// Fully qualified class name, without a leading backslash.
+        class: String,
+        /// Properties, as an ordered list of key-value pairs.
+        properties: Vec<(PhpKey, Value)>,
+    },
+    /// Object using custom serialization, see `CustomSerialized`.
+    Custom(CustomSerialized),
+    /// Case of a PHP 8.1 enum.
+    Enum {
+        /// Fully qualified class name of the enum, without a leading backslash.
+        class: String,
+        /// Name of the case.
+        case: String,
+    },
+}
+
+impl Default for Value {
+    #[inline]
+    fn default() -> Self {
+        Value::Null
+    }
+}
+
+impl Value {
+    /// Returns `true` if the value is `null`.
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    /// Returns the boolean, if the value is one.
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Value::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Returns the integer, if the value is one.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Value::Int(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    /// Returns the float, if the value is one.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Value::Float(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    /// Returns the raw bytes, if the value is a string.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the string, if the value is a string containing valid UTF-8.
+    pub fn as_str(&self) -> Option<&str> {
+        self.as_bytes().and_then(|s| std::str::from_utf8(s).ok())
+    }
+
+    /// Returns the array entries, if the value is an array.
+    pub fn as_array(&self) -> Option<&[(PhpKey, Value)]> {
+        match self {
+            Value::Array(entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    /// Returns the mutable array entries, if the value is an array.
+    pub fn as_array_mut(&mut self) -> Option<&mut Vec<(PhpKey, Value)>> {
+        match self {
+            Value::Array(entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    /// Returns the class name, if the value is an object.
+    pub fn class(&self) -> Option<&str> {
+        match self {
+            Value::Object { class, .. }
+            | Value::Custom(CustomSerialized { class, .. })
+            | Value::Enum { class, .. } => Some(class),
+            _ => None,
+        }
+    }
+
+    /// Returns the entries of an array or the properties of an object.
+    pub fn entries(&self) -> Option<&[(PhpKey, Value)]> {
+        match self {
+            Value::Array(entries)
+            | Value::Object {
+                properties: entries,
+                ..
+            } => Some(entries),
+            _ => None,
+        }
+    }
+
+    /// Returns the entries of an array or the properties of an object,
+    /// mutably.
+    pub fn entries_mut(&mut self) -> Option<&mut Vec<(PhpKey, Value)>> {
+        match self {
+            Value::Array(entries)
+            | Value::Object {
+                properties: entries,
+                ..
+            } => Some(entries),
+            _ => None,
+        }
+    }
+
+    /// Looks up an array entry or object property by key.
+    ///
+    /// The key is coerced like PHP would, so `get("1")` and `get(1)` are
+    /// equivalent. Returns `None` if the value is neither an array nor an
+    /// object, or the key is missing.
+    pub fn get(&self, key: impl Into<PhpKey>) -> Option<&Value> {
+        let key = key.into();
+        self.entries()?
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| v)
+    }
+
+    /// Looks up an array entry or object property by key, mutably.
+    pub fn get_mut(&mut self, key: impl Into<PhpKey>) -> Option<&mut Value> {
+        let key = key.into();
+        self.entries_mut()?
+            .iter_mut()
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| v)
+    }
+
+    /// Looks up an object property by its plain name, regardless of its
+    /// visibility.
+    ///
+    /// Returns the property along with its visibility, or `None` if the
+    /// value is not an object or has no such property.
+    ///
+    /// ```rust
+    /// use php_serde::{from_bytes, Value, Visibility};
+    ///
+    /// let value: Value = from_bytes(b"O:1:\"A\":1:{s:4:\"\0*\0x\";i:1;}").unwrap();
+    /// assert_eq!(
+    ///     value.property("x"),
+    ///     Some((Visibility::Protected, &Value::Int(1)))
+    /// );
+    /// ```
+    pub fn property(&self, name: &str) -> Option<(Visibility, &Value)> {
+        let Value::Object { properties, .. } = self else {
+            return None;
+        };
+
+        properties.iter().find_map(|(k, v)| {
+            let (visibility, plain) = Visibility::demangle(k.as_bytes()?);
+            (plain == name.as_bytes()).then_some((visibility, v))
+        })
+    }
+
+    /// Converts to an integer following PHP's `(int)` cast rules.
+    ///
+    /// Strings are parsed up to the first non-numeric character, so `"42abc"`
+    /// becomes `42` and `"abc"` becomes `0`. Floats are truncated, arrays
+    /// become `0` if empty and `1` otherwise. Objects become `1`.
+    pub fn as_i64_loose(&self) -> i64 {
+        match self {
+            Value::Null => 0,
+            Value::Bool(b) => i64::from(*b),
+            Value::Int(n) => *n,
+            Value::Float(n) => numeric::float_to_int(*n),
+            Value::String(s) => match numeric::parse_numeric(s) {
+                Some((Number::Int(n), _)) => n,
+                Some((Number::Float(n), _)) => numeric::float_to_int_saturating(n),
+                None => 0,
+            },
+            Value::Array(entries) => i64::from(!entries.is_empty()),
+            Value::Object { .. } | Value::Custom(_) | Value::Enum { .. } => 1,
+        }
+    }
+
+    /// Converts to a float following PHP's `(float)` cast rules.
+    ///
+    /// Strings are parsed up to the first non-numeric character, see
+    /// [`as_i64_loose`](Value::as_i64_loose).
+    #[allow(clippy::cast_precision_loss)]
+    pub fn as_f64_loose(&self) -> f64 {
+        match self {
+            Value::Float(n) => *n,
+            Value::String(s) => match numeric::parse_numeric(s) {
+                Some((Number::Int(n), _)) => n as f64,
+                Some((Number::Float(n), _)) => n,
+                None => 0.0,
+            },
+            other => other.as_i64_loose() as f64,
+        }
+    }
+
+    /// Converts to a boolean following PHP's `(bool)` cast rules.
+    ///
+    /// `null`, `0`, `0.0`, `""`, `"0"` and empty arrays are `false`,
+    /// everything else (including all objects) is `true`.
+    pub fn as_bool_loose(&self) -> bool {
+        match self {
+            Value::Null => false,
+            Value::Bool(b) => *b,
+            Value::Int(n) => *n != 0,
+            Value::Float(n) => *n != 0.0,
+            Value::String(s) => !(s.is_empty() || s == b"0"),
+            Value::Array(entries) => !entries.is_empty(),
+            Value::Object { .. } | Value::Custom(_) | Value::Enum { .. } => true,
+        }
+    }
+
+    /// Converts to a string following PHP's `(string)` cast rules.
+    ///
+    /// `true` becomes `"1"`, `false` and `null` become `""`, floats are
+    /// formatted with PHP's default precision of 14 digits and arrays become
+    /// `"Array"`. Objects, which PHP refuses to convert unless they implement
+    /// `__toString()`, become their class name. Invalid UTF-8 in strings is
+    /// replaced with `U+FFFD`.
+    pub fn as_string_lossy(&self) -> Cow<'_, str> {
+        match self {
+            Value::Null | Value::Bool(false) => Cow::Borrowed(""),
+            Value::Bool(true) => Cow::Borrowed("1"),
+            Value::Int(n) => Cow::Owned(n.to_string()),
+            Value::Float(n) => Cow::Owned(numeric::format_float(*n, Some(14))),
+            Value::String(s) => String::from_utf8_lossy(s),
+            Value::Array(_) => Cow::Borrowed("Array"),
+            Value::Object { class, .. }
+            | Value::Custom(CustomSerialized { class, .. })
+            | Value::Enum { class, .. } => Cow::Borrowed(class),
+        }
+    }
+}
+
+impl From<bool> for Value {
+    #[inline]
+    fn from(v: bool) -> Self {
+        Value::Bool(v)
+    }
+}
+
+macro_rules! impl_from_int {
+    ($($ty:ty),*) => {
+        $(
+            impl From<$ty> for Value {
+                #[inline]
+                fn from(v: $ty) -> Self {
+                    Value::Int(i64::from(v))
+                }
+            }
+        )*
+    };
+}
+
+impl_from_int!(i8, i16, i32, i64, u8, u16, u32);
+
+impl From<f32> for Value {
+    #[inline]
+    fn from(v: f32) -> Self {
+        Value::Float(f64::from(v))
+    }
+}
+
+impl From<f64> for Value {
+    #[inline]
+    fn from(v: f64) -> Self {
+        Value::Float(v)
+    }
+}
+
+impl From<&str> for Value {
+    #[inline]
+    fn from(v: &str) -> Self {
+        Value::String(v.as_bytes().to_vec())
+    }
+}
+
+impl From<String> for Value {
+    #[inline]
+    fn from(v: String) -> Self {
+        Value::String(v.into_bytes())
+    }
+}
+
+impl From<&[u8]> for Value {
+    #[inline]
+    fn from(v: &[u8]) -> Self {
+        Value::String(v.to_vec())
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    #[inline]
+    fn from(v: Option<T>) -> Self {
+        v.map_or(Value::Null, Into::into)
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    /// Creates a list, i.e. an array with keys `0..n`.
+    fn from(v: Vec<T>) -> Self {
+        Value::Array(
+            (0..)
+                .zip(v)
+                .map(|(idx, v)| (PhpKey::Int(idx), v.into()))
+                .collect(),
+        )
+    }
+}
+
+impl<K: Into<PhpKey>, V: Into<Value>> std::iter::FromIterator<(K, V)> for Value {
+    /// Creates an array from key-value pairs.
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Value::Array(
+            iter.into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        )
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Value::Null => serializer.serialize_unit(),
+            Value::Bool(b) => serializer.serialize_bool(*b),
+            Value::Int(n) => serializer.serialize_i64(*n),
+            Value::Float(n) => serializer.serialize_f64(*n),
+            Value::String(s) => serializer.serialize_bytes(s),
+            Value::Array(entries) => {
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (k, v) in entries {
+                    map.serialize_entry(k, v)?;
+                }
+                map.end()
+            }
+            Value::Object { class, properties } => {
+                // Like PHP, incomplete objects are written under their
+                // original class.
+                let (class, properties) = allowed_classes::original_class(class, properties)
+                    .unwrap_or((class, properties));
+                let mut map = serializer.serialize_map(Some(properties.len() + 1))?;
+                map.serialize_entry(CLASS_KEY, class)?;
+                for (k, v) in properties {
+                    map.serialize_entry(k, v)?;
+                }
+                map.end()
+            }
+            Value::Custom(custom) => custom.serialize(serializer),
+            Value::Enum { class, case } => {
+                let mut map = serializer.serialize_map(Some(2))?;
+                map.serialize_entry(CLASS_KEY, class)?;
+                map.serialize_entry(CASE_KEY, case)?;
+                map.end()
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_newtype_struct(TOKEN, ValueVisitor)
+    }
+}
+
+/// Capacity to reserve for a collection, without trusting the size hint
+/// too far.
+fn cautious(hint: Option<usize>) -> usize {
+    hint.unwrap_or(0).min(4096)
+}
+
+/// Visitor building a `Value` from any self-describing input.
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("any PHP value")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Value, E> {
+        Ok(Value::Int(v))
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Value, E> {
+        // PHP itself overflows large integers into floats.
+        Ok(i64::try_from(v).map_or(Value::Float(v as f64), Value::Int))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Value, E> {
+        Ok(Value::Float(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Value, E> {
+        Ok(Value::String(v))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Value::deserialize(deserializer)
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // Other deserializers do not know our token, so we end up here.
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut entries = Vec::with_capacity(cautious(seq.size_hint()));
+        let mut idx = 0;
+        while let Some(v) = seq.next_element()? {
+            entries.push((PhpKey::Int(idx), v));
+            idx += 1;
+        }
+        Ok(Value::Array(entries))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut entries = Vec::with_capacity(cautious(map.size_hint()));
+
+        let mut class = None;
+        if let Some(key) = map.next_key::<PhpKey>()? {
+            if key.as_bytes() == Some(CLASS_KEY.as_bytes()) {
+                class = Some(map.next_value::<String>()?);
+            } else {
+                entries.push((key, map.next_value()?));
+            }
+        }
+
+        // A payload or case right after the class makes for a custom
+        // serialized object or an enum case.
+        if let Some(class) = &mut class {
+            if let Some(key) = map.next_key::<PhpKey>()? {
+                match key.as_bytes() {
+                    Some(k) if k == PAYLOAD_KEY.as_bytes() => {
+                        let payload = map.next_value::<Vec<u8>>()?;
+                        return Ok(Value::Custom(CustomSerialized {
+                            class: std::mem::take(class),
+                            payload,
+                        }));
+                    }
+                    Some(k) if k == CASE_KEY.as_bytes() => {
+                        let case = map.next_value::<String>()?;
+                        return Ok(Value::Enum {
+                            class: std::mem::take(class),
+                            case,
+                        });
+                    }
+                    _ => entries.push((key, map.next_value()?)),
+                }
+            }
+        }
+
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+
+        Ok(match class {
+            Some(class) => Value::Object {
+                class,
+                properties: entries,
+            },
+            None => Value::Array(entries),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Value;
+    use crate::{from_bytes, to_vec, PhpKey};
+
+    #[test]
+    fn deserialize_scalars() {
+        assert_eq!(from_bytes::<Value>(b"N;").unwrap(), Value::Null);
+        assert_eq!(from_bytes::<Value>(b"b:1;").unwrap(), Value::Bool(true));
+        assert_eq!(from_bytes::<Value>(b"i:-7;").unwrap(), Value::Int(-7));
+        assert_eq!(from_bytes::<Value>(b"d:0.5;").unwrap(), Value::Float(0.5));
+        assert_eq!(
+            from_bytes::<Value>(b"s:3:\"\xff\x00a\";").unwrap(),
+            Value::String(b"\xff\x00a".to_vec())
+        );
+    }
+
+    #[test]
+    fn deserialize_unordered_array() {
+        let value: Value = from_bytes(br#"a:3:{i:2;s:1:"c";i:0;s:1:"a";s:1:"k";a:0:{}}"#).unwrap();
+
+        assert_eq!(
+            value,
+            Value::Array(vec![
+                (PhpKey::Int(2), Value::from("c")),
+                (PhpKey::Int(0), Value::from("a")),
+                (PhpKey::from("k"), Value::Array(Vec::new())),
+            ])
+        );
+        assert_eq!(value.get(0), Some(&Value::from("a")));
+        assert_eq!(value.get("2"), Some(&Value::from("c")));
+        assert_eq!(value.get(1), None);
+    }
+
+    #[test]
+    fn loose_conversions() {
+        assert_eq!(Value::from("42abc").as_i64_loose(), 42);
+        assert_eq!(Value::from(" 1e3").as_i64_loose(), 1000);
+        assert_eq!(Value::from("abc").as_i64_loose(), 0);
+        assert_eq!(Value::from("9999999999999999999").as_i64_loose(), i64::MAX);
+        assert_eq!(Value::Float(-3.9).as_i64_loose(), -3);
+        assert_eq!(Value::Bool(true).as_i64_loose(), 1);
+
+        assert_eq!(Value::from("1.5kg").as_f64_loose(), 1.5);
+        assert_eq!(Value::Int(2).as_f64_loose(), 2.0);
+        assert_eq!(Value::Null.as_f64_loose(), 0.0);
+
+        assert!(!Value::from("").as_bool_loose());
+        assert!(!Value::from("0").as_bool_loose());
+        assert!(Value::from("0.0").as_bool_loose());
+        assert!(!Value::Float(0.0).as_bool_loose());
+        assert!(!Value::Array(Vec::new()).as_bool_loose());
+        assert!(Value::from(vec![0]).as_bool_loose());
+
+        assert_eq!(Value::Bool(false).as_string_lossy(), "");
+        assert_eq!(Value::Bool(true).as_string_lossy(), "1");
+        assert_eq!(Value::Float(0.1 + 0.2).as_string_lossy(), "0.3");
+        assert_eq!(Value::Float(1e20).as_string_lossy(), "1.0E+20");
+        assert_eq!(
+            Value::String(b"a\xffb".to_vec()).as_string_lossy(),
+            "a\u{fffd}b"
+        );
+        assert_eq!(Value::from(vec![1]).as_string_lossy(), "Array");
+    }
+
+    #[test]
+    fn deserialize_object() {
+        let value: Value = from_bytes(br#"O:8:"stdClass":1:{s:1:"a";O:3:"Foo":0:{}}"#).unwrap();
+
+        assert_eq!(
+            value,
+            Value::Object {
+                class: "stdClass".to_owned(),
+                properties: vec![(
+                    PhpKey::from("a"),
+                    Value::Object {
+                        class: "Foo".to_owned(),
+                        properties: Vec::new(),
+                    }
+                )],
+            }
+        );
+        assert_eq!(value.class(), Some("stdClass"));
+        assert_eq!(value.get("a").and_then(Value::class), Some("Foo"));
+    }
+
+    #[test]
+    fn roundtrip() {
+        let input = br#"a:5:{i:0;N;i:5;d:1.5;s:3:"foo";a:1:{i:0;b:0;}s:3:"bar";s:2:"xy";i:6;O:1:"A":1:{s:1:"b";a:0:{}}}"#;
+        let value: Value = from_bytes(input).unwrap();
+
+        assert_eq!(to_vec(&value).unwrap(), &input[..]);
+    }
+}
@@ -0,0 +1,452 @@
+//! Deserializing Rust types out of a `Value`.
+
+use super::{Value, CASE_KEY, CLASS_KEY, PAYLOAD_KEY, TOKEN};
+use crate::custom::CustomSerialized;
+use crate::error::{Error, Result};
+use crate::key::PhpKey;
+use crate::{property, spl};
+use serde::de::value::{MapAccessDeserializer, MapDeserializer, SeqDeserializer};
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Unexpected, Visitor};
+use serde::{forward_to_deserialize_any, Deserializer};
+use std::convert::TryFrom;
+
+/// Deserialize an instance of type `T` from a `Value`.
+///
+/// The value is interpreted the same way `from_bytes` would interpret its
+/// serialized form.
+///
+/// ```rust
+/// use php_serde::{from_bytes, from_value, Value};
+/// use serde::Deserialize;
+///
+/// #[derive(Debug, Deserialize, PartialEq)]
+/// struct User {
+///     name: String,
+/// }
+///
+/// let value: Value = from_bytes(br#"O:4:"User":1:{s:4:"name";s:3:"Bob";}"#).unwrap();
+/// assert_eq!(value.class(), Some("User"));
+///
+/// let user: User = from_value(value).unwrap();
+/// assert_eq!(user, User { name: "Bob".to_owned() });
+/// ```
+pub fn from_value<T>(value: Value) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    T::deserialize(value)
+}
+
+/// Returns `true` if the entries use the keys `0..n`, in order.
+pub fn is_list(entries: &[(PhpKey, Value)]) -> bool {
+    (0..)
+        .zip(entries)
+        .all(|(idx, (k, _))| *k == PhpKey::Int(idx))
+}
+
+impl Value {
+    /// Describe the value for type mismatch errors.
+    fn unexpected(&self) -> Unexpected<'_> {
+        match self {
+            Value::Null => Unexpected::Unit,
+            Value::Bool(b) => Unexpected::Bool(*b),
+            Value::Int(n) => Unexpected::Signed(*n),
+            Value::Float(n) => Unexpected::Float(*n),
+            Value::String(s) => Unexpected::Bytes(s),
+            Value::Array(_) => Unexpected::Map,
+            Value::Object { .. } | Value::Custom(_) => Unexpected::Other("object"),
+            Value::Enum { .. } => Unexpected::Other("enum case"),
+        }
+    }
+
+    /// Deserialize without guessing at the Rust representation, see
+    /// `value::TOKEN`.
+    fn deserialize_value<'de, V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::String(s) => visitor.visit_byte_buf(s),
+            Value::Array(entries) => visit_entries(entries, visitor),
+            Value::Object { class, properties } => {
+                let class_entry = (
+                    PhpKey::String(CLASS_KEY.as_bytes().to_vec()),
+                    Value::String(class.into_bytes()),
+                );
+                visit_entries(std::iter::once(class_entry).chain(properties), visitor)
+            }
+            // SPL containers are not unwrapped here.
+            Value::Custom(custom) => visit_custom(custom, visitor),
+            other => other.deserialize_any(visitor),
+        }
+    }
+}
+
+/// Pass array entries or object properties on as a map.
+fn visit_entries<'de, I, V>(entries: I, visitor: V) -> Result<V::Value>
+where
+    I: IntoIterator<Item = (PhpKey, Value)>,
+    V: Visitor<'de>,
+{
+    let mut map = MapDeserializer::new(entries.into_iter());
+    let rval = visitor.visit_map(&mut map)?;
+    map.end()?;
+    Ok(rval)
+}
+
+/// Pass object properties on as a map, with plain property names.
+fn visit_properties<'de, V>(properties: Vec<(PhpKey, Value)>, visitor: V) -> Result<V::Value>
+where
+    V: Visitor<'de>,
+{
+    visit_entries(
+        properties
+            .into_iter()
+            .map(|(k, v)| (property::demangle_key(k), v)),
+        visitor,
+    )
+}
+
+/// Pass a custom serialized object on as a map of its class and payload.
+fn visit_custom<'de, V>(custom: CustomSerialized, visitor: V) -> Result<V::Value>
+where
+    V: Visitor<'de>,
+{
+    let entries = vec![
+        (
+            PhpKey::String(CLASS_KEY.as_bytes().to_vec()),
+            Value::String(custom.class.into_bytes()),
+        ),
+        (
+            PhpKey::String(PAYLOAD_KEY.as_bytes().to_vec()),
+            Value::String(custom.payload),
+        ),
+    ];
+    visit_entries(entries, visitor)
+}
+
+impl IntoDeserializer<'_, Error> for Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> Deserializer<'de> for Value {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if spl::is_container(&self) {
+            return spl::unwrap(self)?.deserialize_any(visitor);
+        }
+
+        match self {
+            Value::Null => visitor.visit_unit(),
+            Value::Bool(b) => visitor.visit_bool(b),
+            Value::Int(n) => visitor.visit_i64(n),
+            Value::Float(n) => visitor.visit_f64(n),
+            // Like the PHP deserializer, strings are offered as text if they
+            // are valid UTF-8, and as bytes otherwise.
+            Value::String(s) => match String::from_utf8(s) {
+                Ok(s) => visitor.visit_string(s),
+                Err(e) => visitor.visit_byte_buf(e.into_bytes()),
+            },
+            Value::Array(entries) => {
+                if is_list(&entries) {
+                    let mut seq = SeqDeserializer::new(entries.into_iter().map(|(_, v)| v));
+                    let rval = visitor.visit_seq(&mut seq)?;
+                    seq.end()?;
+                    Ok(rval)
+                } else {
+                    visit_entries(entries, visitor)
+                }
+            }
+            Value::Object { properties, .. } => visit_properties(properties, visitor),
+            Value::Custom(custom) => visit_custom(custom, visitor),
+            Value::Enum { class, case } => visit_entries(
+                vec![
+                    (
+                        PhpKey::String(CLASS_KEY.as_bytes().to_vec()),
+                        Value::String(class.into_bytes()),
+                    ),
+                    (
+                        PhpKey::String(CASE_KEY.as_bytes().to_vec()),
+                        Value::String(case.into_bytes()),
+                    ),
+                ],
+                visitor,
+            ),
+        }
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::String(s) => visitor.visit_string(
+                String::from_utf8(s).map_err(|e| Error::NotUtf8String(e.utf8_error()))?,
+            ),
+            other => other.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::String(s) => visitor.visit_byte_buf(s),
+            other => other.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            // Strings are sequences of bytes, e.g. for `Vec<u8>`.
+            Value::String(s) => {
+                let mut seq = SeqDeserializer::new(s.into_iter());
+                let rval = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(rval)
+            }
+            other => other.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        // Characters are serialized as integers.
+        match self {
+            Value::Int(n) => {
+                let c = u32::try_from(n)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| de::Error::invalid_value(Unexpected::Signed(n), &visitor))?;
+                visitor.visit_char(c)
+            }
+            other => Err(de::Error::invalid_type(other.unexpected(), &visitor)),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Null => visitor.visit_none(),
+            other => visitor.visit_some(other),
+        }
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Array(entries) if entries.is_empty() => visitor.visit_unit(),
+            other => other.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if spl::is_container(&self) {
+            return spl::unwrap(self)?.deserialize_map(visitor);
+        }
+
+        match self {
+            Value::Array(entries) => visit_entries(entries, visitor),
+            Value::Object { properties, .. } => visit_properties(properties, visitor),
+            Value::Custom(custom) => visit_custom(custom, visitor),
+            other => Err(de::Error::invalid_type(other.unexpected(), &visitor)),
+        }
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if name == TOKEN {
+            return self.deserialize_value(visitor);
+        }
+
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        drop(self);
+        visitor.visit_unit()
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Enum { case, .. } => visitor.visit_enum(case.into_deserializer()),
+            Value::String(s) => {
+                let variant =
+                    String::from_utf8(s).map_err(|e| Error::NotUtf8String(e.utf8_error()))?;
+                visitor.visit_enum(variant.into_deserializer())
+            }
+            Value::Array(entries) if entries.len() == 1 => visitor.visit_enum(
+                MapAccessDeserializer::new(MapDeserializer::new(entries.into_iter())),
+            ),
+            other => other.deserialize_any(visitor),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64
+        tuple_struct identifier
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::from_value;
+    use crate::{from_bytes, Value};
+    use serde::Deserialize;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn typed_from_value() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Data {
+            name: String,
+            raw: Vec<u8>,
+            tags: Vec<String>,
+            sparse: BTreeMap<i64, f64>,
+            missing: Option<bool>,
+            letter: char,
+        }
+
+        let value: Value = from_bytes(
+            br#"a:6:{s:4:"name";s:3:"foo";s:3:"raw";s:2:"ab";s:4:"tags";a:1:{i:0;s:1:"x";}s:6:"sparse";a:2:{i:3;d:0.5;i:1;d:1;}s:7:"missing";N;s:6:"letter";i:97;}"#,
+        )
+        .unwrap();
+
+        let mut sparse = BTreeMap::new();
+        sparse.insert(1, 1.0);
+        sparse.insert(3, 0.5);
+
+        assert_eq!(
+            from_value::<Data>(value).unwrap(),
+            Data {
+                name: "foo".to_owned(),
+                raw: b"ab".to_vec(),
+                tags: vec!["x".to_owned()],
+                sparse,
+                missing: None,
+                letter: 'a',
+            }
+        );
+    }
+
+    #[test]
+    fn mangled_from_value() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct A {
+            x: i64,
+            y: i64,
+        }
+
+        let value: Value =
+            from_bytes(b"O:1:\"A\":2:{s:4:\"\0*\0x\";i:1;s:4:\"\0A\0y\";i:2;}").unwrap();
+        // The value itself retains the mangled names.
+        assert!(value.get("x").is_none());
+        assert_eq!(from_value::<A>(value).unwrap(), A { x: 1, y: 2 });
+    }
+
+    #[test]
+    fn untagged_from_value() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        #[serde(untagged)]
+        enum Field {
+            Int(i64),
+            Text(String),
+            Bytes(serde_bytes::ByteBuf),
+        }
+
+        let value = Value::from(vec![
+            Value::Int(1),
+            Value::from("a"),
+            Value::String(vec![0xff]),
+        ]);
+        assert_eq!(
+            from_value::<Vec<Field>>(value).unwrap(),
+            vec![
+                Field::Int(1),
+                Field::Text("a".to_owned()),
+                Field::Bytes(serde_bytes::ByteBuf::from(vec![0xff])),
+            ]
+        );
+    }
+
+    #[test]
+    fn value_from_value() {
+        let value: Value =
+            from_bytes(br#"a:2:{i:1;s:1:"a";i:0;O:1:"A":1:{s:1:"b";s:1:"c";}}"#).unwrap();
+        assert_eq!(from_value::<Value>(value.clone()).unwrap(), value);
+    }
+}
@@ -0,0 +1,530 @@
+//! Merging, patching and diffing of `Value`s.
+
+use super::Value;
+use crate::de::from_bytes;
+use crate::error::{Error, Result};
+use crate::key::PhpKey;
+use crate::ser::to_vec;
+use std::fmt;
+
+/// How arrays are combined by [`Value::merge_with`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MergePolicy {
+    /// Entries replace existing entries with the same key, arrays present on
+    /// both sides are merged recursively.
+    ///
+    /// Equivalent to PHP's `array_replace_recursive()`.
+    Replace,
+    /// Integer-keyed entries are appended to the array with fresh indices,
+    /// string-keyed entries are handled like in `Replace`.
+    ///
+    /// This is what `array_merge()` does on each level, so lists get
+    /// concatenated instead of overwritten.
+    Append,
+}
+
+impl Default for MergePolicy {
+    #[inline]
+    fn default() -> Self {
+        MergePolicy::Replace
+    }
+}
+
+/// A single operation applied by [`Value::patch`].
+///
+/// Paths are given as a list of array keys, starting at the patched value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PatchOp {
+    /// Set the value at the path, creating missing arrays along the way.
+    Set(Vec<PhpKey>, Value),
+    /// Remove the value at the path, if present.
+    Remove(Vec<PhpKey>),
+    /// Merge a value into the one at the path, or set it if missing.
+    Merge(Vec<PhpKey>, Value, MergePolicy),
+}
+
+/// A difference between two values, found by [`Value::diff`].
+///
+/// Paths are given as a list of array keys, starting at the compared
+/// values. It is displayed with the path written the way it would be in
+/// PHP and the values serialized, e.g. `changed ["name"]: s:1:"a"; -> i:1;`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Difference {
+    /// An entry only present in the new value.
+    Added {
+        /// Path of the entry.
+        path: Vec<PhpKey>,
+        /// The added value.
+        value: Value,
+    },
+    /// An entry only present in the old value.
+    Removed {
+        /// Path of the entry.
+        path: Vec<PhpKey>,
+        /// The removed value.
+        value: Value,
+    },
+    /// A value that differs between the old and the new value.
+    Changed {
+        /// Path of the value.
+        path: Vec<PhpKey>,
+        /// The old value.
+        old: Value,
+        /// The new value.
+        new: Value,
+    },
+}
+
+impl Difference {
+    /// Path of the entry or value that differs.
+    pub fn path(&self) -> &[PhpKey] {
+        match self {
+            Difference::Added { path, .. }
+            | Difference::Removed { path, .. }
+            | Difference::Changed { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let serialized = |value: &Value| {
+            to_vec(value).map_or_else(
+                |_| "?".to_owned(),
+                |bytes| String::from_utf8_lossy(&bytes).into_owned(),
+            )
+        };
+        let path = match display_path(self.path()) {
+            path if path.is_empty() => "value".to_owned(),
+            path => path,
+        };
+        match self {
+            Difference::Added { value, .. } => write!(f, "added {path}: {}", serialized(value)),
+            Difference::Removed { value, .. } => {
+                write!(f, "removed {path}: {}", serialized(value))
+            }
+            Difference::Changed { old, new, .. } => {
+                write!(
+                    f,
+                    "changed {path}: {} -> {}",
+                    serialized(old),
+                    serialized(new)
+                )
+            }
+        }
+    }
+}
+
+/// Patching the old value with the differences gives the new one.
+impl From<Difference> for PatchOp {
+    fn from(difference: Difference) -> Self {
+        match difference {
+            Difference::Added { path, value }
+            | Difference::Changed {
+                path, new: value, ..
+            } => PatchOp::Set(path, value),
+            Difference::Removed { path, .. } => PatchOp::Remove(path),
+        }
+    }
+}
+
+/// Compare two serialized values, see [`Value::diff`].
+///
+/// ```rust
+/// use php_serde::diff;
+///
+/// let before = br#"a:2:{s:4:"name";s:3:"Bob";s:4:"tags";a:1:{i:0;s:1:"a";}}"#;
+/// let after = br#"a:2:{s:4:"name";s:5:"Alice";s:4:"tags";a:2:{i:0;s:1:"a";i:1;s:1:"b";}}"#;
+/// let differences: Vec<String> = diff(before, after)
+///     .unwrap()
+///     .iter()
+///     .map(ToString::to_string)
+///     .collect();
+/// assert_eq!(
+///     differences,
+///     [
+///         r#"changed ["name"]: s:3:"Bob"; -> s:5:"Alice";"#,
+///         r#"added ["tags"][1]: s:1:"b";"#,
+///     ]
+/// );
+/// ```
+pub fn diff(a: &[u8], b: &[u8]) -> Result<Vec<Difference>> {
+    let a: Value = from_bytes(a)?;
+    let b: Value = from_bytes(b)?;
+    Ok(a.diff(&b))
+}
+
+impl Value {
+    /// Deep merge another value into this one, replacing entries with the same
+    /// key.
+    ///
+    /// Shorthand for `merge_with(other, MergePolicy::Replace)`.
+    ///
+    /// ```rust
+    /// use php_serde::{from_bytes, to_vec, Value};
+    ///
+    /// let mut config: Value = from_bytes(br#"a:2:{s:4:"name";s:1:"a";s:3:"ids";a:1:{i:0;i:1;}}"#).unwrap();
+    /// let update: Value = from_bytes(br#"a:1:{s:4:"name";s:1:"b";}"#).unwrap();
+    /// config.merge(&update);
+    ///
+    /// assert_eq!(
+    ///     to_vec(&config).unwrap(),
+    ///     br#"a:2:{s:4:"name";s:1:"b";s:3:"ids";a:1:{i:0;i:1;}}"#.to_vec()
+    /// );
+    /// ```
+    pub fn merge(&mut self, other: &Value) {
+        self.merge_with(other, MergePolicy::Replace);
+    }
+
+    /// Deep merge another value into this one, using the given policy.
+    ///
+    /// If either side is not an array, `other` simply replaces `self`.
+    pub fn merge_with(&mut self, other: &Value, policy: MergePolicy) {
+        let (Some(entries), Value::Array(other_entries)) = (self.as_array_mut(), other) else {
+            *self = other.clone();
+            return;
+        };
+
+        for (key, value) in other_entries {
+            if let (MergePolicy::Append, PhpKey::Int(_)) = (policy, key) {
+                let idx = next_index(entries);
+                entries.push((PhpKey::Int(idx), value.clone()));
+                continue;
+            }
+
+            match entries.iter_mut().find(|(k, _)| k == key) {
+                Some((_, existing)) => existing.merge_with(value, policy),
+                None => entries.push((key.clone(), value.clone())),
+            }
+        }
+    }
+
+    /// Looks up a nested value by its path.
+    pub fn pointer(&self, path: &[PhpKey]) -> Option<&Value> {
+        path.iter().try_fold(self, |value, key| {
+            value
+                .entries()?
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v)
+        })
+    }
+
+    /// Looks up a nested value by its path, mutably.
+    pub fn pointer_mut(&mut self, path: &[PhpKey]) -> Option<&mut Value> {
+        path.iter().try_fold(self, |value, key| {
+            value
+                .entries_mut()?
+                .iter_mut()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v)
+        })
+    }
+
+    /// Sets the value at the given path and returns the previous one.
+    ///
+    /// Like assignments in PHP, missing entries and `null` values along the
+    /// way are turned into arrays. Object properties can be set as well.
+    /// Fails if the path leads through any other non-array value.
+    pub fn set_path(&mut self, path: &[PhpKey], value: Value) -> Result<Option<Value>> {
+        let slot = self.vivify(path)?;
+        let previous = std::mem::replace(slot, value);
+        Ok(if previous.is_null() {
+            None
+        } else {
+            Some(previous)
+        })
+    }
+
+    /// Removes the value at the given path and returns it.
+    pub fn remove_path(&mut self, path: &[PhpKey]) -> Option<Value> {
+        let (last, parent) = path.split_last()?;
+        let entries = self.pointer_mut(parent)?.entries_mut()?;
+        let pos = entries.iter().position(|(k, _)| k == last)?;
+        Some(entries.remove(pos).1)
+    }
+
+    /// Lists the differences from this value to `other`.
+    ///
+    /// Arrays, and objects of the same class, are compared entry by entry,
+    /// matching entries by key, so that added and removed entries are
+    /// reported along with the changes of nested values. The order of the
+    /// entries is not compared. Other values are compared as a whole. The
+    /// differences are listed in the order of the entries of this value,
+    /// followed by the added entries.
+    ///
+    /// The differences can be turned into `PatchOp`s, which patch this value
+    /// into `other`, apart from the order of its entries.
+    pub fn diff(&self, other: &Value) -> Vec<Difference> {
+        let mut differences = Vec::new();
+        diff_into(self, other, &mut Vec::new(), &mut differences);
+        differences
+    }
+
+    /// Applies a list of patch operations in order.
+    ///
+    /// Stops at the first failing operation; operations applied before it are
+    /// not rolled back.
+    ///
+    /// ```rust
+    /// use php_serde::{from_bytes, to_vec, PatchOp, PhpKey, Value};
+    ///
+    /// let mut options: Value = from_bytes(br#"a:1:{s:5:"theme";s:4:"dark";}"#).unwrap();
+    /// options
+    ///     .patch(vec![
+    ///         PatchOp::Remove(vec![PhpKey::from("theme")]),
+    ///         PatchOp::Set(vec![PhpKey::from("mail"), PhpKey::from("port")], Value::Int(25)),
+    ///     ])
+    ///     .unwrap();
+    ///
+    /// assert_eq!(
+    ///     to_vec(&options).unwrap(),
+    ///     br#"a:1:{s:4:"mail";a:1:{s:4:"port";i:25;}}"#.to_vec()
+    /// );
+    /// ```
+    pub fn patch<I>(&mut self, ops: I) -> Result<()>
+    where
+        I: IntoIterator<Item = PatchOp>,
+    {
+        for op in ops {
+            match op {
+                PatchOp::Set(path, value) => {
+                    self.set_path(&path, value)?;
+                }
+                PatchOp::Remove(path) => {
+                    self.remove_path(&path);
+                }
+                PatchOp::Merge(path, value, policy) => {
+                    self.vivify(&path)?.merge_with(&value, policy);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the slot at the given path, creating arrays and entries as
+    /// needed. Newly created entries are `null`.
+    fn vivify(&mut self, path: &[PhpKey]) -> Result<&mut Value> {
+        let mut current = self;
+
+        for (depth, key) in path.iter().enumerate() {
+            if current.is_null() {
+                *current = Value::Array(Vec::new());
+            }
+
+            let Some(entries) = current.entries_mut() else {
+                return Err(Error::NotAnArray(display_path(&path[..depth])));
+            };
+
+            let pos = entries
+                .iter()
+                .position(|(k, _)| k == key)
+                .unwrap_or_else(|| {
+                    entries.push((key.clone(), Value::Null));
+                    entries.len() - 1
+                });
+            current = &mut entries[pos].1;
+        }
+
+        Ok(current)
+    }
+}
+
+fn diff_into(a: &Value, b: &Value, path: &mut Vec<PhpKey>, differences: &mut Vec<Difference>) {
+    let comparable = match (a, b) {
+        (Value::Array(_), Value::Array(_)) => true,
+        (Value::Object { class, .. }, Value::Object { class: other, .. }) => class == other,
+        _ => false,
+    };
+    let (Some(entries), Some(other_entries), true) = (a.entries(), b.entries(), comparable) else {
+        if a != b {
+            differences.push(Difference::Changed {
+                path: path.clone(),
+                old: a.clone(),
+                new: b.clone(),
+            });
+        }
+        return;
+    };
+
+    for (key, value) in entries {
+        path.push(key.clone());
+        match other_entries.iter().find(|(k, _)| k == key) {
+            Some((_, other)) => diff_into(value, other, path, differences),
+            None => differences.push(Difference::Removed {
+                path: path.clone(),
+                value: value.clone(),
+            }),
+        }
+        path.pop();
+    }
+    for (key, value) in other_entries {
+        if !entries.iter().any(|(k, _)| k == key) {
+            let mut path = path.clone();
+            path.push(key.clone());
+            differences.push(Difference::Added {
+                path,
+                value: value.clone(),
+            });
+        }
+    }
+}
+
+/// Next free integer index of an array, as used by PHP when appending.
+fn next_index(entries: &[(PhpKey, Value)]) -> i64 {
+    entries
+        .iter()
+        .filter_map(|(k, _)| k.as_int())
+        .max()
+        .map_or(0, |max| max.saturating_add(1))
+}
+
+/// Render a path the way it would be written in PHP, e.g. `["a"][0]`.
+fn display_path(path: &[PhpKey]) -> String {
+    path.iter()
+        .map(|key| match key {
+            PhpKey::Int(n) => format!("[{n}]"),
+            PhpKey::String(_) => format!("[\"{key}\"]"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Difference, MergePolicy, PatchOp};
+    use crate::{PhpKey, Value};
+
+    fn list(items: &[i64]) -> Value {
+        Value::from(items.to_vec())
+    }
+
+    #[test]
+    fn merge_replace() {
+        let mut a: Value = vec![
+            ("x", Value::Int(1)),
+            ("sub", vec![("y", Value::Int(2))].into_iter().collect()),
+            ("ids", list(&[1, 2])),
+        ]
+        .into_iter()
+        .collect();
+        let b: Value = vec![
+            ("sub", vec![("z", Value::Int(3))].into_iter().collect()),
+            ("ids", list(&[9])),
+        ]
+        .into_iter()
+        .collect();
+
+        a.merge(&b);
+
+        assert_eq!(a.get("x"), Some(&Value::Int(1)));
+        assert_eq!(
+            a.get("sub"),
+            Some(
+                &vec![("y", Value::Int(2)), ("z", Value::Int(3))]
+                    .into_iter()
+                    .collect()
+            )
+        );
+        assert_eq!(a.get("ids"), Some(&list(&[9, 2])));
+    }
+
+    #[test]
+    fn merge_append() {
+        let mut a: Value = vec![("ids", list(&[1, 2]))].into_iter().collect();
+        let b: Value = vec![("ids", list(&[9]))].into_iter().collect();
+
+        a.merge_with(&b, MergePolicy::Append);
+
+        assert_eq!(a.get("ids"), Some(&list(&[1, 2, 9])));
+    }
+
+    #[test]
+    fn merge_scalar_replaces() {
+        let mut a = list(&[1]);
+        a.merge(&Value::from("x"));
+        assert_eq!(a, Value::from("x"));
+    }
+
+    #[test]
+    fn patch_ops() {
+        let mut v = Value::Null;
+        let path = vec![PhpKey::from("a"), PhpKey::Int(0)];
+
+        v.patch(vec![
+            PatchOp::Set(path.clone(), Value::Int(1)),
+            PatchOp::Merge(vec![PhpKey::from("b")], list(&[1]), MergePolicy::Append),
+            PatchOp::Merge(vec![PhpKey::from("b")], list(&[2]), MergePolicy::Append),
+        ])
+        .unwrap();
+
+        assert_eq!(v.pointer(&path), Some(&Value::Int(1)));
+        assert_eq!(v.get("b"), Some(&list(&[1, 2])));
+        assert_eq!(v.remove_path(&path), Some(Value::Int(1)));
+        assert_eq!(v.pointer(&path), None);
+    }
+
+    #[test]
+    fn patch_through_scalar_fails() {
+        let mut v: Value = vec![("a", Value::Int(1))].into_iter().collect();
+        let err = v
+            .set_path(&[PhpKey::from("a"), PhpKey::from("b")], Value::Null)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Cannot use a scalar value as an array: [\"a\"]"
+        );
+    }
+
+    #[test]
+    fn diff() {
+        let a: Value = crate::from_bytes(
+            br#"a:4:{s:1:"a";i:1;s:1:"b";a:2:{i:0;N;i:1;b:1;}s:1:"c";O:3:"Foo":1:{s:1:"x";d:0.5;}s:1:"d";s:1:"x";}"#,
+        )
+        .unwrap();
+        let b: Value = crate::from_bytes(
+            br#"a:4:{s:1:"b";a:2:{i:1;b:0;i:0;N;}s:1:"c";O:3:"Bar":1:{s:1:"x";d:0.5;}s:1:"d";s:1:"x";s:1:"e";a:0:{}}"#,
+        )
+        .unwrap();
+        let key = PhpKey::from;
+        let differences = a.diff(&b);
+        assert_eq!(
+            differences,
+            [
+                Difference::Removed {
+                    path: vec![key("a")],
+                    value: Value::Int(1)
+                },
+                Difference::Changed {
+                    path: vec![key("b"), PhpKey::Int(1)],
+                    old: Value::Bool(true),
+                    new: Value::Bool(false)
+                },
+                Difference::Changed {
+                    path: vec![key("c")],
+                    old: a.get("c").unwrap().clone(),
+                    new: b.get("c").unwrap().clone()
+                },
+                Difference::Added {
+                    path: vec![key("e")],
+                    value: Value::Array(Vec::new())
+                },
+            ]
+        );
+        assert_eq!(
+            differences[1].to_string(),
+            r#"changed ["b"][1]: b:1; -> b:0;"#
+        );
+
+        let mut patched = a.clone();
+        patched
+            .patch(differences.into_iter().map(PatchOp::from))
+            .unwrap();
+        assert!(patched.diff(&b).is_empty());
+        assert!(a.diff(&a).is_empty());
+        assert_eq!(
+            Value::Int(1).diff(&Value::Null)[0].to_string(),
+            "changed value: i:1; -> N;"
+        );
+    }
+}
@@ -0,0 +1,279 @@
+//! Converting `Value` to and from JavaScript values, with the `wasm` feature.
+
+use crate::custom::CustomSerialized;
+use crate::error::{Error, Result};
+use crate::key::PhpKey;
+use crate::options::DeserializerOptions;
+use crate::value::{self, Value};
+use js_sys::{Array, Map, Number, Object, Reflect, Uint8Array};
+use std::convert::TryFrom;
+use wasm_bindgen::{JsCast, JsValue, UnwrapThrowExt};
+
+/// Largest integer a JavaScript number holds exactly, `2^53 - 1`.
+const MAX_SAFE_INTEGER: i64 = (1 << 53) - 1;
+
+/// Convert a PHP value to JavaScript.
+///
+/// * Integers become numbers, or `BigInt`s beyond `Number.MAX_SAFE_INTEGER`.
+/// * Strings become strings if they are valid UTF-8, `Uint8Array`s of their
+///   bytes otherwise.
+/// * Lists become arrays. Other arrays become `Map`s, which keep the order
+///   of their entries and integer keys as numbers, unlike plain objects.
+/// * Objects become plain objects `{ class, properties }` with a `Map` of
+///   their properties, custom serialized objects `{ class, payload }` with a
+///   `Uint8Array` and enum cases `{ class, case }`.
+impl From<Value> for JsValue {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Null => JsValue::NULL,
+            Value::Bool(b) => JsValue::from_bool(b),
+            Value::Int(n) => int(n),
+            Value::Float(v) => JsValue::from_f64(v),
+            Value::String(s) => bytes(s),
+            Value::Array(entries) if value::is_list(&entries) => {
+                let array = Array::new();
+                for (_, v) in entries {
+                    array.push(&JsValue::from(v));
+                }
+                array.into()
+            }
+            Value::Array(entries) => map(entries),
+            Value::Object { class, properties } => record(class, "properties", map(properties)),
+            Value::Custom(CustomSerialized { class, payload }) => {
+                record(class, "payload", Uint8Array::from(&payload[..]).into())
+            }
+            Value::Enum { class, case } => record(class, "case", JsValue::from(case)),
+        }
+    }
+}
+
+/// Convert a JavaScript value to PHP, the reverse of `From<Value>`.
+///
+/// `null` and `undefined` become `Value::Null`. Numbers that are safe
+/// integers become integers, other numbers floats, so a float such as `1.0`
+/// comes back as an integer. `BigInt`s must fit into a PHP integer.
+/// Uint8Arrays become strings of their bytes.
+///
+/// Plain objects shaped like the objects, custom serialized objects and
+/// enum cases written by `From<Value>` are read back as such. Other plain
+/// objects and `Map`s become arrays, with keys coerced like PHP array keys.
+///
+/// Functions, symbols and other values fail, as do values nested deeper
+/// than the default `DeserializerOptions::max_depth`, e.g. cyclic ones.
+impl TryFrom<JsValue> for Value {
+    type Error = Error;
+
+    fn try_from(js: JsValue) -> Result<Self> {
+        from_js(&js, DeserializerOptions::default().max_depth)
+    }
+}
+
+// Exact within the range of safe integers.
+#[allow(clippy::cast_precision_loss)]
+fn int(n: i64) -> JsValue {
+    if (-MAX_SAFE_INTEGER..=MAX_SAFE_INTEGER).contains(&n) {
+        JsValue::from_f64(n as f64)
+    } else {
+        JsValue::from(n)
+    }
+}
+
+fn bytes(s: Vec<u8>) -> JsValue {
+    match String::from_utf8(s) {
+        Ok(s) => JsValue::from(s),
+        Err(e) => Uint8Array::from(e.as_bytes()).into(),
+    }
+}
+
+fn map(entries: Vec<(PhpKey, Value)>) -> JsValue {
+    let map = Map::new();
+    for (k, v) in entries {
+        let key = match k {
+            PhpKey::Int(n) => int(n),
+            PhpKey::String(s) => bytes(s),
+        };
+        map.set(&key, &JsValue::from(v));
+    }
+    map.into()
+}
+
+/// Write an object as a plain object with its class and one more property.
+fn record(class: String, name: &str, inner: JsValue) -> JsValue {
+    let object = Object::new();
+    Reflect::set(&object, &JsValue::from_str("class"), &JsValue::from(class)).unwrap_throw();
+    Reflect::set(&object, &JsValue::from_str(name), &inner).unwrap_throw();
+    object.into()
+}
+
+// Safe integers convert exactly.
+#[allow(clippy::cast_possible_truncation)]
+fn from_js(js: &JsValue, depth: usize) -> Result<Value> {
+    if js.is_null_or_undefined() {
+        return Ok(Value::Null);
+    }
+    if let Some(b) = js.as_bool() {
+        return Ok(Value::Bool(b));
+    }
+    if let Some(v) = js.as_f64() {
+        return Ok(if Number::is_safe_integer(js) {
+            Value::Int(v as i64)
+        } else {
+            Value::Float(v)
+        });
+    }
+    if js.is_bigint() {
+        return big_int(js).map(Value::Int);
+    }
+    if let Some(s) = js.as_string() {
+        return Ok(Value::from(s));
+    }
+    if let Some(bytes) = js.dyn_ref::<Uint8Array>() {
+        return Ok(Value::String(bytes.to_vec()));
+    }
+    if !js.is_object() || js.is_function() {
+        return Err(Error::DeserializationFailed(format!(
+            "{js:?} cannot be converted to a PHP value"
+        )));
+    }
+
+    let depth = depth.checked_sub(1).ok_or(Error::DepthLimitExceeded)?;
+    if Array::is_array(js) {
+        let array: &Array = js.unchecked_ref();
+        return array
+            .iter()
+            .map(|v| from_js(&v, depth))
+            .collect::<Result<Vec<_>>>()
+            .map(Value::from);
+    }
+
+    let mut entries = Vec::new();
+    if let Some(map) = js.dyn_ref::<Map>() {
+        let mut result = Ok(());
+        map.for_each(&mut |v, k| {
+            if result.is_ok() {
+                result = key(&k).and_then(|k| {
+                    entries.push((k, from_js(&v, depth)?));
+                    Ok(())
+                });
+            }
+        });
+        result?;
+        return Ok(Value::Array(entries));
+    }
+
+    let properties: Vec<(String, JsValue)> = Object::entries(js.unchecked_ref())
+        .iter()
+        .map(|entry| {
+            let entry: &Array = entry.unchecked_ref();
+            (entry.get(0).as_string().unwrap_or_default(), entry.get(1))
+        })
+        .collect();
+    if let Some(value) = read_record(&properties, depth)? {
+        return Ok(value);
+    }
+    for (k, v) in &properties {
+        entries.push((PhpKey::from(k.as_str()), from_js(v, depth)?));
+    }
+    Ok(Value::Array(entries))
+}
+
+/// Read back a plain object written by `record`, if it is one.
+fn read_record(properties: &[(String, JsValue)], depth: usize) -> Result<Option<Value>> {
+    let (class, name, inner) = match properties {
+        [(class_key, class), (name, inner)] if class_key == "class" => match class.as_string() {
+            Some(class) => (class, name.as_str(), inner),
+            None => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+
+    Ok(match name {
+        "properties" if inner.is_instance_of::<Map>() => match from_js(inner, depth)? {
+            Value::Array(properties) => Some(Value::Object { class, properties }),
+            _ => None,
+        },
+        "payload" => inner.dyn_ref::<Uint8Array>().map(|payload| {
+            Value::Custom(CustomSerialized {
+                class,
+                payload: payload.to_vec(),
+            })
+        }),
+        "case" => inner.as_string().map(|case| Value::Enum { class, case }),
+        _ => None,
+    })
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn key(js: &JsValue) -> Result<PhpKey> {
+    if let Some(s) = js.as_string() {
+        Ok(PhpKey::from(s))
+    } else if let Some(bytes) = js.dyn_ref::<Uint8Array>() {
+        Ok(PhpKey::String(bytes.to_vec()))
+    } else if js.is_bigint() {
+        big_int(js).map(PhpKey::Int)
+    } else if Number::is_safe_integer(js) {
+        Ok(PhpKey::Int(js.as_f64().unwrap_or_default() as i64))
+    } else {
+        Err(Error::DeserializationFailed(format!(
+            "{js:?} cannot be converted to a PHP array key"
+        )))
+    }
+}
+
+fn big_int(js: &JsValue) -> Result<i64> {
+    i64::try_from(js.clone()).map_err(|_| {
+        Error::DeserializationFailed(format!("{js:?} is out of range for a PHP integer"))
+    })
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use crate::{from_bytes, ErrorKind, PhpKey, Value};
+    use js_sys::{Map, Object, Reflect};
+    use std::convert::TryFrom;
+    use wasm_bindgen::JsValue;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test]
+    fn roundtrip() {
+        let input: &[u8] = b"a:6:{i:0;N;i:1;d:0.5;i:2;i:9007199254740993;i:3;s:1:\"\xff\";\
+            i:4;a:2:{i:7;s:1:\"a\";s:1:\"b\";b:1;}i:5;O:3:\"Foo\":1:{s:1:\"x\";a:0:{}}}";
+        let value: Value = from_bytes(input).unwrap();
+        let js = JsValue::from(value.clone());
+        assert_eq!(Value::try_from(js).unwrap(), value);
+
+        let js = JsValue::from(Value::Enum {
+            class: "Suit".to_owned(),
+            case: "Hearts".to_owned(),
+        });
+        assert_eq!(
+            Reflect::get(&js, &JsValue::from_str("case")).unwrap(),
+            "Hearts"
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn from_js() {
+        let object = Object::new();
+        Reflect::set(&object, &"1".into(), &JsValue::from_f64(1.5)).unwrap();
+        Reflect::set(&object, &"x".into(), &JsValue::UNDEFINED).unwrap();
+        assert_eq!(
+            Value::try_from(JsValue::from(object)).unwrap(),
+            vec![
+                (PhpKey::Int(1), Value::Float(1.5)),
+                (PhpKey::from("x"), Value::Null)
+            ]
+            .into_iter()
+            .collect()
+        );
+
+        let map = Map::new();
+        map.set(&JsValue::from_f64(0.5), &JsValue::NULL);
+        let err = Value::try_from(JsValue::from(map)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Custom);
+
+        let cyclic = Object::new();
+        Reflect::set(&cyclic, &"self".into(), &cyclic).unwrap();
+        assert!(Value::try_from(JsValue::from(cyclic)).is_err());
+    }
+}
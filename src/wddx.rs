@@ -0,0 +1,581 @@
+//! WDDX packets, as written by `wddx_serialize_value` of PHP's former wddx
+//! extension, with the `wddx` feature.
+//!
+//! Values are mapped like in the serialized format, by way of [`Value`]:
+//! lists become `<array>`s, other arrays `<struct>`s and objects
+//! `<struct>`s with their class in a `php_class_name` variable, like PHP
+//! writes them. Integers and floats are both `<number>`s, and read back as
+//! integers if they have no fractional part or exponent.
+//!
+//! ```rust
+//! use php_serde::wddx;
+//! use std::collections::BTreeMap;
+//!
+//! let mut map = BTreeMap::new();
+//! map.insert("a<b", 1);
+//! let packet = wddx::to_vec(&map).unwrap();
+//! assert_eq!(
+//!     packet,
+//!     &b"<wddxPacket version='1.0'><header/><data><struct>\
+//!        <var name='a&lt;b'><number>1</number></var></struct></data></wddxPacket>"[..]
+//! );
+//! let read: BTreeMap<String, i64> = wddx::from_bytes(&packet).unwrap();
+//! assert_eq!(read["a<b"], 1);
+//! ```
+//!
+//! Custom serialized objects and enum cases have no equivalent in WDDX and
+//! fail to serialize. Record sets, binary data and dates are not read.
+
+use crate::de;
+use crate::error::{Error, Result};
+use crate::key::PhpKey;
+use crate::options::{DeserializerOptions, FloatPrecision};
+use crate::property::Visibility;
+use crate::ser::{self, write_float};
+use crate::value::{from_value, is_list, Value};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Variable holding the class of an object.
+const CLASS_NAME: &[u8] = b"php_class_name";
+
+/// Serialize a value into a WDDX packet.
+pub fn to_vec<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: Serialize + ?Sized,
+{
+    let value: Value = de::from_bytes(&ser::to_vec(value)?)?;
+    let mut output = b"<wddxPacket version='1.0'><header/><data>".to_vec();
+    write_value(&mut output, &value)?;
+    output.extend_from_slice(b"</data></wddxPacket>");
+    Ok(output)
+}
+
+/// Deserialize a value from a WDDX packet.
+pub fn from_bytes<T>(input: &[u8]) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    let mut reader = Reader { input, offset: 0 };
+    let value = reader
+        .packet(DeserializerOptions::default().max_depth)
+        .map_err(|e| e.at(reader.offset))?;
+    from_value(value)
+}
+
+fn write_value(output: &mut Vec<u8>, value: &Value) -> Result<()> {
+    match value {
+        Value::Null => output.extend_from_slice(b"<null/>"),
+        Value::Bool(b) => {
+            output.extend_from_slice(b"<boolean value='");
+            output.extend_from_slice(if *b { b"true" } else { b"false" });
+            output.extend_from_slice(b"'/>");
+        }
+        Value::Int(n) => {
+            output.extend_from_slice(b"<number>");
+            output.extend_from_slice(itoa::Buffer::new().format(*n).as_bytes());
+            output.extend_from_slice(b"</number>");
+        }
+        Value::Float(v) => {
+            output.extend_from_slice(b"<number>");
+            write_float(output, *v, FloatPrecision::Shortest).map_err(Error::WriteSerialized)?;
+            output.extend_from_slice(b"</number>");
+        }
+        Value::String(s) => write_string(output, s),
+        Value::Array(entries) if is_list(entries) => {
+            output.extend_from_slice(b"<array length='");
+            output.extend_from_slice(itoa::Buffer::new().format(entries.len()).as_bytes());
+            output.extend_from_slice(b"'>");
+            for (_, value) in entries {
+                write_value(output, value)?;
+            }
+            output.extend_from_slice(b"</array>");
+        }
+        Value::Array(entries) => {
+            output.extend_from_slice(b"<struct>");
+            write_vars(output, entries)?;
+            output.extend_from_slice(b"</struct>");
+        }
+        Value::Object { class, properties } => {
+            output.extend_from_slice(b"<struct><var name='php_class_name'>");
+            write_string(output, class.as_bytes());
+            output.extend_from_slice(b"</var>");
+            write_vars(output, properties)?;
+            output.extend_from_slice(b"</struct>");
+        }
+        Value::Custom(_) | Value::Enum { .. } => {
+            return Err(Error::SerializationFailed(
+                "custom serialized objects and enum cases cannot be written as WDDX".to_owned(),
+            ))
+        }
+    }
+    Ok(())
+}
+
+/// Write the entries of a struct, with property names demangled like PHP
+/// does.
+fn write_vars(output: &mut Vec<u8>, entries: &[(PhpKey, Value)]) -> Result<()> {
+    for (key, value) in entries {
+        output.extend_from_slice(b"<var name='");
+        match key {
+            PhpKey::Int(n) => output.extend_from_slice(itoa::Buffer::new().format(*n).as_bytes()),
+            PhpKey::String(name) => {
+                for &b in Visibility::demangle(name).1 {
+                    match b {
+                        b'\'' => output.extend_from_slice(b"&#039;"),
+                        b'"' => output.extend_from_slice(b"&quot;"),
+                        b => escape(output, b),
+                    }
+                }
+            }
+        }
+        output.extend_from_slice(b"'>");
+        write_value(output, value)?;
+        output.extend_from_slice(b"</var>");
+    }
+    Ok(())
+}
+
+/// Write a string, with control characters as `<char>` elements.
+fn write_string(output: &mut Vec<u8>, s: &[u8]) {
+    output.extend_from_slice(b"<string>");
+    for &b in s {
+        if b.is_ascii_control() {
+            output.extend_from_slice(format!("<char code='{b:02X}'/>").as_bytes());
+        } else {
+            escape(output, b);
+        }
+    }
+    output.extend_from_slice(b"</string>");
+}
+
+fn escape(output: &mut Vec<u8>, b: u8) {
+    match b {
+        b'<' => output.extend_from_slice(b"&lt;"),
+        b'>' => output.extend_from_slice(b"&gt;"),
+        b'&' => output.extend_from_slice(b"&amp;"),
+        b => output.push(b),
+    }
+}
+
+/// A start tag, `<name attr='value'>` or `<name/>`.
+struct Tag<'a> {
+    name: &'a [u8],
+    attributes: Vec<(&'a [u8], Vec<u8>)>,
+    /// Whether the element is empty, written as `<name/>`.
+    empty: bool,
+}
+
+impl Tag<'_> {
+    fn attribute(&self, name: &[u8]) -> Option<&[u8]> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, value)| &value[..])
+    }
+}
+
+/// Reader of the XML subset WDDX packets are written in.
+struct Reader<'a> {
+    input: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn packet(&mut self, max_depth: usize) -> Result<Value> {
+        let packet = self.start()?;
+        if packet.name != b"wddxPacket" || packet.empty {
+            return Err(malformed("expected a <wddxPacket>"));
+        }
+
+        let mut tag = self.start()?;
+        if tag.name == b"header" {
+            if !tag.empty {
+                self.skip_element(b"header")?;
+            }
+            tag = self.start()?;
+        }
+        if tag.name != b"data" || tag.empty {
+            return Err(malformed("expected <data>"));
+        }
+        let value = self.value(max_depth)?;
+        self.end(b"data")?;
+        self.end(b"wddxPacket")?;
+
+        self.skip_whitespace()?;
+        if self.offset < self.input.len() {
+            return Err(malformed("trailing data after the packet"));
+        }
+        Ok(value)
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value> {
+        let tag = self.start()?;
+        let value = match tag.name {
+            b"null" => Value::Null,
+            b"boolean" => match tag.attribute(b"value") {
+                Some(b"true") => Value::Bool(true),
+                Some(b"false") => Value::Bool(false),
+                _ => return Err(malformed("expected a boolean value")),
+            },
+            b"number" if !tag.empty => {
+                let text = self.text()?;
+                let text = std::str::from_utf8(&text).unwrap_or_default().trim();
+                match text.parse() {
+                    Ok(n) => Value::Int(n),
+                    Err(_) => Value::Float(text.parse().map_err(|_| malformed("invalid number"))?),
+                }
+            }
+            b"string" if !tag.empty => Value::String(self.string()?),
+            b"string" => Value::String(Vec::new()),
+            b"array" | b"struct" if !tag.empty => {
+                let depth = depth.checked_sub(1).ok_or(Error::DepthLimitExceeded)?;
+                return if tag.name == b"array" {
+                    self.array(depth)
+                } else {
+                    self.structure(depth)
+                };
+            }
+            b"array" | b"struct" => Value::Array(Vec::new()),
+            _ => return Err(malformed("expected a value")),
+        };
+        if !tag.empty {
+            self.end(tag.name)?;
+        }
+        Ok(value)
+    }
+
+    fn array(&mut self, depth: usize) -> Result<Value> {
+        let mut values = Vec::new();
+        while !self.at_end()? {
+            values.push(self.value(depth)?);
+        }
+        self.end(b"array")?;
+        Ok(Value::from(values))
+    }
+
+    fn structure(&mut self, depth: usize) -> Result<Value> {
+        let mut entries = Vec::new();
+        while !self.at_end()? {
+            let var = self.start()?;
+            let name = match var.attribute(b"name") {
+                Some(name) if var.name == b"var" && !var.empty => name.to_vec(),
+                _ => return Err(malformed("expected a <var> with a name")),
+            };
+            entries.push((PhpKey::from_bytes(name), self.value(depth)?));
+            self.end(b"var")?;
+        }
+        self.end(b"struct")?;
+
+        match entries.first() {
+            Some((PhpKey::String(name), Value::String(class))) if name == CLASS_NAME => {
+                let class = String::from_utf8_lossy(class).into_owned();
+                entries.remove(0);
+                Ok(Value::Object {
+                    class,
+                    properties: entries,
+                })
+            }
+            _ => Ok(Value::Array(entries)),
+        }
+    }
+
+    /// Read the contents of a `<string>`, including `<char>` elements.
+    fn string(&mut self) -> Result<Vec<u8>> {
+        let mut s = self.text()?;
+        while !self.input[self.offset..].starts_with(b"</") {
+            let tag = self.start()?;
+            let code = tag
+                .attribute(b"code")
+                .and_then(|code| std::str::from_utf8(code).ok())
+                .and_then(|code| u8::from_str_radix(code, 16).ok());
+            match code {
+                Some(code) if tag.name == b"char" && tag.empty => s.push(code),
+                _ => return Err(malformed("expected a <char> with a code")),
+            }
+            s.extend_from_slice(&self.text()?);
+        }
+        Ok(s)
+    }
+
+    /// Whether the closing tag of the current element comes next.
+    fn at_end(&mut self) -> Result<bool> {
+        self.skip_whitespace()?;
+        Ok(self.input[self.offset..].starts_with(b"</"))
+    }
+
+    /// Read a start tag.
+    fn start(&mut self) -> Result<Tag<'a>> {
+        self.skip_whitespace()?;
+        self.expect(b"<")?;
+        let name = self.name();
+        let mut attributes = Vec::new();
+        loop {
+            self.skip_spaces();
+            match self.peek()? {
+                b'>' => {
+                    self.offset += 1;
+                    return Ok(Tag {
+                        name,
+                        attributes,
+                        empty: false,
+                    });
+                }
+                b'/' => {
+                    self.expect(b"/>")?;
+                    return Ok(Tag {
+                        name,
+                        attributes,
+                        empty: true,
+                    });
+                }
+                _ => {
+                    let attribute = self.name();
+                    self.skip_spaces();
+                    self.expect(b"=")?;
+                    self.skip_spaces();
+                    let quote = self.peek()?;
+                    if quote != b'\'' && quote != b'"' {
+                        return Err(malformed("expected a quoted attribute value"));
+                    }
+                    self.offset += 1;
+                    let value = self.text_until(quote)?;
+                    self.offset += 1;
+                    attributes.push((attribute, value));
+                }
+            }
+        }
+    }
+
+    /// Read the closing tag of `name`.
+    fn end(&mut self, name: &[u8]) -> Result<()> {
+        self.skip_whitespace()?;
+        let start = self.offset;
+        self.expect(b"</")?;
+        if self.name() != name {
+            return Err(malformed("mismatched closing tag").at(start));
+        }
+        self.skip_spaces();
+        self.expect(b">")
+    }
+
+    /// Skip the rest of an element, e.g. a `<header>` with a comment.
+    fn skip_element(&mut self, name: &[u8]) -> Result<()> {
+        let mut closing = b"</".to_vec();
+        closing.extend_from_slice(name);
+        let end = self.input[self.offset..]
+            .windows(closing.len())
+            .position(|w| w == &closing[..])
+            .ok_or(Error::UnexpectedEof)?;
+        self.offset += end;
+        self.end(name)
+    }
+
+    fn text(&mut self) -> Result<Vec<u8>> {
+        self.text_until(b'<')
+    }
+
+    /// Read text up to `delim`, decoding entities.
+    fn text_until(&mut self, delim: u8) -> Result<Vec<u8>> {
+        let mut text = Vec::new();
+        loop {
+            match self.peek()? {
+                b if b == delim => return Ok(text),
+                b'&' => {
+                    let rest = &self.input[self.offset + 1..];
+                    let len = rest
+                        .iter()
+                        .take(10)
+                        .position(|&b| b == b';')
+                        .ok_or_else(|| malformed("unterminated entity"))?;
+                    text.extend_from_slice(&entity(&rest[..len])?);
+                    self.offset += len + 2;
+                }
+                b => {
+                    text.push(b);
+                    self.offset += 1;
+                }
+            }
+        }
+    }
+
+    fn name(&mut self) -> &'a [u8] {
+        let start = self.offset;
+        while self
+            .input
+            .get(self.offset)
+            .is_some_and(|&b| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'-' | b'.' | b':'))
+        {
+            self.offset += 1;
+        }
+        &self.input[start..self.offset]
+    }
+
+    /// Skip whitespace, the XML declaration and comments.
+    fn skip_whitespace(&mut self) -> Result<()> {
+        loop {
+            self.skip_spaces();
+            let rest = &self.input[self.offset..];
+            let (open, close): (&[u8], &[u8]) = if rest.starts_with(b"<?") {
+                (b"<?", b"?>")
+            } else if rest.starts_with(b"<!--") {
+                (b"<!--", b"-->")
+            } else {
+                return Ok(());
+            };
+            let end = rest[open.len()..]
+                .windows(close.len())
+                .position(|w| w == close)
+                .ok_or(Error::UnexpectedEof)?;
+            self.offset += open.len() + end + close.len();
+        }
+    }
+
+    fn skip_spaces(&mut self) {
+        while self
+            .input
+            .get(self.offset)
+            .is_some_and(u8::is_ascii_whitespace)
+        {
+            self.offset += 1;
+        }
+    }
+
+    fn peek(&self) -> Result<u8> {
+        self.input
+            .get(self.offset)
+            .copied()
+            .ok_or(Error::UnexpectedEof)
+    }
+
+    fn expect(&mut self, expected: &[u8]) -> Result<()> {
+        let rest = &self.input[self.offset..];
+        if rest.starts_with(expected) {
+            self.offset += expected.len();
+            Ok(())
+        } else if expected.starts_with(rest) {
+            Err(Error::UnexpectedEof)
+        } else {
+            Err(Error::Unexpected {
+                expected: char::from(expected[0]),
+                actual: char::from(rest[0]),
+            })
+        }
+    }
+}
+
+/// Decode the entity `&name;`, given its name.
+fn entity(name: &[u8]) -> Result<Vec<u8>> {
+    let code = match name {
+        b"lt" => return Ok(b"<".to_vec()),
+        b"gt" => return Ok(b">".to_vec()),
+        b"amp" => return Ok(b"&".to_vec()),
+        b"quot" => return Ok(b"\"".to_vec()),
+        b"apos" => return Ok(b"'".to_vec()),
+        [b'#', b'x' | b'X', hex @ ..] => std::str::from_utf8(hex)
+            .ok()
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok()),
+        [b'#', decimal @ ..] => std::str::from_utf8(decimal)
+            .ok()
+            .and_then(|decimal| decimal.parse().ok()),
+        _ => None,
+    };
+    let c = code
+        .and_then(char::from_u32)
+        .ok_or_else(|| malformed("unknown entity"))?;
+    Ok(c.encode_utf8(&mut [0; 4]).as_bytes().to_vec())
+}
+
+fn malformed(reason: &str) -> Error {
+    Error::DeserializationFailed(format!("invalid WDDX packet: {reason}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_bytes, to_vec};
+    use crate::{CustomSerialized, ErrorKind, Value};
+    use serde::{Deserialize, Serialize};
+
+    #[test]
+    fn roundtrip() {
+        let value: Value = crate::from_bytes(
+            b"a:4:{i:0;N;s:4:\"l'st\";a:3:{i:0;b:1;i:1;d:0.5;i:2;s:4:\"a\n<&\";}\
+              s:1:\"o\";O:3:\"Foo\":2:{s:4:\"\0*\0x\";i:-7;s:1:\"y\";a:0:{}}i:5;s:0:\"\";}",
+        )
+        .unwrap();
+        let packet = to_vec(&value).unwrap();
+        assert_eq!(
+            packet,
+            &b"<wddxPacket version='1.0'><header/><data><struct>\
+               <var name='0'><null/></var>\
+               <var name='l&#039;st'><array length='3'><boolean value='true'/><number>0.5</number>\
+               <string>a<char code='0A'/>&lt;&amp;</string></array></var>\
+               <var name='o'><struct><var name='php_class_name'><string>Foo</string></var>\
+               <var name='x'><number>-7</number></var><var name='y'><array length='0'></array></var>\
+               </struct></var>\
+               <var name='5'><string></string></var>\
+               </struct></data></wddxPacket>"[..]
+        );
+
+        // Property names are written without their visibility.
+        let expected = crate::from_bytes::<Value>(
+            b"a:4:{i:0;N;s:4:\"l'st\";a:3:{i:0;b:1;i:1;d:0.5;i:2;s:4:\"a\n<&\";}\
+              s:1:\"o\";O:3:\"Foo\":2:{s:1:\"x\";i:-7;s:1:\"y\";a:0:{}}i:5;s:0:\"\";}",
+        )
+        .unwrap();
+        assert_eq!(from_bytes::<Value>(&packet).unwrap(), expected);
+    }
+
+    #[test]
+    fn php_packets() {
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct Item {
+            id: i64,
+            price: f64,
+            tags: Vec<String>,
+        }
+
+        let packet = br#"<?xml version="1.0"?>
+            <wddxPacket version="1.0">
+              <header><comment>items</comment></header>
+              <data>
+                <struct>
+                  <var name="id"><number>3</number></var>
+                  <var name="price"><number>1.5E+2</number></var>
+                  <var name="tags"><array length="1"><string>&#233;&#x41;<char code="09"/></string></array></var>
+                </struct>
+              </data>
+            </wddxPacket>
+        "#;
+        let item: Item = from_bytes(packet).unwrap();
+        assert_eq!(
+            item,
+            Item {
+                id: 3,
+                price: 150.0,
+                tags: vec!["\u{e9}A\t".to_owned()],
+            }
+        );
+        assert_eq!(from_bytes::<Item>(&to_vec(&item).unwrap()).unwrap(), item);
+    }
+
+    #[test]
+    fn malformed() {
+        let err =
+            from_bytes::<Value>(b"<wddxPacket version='1.0'><data><number>1</data>").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Custom);
+        assert_eq!(err.position(), Some(41));
+        let err = from_bytes::<Value>(b"<wddxPacket version='1.0'><data><string>ab").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Eof);
+        assert!(
+            from_bytes::<Value>(b"<wddxPacket><data><recordset/></data></wddxPacket>").is_err()
+        );
+        assert!(from_bytes::<Value>(b"<wddxPacket><data><null/></data></wddxPacket>x").is_err());
+
+        let custom = CustomSerialized {
+            class: "Foo".to_owned(),
+            payload: Vec::new(),
+        };
+        assert_eq!(to_vec(&custom).unwrap_err().kind(), ErrorKind::Custom);
+    }
+}
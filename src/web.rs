@@ -0,0 +1,252 @@
+//! Serialized request and response bodies, with the `axum` and `actix`
+//! features.
+
+/// Media type of serialized bodies.
+const CONTENT_TYPE: &str = "application/vnd.php.serialized";
+
+/// A request or response body holding a serialized value.
+///
+/// As an extractor, `PhpSerialized<T>` requires the
+/// `application/vnd.php.serialized` content type, rejecting other requests
+/// with `415 Unsupported Media Type`, and deserializes the body, rejecting
+/// malformed bodies with `400 Bad Request`. As a response, it serializes the
+/// value with the same content type, or responds with
+/// `500 Internal Server Error` if the value cannot be serialized.
+///
+/// With the `axum` feature, it implements `FromRequest` and `IntoResponse`
+/// of `axum`, with the `actix` feature `FromRequest` and `Responder` of
+/// `actix-web`. Body size limits are those of the framework, as for its own
+/// `Bytes` extractor.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct PhpSerialized<T>(pub T);
+
+/// Whether a `Content-Type` header names the serialized media type,
+/// ignoring parameters such as `charset`.
+fn is_serialized(content_type: Option<&[u8]>) -> bool {
+    content_type
+        .and_then(|value| std::str::from_utf8(value).ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|essence| essence.trim().eq_ignore_ascii_case(CONTENT_TYPE))
+}
+
+#[cfg(feature = "axum")]
+mod axum {
+    use super::{is_serialized, PhpSerialized, CONTENT_TYPE};
+    use crate::de::from_bytes;
+    use crate::ser::to_vec;
+    use ::axum::body::Bytes;
+    use ::axum::extract::{FromRequest, Request};
+    use ::axum::http::{header, HeaderValue, StatusCode};
+    use ::axum::response::{IntoResponse, Response};
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+
+    impl<T, S> FromRequest<S> for PhpSerialized<T>
+    where
+        T: DeserializeOwned,
+        S: Send + Sync,
+    {
+        type Rejection = Response;
+
+        async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+            let content_type = req.headers().get(header::CONTENT_TYPE);
+            if !is_serialized(content_type.map(HeaderValue::as_bytes)) {
+                return Err(StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response());
+            }
+
+            let body = Bytes::from_request(req, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            from_bytes(&body)
+                .map(PhpSerialized)
+                .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()).into_response())
+        }
+    }
+
+    impl<T: Serialize> IntoResponse for PhpSerialized<T> {
+        fn into_response(self) -> Response {
+            match to_vec(&self.0) {
+                Ok(body) => (
+                    [(header::CONTENT_TYPE, HeaderValue::from_static(CONTENT_TYPE))],
+                    body,
+                )
+                    .into_response(),
+                Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use crate::PhpSerialized;
+        use ::axum::body::{to_bytes, Body};
+        use ::axum::extract::{FromRequest, Request};
+        use ::axum::http::{header, StatusCode};
+        use ::axum::response::IntoResponse;
+        use serde::Serialize;
+        use std::collections::BTreeMap;
+        use std::future::Future;
+
+        #[derive(Serialize)]
+        struct Unit;
+
+        fn block_on<F: Future>(future: F) -> F::Output {
+            tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+                .block_on(future)
+        }
+
+        fn extract(content_type: &str, body: &'static [u8]) -> Result<Vec<i64>, StatusCode> {
+            let req = Request::builder()
+                .header(header::CONTENT_TYPE, content_type)
+                .body(Body::from(body))
+                .unwrap();
+            block_on(PhpSerialized::from_request(req, &()))
+                .map(|PhpSerialized(v)| v)
+                .map_err(|rejection| rejection.status())
+        }
+
+        #[test]
+        fn extractor() {
+            let content_type = "application/vnd.php.serialized; charset=utf-8";
+            assert_eq!(extract(content_type, b"a:1:{i:0;i:5;}").unwrap(), vec![5]);
+
+            assert_eq!(
+                extract("application/json", b"[5]"),
+                Err(StatusCode::UNSUPPORTED_MEDIA_TYPE)
+            );
+            assert_eq!(
+                extract(content_type, b"a:1:{i:0;"),
+                Err(StatusCode::BAD_REQUEST)
+            );
+        }
+
+        #[test]
+        fn responder() {
+            let map: BTreeMap<_, _> = vec![("a", 1)].into_iter().collect();
+            let response = PhpSerialized(map).into_response();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(
+                response.headers()[header::CONTENT_TYPE],
+                "application/vnd.php.serialized"
+            );
+            let body = block_on(to_bytes(response.into_body(), usize::MAX)).unwrap();
+            assert_eq!(body, &br#"a:1:{s:1:"a";i:1;}"#[..]);
+
+            // Unit structs are not supported.
+            let response = PhpSerialized(Unit).into_response();
+            assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+}
+
+#[cfg(feature = "actix")]
+mod actix {
+    use super::{is_serialized, PhpSerialized, CONTENT_TYPE};
+    use crate::de::from_bytes;
+    use crate::ser::to_vec;
+    use actix_web::dev::Payload;
+    use actix_web::error::{ErrorBadRequest, ErrorInternalServerError, ErrorUnsupportedMediaType};
+    use actix_web::http::header::{self, HeaderValue};
+    use actix_web::web::Bytes;
+    use actix_web::{Error, FromRequest, HttpRequest, HttpResponse, Responder};
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+    use std::future::Future;
+    use std::pin::Pin;
+
+    impl<T: DeserializeOwned> FromRequest for PhpSerialized<T> {
+        type Error = Error;
+        type Future = Pin<Box<dyn Future<Output = Result<Self, Error>>>>;
+
+        fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+            let content_type = req.headers().get(header::CONTENT_TYPE);
+            if !is_serialized(content_type.map(HeaderValue::as_bytes)) {
+                return Box::pin(async {
+                    Err(ErrorUnsupportedMediaType("expected serialized PHP data"))
+                });
+            }
+
+            let body = Bytes::from_request(req, payload);
+            Box::pin(async move {
+                from_bytes(&body.await?)
+                    .map(PhpSerialized)
+                    .map_err(ErrorBadRequest)
+            })
+        }
+    }
+
+    impl<T: Serialize> Responder for PhpSerialized<T> {
+        type Body = actix_web::body::BoxBody;
+
+        fn respond_to(self, _req: &HttpRequest) -> HttpResponse {
+            match to_vec(&self.0) {
+                Ok(body) => HttpResponse::Ok().content_type(CONTENT_TYPE).body(body),
+                Err(e) => HttpResponse::from_error(ErrorInternalServerError(e)),
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use crate::PhpSerialized;
+        use actix_web::body::to_bytes;
+        use actix_web::http::{header, StatusCode};
+        use actix_web::rt::System;
+        use actix_web::test::TestRequest;
+        use actix_web::{Error, FromRequest, Responder};
+        use serde::Serialize;
+        use std::collections::BTreeMap;
+
+        #[derive(Serialize)]
+        struct Unit;
+
+        fn extract(content_type: &str, body: &'static [u8]) -> Result<Vec<i64>, Error> {
+            let (req, mut payload) = TestRequest::default()
+                .insert_header((header::CONTENT_TYPE, content_type))
+                .set_payload(body)
+                .to_http_parts();
+            System::new()
+                .block_on(PhpSerialized::from_request(&req, &mut payload))
+                .map(|PhpSerialized(v)| v)
+        }
+
+        #[test]
+        fn extractor() {
+            let content_type = "application/vnd.php.serialized";
+            assert_eq!(extract(content_type, b"a:1:{i:0;i:5;}").unwrap(), vec![5]);
+
+            let err = extract("text/plain", b"a:1:{i:0;i:5;}").unwrap_err();
+            assert_eq!(
+                err.as_response_error().status_code(),
+                StatusCode::UNSUPPORTED_MEDIA_TYPE
+            );
+            let err = extract(content_type, b"a:1:{i:0;").unwrap_err();
+            assert_eq!(
+                err.as_response_error().status_code(),
+                StatusCode::BAD_REQUEST
+            );
+        }
+
+        #[test]
+        fn responder() {
+            let req = TestRequest::default().to_http_request();
+            let map: BTreeMap<_, _> = vec![("a", 1)].into_iter().collect();
+            let response = PhpSerialized(map).respond_to(&req);
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(
+                response.headers().get(header::CONTENT_TYPE).unwrap(),
+                "application/vnd.php.serialized"
+            );
+            let body = System::new()
+                .block_on(to_bytes(response.into_body()))
+                .unwrap();
+            assert_eq!(body, &br#"a:1:{s:1:"a";i:1;}"#[..]);
+
+            // Unit structs are not supported.
+            let response = PhpSerialized(Unit).respond_to(&req);
+            assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+}